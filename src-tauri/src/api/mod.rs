@@ -0,0 +1,257 @@
+//! Opt-in local automation API.
+//!
+//! - `POST /rpc`: JSON-RPC 2.0. Methods and params mirror the Tauri commands
+//!   (`ping` with `{"setting": {...}}`, `cancel_run` with `{"runId": "..."}`, ...).
+//! - `GET /events`: Server-Sent Events stream of probe events, using the same
+//!   event names and payloads as the webview (`ping:progress`, `portscan:progress`, ...).
+//!
+//! The server binds to 127.0.0.1 only and every request must carry the configured
+//! token as `Authorization: Bearer <token>`. `/events` also accepts `?token=<token>`,
+//! as EventSource clients cannot set headers.
+
+pub mod rpc;
+
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures::{stream, Stream};
+use serde::Deserialize;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use tauri::{async_runtime, AppHandle};
+use tokio::sync::{broadcast, watch};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{ApiConfig, AppConfig};
+use crate::probe::event::ProbeEvent;
+
+/// Capacity of the event broadcast. Slow SSE clients skip events beyond this.
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Serialized probe event for API subscribers
+#[derive(Clone, Debug)]
+pub struct ApiEvent {
+    pub name: &'static str,
+    pub data: Arc<str>,
+}
+
+/// Fan-out of probe events to `/events` subscribers
+pub struct EventHub {
+    tx: broadcast::Sender<ApiEvent>,
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    /// Publish an event. Serialization is skipped when nobody is listening.
+    pub fn publish(&self, event: &ProbeEvent) {
+        if self.tx.receiver_count() == 0 {
+            return;
+        }
+        match serde_json::to_string(event) {
+            Ok(data) => {
+                let _ = self.tx.send(ApiEvent {
+                    name: event.name(),
+                    data: data.into(),
+                });
+            }
+            Err(e) => tracing::warn!("failed to serialize {}: {}", event.name(), e),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ApiEvent> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    app: AppHandle,
+    token: Arc<str>,
+    events: broadcast::Sender<ApiEvent>,
+    shutdown: CancellationToken,
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Compare without short-circuiting on the first differing byte.
+fn token_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn bearer_token(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string())
+}
+
+async fn check_token(
+    state: &ApiState,
+    given: Option<String>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let given = given.unwrap_or_default();
+    if !token_eq(given.as_bytes(), state.token.as_bytes()) {
+        return (StatusCode::UNAUTHORIZED, "invalid or missing token").into_response();
+    }
+    next.run(request).await
+}
+
+/// Require the token in the `Authorization` header.
+async fn auth(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let given = bearer_token(&request);
+    check_token(&state, given, request, next).await
+}
+
+/// Require the token in the `Authorization` header or the `token` query parameter.
+/// Only for `/events`, so the token does not end up in the URLs of other requests.
+async fn auth_header_or_query(
+    State(state): State<ApiState>,
+    Query(query): Query<TokenQuery>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let given = bearer_token(&request).or(query.token);
+    check_token(&state, given, request, next).await
+}
+
+async fn handle_rpc(State(state): State<ApiState>, body: String) -> Json<rpc::RpcResponse> {
+    Json(rpc::handle(&state.app, &body).await)
+}
+
+async fn handle_events(
+    State(state): State<ApiState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = state.events.subscribe();
+    let events = stream::unfold(
+        (rx, state.shutdown.clone()),
+        |(mut rx, shutdown)| async move {
+            loop {
+                let next = tokio::select! {
+                    _ = shutdown.cancelled() => return None,
+                    next = rx.recv() => next,
+                };
+                match next {
+                    Ok(ev) => {
+                        let event = Event::default().event(ev.name).data(&*ev.data);
+                        return Some((Ok(event), (rx, shutdown)));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("api event subscriber lagged, skipped {n} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Run the API server until `shutdown` is cancelled.
+async fn serve(
+    app: AppHandle,
+    hub: broadcast::Sender<ApiEvent>,
+    cfg: ApiConfig,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let state = ApiState {
+        app,
+        token: cfg.token.into(),
+        events: hub,
+        shutdown: shutdown.clone(),
+    };
+    let router = Router::new()
+        .route(
+            "/rpc",
+            post(handle_rpc).route_layer(middleware::from_fn_with_state(state.clone(), auth)),
+        )
+        .route(
+            "/events",
+            get(handle_events).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth_header_or_query,
+            )),
+        )
+        .with_state(state);
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], cfg.port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("automation API listening on http://{addr}");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    tracing::info!("automation API stopped");
+    Ok(())
+}
+
+/// Spawn a task that starts, stops and restarts the API server
+/// as `api` settings published on `config` change.
+pub fn spawn_api_server(
+    app: AppHandle,
+    hub: &EventHub,
+    mut config: watch::Receiver<AppConfig>,
+) -> async_runtime::JoinHandle<()> {
+    let events = hub.tx.clone();
+    async_runtime::spawn(async move {
+        let mut running: Option<(ApiConfig, CancellationToken, async_runtime::JoinHandle<()>)> =
+            None;
+        loop {
+            let api = config.borrow_and_update().api.clone();
+            let wanted = api.enabled.then_some(&api);
+            if running.as_ref().map(|(cfg, _, _)| cfg) != wanted {
+                if let Some((_, token, handle)) = running.take() {
+                    token.cancel();
+                    let _ = handle.await;
+                }
+                if api.enabled && api.token.len() >= crate::config::MIN_API_TOKEN_LEN {
+                    let token = CancellationToken::new();
+                    let handle = async_runtime::spawn({
+                        let (app, events, api, token) =
+                            (app.clone(), events.clone(), api.clone(), token.clone());
+                        async move {
+                            if let Err(e) = serve(app, events, api, token).await {
+                                tracing::error!("automation API failed: {e:?}");
+                            }
+                        }
+                    });
+                    running = Some((api, token, handle));
+                } else if api.enabled {
+                    tracing::error!("automation API not started: token is too short");
+                }
+            }
+            if config.changed().await.is_err() {
+                break;
+            }
+        }
+        if let Some((_, token, handle)) = running {
+            token.cancel();
+            let _ = handle.await;
+        }
+    })
+}
@@ -0,0 +1,280 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+
+use crate::command;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The command itself returned an error
+pub const COMMAND_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RpcResponse {
+    pub jsonrpc: &'static str,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    fn new(id: Value, outcome: Result<Value, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(v) => (Some(v), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// Parse and execute a JSON-RPC request body.
+pub async fn handle(app: &AppHandle, body: &str) -> RpcResponse {
+    let req: RpcRequest = match serde_json::from_str::<Value>(body) {
+        Err(e) => {
+            return RpcResponse::new(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string())))
+        }
+        Ok(v) => match serde_json::from_value(v) {
+            Ok(req) => req,
+            Err(e) => {
+                return RpcResponse::new(
+                    Value::Null,
+                    Err(RpcError::new(INVALID_REQUEST, e.to_string())),
+                )
+            }
+        },
+    };
+    if req.jsonrpc != "2.0" {
+        return RpcResponse::new(
+            req.id,
+            Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")),
+        );
+    }
+    let outcome = dispatch(app, &req.method, &req.params).await;
+    RpcResponse::new(req.id, outcome)
+}
+
+/// Required named parameter. Names follow the Tauri invoke arguments (camelCase).
+fn arg<T: DeserializeOwned>(params: &Value, name: &str) -> Result<T, RpcError> {
+    let value = params
+        .get(name)
+        .cloned()
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing param: {name}")))?;
+    serde_json::from_value(value)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid param {name}: {e}")))
+}
+
+/// Optional named parameter; absent and `null` are both `None`.
+fn opt_arg<T: DeserializeOwned>(params: &Value, name: &str) -> Result<Option<T>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(_) => arg(params, name).map(Some),
+    }
+}
+
+/// Convert a command result into a JSON-RPC outcome.
+fn reply<T: Serialize, E: Serialize>(result: Result<T, E>) -> Result<Value, RpcError> {
+    match result {
+        Ok(v) => serde_json::to_value(v).map_err(|e| RpcError::new(COMMAND_ERROR, e.to_string())),
+        Err(e) => {
+            let data = serde_json::to_value(&e).unwrap_or(Value::Null);
+            let message = match &data {
+                Value::String(s) => s.clone(),
+                _ => "command failed".to_string(),
+            };
+            Err(RpcError {
+                code: COMMAND_ERROR,
+                message,
+                data: Some(data),
+            })
+        }
+    }
+}
+
+fn ok<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    reply::<T, String>(Ok(value))
+}
+
+/// Call the command registered in the app's `invoke_handler` under `method`.
+/// Config and log commands are not exposed.
+async fn dispatch(app: &AppHandle, method: &str, params: &Value) -> Result<Value, RpcError> {
+    let app = app.clone();
+    match method {
+        "about" => ok(command::about().await),
+        // Interfaces
+        "get_network_interfaces" => {
+            reply(command::interfaces::get_network_interfaces(app.state()).await)
+        }
+        "reload_interfaces" => {
+            reply(command::interfaces::reload_interfaces(app.clone(), app.state()).await)
+        }
+        "get_default_network_interface" => {
+            reply(command::interfaces::get_default_network_interface().await)
+        }
+        "get_network_address_map" => reply(command::interfaces::get_network_address_map().await),
+        // Routes, neighbors, sockets
+        "get_routes" => reply(command::routes::get_routes(app.state()).await),
+        "get_neighbor_table" => reply(command::routes::get_neighbor_table(app.state()).await),
+        "get_sockets_all" => reply(command::socket::get_sockets_all()),
+        // System / Internet
+        "get_public_ip_info" => reply(command::internet::get_public_ip_info().await),
+        "get_sys_info" => ok(command::system::get_sys_info()),
+        // DNS
+        "lookup_host" => {
+            let host: String = arg(params, "host")?;
+            reply(command::dns::lookup_host(&host).await)
+        }
+        "lookup_domain" => {
+            let hostname: String = arg(params, "hostname")?;
+            reply(command::dns::lookup_domain(&hostname).await)
+        }
+        "lookup_ip" => {
+            let hostname: String = arg(params, "hostname")?;
+            reply(command::dns::lookup_ip(&hostname).await)
+        }
+        "reverse_lookup" => reply(command::dns::reverse_lookup(arg(params, "ip")?).await),
+        "lookup_all" => {
+            let hostname: String = arg(params, "hostname")?;
+            reply(command::dns::lookup_all(&hostname).await)
+        }
+        "tls_inspect" => reply(
+            command::tls::tls_inspect(
+                arg(params, "host")?,
+                opt_arg(params, "port")?,
+                opt_arg(params, "sni")?,
+                opt_arg(params, "starttls")?,
+            )
+            .await,
+        ),
+        "ntp_query" => reply(
+            command::ntp::ntp_query(arg(params, "servers")?, opt_arg(params, "port")?).await,
+        ),
+        // Probes
+        "ping" => {
+            reply(command::ping::ping(app.clone(), app.state(), arg(params, "setting")?).await)
+        }
+        "multi_ping" => reply(
+            command::ping::multi_ping(
+                app.clone(),
+                app.state(),
+                app.state(),
+                arg(params, "setting")?,
+            )
+            .await,
+        ),
+        "pmtu_discover" => reply(
+            command::pmtu::pmtu_discover(app.clone(), app.state(), arg(params, "setting")?).await,
+        ),
+        "port_scan" => reply(
+            command::scan::port_scan(
+                app.clone(),
+                app.state(),
+                app.state(),
+                arg(params, "setting")?,
+            )
+            .await,
+        ),
+        "host_scan" => reply(
+            command::scan::host_scan(
+                app.clone(),
+                app.state(),
+                app.state(),
+                arg(params, "setting")?,
+            )
+            .await,
+        ),
+        "neighbor_scan" => reply(
+            command::scan::neighbor_scan(
+                app.clone(),
+                app.state(),
+                app.state(),
+                opt_arg(params, "ifaceName")?,
+            )
+            .await,
+        ),
+        "traceroute" => reply(
+            command::trace::traceroute(app.clone(), app.state(), arg(params, "setting")?).await,
+        ),
+        "mtr" => {
+            reply(command::trace::mtr(app.clone(), app.state(), arg(params, "setting")?).await)
+        }
+        // Runs
+        "cancel_run" => reply(command::run::cancel_run(app.state(), arg(params, "runId")?).await),
+        "list_runs" => reply(command::run::list_runs(app.state()).await),
+        "get_run" => reply(command::run::get_run(app.state(), arg(params, "runId")?).await),
+        // History
+        "query_history" => reply(
+            command::history::query_history(
+                app.state(),
+                opt_arg(params, "query")?.unwrap_or_default(),
+            )
+            .await,
+        ),
+        "get_history_record" => {
+            reply(command::history::get_history_record(app.state(), arg(params, "runId")?).await)
+        }
+        "delete_history" => {
+            reply(command::history::delete_history(app.state(), arg(params, "runIds")?).await)
+        }
+        "prune_history" => reply(
+            command::history::prune_history(
+                app.state(),
+                opt_arg(params, "maxAgeMs")?,
+                opt_arg(params, "maxBytes")?,
+            )
+            .await,
+        ),
+        // Export
+        "export_result" => reply(
+            command::export::export_result(
+                arg(params, "kind")?,
+                arg(params, "data")?,
+                arg(params, "format")?,
+                arg(params, "path")?,
+            )
+            .await,
+        ),
+        // Monitors
+        "list_monitors" => reply(command::monitor::list_monitors(app.state()).await),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("method not found: {method}"),
+        )),
+    }
+}
@@ -9,8 +9,9 @@ use tauri::{
 use tauri_plugin_autostart::{init as autostart_init, MacosLauncher, ManagerExt};
 
 use crate::{
+    api::{self, EventHub},
     command::{self, config::ConfigState},
-    service,
+    metrics, service,
     state::AppState,
 };
 
@@ -45,7 +46,7 @@ pub fn run() {
     let startup = app_conf.startup;
     let _ = crate::log::init_logger(&app_conf);
 
-    let conf_state = ConfigState(tokio::sync::RwLock::new(app_conf));
+    let conf_state = ConfigState::new(app_conf);
 
     let shared_app_state = Arc::new(AppState::default());
 
@@ -55,17 +56,27 @@ pub fn run() {
         // Register AppState as Tauri State
         .manage(conf_state)
         .manage(shared_app_state.clone())
+        .manage(EventHub::new())
         // Setup: spawn background task
         .setup(move |app| {
             // Spawn background supervisor task
-            let handle = service::spawn_supervisor(app.handle().clone(), shared_app_state.clone());
-            tauri::async_runtime::spawn({
-                let shared = shared_app_state.clone();
-                async move {
-                    let mut t = shared.task.lock().await;
-                    *t = Some(handle);
-                }
-            });
+            let config_rx = app.state::<ConfigState>().subscribe();
+            tauri::async_runtime::spawn(service::restart_supervisor(
+                app.handle().clone(),
+                shared_app_state.clone(),
+                config_rx,
+            ));
+            // Automation API (started only when enabled in config)
+            api::spawn_api_server(
+                app.handle().clone(),
+                &app.state::<EventHub>(),
+                app.state::<ConfigState>().subscribe(),
+            );
+            // Prometheus metrics endpoint (started only when enabled in config)
+            metrics::spawn_metrics_server(
+                shared_app_state.clone(),
+                app.state::<ConfigState>().subscribe(),
+            );
 
             let tray_icon_bytes = tray_icon_bytes(theme_is_dark(&app));
             let tray_icon = tauri::image::Image::from_bytes(tray_icon_bytes)
@@ -189,11 +200,25 @@ pub fn run() {
             command::dns::lookup_ip,
             command::dns::reverse_lookup,
             command::dns::lookup_all,
+            command::tls::tls_inspect,
+            command::ntp::ntp_query,
             command::ping::ping,
+            command::ping::multi_ping,
+            command::pmtu::pmtu_discover,
             command::scan::port_scan,
             command::scan::host_scan,
             command::scan::neighbor_scan,
             command::trace::traceroute,
+            command::trace::mtr,
+            command::run::cancel_run,
+            command::run::list_runs,
+            command::run::get_run,
+            command::history::query_history,
+            command::history::get_history_record,
+            command::history::delete_history,
+            command::history::prune_history,
+            command::export::export_result,
+            command::monitor::list_monitors,
         ])
         .run(tauri::generate_context!())
         .expect("error while running netpulsar application");
@@ -0,0 +1,3 @@
+fn main() -> std::process::ExitCode {
+    netpulsar_lib::run_cli()
+}
@@ -0,0 +1,1334 @@
+mod output;
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use clap::{Args, Parser, Subcommand};
+use netsock::{
+    family::AddressFamilyFlags, get_sockets, protocol::ProtocolFlags, socket::ProtocolSocketInfo,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::model::ping::{
+    DnsPingOption, DnsTransport, HttpHeader, HttpMethod, HttpPingOption, IcmpPingOption,
+    MultiPingSetting, PingProtocol, PingSetting, PingStartPayload, PingStat, UdpPingMode,
+    UdpPingOption, DEFAULT_ICMP_PAYLOAD_SIZE,
+};
+use crate::model::pmtu::PmtuSetting;
+use crate::model::quic::QuicOption;
+use crate::model::scan::{
+    HostScanSetting, HostScanStartPayload, PortScanProtocol, PortScanSetting, PortScanStartPayload,
+    PortState, TargetPortsPreset,
+};
+use crate::model::tls::StartTls;
+use crate::net::dns::resolver::DnsResolver;
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::trace::{MtrSetting, TraceProtocol, TracerouteSetting};
+use output::{opt, print_json_line, CliEventSink, Table};
+
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Headless network diagnostics sharing the probe engine of the desktop app
+#[derive(Parser)]
+#[command(name = "netpulsar-cli", version, about)]
+struct Cli {
+    /// Print JSON lines instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Ping a host over ICMP, TCP, UDP, QUIC, HTTP or DNS
+    Ping(Box<PingArgs>),
+    /// Ping many hosts over ICMP in parallel
+    Multiping(MultiPingArgs),
+    /// Trace the route to a host
+    Trace(TraceArgs),
+    /// Trace the route to a host repeatedly, with loss and RTT statistics per hop
+    Mtr(MtrArgs),
+    /// Discover the path MTU to a host
+    Pmtu(PmtuArgs),
+    /// Scan ports on a host
+    Portscan(PortScanArgs),
+    /// Discover alive hosts by ICMP echo
+    Hostscan(HostScanArgs),
+    /// Show the neighbor table, or scan the local network with --scan
+    Neigh(NeighArgs),
+    /// Resolve a hostname, or reverse lookup an IP address
+    Dns(DnsArgs),
+    /// Inspect the TLS handshake and certificate chain of a server
+    Tls(TlsArgs),
+    /// Query NTP servers for the offset of the local clock
+    Ntp(NtpArgs),
+    /// Show the routing table
+    Routes,
+    /// Show TCP and UDP sockets
+    Sockets,
+    /// Show network interfaces
+    Ifaces,
+}
+
+#[derive(Args)]
+struct PingArgs {
+    /// IP address, hostname or URL (for http); the server for dns and ntp
+    target: String,
+    /// icmp, tcp, udp, quic, http, dns or ntp
+    #[arg(short = 'P', long, default_value = "icmp", value_parser = parse_ping_protocol)]
+    protocol: PingProtocol,
+    /// Destination port (tcp, udp, quic, http, dns, ntp)
+    #[arg(short, long)]
+    port: Option<u16>,
+    /// Number of probes
+    #[arg(short, long, default_value_t = 4)]
+    count: u32,
+    /// Timeout per probe in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    timeout: u64,
+    /// Interval between probes in milliseconds
+    #[arg(short, long, default_value_t = 1000)]
+    interval: u64,
+    /// TTL / hop limit
+    #[arg(long, default_value_t = 64)]
+    ttl: u8,
+    /// HTTP method: get or head (http)
+    #[arg(long, value_parser = parse_http_method)]
+    method: Option<HttpMethod>,
+    /// Extra request header as "Name: value", repeatable (http)
+    #[arg(short = 'H', long = "header", value_parser = parse_http_header)]
+    headers: Vec<HttpHeader>,
+    /// Status codes that count as success, e.g. 200,204 (http)
+    #[arg(long, value_delimiter = ',')]
+    expect_status: Vec<u16>,
+    /// Text the response body must contain (http)
+    #[arg(long)]
+    expect_body: Option<String>,
+    /// Query transport: udp, tcp, tls (DoT) or https (DoH) (dns)
+    #[arg(long, value_parser = parse_dns_transport)]
+    transport: Option<DnsTransport>,
+    /// Name to query (dns, default: example.com)
+    #[arg(long)]
+    query: Option<String>,
+    /// Record type to query, e.g. A, AAAA, MX (dns, default: A)
+    #[arg(long)]
+    qtype: Option<String>,
+    /// Clear the recursion desired flag (dns)
+    #[arg(long)]
+    norecurse: bool,
+    /// URL path of DoH queries (dns, default: /dns-query)
+    #[arg(long)]
+    doh_path: Option<String>,
+    /// Request to send: unreach, dns, ntp, snmp, echo or hex (udp, default: unreach)
+    #[arg(long, value_parser = parse_udp_mode)]
+    udp_mode: Option<UdpPingMode>,
+    /// Payload as hex digits for --udp-mode hex (udp)
+    #[arg(long)]
+    payload: Option<String>,
+    /// Community for --udp-mode snmp (udp, default: public)
+    #[arg(long)]
+    community: Option<String>,
+    /// Payload size in bytes (icmp, default: 56)
+    #[arg(short = 's', long)]
+    size: Option<usize>,
+    /// Bytes to fill the payload with, as hex digits (icmp)
+    #[arg(long)]
+    pattern: Option<String>,
+    #[command(flatten)]
+    quic: QuicArgs,
+    /// Reconnect after each handshake to check whether 0-RTT is accepted (quic)
+    #[arg(long)]
+    zero_rtt: bool,
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Args)]
+struct MultiPingArgs {
+    /// IP addresses, CIDR networks or hostnames
+    #[arg(required = true)]
+    targets: Vec<String>,
+    /// Number of probes per host
+    #[arg(short, long, default_value_t = 4)]
+    count: u32,
+    /// Timeout per round in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    timeout: u64,
+    /// Interval between rounds in milliseconds
+    #[arg(short, long, default_value_t = 1000)]
+    interval: u64,
+    /// TTL / hop limit
+    #[arg(long, default_value_t = 64)]
+    ttl: u8,
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Args)]
+struct TraceArgs {
+    /// IP address or hostname
+    target: String,
+    /// icmp or udp
+    #[arg(short = 'P', long, default_value = "icmp", value_parser = parse_trace_protocol)]
+    protocol: TraceProtocol,
+    /// Maximum number of hops
+    #[arg(short, long, default_value_t = 30)]
+    max_hops: u8,
+    /// Number of tries per hop
+    #[arg(short = 'q', long, default_value_t = 1)]
+    tries: u8,
+    /// Timeout per try in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    timeout: u64,
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Args)]
+struct MtrArgs {
+    /// IP address or hostname
+    target: String,
+    /// Number of rounds (runs until interrupted if omitted)
+    #[arg(short = 'c', long)]
+    rounds: Option<u32>,
+    /// Interval between rounds in milliseconds
+    #[arg(short, long, default_value_t = 1000)]
+    interval: u64,
+    /// Timeout per round in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    timeout: u64,
+    /// Maximum number of hops
+    #[arg(short, long, default_value_t = 30)]
+    max_hops: u8,
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Args)]
+struct PmtuArgs {
+    /// IP address or hostname
+    target: String,
+    /// Largest packet size to try (defaults to the interface MTU)
+    #[arg(long)]
+    max_mtu: Option<u32>,
+    /// Timeout per probe in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    timeout: u64,
+    /// Probes per packet size
+    #[arg(short = 'q', long, default_value_t = 2)]
+    tries: u8,
+    /// TTL / hop limit
+    #[arg(long, default_value_t = 64)]
+    ttl: u8,
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Args)]
+struct PortScanArgs {
+    /// IP address or hostname
+    target: String,
+    /// common, wellknown, top1000, full or custom
+    #[arg(long, value_parser = parse_ports_preset)]
+    preset: Option<TargetPortsPreset>,
+    /// Additional ports, e.g. 22,80,8000-8100
+    #[arg(short, long, value_parser = parse_ports)]
+    ports: Option<Vec<u16>>,
+    /// tcp or quic
+    #[arg(short = 'P', long, default_value = "tcp", value_parser = parse_portscan_protocol)]
+    protocol: PortScanProtocol,
+    /// Timeout per port in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    timeout: u64,
+    /// Report results in port order
+    #[arg(long)]
+    ordered: bool,
+    #[command(flatten)]
+    quic: QuicArgs,
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Args)]
+struct HostScanArgs {
+    /// IP addresses, CIDR networks or hostnames
+    #[arg(required = true)]
+    targets: Vec<String>,
+    /// Number of echo requests per host
+    #[arg(short, long, default_value_t = 1)]
+    count: u32,
+    /// Timeout in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    timeout: u64,
+    /// TTL / hop limit
+    #[arg(long, default_value_t = 64)]
+    ttl: u8,
+    /// Maximum number of hosts probed at once
+    #[arg(long)]
+    concurrency: Option<usize>,
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+/// QUIC handshake options shared by ping and portscan
+#[derive(Args)]
+struct QuicArgs {
+    /// ALPN protocols to offer, e.g. h3,hq-interop (quic, default: h3,hq-29,hq-interop)
+    #[arg(long, value_delimiter = ',')]
+    alpn: Vec<String>,
+    /// Server name to send in the handshake (quic, default: the target hostname)
+    #[arg(long)]
+    sni: Option<String>,
+    /// Fail the handshake if the certificate does not validate (quic)
+    #[arg(long)]
+    verify: bool,
+}
+
+impl QuicArgs {
+    fn option(&self) -> QuicOption {
+        let default = QuicOption::default();
+        QuicOption {
+            skip_verify: !self.verify,
+            alpn: if self.alpn.is_empty() {
+                default.alpn
+            } else {
+                self.alpn.clone()
+            },
+            server_name: self.sni.clone(),
+            zero_rtt: false,
+        }
+    }
+}
+
+/// Source selection shared by the probe commands
+#[derive(Args)]
+struct SourceArgs {
+    /// Send probes out of this interface
+    #[arg(short = 'I', long)]
+    interface: Option<String>,
+    /// Source IP address (must be assigned to this host)
+    #[arg(short = 'S', long = "source")]
+    src_ip: Option<IpAddr>,
+}
+
+impl SourceArgs {
+    fn is_set(&self) -> bool {
+        self.interface.is_some() || self.src_ip.is_some()
+    }
+
+    /// Source address for probes to `dst`, and the `src_ip` to put into the setting
+    /// (only set when a source was requested, so the default stays unbound).
+    fn select(&self, dst: IpAddr) -> Result<(IpAddr, Option<IpAddr>)> {
+        let src_ip =
+            crate::net::interface::select_source_ip(dst, self.interface.as_deref(), self.src_ip)?;
+        Ok((src_ip, self.is_set().then_some(src_ip)))
+    }
+}
+
+#[derive(Args)]
+struct NeighArgs {
+    /// Interface name (defaults to the default interface)
+    #[arg(short, long)]
+    iface: Option<String>,
+    /// Probe the local network before reading the neighbor table
+    #[arg(long)]
+    scan: bool,
+}
+
+#[derive(Args)]
+struct DnsArgs {
+    /// Hostname or IP address
+    name: String,
+    /// Query all record types (A, AAAA, MX, NS, SOA, SRV, TLSA, TXT, CERT)
+    #[arg(long)]
+    all: bool,
+}
+
+#[derive(Args)]
+struct TlsArgs {
+    /// IP address or hostname
+    host: String,
+    /// Destination port (default: 443, or the plaintext port with --starttls)
+    #[arg(short, long)]
+    port: Option<u16>,
+    /// Server name to send (default: the host if it is a hostname)
+    #[arg(long)]
+    sni: Option<String>,
+    /// Upgrade a plaintext connection first: smtp, imap or pop3
+    #[arg(long, value_parser = parse_starttls)]
+    starttls: Option<StartTls>,
+    /// Timeout in milliseconds
+    #[arg(long, default_value_t = 10000)]
+    timeout: u64,
+}
+
+#[derive(Args)]
+struct NtpArgs {
+    /// NTP servers (IP addresses or hostnames)
+    #[arg(default_value = "pool.ntp.org")]
+    servers: Vec<String>,
+    /// Destination port
+    #[arg(short, long)]
+    port: Option<u16>,
+    /// Timeout in milliseconds
+    #[arg(long, default_value_t = 5000)]
+    timeout: u64,
+}
+
+fn parse_ping_protocol(s: &str) -> Result<PingProtocol, String> {
+    PingProtocol::from_str(s).map_err(|_| format!("unknown protocol: {s}"))
+}
+
+fn parse_http_method(s: &str) -> Result<HttpMethod, String> {
+    HttpMethod::from_str(s).map_err(|_| format!("unknown method: {s}"))
+}
+
+fn parse_dns_transport(s: &str) -> Result<DnsTransport, String> {
+    DnsTransport::from_str(s).map_err(|_| format!("unknown transport: {s}"))
+}
+
+fn parse_udp_mode(s: &str) -> Result<UdpPingMode, String> {
+    UdpPingMode::from_str(s).map_err(|_| format!("unknown UDP mode: {s}"))
+}
+
+fn parse_http_header(s: &str) -> Result<HttpHeader, String> {
+    match s.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => Ok(HttpHeader {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        }),
+        _ => Err(format!("invalid header (expected \"Name: value\"): {s}")),
+    }
+}
+
+fn parse_starttls(s: &str) -> Result<StartTls, String> {
+    StartTls::from_str(s).map_err(|_| format!("unknown protocol: {s}"))
+}
+
+fn parse_trace_protocol(s: &str) -> Result<TraceProtocol, String> {
+    match s.to_lowercase().as_str() {
+        "icmp" => Ok(TraceProtocol::Icmp),
+        "udp" => Ok(TraceProtocol::Udp),
+        _ => Err(format!("unknown protocol: {s}")),
+    }
+}
+
+fn parse_portscan_protocol(s: &str) -> Result<PortScanProtocol, String> {
+    match s.to_lowercase().as_str() {
+        "tcp" => Ok(PortScanProtocol::Tcp),
+        "quic" => Ok(PortScanProtocol::Quic),
+        _ => Err(format!("unknown protocol: {s}")),
+    }
+}
+
+fn parse_ports_preset(s: &str) -> Result<TargetPortsPreset, String> {
+    match s.to_lowercase().as_str() {
+        "common" => Ok(TargetPortsPreset::Common),
+        "wellknown" | "well-known" => Ok(TargetPortsPreset::WellKnown),
+        "top1000" => Ok(TargetPortsPreset::Top1000),
+        "full" => Ok(TargetPortsPreset::Full),
+        "custom" => Ok(TargetPortsPreset::Custom),
+        _ => Err(format!("unknown preset: {s}")),
+    }
+}
+
+/// Parse a comma separated list of ports and ranges, e.g. `22,80,8000-8100`.
+fn parse_ports(s: &str) -> Result<Vec<u16>, String> {
+    let mut ports = Vec::new();
+    for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start: u16 = start
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid port: {part}"))?;
+                let end: u16 = end
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid port: {part}"))?;
+                if start > end {
+                    return Err(format!("invalid port range: {part}"));
+                }
+                ports.extend(start..=end);
+            }
+            None => ports.push(part.parse().map_err(|_| format!("invalid port: {part}"))?),
+        }
+    }
+    Ok(ports)
+}
+
+/// Resolve a target given as an IP address or hostname.
+async fn resolve_target(target: &str) -> Result<(IpAddr, Option<String>)> {
+    crate::net::dns::resolve_target(target, DNS_TIMEOUT).await
+}
+
+/// Entry point of the `netpulsar-cli` binary.
+pub fn run() -> ExitCode {
+    let cli = Cli::parse();
+    let rt = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("Failed to start runtime: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match rt.block_on(execute(cli)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn execute(cli: Cli) -> Result<()> {
+    let json = cli.json;
+    // Ctrl-C stops the running probe, which then reports its partial result
+    let token = CancellationToken::new();
+    let ctrl_c_token = token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            ctrl_c_token.cancel();
+        }
+    });
+    match cli.command {
+        Command::Ping(args) => ping(*args, json, &token).await,
+        Command::Multiping(args) => multi_ping(args, json, &token).await,
+        Command::Trace(args) => trace(args, json, &token).await,
+        Command::Mtr(args) => mtr(args, json, &token).await,
+        Command::Pmtu(args) => pmtu(args, json, &token).await,
+        Command::Portscan(args) => port_scan(args, json, &token).await,
+        Command::Hostscan(args) => host_scan(args, json, &token).await,
+        Command::Neigh(args) => neigh(args, json, &token).await,
+        Command::Dns(args) => dns(args, json).await,
+        Command::Tls(args) => tls(args, json).await,
+        Command::Ntp(args) => ntp(args, json).await,
+        Command::Routes => routes(json),
+        Command::Sockets => sockets(json),
+        Command::Ifaces => ifaces(json),
+    }
+}
+
+async fn ping(args: PingArgs, json: bool, token: &CancellationToken) -> Result<()> {
+    let (ip_addr, hostname, port) =
+        crate::probe::ping::resolve_target(&args.target, args.protocol, args.port, DNS_TIMEOUT)
+            .await?;
+    let (src_ip, pinned_src) = args.source.select(ip_addr)?;
+    let http = (args.protocol == PingProtocol::Http).then(|| HttpPingOption {
+        method: args.method.unwrap_or_default(),
+        headers: args.headers,
+        expected_status: args.expect_status,
+        body_contains: args.expect_body,
+    });
+    let dns = (args.protocol == PingProtocol::Dns).then(|| {
+        let default = DnsPingOption::default();
+        DnsPingOption {
+            transport: args.transport.unwrap_or_default(),
+            query_name: args.query.unwrap_or(default.query_name),
+            record_type: args.qtype.unwrap_or(default.record_type),
+            recursion_desired: !args.norecurse,
+            doh_path: args.doh_path.unwrap_or(default.doh_path),
+        }
+    });
+    let udp = (args.protocol == PingProtocol::Udp).then(|| {
+        let default = UdpPingOption::default();
+        UdpPingOption {
+            mode: args.udp_mode.unwrap_or_default(),
+            payload_hex: args.payload,
+            snmp_community: args.community.unwrap_or(default.snmp_community),
+        }
+    });
+    let icmp = (args.protocol == PingProtocol::Icmp).then(|| IcmpPingOption {
+        payload_size: args.size.unwrap_or(DEFAULT_ICMP_PAYLOAD_SIZE),
+        pattern_hex: args.pattern,
+    });
+    let quic = (args.protocol == PingProtocol::Quic).then(|| QuicOption {
+        zero_rtt: args.zero_rtt,
+        ..args.quic.option()
+    });
+    let setting = PingSetting {
+        ip_addr,
+        hostname,
+        port,
+        hop_limit: args.ttl,
+        protocol: args.protocol,
+        count: args.count.max(1),
+        timeout_ms: args.timeout,
+        send_rate_ms: args.interval,
+        interface: args.source.interface.clone(),
+        src_ip: pinned_src,
+        http,
+        dns,
+        quic,
+        udp,
+        icmp,
+    };
+
+    let sink = CliEventSink::new(json);
+    let run_id = uuid::Uuid::new_v4().to_string();
+    sink.emit(ProbeEvent::PingStart(PingStartPayload {
+        run_id: run_id.clone(),
+        setting: setting.clone(),
+    }));
+    if !json {
+        println!(
+            "{} ping to {} ({})",
+            setting.protocol,
+            setting.hostname.as_deref().unwrap_or(&args.target),
+            setting.ip_addr
+        );
+    }
+
+    let stat: PingStat = crate::probe::ping::ping(&sink, &run_id, token, src_ip, setting).await?;
+
+    if !json {
+        println!();
+        println!(
+            "{} transmitted, {} received, {:.1}% loss",
+            stat.transmitted_count,
+            stat.received_count,
+            stat.loss_rate() * 100.0
+        );
+        if stat.duplicate_count > 0 || stat.late_count > 0 {
+            println!(
+                "+{} duplicates, +{} late replies",
+                stat.duplicate_count, stat.late_count
+            );
+        }
+        if let (Some(min), Some(avg), Some(max), Some(stddev)) =
+            (stat.min, stat.avg, stat.max, stat.stddev)
+        {
+            println!(
+                "rtt min/avg/max/stddev = {}/{}/{}/{} ms",
+                min, avg, max, stddev
+            );
+        }
+        if let (Some(p50), Some(p90), Some(p99)) = (stat.p50, stat.p90, stat.p99) {
+            println!("rtt p50/p90/p99 = {}/{}/{} ms", p50, p90, p99);
+        }
+        let offsets: Vec<f64> = stat
+            .samples
+            .iter()
+            .filter(|s| s.is_received())
+            .filter_map(|s| s.ntp.as_ref().map(|n| n.offset_ms))
+            .collect();
+        if !offsets.is_empty() {
+            let min = offsets.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = offsets.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let avg = offsets.iter().sum::<f64>() / offsets.len() as f64;
+            // Change of the offset from the first to the last reply
+            let drift = offsets[offsets.len() - 1] - offsets[0];
+            println!(
+                "offset min/avg/max = {:+.3}/{:+.3}/{:+.3} ms, drift {:+.3} ms",
+                min, avg, max, drift
+            );
+        }
+        if let Some(jitter) = stat.jitter {
+            println!("jitter = {} ms", jitter);
+        }
+        if stat.max_loss_burst > 1 {
+            println!("longest loss burst = {} probes", stat.max_loss_burst);
+        }
+    }
+    if stat.received_count == 0 {
+        bail!("no response from {}", stat.ip_addr);
+    }
+    Ok(())
+}
+
+async fn multi_ping(args: MultiPingArgs, json: bool, token: &CancellationToken) -> Result<()> {
+    let targets = crate::probe::ping::multi::resolve_targets(&args.targets, DNS_TIMEOUT).await?;
+    if targets.is_empty() {
+        bail!("no targets to ping");
+    }
+    let ips: Vec<IpAddr> = targets.iter().map(|t| t.ip_addr).collect();
+    let (src_ipv4, src_ipv6) = crate::net::interface::select_source_pair(
+        args.source.interface.as_deref(),
+        args.source.src_ip,
+        &ips,
+    )?;
+    let setting = MultiPingSetting {
+        targets: args.targets,
+        hop_limit: args.ttl,
+        count: args.count.max(1),
+        timeout_ms: args.timeout,
+        send_rate_ms: args.interval,
+        interface: args.source.interface.clone(),
+        src_ip: args.source.src_ip,
+    };
+
+    let sink = CliEventSink::new(json);
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let report = crate::probe::ping::multi::multi_ping(
+        &sink, &run_id, token, src_ipv4, src_ipv6, targets, setting,
+    )
+    .await?;
+    if json {
+        return Ok(());
+    }
+
+    println!();
+    let mut table = Table::new(&[
+        "HOST", "SENT", "RECV", "LOSS", "MIN", "AVG", "MAX", "JITTER",
+    ]);
+    for stat in &report.stats {
+        let host = match &stat.hostname {
+            Some(name) => format!("{} ({})", name, stat.ip_addr),
+            None => stat.ip_addr.to_string(),
+        };
+        table.add_row(vec![
+            host,
+            stat.transmitted_count.to_string(),
+            stat.received_count.to_string(),
+            format!("{:.1}%", stat.loss_rate() * 100.0),
+            opt(stat.min),
+            opt(stat.avg),
+            opt(stat.max),
+            opt(stat.jitter),
+        ]);
+    }
+    table.print();
+    let alive = report.stats.iter().filter(|s| s.received_count > 0).count();
+    println!("{} of {} hosts responded", alive, report.stats.len());
+    Ok(())
+}
+
+async fn trace(args: TraceArgs, json: bool, token: &CancellationToken) -> Result<()> {
+    let (ip_addr, hostname) = resolve_target(&args.target).await?;
+    let (src_ip, pinned_src) = args.source.select(ip_addr)?;
+    let setting = TracerouteSetting {
+        ip_addr,
+        hostname,
+        max_hops: args.max_hops,
+        tries_per_hop: args.tries,
+        timeout_ms: args.timeout,
+        protocol: args.protocol,
+        interface: args.source.interface.clone(),
+        src_ip: pinned_src,
+    };
+    if !json {
+        println!(
+            "traceroute to {} ({}), {} hops max",
+            args.target, ip_addr, setting.max_hops
+        );
+    }
+    let sink = CliEventSink::new(json);
+    let run_id = uuid::Uuid::new_v4().to_string();
+    crate::probe::trace::traceroute(&sink, &run_id, token, src_ip, setting).await?;
+    Ok(())
+}
+
+async fn mtr(args: MtrArgs, json: bool, token: &CancellationToken) -> Result<()> {
+    let (ip_addr, hostname) = resolve_target(&args.target).await?;
+    let (src_ip, pinned_src) = args.source.select(ip_addr)?;
+    let setting = MtrSetting {
+        ip_addr,
+        hostname,
+        max_hops: args.max_hops,
+        rounds: args.rounds,
+        interval_ms: args.interval,
+        timeout_ms: args.timeout,
+        interface: args.source.interface.clone(),
+        src_ip: pinned_src,
+    };
+    if !json {
+        println!(
+            "mtr to {} ({}), {} hops max",
+            args.target, ip_addr, setting.max_hops
+        );
+    }
+    let sink = CliEventSink::new(json);
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let report = crate::probe::trace::mtr::mtr(&sink, &run_id, token, src_ip, setting).await?;
+    if json {
+        return Ok(());
+    }
+
+    println!();
+    let mut table = Table::new(&[
+        "HOP", "HOST", "LOSS", "SENT", "LAST", "AVG", "BEST", "WORST", "STDEV",
+    ]);
+    for hop in &report.hops {
+        let host = match hop.ip_addr {
+            Some(ip) if hop.ip_addrs.len() > 1 => {
+                format!("{} (+{})", ip, hop.ip_addrs.len() - 1)
+            }
+            Some(ip) => ip.to_string(),
+            None => "???".to_string(),
+        };
+        table.add_row(vec![
+            hop.hop.to_string(),
+            host,
+            format!("{:.1}%", hop.loss_pct),
+            hop.sent.to_string(),
+            opt(hop.last_ms),
+            opt(hop.avg_ms),
+            opt(hop.best_ms),
+            opt(hop.worst_ms),
+            opt(hop.stddev_ms),
+        ]);
+    }
+    table.print();
+    for hop in report.hops.iter().filter(|h| h.note.is_some()) {
+        println!(
+            "{:>3}: {}",
+            hop.hop,
+            hop.note.as_deref().unwrap_or_default()
+        );
+    }
+    if !report.reached {
+        println!("destination not reached");
+    }
+    if let Some(note) = &report.note {
+        println!("stopped after {} rounds: {}", report.rounds, note);
+    }
+    Ok(())
+}
+
+async fn pmtu(args: PmtuArgs, json: bool, token: &CancellationToken) -> Result<()> {
+    let (ip_addr, hostname) = resolve_target(&args.target).await?;
+    let (src_ip, pinned_src) = args.source.select(ip_addr)?;
+    let setting = PmtuSetting {
+        ip_addr,
+        hostname,
+        max_mtu: args.max_mtu,
+        hop_limit: args.ttl,
+        timeout_ms: args.timeout,
+        tries: args.tries,
+        interface: args.source.interface.clone(),
+        src_ip: pinned_src,
+    };
+    if !json {
+        println!("path MTU discovery to {} ({})", args.target, ip_addr);
+    }
+    let sink = CliEventSink::new(json);
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let report = crate::probe::pmtu::pmtu_discover(&sink, &run_id, token, src_ip, setting).await?;
+    if json {
+        return Ok(());
+    }
+
+    println!();
+    match report.path_mtu {
+        Some(mtu) => println!("path MTU = {} bytes", mtu),
+        None => println!("path MTU = unknown"),
+    }
+    if let Some(hop) = report.constraining_hop {
+        println!("constrained by {}", hop);
+    }
+    if let (Some(name), Some(mtu)) = (&report.interface, report.interface_mtu) {
+        let note = if report.below_interface_mtu {
+            " (the path is narrower than the local link)"
+        } else if report.path_mtu == Some(mtu) {
+            " (the local link is the limit)"
+        } else {
+            ""
+        };
+        println!("interface {} MTU = {} bytes{}", name, mtu, note);
+    }
+    Ok(())
+}
+
+async fn port_scan(args: PortScanArgs, json: bool, token: &CancellationToken) -> Result<()> {
+    let (ip_addr, hostname) = resolve_target(&args.target).await?;
+    let user_ports = args.ports.unwrap_or_default();
+    let preset = args.preset.unwrap_or(if user_ports.is_empty() {
+        TargetPortsPreset::Common
+    } else {
+        TargetPortsPreset::Custom
+    });
+    let (src_ip, pinned_src) = args.source.select(ip_addr)?;
+    let setting = PortScanSetting {
+        ip_addr,
+        hostname,
+        target_ports_preset: preset,
+        user_ports,
+        protocol: args.protocol,
+        timeout_ms: args.timeout,
+        ordered: args.ordered,
+        interface: args.source.interface.clone(),
+        src_ip: pinned_src,
+        quic: matches!(args.protocol, PortScanProtocol::Quic).then(|| args.quic.option()),
+    };
+
+    let sink = CliEventSink::new(json);
+    let run_id = uuid::Uuid::new_v4().to_string();
+    sink.emit(ProbeEvent::PortScanStart(PortScanStartPayload {
+        run_id: run_id.clone(),
+    }));
+    let report = match setting.protocol {
+        PortScanProtocol::Tcp => {
+            crate::probe::scan::tcp::port_scan(&sink, &run_id, token, src_ip, setting).await?
+        }
+        PortScanProtocol::Quic => {
+            crate::probe::scan::quic::port_scan(&sink, &run_id, token, src_ip, setting).await?
+        }
+    };
+    if json {
+        return Ok(());
+    }
+
+    let mut open: Vec<_> = report
+        .samples
+        .iter()
+        .filter(|s| s.state == PortState::Open)
+        .collect();
+    open.sort_by_key(|s| s.port);
+    println!("Port scan report for {}", report.ip_addr);
+    let mut table = Table::new(&["PORT", "STATE", "SERVICE", "RTT(ms)"]);
+    for s in &open {
+        table.add_row(vec![
+            s.port.to_string(),
+            "open".to_string(),
+            s.service_name.clone().unwrap_or_default(),
+            opt(s.rtt_ms),
+        ]);
+    }
+    table.print();
+    println!(
+        "{} open of {} scanned ports",
+        open.len(),
+        report.samples.len()
+    );
+    Ok(())
+}
+
+async fn host_scan(args: HostScanArgs, json: bool, token: &CancellationToken) -> Result<()> {
+    let mut targets: Vec<IpAddr> = Vec::new();
+    for target in &args.targets {
+        if let Ok(net) = target.parse::<netdev::ipnet::IpNet>() {
+            targets.extend(net.hosts());
+        } else {
+            let (ip, _) = resolve_target(target).await?;
+            targets.push(ip);
+        }
+    }
+    // Drop duplicates, keeping the first occurrence of each address
+    let mut seen = HashSet::new();
+    targets.retain(|ip| seen.insert(*ip));
+
+    let setting = HostScanSetting {
+        targets,
+        hop_limit: args.ttl,
+        timeout_ms: args.timeout,
+        count: args.count.max(1),
+        payload: None,
+        ordered: true,
+        concurrency: args.concurrency,
+        interface: args.source.interface.clone(),
+        src_ip: args.source.src_ip,
+    };
+    let (src_ipv4_opt, src_ipv6_opt) = crate::net::interface::select_source_pair(
+        args.source.interface.as_deref(),
+        args.source.src_ip,
+        &setting.targets,
+    )?;
+
+    let sink = CliEventSink::new(json);
+    let run_id = uuid::Uuid::new_v4().to_string();
+    sink.emit(ProbeEvent::HostScanStart(HostScanStartPayload {
+        run_id: run_id.clone(),
+    }));
+    let report = crate::probe::scan::icmp::host_scan(
+        &sink,
+        &run_id,
+        token,
+        src_ipv4_opt,
+        src_ipv6_opt,
+        setting,
+    )
+    .await?;
+    if json {
+        return Ok(());
+    }
+
+    let mut alive = report.alive.clone();
+    alive.sort_by_key(|(ip, _)| *ip);
+    let mut table = Table::new(&["HOST", "RTT(ms)"]);
+    for (ip, rtt) in &alive {
+        table.add_row(vec![ip.to_string(), rtt.to_string()]);
+    }
+    table.print();
+    println!("{} alive of {} hosts", alive.len(), report.total);
+    Ok(())
+}
+
+async fn neigh(args: NeighArgs, json: bool, token: &CancellationToken) -> Result<()> {
+    let iface = match &args.iface {
+        Some(name) => netdev::get_interfaces()
+            .into_iter()
+            .find(|i| &i.name == name)
+            .ok_or_else(|| anyhow::anyhow!("interface not found: {name}"))?,
+        None => netdev::get_default_interface().map_err(|e| anyhow::anyhow!(e))?,
+    };
+
+    if args.scan {
+        let sink = CliEventSink::new(json);
+        let run_id = uuid::Uuid::new_v4().to_string();
+        sink.emit(ProbeEvent::NeighborScanStart(run_id.clone()));
+        let report = crate::probe::scan::neigh::neighbor_scan(&sink, &run_id, token, iface).await?;
+        if json {
+            print_json_line(&report);
+            return Ok(());
+        }
+        let mut table = Table::new(&["IP ADDRESS", "MAC ADDRESS", "VENDOR", "RTT(ms)", "TAGS"]);
+        for n in &report.neighbors {
+            table.add_row(vec![
+                n.ip_addr.to_string(),
+                opt(n.mac_addr),
+                n.vendor.clone().unwrap_or_default(),
+                opt(n.rtt_ms),
+                n.tags.join(","),
+            ]);
+        }
+        table.print();
+        return Ok(());
+    }
+
+    let mut entries: Vec<_> = crate::net::neigh::get_neighbor_table()?
+        .into_iter()
+        .collect();
+    entries.sort_by_key(|(ip, _)| *ip);
+    if json {
+        for (ip, mac) in &entries {
+            print_json_line(&serde_json::json!({ "ip_addr": ip, "mac_addr": mac }));
+        }
+        return Ok(());
+    }
+    let mut table = Table::new(&["IP ADDRESS", "MAC ADDRESS"]);
+    for (ip, mac) in &entries {
+        table.add_row(vec![ip.to_string(), mac.to_string()]);
+    }
+    table.print();
+    Ok(())
+}
+
+async fn dns(args: DnsArgs, json: bool) -> Result<()> {
+    if let Ok(ip) = args.name.parse::<IpAddr>() {
+        let name = crate::net::dns::reverse_lookup(ip, DNS_TIMEOUT)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("failed to perform reverse lookup"))?;
+        if json {
+            print_json_line(&serde_json::json!({ "ip_addr": ip, "hostname": name }));
+        } else {
+            println!("{} -> {}", ip, name);
+        }
+        return Ok(());
+    }
+
+    if args.all {
+        let info = DnsResolver::new()?.lookup_all(&args.name).await?;
+        if json {
+            print_json_line(&info);
+            return Ok(());
+        }
+        let mut table = Table::new(&["TYPE", "VALUE"]);
+        for a in &info.a {
+            table.add_row(vec!["A".into(), a.to_string()]);
+        }
+        for aaaa in &info.aaaa {
+            table.add_row(vec!["AAAA".into(), aaaa.to_string()]);
+        }
+        for mx in &info.mx {
+            table.add_row(vec![
+                "MX".into(),
+                format!("{} {}", mx.preference, mx.exchange),
+            ]);
+        }
+        for ns in &info.ns {
+            table.add_row(vec!["NS".into(), ns.clone()]);
+        }
+        for soa in &info.soa {
+            table.add_row(vec![
+                "SOA".into(),
+                format!("{} {} {}", soa.mname, soa.rname, soa.serial),
+            ]);
+        }
+        for srv in &info.srv {
+            table.add_row(vec![
+                "SRV".into(),
+                format!(
+                    "{} {} {} {}",
+                    srv.priority, srv.weight, srv.port, srv.target
+                ),
+            ]);
+        }
+        for tlsa in &info.tlsa {
+            table.add_row(vec![
+                "TLSA".into(),
+                format!(
+                    "{} {} {} {}",
+                    tlsa.cert_usage, tlsa.selector, tlsa.matching, tlsa.cert_data_base64
+                ),
+            ]);
+        }
+        for txt in &info.txt {
+            let value = if txt.key.is_empty() {
+                txt.value.clone()
+            } else {
+                format!("{}={}", txt.key, txt.value)
+            };
+            table.add_row(vec!["TXT".into(), value]);
+        }
+        for cert in &info.cert {
+            table.add_row(vec![
+                "CERT".into(),
+                format!("{} {} {}", cert.cert_type, cert.key_tag, cert.algorithm),
+            ]);
+        }
+        table.print();
+        return Ok(());
+    }
+
+    let domain = crate::net::dns::lookup_domain(&args.name, DNS_TIMEOUT).await;
+    if domain.ips.is_empty() {
+        bail!("failed to resolve IP addresses");
+    }
+    if json {
+        print_json_line(&domain);
+        return Ok(());
+    }
+    for ip in &domain.ips {
+        println!("{} -> {}", domain.name, ip);
+    }
+    Ok(())
+}
+
+async fn tls(args: TlsArgs, json: bool) -> Result<()> {
+    let report = crate::net::tls::inspect(
+        &args.host,
+        args.port,
+        args.sni.as_deref(),
+        args.starttls,
+        Duration::from_millis(args.timeout),
+    )
+    .await?;
+    if json {
+        print_json_line(&report);
+        return Ok(());
+    }
+    let via = report
+        .starttls
+        .map(|p| format!(" via {} STARTTLS", p))
+        .unwrap_or_default();
+    println!(
+        "TLS to {} ({}) port {}{}",
+        report.host, report.ip_addr, report.port, via
+    );
+    println!(
+        "{} {} alpn={} sni={}",
+        report.version,
+        report.cipher_suite,
+        opt(report.alpn.as_deref()),
+        opt(report.sni.as_deref())
+    );
+    println!(
+        "connect={} handshake={} ms",
+        report.connect_ms, report.handshake_ms
+    );
+    match &report.validation_error {
+        None => println!("chain trusted by the native root store"),
+        Some(e) => println!("chain NOT trusted: {}", e),
+    }
+    for (i, cert) in report.chain.iter().enumerate() {
+        println!();
+        if let Some(e) = &cert.parse_error {
+            println!("[{}] unparsed certificate: {}", i, e);
+            println!("    sha256   {}", cert.sha256);
+            println!("    sha1     {}", cert.sha1);
+            continue;
+        }
+        println!("[{}] {}", i, cert.subject);
+        println!("    issuer   {}", cert.issuer);
+        if !cert.sans.is_empty() {
+            println!("    names    {}", cert.sans.join(", "));
+        }
+        let state = if cert.expired {
+            "expired".to_string()
+        } else if cert.not_yet_valid {
+            "not yet valid".to_string()
+        } else {
+            format!("{} days left", cert.days_remaining)
+        };
+        println!(
+            "    valid    {} - {} ({})",
+            cert.not_before, cert.not_after, state
+        );
+        println!(
+            "    key      {}{}, {}",
+            cert.key_type,
+            cert.key_bits
+                .map(|b| format!(" ({} bits)", b))
+                .unwrap_or_default(),
+            cert.signature_algorithm
+        );
+        println!("    serial   {}", cert.serial);
+        println!("    sha256   {}", cert.sha256);
+        println!("    sha1     {}", cert.sha1);
+    }
+    Ok(())
+}
+
+async fn ntp(args: NtpArgs, json: bool) -> Result<()> {
+    let results = crate::net::ntp::query_servers(
+        &args.servers,
+        args.port,
+        Duration::from_millis(args.timeout),
+    )
+    .await;
+    if json {
+        print_json_line(&results);
+        return Ok(());
+    }
+    let mut table = Table::new(&[
+        "SERVER", "ADDRESS", "ST", "REFID", "OFFSET", "DELAY", "DISP", "LEAP",
+    ]);
+    for r in &results {
+        let address = opt(r.ip_addr);
+        match (&r.response, &r.error) {
+            (Some(resp), _) => table.add_row(vec![
+                r.server.clone(),
+                address,
+                resp.stratum.to_string(),
+                resp.reference_id.clone(),
+                format!("{:+.3}", resp.offset_ms),
+                format!("{:.3}", resp.delay_ms),
+                format!("{:.3}", resp.root_dispersion_ms),
+                resp.leap.to_string(),
+            ]),
+            (None, error) => table.add_row(vec![
+                r.server.clone(),
+                address,
+                "-".to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                opt(error.as_deref()),
+            ]),
+        }
+    }
+    table.print();
+    println!(
+        "offset, delay and dispersion in ms; a positive offset means the local clock is behind"
+    );
+    Ok(())
+}
+
+fn routes(json: bool) -> Result<()> {
+    let routes = crate::net::route::list_routes()?;
+    if json {
+        for route in &routes {
+            print_json_line(route);
+        }
+        return Ok(());
+    }
+    let mut table = Table::new(&["DESTINATION", "GATEWAY", "INTERFACE", "METRIC"]);
+    for r in &routes {
+        table.add_row(vec![
+            format!("{}/{}", r.destination.addr, r.destination.prefix_len),
+            r.gateway
+                .map(|gw| gw.to_string())
+                .unwrap_or_else(|| "on-link".to_string()),
+            r.ifname
+                .clone()
+                .or_else(|| r.ifindex.map(|i| i.to_string()))
+                .unwrap_or_default(),
+            opt(r.metric),
+        ]);
+    }
+    table.print();
+    Ok(())
+}
+
+fn sockets(json: bool) -> Result<()> {
+    let af = AddressFamilyFlags::IPV4 | AddressFamilyFlags::IPV6;
+    let pf = ProtocolFlags::TCP | ProtocolFlags::UDP;
+    let sockets = get_sockets(af, pf)?;
+    if json {
+        for socket in &sockets {
+            print_json_line(socket);
+        }
+        return Ok(());
+    }
+    let mut table = Table::new(&["PROTO", "LOCAL", "REMOTE", "STATE", "PROCESS"]);
+    for s in &sockets {
+        let process = s
+            .processes
+            .iter()
+            .map(|p| format!("{}({})", p.name, p.pid))
+            .collect::<Vec<_>>()
+            .join(",");
+        match &s.protocol_socket_info {
+            ProtocolSocketInfo::Tcp(tcp) => table.add_row(vec![
+                "TCP".to_string(),
+                format_endpoint(tcp.local_addr, tcp.local_port),
+                format_endpoint(tcp.remote_addr, tcp.remote_port),
+                tcp.state.to_string(),
+                process,
+            ]),
+            ProtocolSocketInfo::Udp(udp) => table.add_row(vec![
+                "UDP".to_string(),
+                format_endpoint(udp.local_addr, udp.local_port),
+                "-".to_string(),
+                "-".to_string(),
+                process,
+            ]),
+        }
+    }
+    table.print();
+    Ok(())
+}
+
+fn format_endpoint(ip: IpAddr, port: u16) -> String {
+    match ip {
+        IpAddr::V4(v4) => format!("{}:{}", v4, port),
+        IpAddr::V6(v6) => format!("[{}]:{}", v6, port),
+    }
+}
+
+fn ifaces(json: bool) -> Result<()> {
+    let ifaces = crate::net::interface::list_interfaces();
+    if json {
+        for iface in &ifaces {
+            print_json_line(iface);
+        }
+        return Ok(());
+    }
+    let mut table = Table::new(&["INDEX", "NAME", "STATE", "MAC", "IPV4", "IPV6", "DEFAULT"]);
+    for iface in &ifaces {
+        table.add_row(vec![
+            iface.index.to_string(),
+            crate::net::interface::get_display_name(iface),
+            iface.oper_state.to_string(),
+            opt(iface.mac_addr),
+            iface
+                .ipv4
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            iface
+                .ipv6
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            if iface.default {
+                "*".to_string()
+            } else {
+                String::new()
+            },
+        ]);
+    }
+    table.print();
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use serde::Serialize;
+
+use crate::model::ping::PingSample;
+use crate::model::pmtu::PmtuProbeResult;
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+
+/// Plain text table with left aligned columns.
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn print(&self) {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                if i < widths.len() {
+                    widths[i] = widths[i].max(cell.chars().count());
+                }
+            }
+        }
+        print_row(&self.headers, &widths);
+        let sep: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        print_row(&sep, &widths);
+        for row in &self.rows {
+            print_row(row, &widths);
+        }
+    }
+}
+
+fn print_row(cells: &[String], widths: &[usize]) {
+    let line = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, w)| format!("{:<width$}", cell, width = *w))
+        .collect::<Vec<_>>()
+        .join("  ");
+    println!("{}", line.trim_end());
+}
+
+/// Print a value as a single line of JSON.
+pub fn print_json_line<T: Serialize>(value: &T) {
+    match serde_json::to_string(value) {
+        Ok(line) => println!("{}", line),
+        Err(e) => eprintln!("Failed to serialize output: {}", e),
+    }
+}
+
+/// Format an optional value, using `-` when absent.
+pub fn opt<T: std::fmt::Display>(value: Option<T>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Sink that writes probe events to stdout.
+///
+/// In JSON mode every event is printed as `{"event": ..., "data": ...}`.
+/// Otherwise only per-sample events are printed; summaries are rendered
+/// from the values returned by the probes.
+pub struct CliEventSink {
+    json: bool,
+}
+
+impl CliEventSink {
+    pub fn new(json: bool) -> Self {
+        Self { json }
+    }
+}
+
+impl ProbeEventSink for CliEventSink {
+    fn emit(&self, event: ProbeEvent) {
+        if self.json {
+            print_json_line(&serde_json::json!({ "event": event.name(), "data": event }));
+            return;
+        }
+        match event {
+            ProbeEvent::PingProgress(p) => print_ping_sample(&p.sample),
+            ProbeEvent::MultiPingProgress(p) => print_ping_sample(&p.sample),
+            ProbeEvent::PmtuProgress(p) => match p.result {
+                PmtuProbeResult::Ok => println!("size={}: ok time={} ms", p.size, opt(p.rtt_ms)),
+                PmtuProbeResult::TooBig => match (p.reported_mtu, p.from) {
+                    (Some(mtu), Some(from)) => {
+                        println!("size={}: too big (mtu {} from {})", p.size, mtu, from)
+                    }
+                    _ => println!("size={}: too big {}", p.size, p.message.unwrap_or_default()),
+                },
+                _ => println!(
+                    "size={}: {:?} {}",
+                    p.size,
+                    p.result,
+                    p.message.unwrap_or_default()
+                ),
+            },
+            ProbeEvent::TraceProgress(hop) => {
+                println!(
+                    "{:>3}  {:<39}  {}",
+                    hop.hop,
+                    opt(hop.ip_addr),
+                    match hop.rtt_ms {
+                        Some(rtt) => format!("{} ms", rtt),
+                        None => hop.note.unwrap_or_else(|| "*".to_string()),
+                    }
+                );
+            }
+            ProbeEvent::MtrSnapshot(s) => {
+                let answered = s.hops.iter().filter(|h| h.last_ms.is_some()).count();
+                println!(
+                    "round {}: {} hops, {} answered{}",
+                    s.round,
+                    s.hops.len(),
+                    answered,
+                    if s.reached {
+                        ""
+                    } else {
+                        ", destination not reached"
+                    }
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+fn print_ping_sample(s: &PingSample) {
+    let flag = match &s.icmp {
+        Some(d) if d.duplicate => " (DUP!)",
+        Some(d) if d.late => " (late)",
+        _ => "",
+    };
+    match s.rtt_ms {
+        Some(rtt) if s.probe_status.is_ok() => {
+            println!("seq={} from {}: time={} ms{}", s.seq, s.ip_addr, rtt, flag)
+        }
+        _ => println!(
+            "seq={} from {}: {} {}",
+            s.seq, s.ip_addr, s.probe_status.kind, s.probe_status.message
+        ),
+    }
+    if let Some(h) = &s.http {
+        println!(
+            "  {} {} dns={} connect={} tls={} ttfb={} download={} total={} ms, {} bytes",
+            opt(h.version.as_deref()),
+            opt(h.status),
+            opt(h.dns_ms),
+            opt(h.connect_ms),
+            opt(h.tls_ms),
+            opt(h.ttfb_ms),
+            opt(h.download_ms),
+            opt(h.total_ms),
+            opt(h.body_bytes)
+        );
+    }
+    if let Some(i) = &s.icmp {
+        println!("  {} bytes ttl={}", i.bytes, opt(i.ttl));
+    }
+    if let Some(d) = &s.dns {
+        let tc = if d.truncated { " (truncated)" } else { "" };
+        println!(
+            "  {} {} answers={} connect={} ms{}",
+            d.transport,
+            opt(d.rcode.as_deref()),
+            opt(d.answer_count),
+            opt(d.connect_ms),
+            tc
+        );
+    }
+    if let Some(n) = &s.ntp {
+        println!(
+            "  offset={:+.3} delay={:.3} ms stratum={} refid={} leap={} root_dispersion={:.3} ms",
+            n.offset_ms, n.delay_ms, n.stratum, n.reference_id, n.leap, n.root_dispersion_ms
+        );
+    }
+    if let Some(q) = &s.quic {
+        let trust = match &q.validation_error {
+            None => "trusted".to_string(),
+            Some(e) => format!("untrusted ({})", e),
+        };
+        let zero_rtt = match q.zero_rtt_accepted {
+            Some(true) => " 0-RTT=accepted",
+            Some(false) => " 0-RTT=rejected",
+            None => "",
+        };
+        println!(
+            "  QUIC {} alpn={} sni={} {}{}",
+            q.version,
+            opt(q.alpn.as_deref()),
+            q.server_name,
+            trust,
+            zero_rtt
+        );
+        if let Some(c) = &q.certificate {
+            println!(
+                "  cert {} (issuer {}), {} days remaining",
+                c.subject, c.issuer, c.days_remaining
+            );
+        }
+    }
+}
@@ -1,11 +1,39 @@
 use std::path::{Path, PathBuf};
 use tauri::State;
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, ConfigError};
 
-#[derive(Default)]
-pub struct ConfigState(pub RwLock<AppConfig>);
+/// In-memory config. Changes are broadcast to background services
+/// (e.g. the supervisor) through a watch channel.
+pub struct ConfigState(pub RwLock<AppConfig>, watch::Sender<AppConfig>);
+
+impl ConfigState {
+    pub fn new(cfg: AppConfig) -> Self {
+        let (tx, _) = watch::channel(cfg.clone());
+        Self(RwLock::new(cfg), tx)
+    }
+
+    /// Receiver that observes every config update.
+    pub fn subscribe(&self) -> watch::Receiver<AppConfig> {
+        self.1.subscribe()
+    }
+
+    /// Replace the in-memory config and notify subscribers.
+    pub async fn set(&self, cfg: AppConfig) {
+        {
+            let mut write = self.0.write().await;
+            *write = cfg.clone();
+        }
+        self.1.send_replace(cfg);
+    }
+}
+
+impl Default for ConfigState {
+    fn default() -> Self {
+        Self::new(AppConfig::default())
+    }
+}
 
 #[tauri::command]
 pub async fn get_config(state: State<'_, ConfigState>) -> Result<AppConfig, String> {
@@ -20,21 +48,16 @@ pub async fn get_config(state: State<'_, ConfigState>) -> Result<AppConfig, Stri
 #[tauri::command]
 pub async fn reload_config(state: State<'_, ConfigState>) -> Result<AppConfig, String> {
     let cfg = AppConfig::load();
-    {
-        let mut write = state.0.write().await;
-        *write = cfg.clone();
-    }
+    state.set(cfg.clone()).await;
     Ok(cfg)
 }
 
 #[tauri::command]
-pub async fn save_config(state: State<'_, ConfigState>, cfg: AppConfig) -> Result<(), String> {
-    // Persist to disk + update in-memory
-    cfg.save();
-    {
-        let mut write = state.0.write().await;
-        *write = cfg;
-    }
+pub async fn save_config(state: State<'_, ConfigState>, cfg: AppConfig) -> Result<(), ConfigError> {
+    // Validate, persist to disk + update in-memory
+    cfg.validate()?;
+    cfg.save()?;
+    state.set(cfg).await;
     Ok(())
 }
 
@@ -0,0 +1,17 @@
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::api::EventHub;
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+
+/// Forward probe events to the webview under their frontend event names,
+/// and to automation API subscribers if the API is set up.
+impl ProbeEventSink for AppHandle {
+    fn emit(&self, event: ProbeEvent) {
+        if let Some(hub) = self.try_state::<EventHub>() {
+            hub.publish(&event);
+        }
+        if let Err(e) = Emitter::emit(self, event.name(), &event) {
+            tracing::warn!("Failed to emit {}: {}", event.name(), e);
+        }
+    }
+}
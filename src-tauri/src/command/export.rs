@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use crate::export::{ExportFormat, ExportKind};
+
+/// Export a probe result, route list or socket list to `path`.
+/// `data` is the value previously returned by the corresponding command.
+#[tauri::command]
+pub async fn export_result(
+    kind: ExportKind,
+    data: serde_json::Value,
+    format: ExportFormat,
+    path: String,
+) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    crate::export::export_to_file(kind, data, format, &path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+use tauri::State;
+
+use crate::history::{HistoryEntry, HistoryQuery, HistoryRecord, HistoryStore};
+use crate::state::SharedState;
+
+/// Run a blocking history store call off the async runtime.
+async fn with_history<T, F>(state: &SharedState, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&HistoryStore) -> anyhow::Result<T> + Send + 'static,
+{
+    let history = Arc::clone(&state.history);
+    tokio::task::spawn_blocking(move || f(&history))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Query stored runs by kind, target and time range, newest first.
+#[tauri::command]
+pub async fn query_history(
+    state: State<'_, SharedState>,
+    query: HistoryQuery,
+) -> Result<Vec<HistoryEntry>, String> {
+    with_history(&state, move |history| history.query(&query)).await
+}
+
+/// Get a stored run with its settings and result.
+#[tauri::command]
+pub async fn get_history_record(
+    state: State<'_, SharedState>,
+    run_id: String,
+) -> Result<HistoryRecord, String> {
+    let id = run_id.clone();
+    with_history(&state, move |history| history.get(&id))
+        .await?
+        .ok_or_else(|| format!("history record not found: {run_id}"))
+}
+
+/// Delete stored runs. Returns the number of deleted records.
+#[tauri::command]
+pub async fn delete_history(
+    state: State<'_, SharedState>,
+    run_ids: Vec<String>,
+) -> Result<usize, String> {
+    with_history(&state, move |history| history.delete(&run_ids)).await
+}
+
+/// Drop records older than `max_age_ms` and the oldest records beyond `max_bytes`.
+/// Returns the number of deleted records.
+#[tauri::command]
+pub async fn prune_history(
+    state: State<'_, SharedState>,
+    max_age_ms: Option<u64>,
+    max_bytes: Option<u64>,
+) -> Result<usize, String> {
+    with_history(&state, move |history| history.prune(max_age_ms, max_bytes)).await
+}
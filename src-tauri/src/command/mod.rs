@@ -1,12 +1,20 @@
 pub mod config;
 pub mod dns;
+pub mod event;
+pub mod export;
+pub mod history;
 pub mod interfaces;
 pub mod internet;
+pub mod monitor;
+pub mod ntp;
 pub mod ping;
+pub mod pmtu;
 pub mod routes;
+pub mod run;
 pub mod scan;
 pub mod socket;
 pub mod system;
+pub mod tls;
 pub mod trace;
 
 use crate::model::AppInfo;
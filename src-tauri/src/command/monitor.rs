@@ -0,0 +1,12 @@
+use tauri::State;
+
+use crate::model::monitor::MonitorStatus;
+use crate::state::SharedState;
+
+/// Current status of the running monitors, ordered by name.
+#[tauri::command]
+pub async fn list_monitors(state: State<'_, SharedState>) -> Result<Vec<MonitorStatus>, String> {
+    let mut monitors: Vec<MonitorStatus> = state.monitors.lock().await.values().cloned().collect();
+    monitors.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(monitors)
+}
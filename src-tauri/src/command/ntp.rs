@@ -0,0 +1,17 @@
+use std::time::Duration;
+
+use crate::model::ntp::NtpQueryResult;
+
+const NTP_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Query NTP servers once each and report the offset of the local clock from each of them
+#[tauri::command]
+pub async fn ntp_query(
+    servers: Vec<String>,
+    port: Option<u16>,
+) -> Result<Vec<NtpQueryResult>, String> {
+    if servers.is_empty() {
+        return Err("no NTP servers given".to_string());
+    }
+    Ok(crate::net::ntp::query_servers(&servers, port, NTP_QUERY_TIMEOUT).await)
+}
@@ -1,59 +1,95 @@
 use std::net::IpAddr;
+use std::time::Duration;
+use tauri::{AppHandle, State};
 
-use netdev::Interface;
-use tauri::{AppHandle, Emitter};
-
-use crate::model::ping::{PingProtocol, PingSetting, PingStartPayload, PingStat};
+use super::config::ConfigState;
+use super::run::{complete, display_target, display_targets};
+use crate::model::ping::{
+    MultiPingReport, MultiPingSetting, PingSetting, PingStartPayload, PingStat,
+};
+use crate::model::run::RunKind;
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
 use crate::probe::ping;
+use crate::state::SharedState;
 
 #[tauri::command]
-pub async fn ping(app: AppHandle, setting: PingSetting) -> Result<PingStat, String> {
-    let default_interface: Interface = netdev::get_default_interface()
-        .map_err(|e| format!("Failed to get default interface: {}", e))?;
-    let src_ip = match setting.ip_addr {
-        std::net::IpAddr::V4(_) => {
-            // Pick first IPv4 address of default interface
-            let ipv4 = default_interface
-                .ipv4_addrs()
-                .into_iter()
-                .next()
-                .ok_or("No IPv4 address found on default interface")?;
-            IpAddr::V4(ipv4)
-        }
-        std::net::IpAddr::V6(_) => {
-            // Pick first IPv6 address of default interface
-            let ipv6 = default_interface
-                .ipv6_addrs()
-                .into_iter()
-                .next()
-                .ok_or("No IPv6 address found on default interface")?;
-            IpAddr::V6(ipv6)
-        }
-    };
+pub async fn ping(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    mut setting: PingSetting,
+) -> Result<PingStat, String> {
+    let src_ip = crate::net::interface::select_source_ip(
+        setting.ip_addr,
+        setting.interface.as_deref(),
+        setting.src_ip,
+    )
+    .map_err(|e| e.to_string())?;
+    if setting.interface.is_some() {
+        setting.src_ip = Some(src_ip);
+    }
     let run_id = uuid::Uuid::new_v4().to_string();
+    let token = state
+        .runs
+        .register(
+            &run_id,
+            RunKind::Ping,
+            display_target(setting.ip_addr, &setting.hostname),
+            serde_json::to_value(&setting).unwrap_or_default(),
+            setting.count,
+            0,
+        )
+        .map_err(|e| e.to_string())?;
+    let sink = state.runs.sink(&run_id, &app);
     // Start event
-    let _ = app.emit(
-        "ping:start",
-        PingStartPayload {
-            run_id: run_id.clone(),
-            setting: setting.clone(),
-        },
-    );
-    match setting.protocol {
-        PingProtocol::Icmp => ping::icmp::icmp_ping(&app, &run_id, src_ip, setting)
-            .await
-            .map_err(|e| e.to_string()),
-        PingProtocol::Tcp => ping::tcp::tcp_ping(&app, &run_id, src_ip, setting)
-            .await
-            .map_err(|e| e.to_string()),
-        PingProtocol::Udp => ping::udp::udp_ping_icmp_unreach(&app, &run_id, src_ip, setting)
-            .await
-            .map_err(|e| e.to_string()),
-        PingProtocol::Quic => ping::quic::quic_ping(&app, &run_id, src_ip, setting)
-            .await
-            .map_err(|e| e.to_string()),
-        PingProtocol::Http => ping::http::http_ping(&app, &run_id, setting)
-            .await
-            .map_err(|e| e.to_string()),
+    sink.emit(ProbeEvent::PingStart(PingStartPayload {
+        run_id: run_id.clone(),
+        setting: setting.clone(),
+    }));
+    let result = ping::ping(&sink, &run_id, &token, src_ip, setting).await;
+    complete(&state, &run_id, &result).await;
+    result.map_err(|e| e.to_string())
+}
+
+/// DNS timeout for resolving multi-ping target hostnames
+const MULTI_PING_DNS_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tauri::command]
+pub async fn multi_ping(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    config: State<'_, ConfigState>,
+    setting: MultiPingSetting,
+) -> Result<MultiPingReport, String> {
+    let targets = ping::multi::resolve_targets(&setting.targets, MULTI_PING_DNS_TIMEOUT)
+        .await
+        .map_err(|e| e.to_string())?;
+    if targets.is_empty() {
+        return Err("no targets to ping".into());
     }
+    let ips: Vec<IpAddr> = targets.iter().map(|t| t.ip_addr).collect();
+    let (src_ipv4, src_ipv6) = crate::net::interface::select_source_pair(
+        setting.interface.as_deref(),
+        setting.src_ip,
+        &ips,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let max_scans = config.0.read().await.max_concurrent_scans;
+    let token = state
+        .runs
+        .register(
+            &run_id,
+            RunKind::MultiPing,
+            display_targets(&ips),
+            serde_json::to_value(&setting).unwrap_or_default(),
+            (ips.len() as u32).saturating_mul(setting.count.max(1)),
+            max_scans,
+        )
+        .map_err(|e| e.to_string())?;
+    let sink = state.runs.sink(&run_id, &app);
+    let result =
+        ping::multi::multi_ping(&sink, &run_id, &token, src_ipv4, src_ipv6, targets, setting).await;
+    complete(&state, &run_id, &result).await;
+    result.map_err(|e| e.to_string())
 }
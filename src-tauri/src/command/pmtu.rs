@@ -0,0 +1,40 @@
+use tauri::{AppHandle, State};
+
+use super::run::{complete, display_target};
+use crate::model::pmtu::{PmtuReport, PmtuSetting};
+use crate::model::run::RunKind;
+use crate::probe::pmtu;
+use crate::state::SharedState;
+
+#[tauri::command]
+pub async fn pmtu_discover(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    mut setting: PmtuSetting,
+) -> Result<PmtuReport, String> {
+    let src_ip = crate::net::interface::select_source_ip(
+        setting.ip_addr,
+        setting.interface.as_deref(),
+        setting.src_ip,
+    )
+    .map_err(|e| e.to_string())?;
+    if setting.interface.is_some() {
+        setting.src_ip = Some(src_ip);
+    }
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let token = state
+        .runs
+        .register(
+            &run_id,
+            RunKind::Pmtu,
+            display_target(setting.ip_addr, &setting.hostname),
+            serde_json::to_value(&setting).unwrap_or_default(),
+            0,
+            0,
+        )
+        .map_err(|e| e.to_string())?;
+    let sink = state.runs.sink(&run_id, &app);
+    let result = pmtu::pmtu_discover(&sink, &run_id, &token, src_ip, setting).await;
+    complete(&state, &run_id, &result).await;
+    result.map_err(|e| e.to_string())
+}
@@ -0,0 +1,71 @@
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tauri::State;
+
+use crate::model::run::RunInfo;
+use crate::state::{AppState, SharedState};
+
+/// Hostname if given, otherwise the IP address
+pub fn display_target(ip_addr: IpAddr, hostname: &Option<String>) -> String {
+    hostname.clone().unwrap_or_else(|| ip_addr.to_string())
+}
+
+/// Short description of a multi-target run for the job manager and history
+pub fn display_targets(targets: &[IpAddr]) -> String {
+    match targets {
+        [] => String::new(),
+        [ip] => ip.to_string(),
+        [first, .., last] => format!("{first} - {last} ({} hosts)", targets.len()),
+    }
+}
+
+/// Mark a run as finished and persist its result to the history store.
+pub async fn complete<T: Serialize>(state: &AppState, run_id: &str, result: &anyhow::Result<T>) {
+    let Some(info) = state.runs.finish(run_id, result) else {
+        return;
+    };
+    let Ok(value) = result else {
+        return;
+    };
+    let value = match serde_json::to_value(value) {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("failed to serialize run {run_id} for history: {e:?}");
+            return;
+        }
+    };
+    let history = Arc::clone(&state.history);
+    let saved = tokio::task::spawn_blocking(move || history.append(&info, &value)).await;
+    match saved {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::error!("failed to save run {run_id} to history: {e:?}"),
+        Err(e) => tracing::error!("failed to save run {run_id} to history: {e}"),
+    }
+}
+
+/// Cancel a running ping, scan or traceroute by its run_id.
+/// The probe stops and emits its `*:cancelled` event with the partial result.
+#[tauri::command]
+pub async fn cancel_run(state: State<'_, SharedState>, run_id: String) -> Result<(), String> {
+    if state.runs.cancel(&run_id) {
+        Ok(())
+    } else {
+        Err(format!("run not found: {run_id}"))
+    }
+}
+
+/// List tracked runs (running and recently finished), oldest first.
+#[tauri::command]
+pub async fn list_runs(state: State<'_, SharedState>) -> Result<Vec<RunInfo>, String> {
+    Ok(state.runs.list())
+}
+
+/// Get a tracked run by its run_id.
+#[tauri::command]
+pub async fn get_run(state: State<'_, SharedState>, run_id: String) -> Result<RunInfo, String> {
+    state
+        .runs
+        .get(&run_id)
+        .ok_or_else(|| format!("run not found: {run_id}"))
+}
@@ -1,92 +1,116 @@
-use std::net::IpAddr;
+use tauri::{AppHandle, State};
 
-use netdev::Interface;
-use tauri::{AppHandle, Emitter};
+use super::config::ConfigState;
+use super::run::{complete, display_target, display_targets};
 
+use crate::model::run::RunKind;
 use crate::model::scan::{
-    HostScanReport, HostScanSetting, NeighborScanReport, PortScanProtocol, PortScanReport, PortScanSetting
+    HostScanReport, HostScanSetting, HostScanStartPayload, NeighborScanReport, PortScanProtocol,
+    PortScanReport, PortScanSetting, PortScanStartPayload,
 };
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::state::SharedState;
 
 #[tauri::command]
-pub async fn port_scan(app: AppHandle, setting: PortScanSetting) -> Result<PortScanReport, String> {
-    let default_interface: Interface = netdev::get_default_interface()
-        .map_err(|e| format!("Failed to get default interface: {}", e))?;
-    let src_ip = match setting.ip_addr {
-        std::net::IpAddr::V4(_) => {
-            // Pick first IPv4 address of default interface
-            let ipv4 = default_interface
-                .ipv4_addrs()
-                .into_iter()
-                .next()
-                .ok_or("No IPv4 address found on default interface")?;
-            IpAddr::V4(ipv4)
-        }
-        std::net::IpAddr::V6(_) => {
-            // Pick first IPv6 address of default interface
-            let ipv6 = default_interface
-                .ipv6_addrs()
-                .into_iter()
-                .next()
-                .ok_or("No IPv6 address found on default interface")?;
-            IpAddr::V6(ipv6)
-        }
-    };
+pub async fn port_scan(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    config: State<'_, ConfigState>,
+    mut setting: PortScanSetting,
+) -> Result<PortScanReport, String> {
+    let src_ip = crate::net::interface::select_source_ip(
+        setting.ip_addr,
+        setting.interface.as_deref(),
+        setting.src_ip,
+    )
+    .map_err(|e| e.to_string())?;
+    if setting.interface.is_some() {
+        setting.src_ip = Some(src_ip);
+    }
     let run_id = uuid::Uuid::new_v4().to_string();
+    let max_scans = config.0.read().await.max_concurrent_scans;
+    let token = state
+        .runs
+        .register(
+            &run_id,
+            RunKind::PortScan,
+            display_target(setting.ip_addr, &setting.hostname),
+            serde_json::to_value(&setting).unwrap_or_default(),
+            0,
+            max_scans,
+        )
+        .map_err(|e| e.to_string())?;
+    let sink = state.runs.sink(&run_id, &app);
     // Start event
-    let _ = app.emit(
-        "portscan:start",
-        crate::model::scan::PortScanStartPayload {
-            run_id: run_id.clone(),
-        },
-    );
+    sink.emit(ProbeEvent::PortScanStart(PortScanStartPayload {
+        run_id: run_id.clone(),
+    }));
 
-    match setting.protocol {
-        PortScanProtocol::Tcp => crate::probe::scan::tcp::port_scan(&app, &run_id, src_ip, setting)
-            .await
-            .map_err(|e| e.to_string()),
+    let result = match setting.protocol {
+        PortScanProtocol::Tcp => {
+            crate::probe::scan::tcp::port_scan(&sink, &run_id, &token, src_ip, setting).await
+        }
         PortScanProtocol::Quic => {
-            crate::probe::scan::quic::port_scan(&app, &run_id, src_ip, setting)
-                .await
-                .map_err(|e| e.to_string())
+            crate::probe::scan::quic::port_scan(&sink, &run_id, &token, src_ip, setting).await
         }
-    }
+    };
+    complete(&state, &run_id, &result).await;
+    result.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn host_scan(app: AppHandle, setting: HostScanSetting) -> Result<HostScanReport, String> {
+pub async fn host_scan(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    config: State<'_, ConfigState>,
+    setting: HostScanSetting,
+) -> Result<HostScanReport, String> {
     let run_id = uuid::Uuid::new_v4().to_string();
 
-    let default_if = netdev::get_default_interface().map_err(|e| e.to_string())?;
-
-    let src_ipv4_opt = default_if
-        .ipv4_addrs()
-        .into_iter()
-        .next()
-        .map(std::net::IpAddr::V4);
-    let src_ipv6_opt = default_if
-        .ipv6_addrs()
-        .into_iter()
-        .next()
-        .map(std::net::IpAddr::V6);
+    let (src_ipv4_opt, src_ipv6_opt) = crate::net::interface::select_source_pair(
+        setting.interface.as_deref(),
+        setting.src_ip,
+        &setting.targets,
+    )
+    .map_err(|e| e.to_string())?;
 
-    let _ = app.emit(
-        "hostscan:start",
-        crate::model::scan::HostScanStartPayload {
-            run_id: run_id.clone(),
-        },
-    );
-    crate::probe::scan::icmp::host_scan(&app, &run_id, src_ipv4_opt, src_ipv6_opt, setting)
-        .await
-        .map_err(|e| e.to_string())
+    let max_scans = config.0.read().await.max_concurrent_scans;
+    let token = state
+        .runs
+        .register(
+            &run_id,
+            RunKind::HostScan,
+            display_targets(&setting.targets),
+            serde_json::to_value(&setting).unwrap_or_default(),
+            0,
+            max_scans,
+        )
+        .map_err(|e| e.to_string())?;
+    let sink = state.runs.sink(&run_id, &app);
+    sink.emit(ProbeEvent::HostScanStart(HostScanStartPayload {
+        run_id: run_id.clone(),
+    }));
+    let result = crate::probe::scan::icmp::host_scan(
+        &sink,
+        &run_id,
+        &token,
+        src_ipv4_opt,
+        src_ipv6_opt,
+        setting,
+    )
+    .await;
+    complete(&state, &run_id, &result).await;
+    result.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn neighbor_scan(app: AppHandle, iface_name: Option<String>) -> Result<NeighborScanReport, String> {
+pub async fn neighbor_scan(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    config: State<'_, ConfigState>,
+    iface_name: Option<String>,
+) -> Result<NeighborScanReport, String> {
     let run_id = uuid::Uuid::new_v4().to_string();
-    let _ = app.emit(
-        "neighborscan:start",
-        run_id.clone(),
-    );
     let iface = if let Some(name) = iface_name {
         netdev::get_interfaces()
             .into_iter()
@@ -95,7 +119,21 @@ pub async fn neighbor_scan(app: AppHandle, iface_name: Option<String>) -> Result
     } else {
         netdev::get_default_interface().map_err(|e| e.to_string())?
     };
-    crate::probe::scan::neigh::neighbor_scan(&app, &run_id, iface)
-        .await
-        .map_err(|e| e.to_string())
+    let max_scans = config.0.read().await.max_concurrent_scans;
+    let token = state
+        .runs
+        .register(
+            &run_id,
+            RunKind::NeighborScan,
+            iface.name.clone(),
+            serde_json::json!({ "iface_name": iface.name }),
+            0,
+            max_scans,
+        )
+        .map_err(|e| e.to_string())?;
+    let sink = state.runs.sink(&run_id, &app);
+    sink.emit(ProbeEvent::NeighborScanStart(run_id.clone()));
+    let result = crate::probe::scan::neigh::neighbor_scan(&sink, &run_id, &token, iface).await;
+    complete(&state, &run_id, &result).await;
+    result.map_err(|e| e.to_string())
 }
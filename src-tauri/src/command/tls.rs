@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+use crate::model::tls::{StartTls, TlsInspectReport};
+
+const TLS_INSPECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handshake with a TLS server and report its certificate chain and negotiated parameters
+#[tauri::command]
+pub async fn tls_inspect(
+    host: String,
+    port: Option<u16>,
+    sni: Option<String>,
+    starttls: Option<StartTls>,
+) -> Result<TlsInspectReport, String> {
+    crate::net::tls::inspect(&host, port, sni.as_deref(), starttls, TLS_INSPECT_TIMEOUT)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -1,43 +1,90 @@
-use std::net::IpAddr;
-
 use anyhow::Result;
-use netdev::Interface;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, State};
+
+use super::run::{complete, display_target};
+use crate::model::run::RunKind;
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::trace::{self, MtrSetting, TraceErrorPayload, TracerouteSetting};
+use crate::state::SharedState;
+
+#[tauri::command]
+pub async fn traceroute(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    mut setting: TracerouteSetting,
+) -> Result<(), String> {
+    let src_ip = crate::net::interface::select_source_ip(
+        setting.ip_addr,
+        setting.interface.as_deref(),
+        setting.src_ip,
+    )
+    .map_err(|e| e.to_string())?;
+    if setting.interface.is_some() {
+        setting.src_ip = Some(src_ip);
+    }
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let token = state
+        .runs
+        .register(
+            &run_id,
+            RunKind::Traceroute,
+            display_target(setting.ip_addr, &setting.hostname),
+            serde_json::to_value(&setting).unwrap_or_default(),
+            setting.max_hops as u32,
+            0,
+        )
+        .map_err(|e| e.to_string())?;
+    let sink = state.runs.sink(&run_id, &app);
+
+    let result = trace::traceroute(&sink, &run_id, &token, src_ip, setting).await;
+    if let Err(e) = &result {
+        // Emit error event
+        sink.emit(ProbeEvent::TraceError(TraceErrorPayload {
+            message: e.to_string(),
+        }));
+    }
+    complete(&state, &run_id, &result).await;
 
-use crate::probe::trace::{self, TracerouteSetting};
+    Ok(())
+}
 
 #[tauri::command]
-pub async fn traceroute(app: AppHandle, setting: TracerouteSetting) -> Result<(), String> {
-    let default_interface: Interface = netdev::get_default_interface()
-        .map_err(|e| format!("Failed to get default interface: {}", e))?;
-    let src_ip = match setting.ip_addr {
-        std::net::IpAddr::V4(_) => {
-            // Pick first IPv4 address of default interface
-            let ipv4 = default_interface
-                .ipv4_addrs()
-                .into_iter()
-                .next()
-                .ok_or("No IPv4 address found on default interface")?;
-            IpAddr::V4(ipv4)
-        }
-        std::net::IpAddr::V6(_) => {
-            // Pick first IPv6 address of default interface
-            let ipv6 = default_interface
-                .ipv6_addrs()
-                .into_iter()
-                .next()
-                .ok_or("No IPv6 address found on default interface")?;
-            IpAddr::V6(ipv6)
-        }
-    };
-    
-    if let Err(e) = trace::traceroute(&app, src_ip, setting).await {
+pub async fn mtr(
+    app: AppHandle,
+    state: State<'_, SharedState>,
+    mut setting: MtrSetting,
+) -> Result<(), String> {
+    let src_ip = crate::net::interface::select_source_ip(
+        setting.ip_addr,
+        setting.interface.as_deref(),
+        setting.src_ip,
+    )
+    .map_err(|e| e.to_string())?;
+    if setting.interface.is_some() {
+        setting.src_ip = Some(src_ip);
+    }
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let token = state
+        .runs
+        .register(
+            &run_id,
+            RunKind::Mtr,
+            display_target(setting.ip_addr, &setting.hostname),
+            serde_json::to_value(&setting).unwrap_or_default(),
+            setting.rounds.unwrap_or(0),
+            0,
+        )
+        .map_err(|e| e.to_string())?;
+    let sink = state.runs.sink(&run_id, &app);
+
+    let result = trace::mtr::mtr(&sink, &run_id, &token, src_ip, setting).await;
+    if let Err(e) = &result {
         // Emit error event
-        let _ = app.emit(
-            "traceroute:error",
-            &serde_json::json!({ "message": e.to_string() }),
-        );
+        sink.emit(ProbeEvent::MtrError(TraceErrorPayload {
+            message: e.to_string(),
+        }));
     }
+    complete(&state, &run_id, &result).await;
 
     Ok(())
 }
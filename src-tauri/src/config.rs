@@ -1,8 +1,34 @@
 use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use crate::log::DEFAULT_LOG_FILE_NAME;
+use crate::model::ping::PingProtocol;
 
 pub const DEFAULT_CONFIG_FILE_NAME: &str = "netpulsar-config.json";
+pub const DEFAULT_MAX_CONCURRENT_SCANS: u32 = 1;
+pub const DEFAULT_API_PORT: u16 = 47821;
+/// Minimum length of the automation API token
+pub const MIN_API_TOKEN_LEN: usize = 16;
+pub const DEFAULT_METRICS_PORT: u16 = 47822;
+pub const DEFAULT_IFACE_RELOAD_INTERVAL_MS: u64 = 30_000;
+
+/// Current config schema version.
+/// Files without a `version` field are treated as version 1. Files from a newer
+/// version are loaded as far as they are understood but never overwritten.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Bounds of `refresh_interval_ms`
+pub const MIN_REFRESH_INTERVAL_MS: u64 = 100;
+pub const MAX_REFRESH_INTERVAL_MS: u64 = 60_000;
+/// Bounds of `iface_reload_interval_ms`
+pub const MIN_IFACE_RELOAD_INTERVAL_MS: u64 = 1_000;
+pub const MAX_IFACE_RELOAD_INTERVAL_MS: u64 = 3_600_000;
+/// Bounds of `monitors[].interval_ms`
+pub const MIN_MONITOR_INTERVAL_MS: u64 = 1_000;
+pub const MAX_MONITOR_INTERVAL_MS: u64 = 3_600_000;
+/// Maximum number of probes in a monitor's rolling window
+pub const MAX_MONITOR_WINDOW: u32 = 1_000;
 
 pub mod bps_unit {
     pub const BITS: &str = "bits";
@@ -10,18 +36,34 @@ pub mod bps_unit {
     pub const BYTES: &str = "bytes";
 }
 
+pub const THEMES: &[&str] = &["system", "light", "dark"];
+pub const DATA_UNITS: &[&str] = &[bps_unit::BITS, bps_unit::BYTES];
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
 pub struct AppConfig {
+    /// Config schema version.
+    pub version: u32,
     /// Whether the app should start automatically.
     pub startup: bool,
     /// Refresh interval in milliseconds.
     pub refresh_interval_ms: u64,
+    /// Interface list reload interval in milliseconds.
+    pub iface_reload_interval_ms: u64,
     /// Theme: "dark", "light", or "system".
     pub theme: String,
     /// Data unit: "bits" or "bytes".
     pub data_unit: String,
     /// Logging configuration.
     pub logging: LoggingConfig,
+    /// Maximum number of port/host/neighbor scans running at once (0 = unlimited).
+    pub max_concurrent_scans: u32,
+    /// Local automation API.
+    pub api: ApiConfig,
+    /// Prometheus metrics endpoint.
+    pub metrics: MetricsConfig,
+    /// Targets monitored continuously by the supervisor.
+    pub monitors: Vec<MonitorConfig>,
 }
 
 // Implement default
@@ -31,59 +73,345 @@ impl Default for AppConfig {
     }
 }
 
+/// A single invalid config field
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfigFieldError {
+    /// Dotted path of the field, e.g. `logging.file_path`
+    pub field: String,
+    pub message: String,
+}
+
+/// Error returned from `save_config`
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConfigError {
+    /// Semantic validation failed
+    Invalid { errors: Vec<ConfigFieldError> },
+    /// The file could not be written
+    Io { message: String },
+    /// The file on disk was written by a newer version and would lose fields
+    UnsupportedVersion { version: u64 },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Invalid { errors } => {
+                let list: Vec<String> = errors
+                    .iter()
+                    .map(|e| format!("{}: {}", e.field, e.message))
+                    .collect();
+                write!(f, "invalid config: {}", list.join(", "))
+            }
+            ConfigError::Io { message } => write!(f, "failed to save config: {message}"),
+            ConfigError::UnsupportedVersion { version } => write!(
+                f,
+                "config file version {version} is newer than {CONFIG_VERSION}, not overwriting it"
+            ),
+        }
+    }
+}
+
+/// Migration steps, `MIGRATIONS[n]` upgrades version `n + 1` to `n + 2`.
+/// New fields do not need a step as missing fields fall back to their defaults.
+const MIGRATIONS: &[fn(&mut serde_json::Map<String, serde_json::Value>)] = &[
+    // 1 -> 2: `version` was introduced; no field changes.
+    |_| {},
+];
+
+/// Schema version of a raw config object. Missing, null and 0 count as version 1.
+fn config_version(value: &serde_json::Value) -> anyhow::Result<u32> {
+    let version = match value.get("version") {
+        None | Some(serde_json::Value::Null) => 1,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| anyhow::anyhow!("invalid config version: {v}"))?,
+    };
+    Ok(version.max(1))
+}
+
+/// Upgrade a raw config object to `CONFIG_VERSION`.
+/// An object from a newer version is returned unchanged.
+fn migrate(mut value: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+    let version = config_version(&value)?;
+    if version > CONFIG_VERSION {
+        return Ok(value);
+    }
+    if let Some(obj) = value.as_object_mut() {
+        for step in MIGRATIONS.iter().skip(version as usize - 1) {
+            step(obj);
+        }
+        obj.insert("version".to_string(), CONFIG_VERSION.into());
+    }
+    Ok(value)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
 impl AppConfig {
     pub fn new() -> AppConfig {
         AppConfig {
+            version: CONFIG_VERSION,
             startup: false,
             refresh_interval_ms: 1000,
+            iface_reload_interval_ms: DEFAULT_IFACE_RELOAD_INTERVAL_MS,
             theme: "system".to_string(),
             data_unit: bps_unit::BITS.to_string(),
             logging: LoggingConfig::new(),
+            max_concurrent_scans: DEFAULT_MAX_CONCURRENT_SCANS,
+            api: ApiConfig::new(),
+            metrics: MetricsConfig::new(),
+            monitors: Vec::new(),
         }
     }
+
+    /// Parse a config file, migrating it to the current version.
+    /// Missing or unknown fields are tolerated.
+    fn read_from(path: &Path) -> anyhow::Result<AppConfig> {
+        let content = std::fs::read_to_string(path)?;
+        let value: serde_json::Value = serde_json::from_str(&content)?;
+        Ok(serde_json::from_value(migrate(value)?)?)
+    }
+
     pub fn load() -> AppConfig {
-        match crate::fs::get_user_file_path(DEFAULT_CONFIG_FILE_NAME) {
-            Some(path) => {
-                match std::fs::read_to_string(&path) {
-                    Ok(content) => match serde_json::from_str(&content) {
-                        Ok(config) => config,
-                        Err(e) => {
-                            tracing::error!("{:?}", e);
-                            AppConfig::new()
-                        }
-                    },
-                    Err(e) => {
-                        tracing::error!("{:?}", e);
-                        // Create default config
-                        let config = AppConfig::new();
-                        config.save();
+        let Some(path) = crate::fs::get_user_file_path(DEFAULT_CONFIG_FILE_NAME) else {
+            return AppConfig::new();
+        };
+        if !path.exists() {
+            // Create default config
+            let config = AppConfig::new();
+            if let Err(e) = config.save() {
+                tracing::error!("{}", e);
+            }
+            return config;
+        }
+        match AppConfig::read_from(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::error!("failed to load {}: {:?}", path.display(), e);
+                // Fall back to the last good config. The broken file is left
+                // untouched until the next successful save.
+                match AppConfig::read_from(&backup_path(&path)) {
+                    Ok(config) => {
+                        tracing::warn!("loaded config from backup");
                         config
                     }
+                    Err(_) => AppConfig::new(),
                 }
             }
-            None => {
-                // Create default config
-                let config = AppConfig::new();
-                config.save();
-                config
+        }
+    }
+
+    /// Check the config for semantic errors.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+        let mut invalid = |field: &str, message: String| {
+            errors.push(ConfigFieldError {
+                field: field.to_string(),
+                message,
+            })
+        };
+        if !(MIN_REFRESH_INTERVAL_MS..=MAX_REFRESH_INTERVAL_MS).contains(&self.refresh_interval_ms)
+        {
+            invalid(
+                "refresh_interval_ms",
+                format!("must be between {MIN_REFRESH_INTERVAL_MS} and {MAX_REFRESH_INTERVAL_MS}"),
+            );
+        }
+        if !(MIN_IFACE_RELOAD_INTERVAL_MS..=MAX_IFACE_RELOAD_INTERVAL_MS)
+            .contains(&self.iface_reload_interval_ms)
+        {
+            invalid(
+                "iface_reload_interval_ms",
+                format!(
+                    "must be between {MIN_IFACE_RELOAD_INTERVAL_MS} and {MAX_IFACE_RELOAD_INTERVAL_MS}"
+                ),
+            );
+        }
+        if !THEMES.contains(&self.theme.as_str()) {
+            invalid("theme", format!("must be one of {}", THEMES.join(", ")));
+        }
+        if !DATA_UNITS.contains(&self.data_unit.as_str()) {
+            invalid(
+                "data_unit",
+                format!("must be one of {}", DATA_UNITS.join(", ")),
+            );
+        }
+        if self.api.enabled {
+            if self.api.port == 0 {
+                invalid("api.port", "must not be 0".to_string());
+            }
+            if self.api.token.len() < MIN_API_TOKEN_LEN {
+                invalid(
+                    "api.token",
+                    format!("must be at least {MIN_API_TOKEN_LEN} characters"),
+                );
+            }
+        }
+        if self.metrics.enabled {
+            if self.metrics.port == 0 {
+                invalid("metrics.port", "must not be 0".to_string());
+            } else if self.api.enabled && self.metrics.port == self.api.port {
+                invalid("metrics.port", "must differ from api.port".to_string());
+            }
+        }
+        let mut ids = std::collections::HashSet::new();
+        let mut names = std::collections::HashSet::new();
+        for (i, m) in self.monitors.iter().enumerate() {
+            let field = |name: &str| format!("monitors[{i}].{name}");
+            if m.id.is_empty() {
+                invalid(&field("id"), "must not be empty".to_string());
+            } else if !ids.insert(m.id.as_str()) {
+                invalid(&field("id"), format!("duplicate id: {}", m.id));
+            }
+            if !names.insert(m.name.as_str()) {
+                invalid(&field("name"), format!("duplicate name: {}", m.name));
+            }
+            if m.target.trim().is_empty() {
+                invalid(&field("target"), "must not be empty".to_string());
+            }
+            if !(MIN_MONITOR_INTERVAL_MS..=MAX_MONITOR_INTERVAL_MS).contains(&m.interval_ms) {
+                invalid(
+                    &field("interval_ms"),
+                    format!(
+                        "must be between {MIN_MONITOR_INTERVAL_MS} and {MAX_MONITOR_INTERVAL_MS}"
+                    ),
+                );
+            }
+            if m.timeout_ms == 0 || m.timeout_ms > m.interval_ms {
+                invalid(
+                    &field("timeout_ms"),
+                    "must be between 1 and interval_ms".to_string(),
+                );
+            }
+            if !(1..=MAX_MONITOR_WINDOW).contains(&m.window) {
+                invalid(
+                    &field("window"),
+                    format!("must be between 1 and {MAX_MONITOR_WINDOW}"),
+                );
+            }
+            if !(0.0..=100.0).contains(&m.thresholds.max_loss_pct) {
+                invalid(
+                    &field("thresholds.max_loss_pct"),
+                    "must be between 0 and 100".to_string(),
+                );
+            }
+            if m.thresholds.down_after_failures == 0 {
+                invalid(
+                    &field("thresholds.down_after_failures"),
+                    "must be at least 1".to_string(),
+                );
             }
         }
+        if let Some(file_path) = &self.logging.file_path {
+            if let Err(e) = check_writable(Path::new(file_path)) {
+                invalid("logging.file_path", format!("not writable: {e}"));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid { errors })
+        }
     }
-    pub fn save(&self) {
-        if let Some(path) = crate::fs::get_user_file_path(DEFAULT_CONFIG_FILE_NAME) {
-            match serde_json::to_string_pretty(&self) {
-                Ok(content) => match std::fs::write(&path, content) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        tracing::error!("{:?}", e);
-                    }
-                },
-                Err(e) => {
-                    tracing::error!("{:?}", e);
-                }
+
+    /// Write the config atomically: the new content goes to a temp file which
+    /// replaces the config file, after the previous file is kept as `.bak`
+    /// if it is still loadable.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let io_err = |e: std::io::Error| ConfigError::Io {
+            message: e.to_string(),
+        };
+        let path = crate::fs::get_user_file_path(DEFAULT_CONFIG_FILE_NAME).ok_or_else(|| {
+            ConfigError::Io {
+                message: "failed to resolve config path".to_string(),
             }
+        })?;
+        // Never downgrade a file written by a newer version
+        let on_disk = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|value| value.get("version").and_then(|v| v.as_u64()));
+        let version = on_disk.unwrap_or(0).max(self.version as u64);
+        if version > CONFIG_VERSION as u64 {
+            return Err(ConfigError::UnsupportedVersion { version });
+        }
+        let content = serde_json::to_string_pretty(&self).map_err(|e| ConfigError::Io {
+            message: e.to_string(),
+        })?;
+
+        let mut tmp_name = path.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_name);
+        {
+            let mut file = std::fs::File::create(&tmp_path).map_err(io_err)?;
+            file.write_all(content.as_bytes()).map_err(io_err)?;
+            file.sync_all().map_err(io_err)?;
         }
+
+        if AppConfig::read_from(&path).is_ok() {
+            if let Err(e) = std::fs::copy(&path, backup_path(&path)) {
+                tracing::warn!("failed to back up config: {:?}", e);
+            }
+        }
+        std::fs::rename(&tmp_path, &path).map_err(io_err)?;
+        Ok(())
+    }
+}
+
+/// Check, without creating anything, that a file could be opened for appending:
+/// an existing file must be writable, otherwise its nearest existing ancestor must be
+/// a writable directory. Missing directories are created by the logger on startup.
+fn check_writable(path: &Path) -> std::io::Result<()> {
+    let existing = path
+        .ancestors()
+        .map(|p| {
+            if p.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                p
+            }
+        })
+        .find(|p| p.exists())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no existing ancestor"))?;
+    let metadata = std::fs::metadata(existing)?;
+    if existing == path {
+        if metadata.is_dir() {
+            return Err(std::io::Error::other("is a directory"));
+        }
+    } else if !metadata.is_dir() {
+        return Err(std::io::Error::other(format!(
+            "{} is not a directory",
+            existing.display()
+        )));
+    }
+    if !is_writable(existing, &metadata) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is read-only", existing.display()),
+        ));
     }
+    Ok(())
+}
+
+/// Whether the current user may write to `path`
+#[cfg(unix)]
+fn is_writable(path: &Path, _metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_writable(_path: &Path, metadata: &std::fs::Metadata) -> bool {
+    !metadata.permissions().readonly()
 }
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
@@ -125,6 +453,7 @@ impl LogLevel {
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
 pub struct LoggingConfig {
     /// Log level.
     pub level: LogLevel,
@@ -144,3 +473,234 @@ impl LoggingConfig {
         }
     }
 }
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Local HTTP/JSON-RPC automation API. Listens on loopback only.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Whether the API server is running.
+    pub enabled: bool,
+    /// TCP port on 127.0.0.1.
+    pub port: u16,
+    /// Bearer token required on every request.
+    pub token: String,
+}
+
+impl ApiConfig {
+    pub fn new() -> ApiConfig {
+        ApiConfig {
+            enabled: false,
+            port: DEFAULT_API_PORT,
+            token: uuid::Uuid::new_v4().simple().to_string(),
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Prometheus `/metrics` endpoint. Listens on loopback only.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Whether the metrics endpoint is served.
+    pub enabled: bool,
+    /// TCP port on 127.0.0.1.
+    pub port: u16,
+}
+
+impl MetricsConfig {
+    pub fn new() -> MetricsConfig {
+        MetricsConfig {
+            enabled: false,
+            port: DEFAULT_METRICS_PORT,
+        }
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A target probed periodically by the supervisor
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Stable identifier used in events.
+    pub id: String,
+    /// Display name, also the `monitor` label on `/metrics`.
+    pub name: String,
+    pub enabled: bool,
+    /// IP address or hostname (URL for HTTP).
+    pub target: String,
+    pub protocol: PingProtocol,
+    pub port: Option<u16>,
+    /// Time between probes in milliseconds.
+    pub interval_ms: u64,
+    /// Probe timeout in milliseconds.
+    pub timeout_ms: u64,
+    /// Number of recent probes the rolling statistics cover.
+    pub window: u32,
+    pub thresholds: MonitorThresholds,
+}
+
+impl MonitorConfig {
+    pub fn new() -> MonitorConfig {
+        MonitorConfig {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: String::new(),
+            enabled: true,
+            target: String::new(),
+            protocol: PingProtocol::Icmp,
+            port: None,
+            interval_ms: 5_000,
+            timeout_ms: 1_000,
+            window: 20,
+            thresholds: MonitorThresholds::new(),
+        }
+    }
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Thresholds that decide whether a monitored target is up, degraded or down
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MonitorThresholds {
+    /// Loss over the window (percent) above which the target is degraded.
+    pub max_loss_pct: f64,
+    /// Average RTT over the window above which the target is degraded.
+    pub max_avg_rtt_ms: Option<u64>,
+    /// Number of consecutive failed probes after which the target is down.
+    pub down_after_failures: u32,
+}
+
+impl MonitorThresholds {
+    pub fn new() -> MonitorThresholds {
+        MonitorThresholds {
+            max_loss_pct: 20.0,
+            max_avg_rtt_ms: None,
+            down_after_failures: 3,
+        }
+    }
+}
+
+impl Default for MonitorThresholds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn migrated_version(value: serde_json::Value) -> serde_json::Value {
+        migrate(value).unwrap()["version"].clone()
+    }
+
+    #[test]
+    fn migrates_old_versions_to_current() {
+        assert_eq!(migrated_version(json!({})), json!(CONFIG_VERSION));
+        for version in [json!(null), json!(0), json!(1), json!(CONFIG_VERSION)] {
+            let value = json!({ "version": version });
+            assert_eq!(migrated_version(value), json!(CONFIG_VERSION));
+        }
+    }
+
+    #[test]
+    fn keeps_fields_while_migrating() {
+        let value = migrate(json!({ "theme": "dark", "max_concurrent_scans": 3 })).unwrap();
+        let config: AppConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.theme, "dark");
+        assert_eq!(config.max_concurrent_scans, 3);
+    }
+
+    #[test]
+    fn leaves_newer_versions_unchanged() {
+        let newer = json!({ "version": CONFIG_VERSION + 1, "theme": "dark", "future": true });
+        assert_eq!(migrate(newer.clone()).unwrap(), newer);
+        let config: AppConfig = serde_json::from_value(newer).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION + 1);
+        assert_eq!(config.theme, "dark");
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        assert!(migrate(json!({ "version": u64::from(u32::MAX) + 1 })).is_err());
+        assert!(migrate(json!({ "version": -1 })).is_err());
+        assert!(migrate(json!({ "version": "2" })).is_err());
+    }
+
+    #[test]
+    fn has_a_step_per_version() {
+        assert_eq!(MIGRATIONS.len(), CONFIG_VERSION as usize - 1);
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("netpulsar-test-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn checks_writable_without_creating() {
+        let tmp = TempDir::new();
+        let file = tmp.0.join("app.log");
+        assert!(check_writable(&file).is_ok());
+        assert!(!file.exists());
+        std::fs::write(&file, "").unwrap();
+        assert!(check_writable(&file).is_ok());
+
+        let nested = tmp.0.join("a").join("b").join("app.log");
+        assert!(check_writable(&nested).is_ok());
+        assert!(!tmp.0.join("a").exists());
+
+        assert!(check_writable(&tmp.0).is_err());
+        assert!(check_writable(&file.join("app.log")).is_err());
+        assert!(check_writable(Path::new("relative.log")).is_ok());
+    }
+
+    #[test]
+    fn validation_has_no_side_effects() {
+        let tmp = TempDir::new();
+        let mut config = AppConfig::default();
+        config.logging.file_path = Some(tmp.0.join("logs").join("app.log").display().to_string());
+        assert!(config.validate().is_ok());
+        assert!(!tmp.0.join("logs").exists());
+
+        config.logging.file_path = Some(tmp.0.display().to_string());
+        let Err(ConfigError::Invalid { errors }) = config.validate() else {
+            panic!("a directory is not a log file");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "logging.file_path");
+    }
+}
@@ -0,0 +1,358 @@
+use anyhow::Result;
+use netroute::RouteEntry;
+use netsock::socket::{ProtocolSocketInfo, SocketInfo};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::model::ping::PingStat;
+use crate::model::scan::{HostScanReport, NeighborScanReport, PortScanReport};
+use crate::probe::trace::{TraceHop, TraceReport};
+
+mod render;
+
+/// Kind of result passed to `export_result`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportKind {
+    Ping,
+    PortScan,
+    HostScan,
+    NeighborScan,
+    Traceroute,
+    Routes,
+    Sockets,
+}
+
+impl ExportKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            ExportKind::Ping => "Ping",
+            ExportKind::PortScan => "Port Scan",
+            ExportKind::HostScan => "Host Scan",
+            ExportKind::NeighborScan => "Neighbor Scan",
+            ExportKind::Traceroute => "Traceroute",
+            ExportKind::Routes => "Routes",
+            ExportKind::Sockets => "Sockets",
+        }
+    }
+}
+
+/// Output format of an export
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Markdown,
+    Html,
+}
+
+/// Traceroute results are accepted either as a full report or as the list of hops
+/// collected from `traceroute:progress` events.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TraceData {
+    Report(TraceReport),
+    Hops(Vec<TraceHop>),
+}
+
+/// Tabular form of a result shared by the CSV, Markdown and HTML renderers
+pub struct ExportTable {
+    pub title: String,
+    /// Key/value summary shown above the table (not included in CSV)
+    pub summary: Vec<(String, String)>,
+    pub headers: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl ExportTable {
+    fn new(kind: ExportKind, headers: &[&'static str]) -> Self {
+        Self {
+            title: format!("{} Report", kind.title()),
+            summary: Vec::new(),
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn summary(&mut self, key: &str, value: impl ToString) {
+        self.summary.push((key.to_string(), value.to_string()));
+    }
+}
+
+/// Render `data` of the given kind in `format`.
+pub fn render(kind: ExportKind, data: serde_json::Value, format: ExportFormat) -> Result<String> {
+    let (table, json) = match kind {
+        ExportKind::Ping => typed::<PingStat>(data, ping_table)?,
+        ExportKind::PortScan => typed::<PortScanReport>(data, port_scan_table)?,
+        ExportKind::HostScan => typed::<HostScanReport>(data, host_scan_table)?,
+        ExportKind::NeighborScan => typed::<NeighborScanReport>(data, neighbor_scan_table)?,
+        ExportKind::Traceroute => match serde_json::from_value::<TraceData>(data)? {
+            TraceData::Report(report) => (
+                trace_table(Some(&report), &report.hops),
+                serde_json::to_string_pretty(&report)?,
+            ),
+            TraceData::Hops(hops) => (
+                trace_table(None, &hops),
+                serde_json::to_string_pretty(&hops)?,
+            ),
+        },
+        ExportKind::Routes => typed::<Vec<RouteEntry>>(data, |r| routes_table(r))?,
+        ExportKind::Sockets => typed::<Vec<SocketInfo>>(data, |s| sockets_table(s))?,
+    };
+    Ok(match format {
+        ExportFormat::Csv => render::csv(&table),
+        ExportFormat::Json => json,
+        ExportFormat::Markdown => render::markdown(&table),
+        ExportFormat::Html => render::html(&table),
+    })
+}
+
+/// Render `data` and write it to `path`.
+pub fn export_to_file(
+    kind: ExportKind,
+    data: serde_json::Value,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<()> {
+    let content = render(kind, data, format)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Deserialize `data` as `T` (validating it) and build its table and pretty JSON.
+fn typed<T>(
+    data: serde_json::Value,
+    to_table: impl Fn(&T) -> ExportTable,
+) -> Result<(ExportTable, String)>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    let value: T = serde_json::from_value(data)?;
+    Ok((to_table(&value), serde_json::to_string_pretty(&value)?))
+}
+
+/// Name of a value as it appears in JSON (e.g. enum variants).
+fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        Ok(v) => v.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn endpoint(ip: IpAddr, port: u16) -> String {
+    match ip {
+        IpAddr::V4(v4) => format!("{}:{}", v4, port),
+        IpAddr::V6(v6) => format!("[{}]:{}", v6, port),
+    }
+}
+
+fn ping_table(stat: &PingStat) -> ExportTable {
+    let mut t = ExportTable::new(
+        ExportKind::Ping,
+        &[
+            "seq", "ip_addr", "hostname", "port", "protocol", "rtt_ms", "status", "message",
+        ],
+    );
+    t.summary("Target", stat.ip_addr);
+    if let Some(hostname) = &stat.hostname {
+        t.summary("Hostname", hostname);
+    }
+    if let Some(port) = stat.port {
+        t.summary("Port", port);
+    }
+    t.summary("Protocol", label(&stat.protocol));
+    t.summary("Transmitted", stat.transmitted_count);
+    t.summary("Received", stat.received_count);
+    if stat.transmitted_count > 0 {
+        let loss = (stat.transmitted_count - stat.received_count) as f64 * 100.0
+            / stat.transmitted_count as f64;
+        t.summary("Loss", format!("{loss:.1}%"));
+    }
+    if let (Some(min), Some(avg), Some(max)) = (stat.min, stat.avg, stat.max) {
+        t.summary("RTT min/avg/max", format!("{min}/{avg}/{max} ms"));
+    }
+    if let (Some(stddev), Some(jitter)) = (stat.stddev, stat.jitter) {
+        t.summary("RTT stddev/jitter", format!("{stddev}/{jitter} ms"));
+    }
+    if let (Some(p50), Some(p90), Some(p99)) = (stat.p50, stat.p90, stat.p99) {
+        t.summary("RTT p50/p90/p99", format!("{p50}/{p90}/{p99} ms"));
+    }
+    t.summary("Longest loss burst", stat.max_loss_burst);
+    for s in &stat.samples {
+        t.rows.push(vec![
+            s.seq.to_string(),
+            s.ip_addr.to_string(),
+            opt(s.hostname.as_ref()),
+            opt(s.port),
+            label(&s.protocol),
+            opt(s.rtt_ms),
+            s.probe_status.kind.to_string(),
+            s.probe_status.message.clone(),
+        ]);
+    }
+    t
+}
+
+fn port_scan_table(report: &PortScanReport) -> ExportTable {
+    let mut t = ExportTable::new(
+        ExportKind::PortScan,
+        &["port", "state", "service", "rtt_ms", "message"],
+    );
+    t.summary("Target", report.ip_addr);
+    if let Some(hostname) = &report.hostname {
+        t.summary("Hostname", hostname);
+    }
+    t.summary("Protocol", label(&report.protocol));
+    t.summary("Open ports", report.samples.len());
+    for s in &report.samples {
+        t.rows.push(vec![
+            s.port.to_string(),
+            label(&s.state),
+            opt(s.service_name.as_ref()),
+            opt(s.rtt_ms),
+            opt(s.message.as_ref()),
+        ]);
+    }
+    t
+}
+
+fn host_scan_table(report: &HostScanReport) -> ExportTable {
+    let mut t = ExportTable::new(ExportKind::HostScan, &["ip_addr", "state", "rtt_ms"]);
+    t.summary("Scanned", report.total);
+    t.summary("Alive", report.alive.len());
+    t.summary("Unreachable", report.unreachable.len());
+    for (ip, rtt) in &report.alive {
+        t.rows
+            .push(vec![ip.to_string(), "alive".to_string(), rtt.to_string()]);
+    }
+    for ip in &report.unreachable {
+        t.rows.push(vec![
+            ip.to_string(),
+            "unreachable".to_string(),
+            String::new(),
+        ]);
+    }
+    t
+}
+
+fn neighbor_scan_table(report: &NeighborScanReport) -> ExportTable {
+    let mut t = ExportTable::new(
+        ExportKind::NeighborScan,
+        &["ip_addr", "mac_addr", "vendor", "rtt_ms", "tags"],
+    );
+    t.summary("Scanned", report.total);
+    t.summary("Neighbors", report.neighbors.len());
+    for n in &report.neighbors {
+        t.rows.push(vec![
+            n.ip_addr.to_string(),
+            opt(n.mac_addr),
+            opt(n.vendor.as_ref()),
+            opt(n.rtt_ms),
+            n.tags.join(" "),
+        ]);
+    }
+    t
+}
+
+fn trace_table(report: Option<&TraceReport>, hops: &[TraceHop]) -> ExportTable {
+    let mut t = ExportTable::new(
+        ExportKind::Traceroute,
+        &["hop", "ip_addr", "rtt_ms", "reached", "note"],
+    );
+    if let Some(report) = report {
+        t.summary("Target", report.ip_addr);
+        if let Some(hostname) = &report.hostname {
+            t.summary("Hostname", hostname);
+        }
+        t.summary("Protocol", label(&report.protocol));
+    }
+    t.summary("Reached", hops.last().is_some_and(|h| h.reached));
+    t.summary("Hops", hops.len());
+    for h in hops {
+        t.rows.push(vec![
+            h.hop.to_string(),
+            opt(h.ip_addr),
+            opt(h.rtt_ms),
+            h.reached.to_string(),
+            opt(h.note.as_ref()),
+        ]);
+    }
+    t
+}
+
+fn routes_table(routes: &[RouteEntry]) -> ExportTable {
+    let mut t = ExportTable::new(
+        ExportKind::Routes,
+        &[
+            "family",
+            "destination",
+            "gateway",
+            "interface",
+            "metric",
+            "flags",
+            "protocol",
+            "scope",
+            "table",
+        ],
+    );
+    t.summary("Routes", routes.len());
+    for r in routes {
+        t.rows.push(vec![
+            r.family.to_string(),
+            r.destination.to_string(),
+            r.gateway
+                .map(|gw| gw.to_string())
+                .unwrap_or_else(|| "on-link".to_string()),
+            r.ifname
+                .clone()
+                .or_else(|| r.ifindex.map(|i| i.to_string()))
+                .unwrap_or_default(),
+            opt(r.metric),
+            r.flags.iter().map(label).collect::<Vec<_>>().join(" "),
+            r.protocol.as_ref().map(label).unwrap_or_default(),
+            r.scope.as_ref().map(label).unwrap_or_default(),
+            opt(r.table),
+        ]);
+    }
+    t
+}
+
+fn sockets_table(sockets: &[SocketInfo]) -> ExportTable {
+    let mut t = ExportTable::new(
+        ExportKind::Sockets,
+        &["protocol", "local", "remote", "state", "processes"],
+    );
+    t.summary("Sockets", sockets.len());
+    for s in sockets {
+        let processes = s
+            .processes
+            .iter()
+            .map(|p| format!("{}({})", p.name, p.pid))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match &s.protocol_socket_info {
+            ProtocolSocketInfo::Tcp(tcp) => t.rows.push(vec![
+                "TCP".to_string(),
+                endpoint(tcp.local_addr, tcp.local_port),
+                endpoint(tcp.remote_addr, tcp.remote_port),
+                tcp.state.to_string(),
+                processes,
+            ]),
+            ProtocolSocketInfo::Udp(udp) => t.rows.push(vec![
+                "UDP".to_string(),
+                endpoint(udp.local_addr, udp.local_port),
+                String::new(),
+                String::new(),
+                processes,
+            ]),
+        }
+    }
+    t
+}
@@ -0,0 +1,126 @@
+use super::ExportTable;
+use crate::model::AppInfo;
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_line<S: AsRef<str>>(cells: &[S]) -> String {
+    cells
+        .iter()
+        .map(|c| csv_field(c.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Header line followed by one line per row (RFC 4180 quoting).
+pub fn csv(table: &ExportTable) -> String {
+    let mut out = csv_line(&table.headers);
+    out.push_str("\r\n");
+    for row in &table.rows {
+        out.push_str(&csv_line(row));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn md_cell(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+fn md_row<S: AsRef<str>>(cells: &[S]) -> String {
+    let cells: Vec<String> = cells.iter().map(|c| md_cell(c.as_ref())).collect();
+    format!("| {} |\n", cells.join(" | "))
+}
+
+/// Heading, summary list and a GitHub flavored Markdown table.
+pub fn markdown(table: &ExportTable) -> String {
+    let mut out = format!("## {}\n\n", table.title);
+    for (key, value) in &table.summary {
+        out.push_str(&format!("- **{}**: {}\n", md_cell(key), md_cell(value)));
+    }
+    if !table.summary.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&md_row(&table.headers));
+    out.push_str(&format!("|{}\n", " --- |".repeat(table.headers.len())));
+    for row in &table.rows {
+        out.push_str(&md_row(row));
+    }
+    out
+}
+
+fn html_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+const HTML_STYLE: &str =
+    "body{font-family:system-ui,-apple-system,'Segoe UI',sans-serif;margin:2rem;color:#1f2328}\
+h1{font-size:1.4rem}\
+dl{display:grid;grid-template-columns:max-content auto;gap:.25rem 1rem}\
+dt{font-weight:600}dd{margin:0}\
+table{border-collapse:collapse;margin-top:1rem;font-size:.9rem}\
+th,td{border:1px solid #d0d7de;padding:.3rem .6rem;text-align:left}\
+th{background:#f6f8fa}tr:nth-child(even) td{background:#fafbfc}\
+footer{margin-top:1.5rem;font-size:.8rem;color:#656d76}";
+
+/// Self-contained HTML document (inline CSS, no external resources).
+pub fn html(table: &ExportTable) -> String {
+    let title = html_escape(&table.title);
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!(
+        "<title>{title}</title>\n<style>{HTML_STYLE}</style>\n"
+    ));
+    out.push_str("</head>\n<body>\n");
+    out.push_str(&format!("<h1>{title}</h1>\n"));
+    if !table.summary.is_empty() {
+        out.push_str("<dl>\n");
+        for (key, value) in &table.summary {
+            out.push_str(&format!(
+                "<dt>{}</dt><dd>{}</dd>\n",
+                html_escape(key),
+                html_escape(value)
+            ));
+        }
+        out.push_str("</dl>\n");
+    }
+    out.push_str("<table>\n<thead><tr>");
+    for header in &table.headers {
+        out.push_str(&format!("<th>{}</th>", html_escape(header)));
+    }
+    out.push_str("</tr></thead>\n<tbody>\n");
+    for row in &table.rows {
+        out.push_str("<tr>");
+        for cell in row {
+            out.push_str(&format!("<td>{}</td>", html_escape(cell)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n");
+    let app = AppInfo::current();
+    out.push_str(&format!(
+        "<footer>Generated by {} {}</footer>\n",
+        app.name, app.version
+    ));
+    out.push_str("</body>\n</html>\n");
+    out
+}
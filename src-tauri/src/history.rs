@@ -0,0 +1,643 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::model::run::{RunInfo, RunKind, RunStatus};
+
+pub const HISTORY_DIR_NAME: &str = "history";
+const RECORDS_FILE_NAME: &str = "runs.jsonl";
+const INDEX_FILE_NAME: &str = "index.jsonl";
+
+/// A completed run as stored on disk
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryRecord {
+    pub run_id: String,
+    pub kind: RunKind,
+    pub target: String,
+    pub status: RunStatus,
+    /// Settings the run was started with
+    pub setting: serde_json::Value,
+    /// Unix time in milliseconds
+    pub started_at_ms: u64,
+    /// Unix time in milliseconds
+    pub finished_at_ms: u64,
+    /// `PingStat`, `PortScanReport`, `HostScanReport`, `NeighborScanReport`, `TraceReport`
+    /// or `MtrReport`
+    pub result: serde_json::Value,
+}
+
+/// Index entry pointing at a record in the records file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub run_id: String,
+    pub kind: RunKind,
+    pub target: String,
+    pub status: RunStatus,
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+    /// Byte offset of the record line
+    pub offset: u64,
+    /// Byte length of the record line (without newline)
+    pub len: u64,
+}
+
+impl HistoryEntry {
+    fn new(record: &HistoryRecord, offset: u64, len: u64) -> Self {
+        Self {
+            run_id: record.run_id.clone(),
+            kind: record.kind,
+            target: record.target.clone(),
+            status: record.status,
+            started_at_ms: record.started_at_ms,
+            finished_at_ms: record.finished_at_ms,
+            offset,
+            len,
+        }
+    }
+}
+
+/// Filter for history queries. All conditions are optional and combined with AND.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub kind: Option<RunKind>,
+    /// Case-insensitive substring of the target (IP, hostname or interface)
+    pub target: Option<String>,
+    /// Started at or after (unix ms)
+    pub from_ms: Option<u64>,
+    /// Started at or before (unix ms)
+    pub to_ms: Option<u64>,
+    /// Maximum number of entries, newest first
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        if let Some(kind) = self.kind {
+            if entry.kind != kind {
+                return false;
+            }
+        }
+        if let Some(target) = &self.target {
+            if !entry.target.to_lowercase().contains(&target.to_lowercase()) {
+                return false;
+            }
+        }
+        if self.from_ms.is_some_and(|from| entry.started_at_ms < from) {
+            return false;
+        }
+        if self.to_ms.is_some_and(|to| entry.started_at_ms > to) {
+            return false;
+        }
+        true
+    }
+}
+
+pub fn unix_millis(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Append-only JSONL store of completed runs under `~/.netpulsar/history`.
+///
+/// `runs.jsonl` holds one `HistoryRecord` per line and `index.jsonl` holds the
+/// matching `HistoryEntry` with the byte range of the record, so queries only
+/// read the index. Deleting and pruning rewrite both files. `runs.jsonl` is the
+/// source of truth: records missing from the index are indexed on the next read,
+/// and an index that points past the end of the records is rebuilt from them.
+///
+/// All calls do blocking file IO; async callers run them with `spawn_blocking`.
+#[derive(Debug)]
+pub struct HistoryStore {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl HistoryStore {
+    /// Store in `dir`, which is created on the first write.
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// `~/.netpulsar/history`, or a directory under the system temp dir if the
+    /// home directory cannot be resolved
+    pub fn default_dir() -> PathBuf {
+        let app_dir = crate::fs::get_app_dir_path().unwrap_or_else(|| {
+            tracing::error!("failed to resolve app dir, keeping history in the temp dir");
+            std::env::temp_dir().join(crate::fs::USER_APP_DIR_NAME)
+        });
+        app_dir.join(HISTORY_DIR_NAME)
+    }
+
+    fn records_path(&self) -> PathBuf {
+        self.dir.join(RECORDS_FILE_NAME)
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join(INDEX_FILE_NAME)
+    }
+
+    fn read_index(&self) -> Result<Vec<HistoryEntry>> {
+        let path = self.index_path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<HistoryEntry>(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!("skipping broken history index line: {e}"),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Index entries, brought in line with the records file: records appended after
+    /// the last indexed one (e.g. when `append` failed between the two writes) are
+    /// added to the index, and an index that points past the end of the records is
+    /// rebuilt from scratch.
+    fn entries(&self) -> Result<Vec<HistoryEntry>> {
+        let mut entries = self.read_index()?;
+        let records_len = std::fs::metadata(self.records_path())
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let indexed_len = entries
+            .iter()
+            .map(|e| e.offset + e.len + 1)
+            .max()
+            .unwrap_or(0);
+        if indexed_len > records_len {
+            tracing::warn!("history index does not match the records file, rebuilding");
+            return self.rebuild_index();
+        }
+        if indexed_len < records_len {
+            let tail = self.scan_records(indexed_len)?;
+            if !tail.is_empty() {
+                tracing::warn!("indexing {} unindexed history records", tail.len());
+                let (mut index, _) = open_append(&self.index_path())?;
+                write_entries(&mut index, &tail)?;
+                entries.extend(tail);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Entries of the records from byte `from` of the records file, which must be
+    /// the start of a line.
+    fn scan_records(&self, from: u64) -> Result<Vec<HistoryEntry>> {
+        let mut entries = Vec::new();
+        let Ok(mut file) = File::open(self.records_path()) else {
+            return Ok(entries);
+        };
+        file.seek(SeekFrom::Start(from))?;
+        let mut reader = BufReader::new(file);
+        let mut line = Vec::new();
+        let mut offset = from;
+        loop {
+            line.clear();
+            let n = reader.read_until(b'\n', &mut line)?;
+            if n == 0 {
+                break;
+            }
+            let content = line.strip_suffix(b"\n").unwrap_or(&line);
+            match serde_json::from_slice::<HistoryRecord>(content) {
+                Ok(record) => {
+                    entries.push(HistoryEntry::new(&record, offset, content.len() as u64))
+                }
+                Err(e) => tracing::warn!("skipping broken history record: {e}"),
+            }
+            offset += n as u64;
+        }
+        Ok(entries)
+    }
+
+    /// Write a fresh index from the records in the records file.
+    fn rebuild_index(&self) -> Result<Vec<HistoryEntry>> {
+        let entries = self.scan_records(0)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let index_path = self.index_path();
+        let index_tmp = index_path.with_extension("jsonl.tmp");
+        let mut index = File::create(&index_tmp)?;
+        write_entries(&mut index, &entries)?;
+        index.sync_all()?;
+        std::fs::rename(&index_tmp, &index_path)?;
+        Ok(entries)
+    }
+
+    fn read_record(file: &mut File, entry: &HistoryEntry) -> Result<HistoryRecord> {
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut buf = vec![0u8; entry.len as usize];
+        file.read_exact(&mut buf)?;
+        let record: HistoryRecord = serde_json::from_slice(&buf)?;
+        if record.run_id != entry.run_id {
+            bail!("history index out of date at run {}", entry.run_id);
+        }
+        Ok(record)
+    }
+
+    /// Persist a finished run together with its result.
+    pub fn append<T: Serialize>(&self, info: &RunInfo, result: &T) -> Result<()> {
+        let record = HistoryRecord {
+            run_id: info.run_id.clone(),
+            kind: info.kind,
+            target: info.target.clone(),
+            status: info.status,
+            setting: info.setting.clone(),
+            started_at_ms: unix_millis(info.started_at),
+            finished_at_ms: unix_millis(info.finished_at.unwrap_or_else(SystemTime::now)),
+            result: serde_json::to_value(result)?,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+
+        let _guard = self.lock.lock().unwrap();
+        std::fs::create_dir_all(&self.dir)?;
+        let (mut records, offset) = open_append(&self.records_path())?;
+        let len = line.len() as u64;
+        line.push(b'\n');
+        records.write_all(&line)?;
+
+        let entry = HistoryEntry::new(&record, offset, len);
+        let (mut index, _) = open_append(&self.index_path())?;
+        write_entries(&mut index, &[entry])
+    }
+
+    /// Index entries matching `query`, newest first.
+    pub fn query(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries: Vec<HistoryEntry> = self
+            .entries()?
+            .into_iter()
+            .filter(|e| query.matches(e))
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.started_at_ms));
+        if let Some(limit) = query.limit {
+            entries.truncate(limit);
+        }
+        Ok(entries)
+    }
+
+    /// Full record of a run.
+    pub fn get(&self, run_id: &str) -> Result<Option<HistoryRecord>> {
+        let _guard = self.lock.lock().unwrap();
+        let find =
+            |entries: Vec<HistoryEntry>| entries.into_iter().rev().find(|e| e.run_id == run_id);
+        let Some(entry) = find(self.entries()?) else {
+            return Ok(None);
+        };
+        let mut file = File::open(self.records_path())?;
+        match Self::read_record(&mut file, &entry) {
+            Ok(record) => Ok(Some(record)),
+            Err(e) => {
+                // The index points at the wrong bytes; rebuild it and look again
+                tracing::warn!("{e}, rebuilding the history index");
+                let Some(entry) = find(self.rebuild_index()?) else {
+                    return Ok(None);
+                };
+                Ok(Some(Self::read_record(&mut file, &entry)?))
+            }
+        }
+    }
+
+    /// Delete the given runs. Returns the number of removed entries.
+    pub fn delete(&self, run_ids: &[String]) -> Result<usize> {
+        let _guard = self.lock.lock().unwrap();
+        let entries = self.entries()?;
+        let keep: Vec<HistoryEntry> = entries
+            .iter()
+            .filter(|e| !run_ids.contains(&e.run_id))
+            .cloned()
+            .collect();
+        let removed = entries.len() - keep.len();
+        if removed > 0 {
+            self.rewrite(keep)?;
+        }
+        Ok(removed)
+    }
+
+    /// Remove entries older than `max_age_ms`, then the oldest entries until
+    /// the records file fits into `max_bytes`. Returns the number of removed entries.
+    pub fn prune(&self, max_age_ms: Option<u64>, max_bytes: Option<u64>) -> Result<usize> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = self.entries()?;
+        let before = entries.len();
+        if let Some(max_age) = max_age_ms {
+            let cutoff = unix_millis(SystemTime::now()).saturating_sub(max_age);
+            entries.retain(|e| e.finished_at_ms >= cutoff);
+        }
+        if let Some(max_bytes) = max_bytes {
+            entries.sort_by_key(|e| e.started_at_ms);
+            let mut total: u64 = entries.iter().map(|e| e.len + 1).sum();
+            let mut skip = 0;
+            while total > max_bytes && skip < entries.len() {
+                total -= entries[skip].len + 1;
+                skip += 1;
+            }
+            entries.drain(..skip);
+        }
+        let removed = before - entries.len();
+        if removed > 0 {
+            self.rewrite(entries)?;
+        }
+        Ok(removed)
+    }
+
+    /// Write the records of `keep` into fresh files and swap them in.
+    ///
+    /// The old index is removed before the records file is replaced, so a crash
+    /// in between leaves no index rather than a stale one, and the next read
+    /// rebuilds it from whichever records file is in place.
+    fn rewrite(&self, keep: Vec<HistoryEntry>) -> Result<()> {
+        let records_path = self.records_path();
+        let index_path = self.index_path();
+        let records_tmp = records_path.with_extension("jsonl.tmp");
+        let index_tmp = index_path.with_extension("jsonl.tmp");
+
+        let mut src = File::open(&records_path)?;
+        let mut records = File::create(&records_tmp)?;
+        let mut entries = Vec::with_capacity(keep.len());
+        let mut offset = 0u64;
+        for mut entry in keep {
+            let record = Self::read_record(&mut src, &entry)?;
+            let buf = serde_json::to_vec(&record)?;
+            records.write_all(&buf)?;
+            records.write_all(b"\n")?;
+            entry.offset = offset;
+            entry.len = buf.len() as u64;
+            offset += entry.len + 1;
+            entries.push(entry);
+        }
+        records.sync_all()?;
+        let mut index = File::create(&index_tmp)?;
+        write_entries(&mut index, &entries)?;
+        index.sync_all()?;
+        drop(src);
+
+        match std::fs::remove_file(&index_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        std::fs::rename(&records_tmp, &records_path)?;
+        std::fs::rename(&index_tmp, &index_path)?;
+        Ok(())
+    }
+}
+
+/// Open a JSONL file for appending and return it with its length. A last line cut
+/// short by an earlier failed write is terminated first, so the next line stays whole.
+fn open_append(path: &Path) -> Result<(File, u64)> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+    let mut len = file.seek(SeekFrom::End(0))?;
+    if len > 0 {
+        let mut last = [0u8];
+        file.seek(SeekFrom::Start(len - 1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            file.write_all(b"\n")?;
+            len += 1;
+        }
+    }
+    Ok((file, len))
+}
+
+/// Write `entries` as JSON lines.
+fn write_entries(file: &mut File, entries: &[HistoryEntry]) -> Result<()> {
+    let mut buf = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut buf, entry)?;
+        buf.push(b'\n');
+    }
+    file.write_all(&buf)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::run::RunProgress;
+    use std::time::Duration;
+
+    /// Store in a fresh directory that is removed on drop
+    struct TempStore {
+        store: HistoryStore,
+    }
+
+    impl TempStore {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("netpulsar-test-{}", uuid::Uuid::new_v4()));
+            Self {
+                store: HistoryStore::new(dir),
+            }
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.store.dir);
+        }
+    }
+
+    fn run(run_id: &str, kind: RunKind, target: &str, started_ms: u64) -> RunInfo {
+        let started_at = UNIX_EPOCH + Duration::from_millis(started_ms);
+        RunInfo {
+            run_id: run_id.to_string(),
+            kind,
+            target: target.to_string(),
+            setting: serde_json::json!({ "count": 4 }),
+            started_at,
+            finished_at: Some(started_at + Duration::from_millis(500)),
+            progress: RunProgress::default(),
+            status: RunStatus::Done,
+            error: None,
+        }
+    }
+
+    fn ids(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.run_id.as_str()).collect()
+    }
+
+    /// Store with runs `a` (ping), `b` (trace) and `c` (ping), started in that order
+    fn populated() -> TempStore {
+        let temp = TempStore::new();
+        let store = &temp.store;
+        store
+            .append(&run("a", RunKind::Ping, "192.0.2.1", 1000), &"result a")
+            .unwrap();
+        store
+            .append(
+                &run("b", RunKind::Traceroute, "example.com", 2000),
+                &"result b",
+            )
+            .unwrap();
+        store
+            .append(&run("c", RunKind::Ping, "Example.net", 3000), &"result c")
+            .unwrap();
+        temp
+    }
+
+    #[test]
+    fn append_query_and_get() {
+        let temp = populated();
+        let store = &temp.store;
+        let all = store.query(&HistoryQuery::default()).unwrap();
+        assert_eq!(ids(&all), ["c", "b", "a"]);
+
+        let query = HistoryQuery {
+            kind: Some(RunKind::Ping),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.query(&query).unwrap()), ["c", "a"]);
+        let query = HistoryQuery {
+            target: Some("EXAMPLE".to_string()),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.query(&query).unwrap()), ["c"]);
+        let query = HistoryQuery {
+            from_ms: Some(1500),
+            to_ms: Some(2500),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.query(&query).unwrap()), ["b"]);
+
+        let record = store.get("b").unwrap().unwrap();
+        assert_eq!(record.kind, RunKind::Traceroute);
+        assert_eq!(record.started_at_ms, 2000);
+        assert_eq!(record.finished_at_ms, 2500);
+        assert_eq!(record.result, "result b");
+        assert_eq!(record.setting["count"], 4);
+        assert!(store.get("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn empty_store() {
+        let temp = TempStore::new();
+        assert!(temp
+            .store
+            .query(&HistoryQuery::default())
+            .unwrap()
+            .is_empty());
+        assert!(temp.store.get("a").unwrap().is_none());
+        assert_eq!(temp.store.delete(&["a".to_string()]).unwrap(), 0);
+    }
+
+    #[test]
+    fn delete_rewrites_both_files() {
+        let temp = populated();
+        let store = &temp.store;
+        assert_eq!(
+            store.delete(&["b".to_string(), "x".to_string()]).unwrap(),
+            1
+        );
+        assert_eq!(
+            ids(&store.query(&HistoryQuery::default()).unwrap()),
+            ["c", "a"]
+        );
+        assert!(store.get("b").unwrap().is_none());
+        assert_eq!(store.get("c").unwrap().unwrap().result, "result c");
+        // The rewritten index matches the rewritten records without a rebuild
+        assert_eq!(ids(&store.read_index().unwrap()), ["a", "c"]);
+    }
+
+    #[test]
+    fn prune_by_size_and_age() {
+        let temp = populated();
+        let store = &temp.store;
+        let entries = store.query(&HistoryQuery::default()).unwrap();
+        let newest_two: u64 = entries[..2].iter().map(|e| e.len + 1).sum();
+        assert_eq!(store.prune(None, Some(newest_two)).unwrap(), 1);
+        assert_eq!(
+            ids(&store.query(&HistoryQuery::default()).unwrap()),
+            ["c", "b"]
+        );
+        // Every run finished in 1970
+        assert_eq!(store.prune(Some(1000), None).unwrap(), 2);
+        assert!(store.query(&HistoryQuery::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn indexes_records_missing_from_the_index() {
+        let temp = populated();
+        let store = &temp.store;
+        // A record written by an `append` that failed before updating the index
+        let record = store.get("a").unwrap().unwrap();
+        let record = HistoryRecord {
+            run_id: "d".to_string(),
+            started_at_ms: 4000,
+            ..record
+        };
+        let mut line = serde_json::to_vec(&record).unwrap();
+        line.push(b'\n');
+        let (mut records, _) = open_append(&store.records_path()).unwrap();
+        records.write_all(&line).unwrap();
+
+        assert_eq!(
+            ids(&store.query(&HistoryQuery::default()).unwrap()),
+            ["d", "c", "b", "a"]
+        );
+        assert_eq!(store.read_index().unwrap().len(), 4);
+        // Rewrites keep it
+        store.delete(&["a".to_string()]).unwrap();
+        assert_eq!(store.get("d").unwrap().unwrap().started_at_ms, 4000);
+    }
+
+    #[test]
+    fn rebuilds_a_missing_or_stale_index() {
+        let temp = populated();
+        let store = &temp.store;
+        let old_index = std::fs::read(store.index_path()).unwrap();
+        store.delete(&["a".to_string()]).unwrap();
+
+        // Index of the records before the rewrite, pointing past their end
+        std::fs::write(store.index_path(), &old_index).unwrap();
+        assert_eq!(
+            ids(&store.query(&HistoryQuery::default()).unwrap()),
+            ["c", "b"]
+        );
+
+        // Crash in `rewrite` after the old index was removed
+        std::fs::remove_file(store.index_path()).unwrap();
+        assert_eq!(
+            ids(&store.query(&HistoryQuery::default()).unwrap()),
+            ["c", "b"]
+        );
+        assert_eq!(store.get("b").unwrap().unwrap().result, "result b");
+    }
+
+    #[test]
+    fn recovers_from_a_truncated_record() {
+        let temp = populated();
+        let store = &temp.store;
+        let (mut records, _) = open_append(&store.records_path()).unwrap();
+        records.write_all(br#"{"run_id":"cut"#).unwrap();
+        store
+            .append(&run("e", RunKind::Mtr, "192.0.2.9", 5000), &"result e")
+            .unwrap();
+        assert_eq!(
+            ids(&store.query(&HistoryQuery::default()).unwrap()),
+            ["e", "c", "b", "a"]
+        );
+        assert_eq!(store.get("e").unwrap().unwrap().result, "result e");
+        store.delete(&["b".to_string()]).unwrap();
+        assert_eq!(
+            ids(&store.query(&HistoryQuery::default()).unwrap()),
+            ["e", "c", "a"]
+        );
+    }
+}
@@ -1,16 +1,27 @@
+mod api;
 mod app;
+mod cli;
 mod command;
 mod config;
+mod export;
 mod fs;
+mod history;
 mod net;
 mod log;
+mod metrics;
 mod model;
 mod probe;
 mod service;
 mod socket;
 mod state;
+mod stats;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     app::run();
 }
+
+/// Entry point of the headless `netpulsar-cli` binary.
+pub fn run_cli() -> std::process::ExitCode {
+    cli::run()
+}
@@ -17,6 +17,11 @@ pub fn init_logger(config: &crate::config::AppConfig) -> Result<()> {
     } else {
         crate::fs::get_user_file_path(DEFAULT_LOG_FILE_NAME).unwrap()
     };
+    if let Some(parent) = log_file_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
     let log_file: File = if log_file_path.exists() {
         File::options().write(true).open(&log_file_path)?
     } else {
@@ -0,0 +1,113 @@
+use std::fmt::{Display, Write};
+
+/// Exposition format negotiated from the `Accept` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Prometheus text format 0.0.4
+    Prometheus,
+    /// OpenMetrics 1.0.0 text format
+    OpenMetrics,
+}
+
+impl Format {
+    pub fn from_accept(accept: Option<&str>) -> Format {
+        match accept {
+            Some(v) if v.contains("application/openmetrics-text") => Format::OpenMetrics,
+            _ => Format::Prometheus,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            Format::OpenMetrics => "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+        }
+    }
+}
+
+/// Writer for the text exposition formats.
+pub struct Encoder {
+    format: Format,
+    out: String,
+}
+
+impl Encoder {
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            out: String::new(),
+        }
+    }
+
+    /// Start a metric family. Counter families are named without the `_total`
+    /// suffix; their samples must carry it.
+    pub fn family(&mut self, name: &str, kind: MetricType, help: &str) {
+        let suffix = match (kind, self.format) {
+            (MetricType::Counter, Format::Prometheus) => "_total",
+            _ => "",
+        };
+        let _ = writeln!(self.out, "# HELP {name}{suffix} {}", escape_help(help));
+        let _ = writeln!(self.out, "# TYPE {name}{suffix} {}", kind.as_str());
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            self.out.push('{');
+            for (i, (key, val)) in labels.iter().enumerate() {
+                if i > 0 {
+                    self.out.push(',');
+                }
+                let _ = write!(self.out, "{key}=\"{}\"", escape_label(val));
+            }
+            self.out.push('}');
+        }
+        let _ = writeln!(self.out, " {value}");
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.format == Format::OpenMetrics {
+            self.out.push_str("# EOF\n");
+        }
+        self.out
+    }
+}
+
+/// Format a float sample value (`+Inf`, `-Inf` and `NaN` as the formats spell them).
+pub fn float(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_help(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
@@ -0,0 +1,294 @@
+//! Prometheus / OpenMetrics exporter.
+//!
+//! Serves `GET /metrics` on 127.0.0.1 with the per-interface traffic counters
+//! maintained by the supervisor and the results of recurring ping monitors.
+
+mod encode;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc, time::Duration};
+use tauri::async_runtime;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{AppConfig, MetricsConfig};
+use crate::state::{AppState, IfStats};
+use encode::{float, Encoder, Format, MetricType};
+
+/// Upper bounds of the ping RTT histogram buckets, in seconds
+const RTT_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+#[derive(Debug, Clone, Default)]
+struct PingSeries {
+    sent: u64,
+    received: u64,
+    /// Per-bucket (non-cumulative) counts. The last entry is `+Inf`.
+    buckets: [u64; RTT_BUCKETS.len() + 1],
+    rtt_sum: f64,
+}
+
+/// Results of recurring ping monitors, keyed by (monitor, target)
+#[derive(Debug, Default)]
+pub struct PingMetrics {
+    series: std::sync::Mutex<BTreeMap<(String, String), PingSeries>>,
+}
+
+impl PingMetrics {
+    /// Record one probe of a monitor. `rtt` is `None` when the probe was lost.
+    pub fn record(&self, monitor: &str, target: &str, rtt: Option<Duration>) {
+        let mut series = self.series.lock().unwrap();
+        let s = series
+            .entry((monitor.to_string(), target.to_string()))
+            .or_default();
+        s.sent += 1;
+        if let Some(rtt) = rtt {
+            let secs = rtt.as_secs_f64();
+            let bucket = RTT_BUCKETS
+                .iter()
+                .position(|le| secs <= *le)
+                .unwrap_or(RTT_BUCKETS.len());
+            s.received += 1;
+            s.buckets[bucket] += 1;
+            s.rtt_sum += secs;
+        }
+    }
+
+    /// Drop the series of a monitor that was stopped.
+    pub fn remove(&self, monitor: &str, target: &str) {
+        self.series
+            .lock()
+            .unwrap()
+            .remove(&(monitor.to_string(), target.to_string()));
+    }
+
+    fn encode(&self, enc: &mut Encoder) {
+        let series = self.series.lock().unwrap();
+        if series.is_empty() {
+            return;
+        }
+        enc.family(
+            "netpulsar_ping_rtt_seconds",
+            MetricType::Histogram,
+            "Round-trip time of successful monitor probes.",
+        );
+        for ((monitor, target), s) in series.iter() {
+            let labels = [("monitor", monitor.as_str()), ("target", target.as_str())];
+            let mut cumulative = 0;
+            for (i, count) in s.buckets.iter().enumerate() {
+                cumulative += count;
+                let le = RTT_BUCKETS.get(i).copied().unwrap_or(f64::INFINITY);
+                let le = float(le);
+                enc.sample(
+                    "netpulsar_ping_rtt_seconds_bucket",
+                    &[labels[0], labels[1], ("le", &le)],
+                    cumulative,
+                );
+            }
+            enc.sample("netpulsar_ping_rtt_seconds_sum", &labels, float(s.rtt_sum));
+            enc.sample("netpulsar_ping_rtt_seconds_count", &labels, s.received);
+        }
+        enc.family(
+            "netpulsar_ping_sent",
+            MetricType::Counter,
+            "Monitor probes sent.",
+        );
+        for ((monitor, target), s) in series.iter() {
+            enc.sample(
+                "netpulsar_ping_sent_total",
+                &[("monitor", monitor), ("target", target)],
+                s.sent,
+            );
+        }
+        enc.family(
+            "netpulsar_ping_received",
+            MetricType::Counter,
+            "Monitor probes answered.",
+        );
+        for ((monitor, target), s) in series.iter() {
+            enc.sample(
+                "netpulsar_ping_received_total",
+                &[("monitor", monitor), ("target", target)],
+                s.received,
+            );
+        }
+        enc.family(
+            "netpulsar_ping_loss_ratio",
+            MetricType::Gauge,
+            "Fraction of monitor probes lost since the monitor started.",
+        );
+        for ((monitor, target), s) in series.iter() {
+            let loss = if s.sent == 0 {
+                0.0
+            } else {
+                (s.sent - s.received) as f64 / s.sent as f64
+            };
+            enc.sample(
+                "netpulsar_ping_loss_ratio",
+                &[("monitor", monitor), ("target", target)],
+                float(loss),
+            );
+        }
+    }
+}
+
+/// Metric family name, type, help and value of a per-interface metric
+type InterfaceMetric = (
+    &'static str,
+    MetricType,
+    &'static str,
+    fn(&IfStats) -> String,
+);
+
+const INTERFACE_METRICS: [InterfaceMetric; 4] = [
+    (
+        "netpulsar_interface_receive_bytes",
+        MetricType::Counter,
+        "Total bytes received on the interface.",
+        |s| s.rx_bytes.to_string(),
+    ),
+    (
+        "netpulsar_interface_transmit_bytes",
+        MetricType::Counter,
+        "Total bytes transmitted on the interface.",
+        |s| s.tx_bytes.to_string(),
+    ),
+    (
+        "netpulsar_interface_receive_bytes_per_second",
+        MetricType::Gauge,
+        "Receive rate over the last supervisor tick.",
+        |s| float(s.rx_bytes_per_sec),
+    ),
+    (
+        "netpulsar_interface_transmit_bytes_per_second",
+        MetricType::Gauge,
+        "Transmit rate over the last supervisor tick.",
+        |s| float(s.tx_bytes_per_sec),
+    ),
+];
+
+/// Render all metrics in `format`.
+async fn render(state: &AppState, format: Format) -> String {
+    let mut enc = Encoder::new(format);
+
+    // (name, index, oper_state, stats) per interface with stats
+    let rows = {
+        let ifaces = state.interfaces.lock().await;
+        let stats = state.stats.lock().await;
+        let mut rows: Vec<_> = ifaces
+            .iter()
+            .filter_map(|(index, iface)| {
+                stats.get(index).map(|s| {
+                    (
+                        iface.name.clone(),
+                        index.to_string(),
+                        iface.oper_state.as_str(),
+                        s.clone(),
+                    )
+                })
+            })
+            .collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        rows
+    };
+    if !rows.is_empty() {
+        for (name, kind, help, value) in INTERFACE_METRICS {
+            enc.family(name, kind, help);
+            let sample_name = match kind {
+                MetricType::Counter => format!("{name}_total"),
+                _ => name.to_string(),
+            };
+            for (ifname, index, oper_state, stats) in &rows {
+                enc.sample(
+                    &sample_name,
+                    &[
+                        ("name", ifname),
+                        ("index", index),
+                        ("oper_state", oper_state),
+                    ],
+                    value(stats),
+                );
+            }
+        }
+    }
+
+    state.ping_metrics.encode(&mut enc);
+    enc.finish()
+}
+
+async fn handle_metrics(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok());
+    let format = Format::from_accept(accept);
+    let body = render(&state, format).await;
+    ([(header::CONTENT_TYPE, format.content_type())], body)
+}
+
+/// Run the metrics server until `shutdown` is cancelled.
+async fn serve(state: Arc<AppState>, port: u16, shutdown: CancellationToken) -> anyhow::Result<()> {
+    let router = Router::new()
+        .route("/metrics", get(handle_metrics))
+        .with_state(state);
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("metrics endpoint listening on http://{addr}/metrics");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    tracing::info!("metrics endpoint stopped");
+    Ok(())
+}
+
+/// Spawn a task that starts, stops and restarts the metrics server
+/// as `metrics` settings published on `config` change.
+pub fn spawn_metrics_server(
+    state: Arc<AppState>,
+    mut config: watch::Receiver<AppConfig>,
+) -> async_runtime::JoinHandle<()> {
+    async_runtime::spawn(async move {
+        let mut running: Option<(
+            MetricsConfig,
+            CancellationToken,
+            async_runtime::JoinHandle<()>,
+        )> = None;
+        loop {
+            let metrics = config.borrow_and_update().metrics.clone();
+            let wanted = metrics.enabled.then_some(&metrics);
+            if running.as_ref().map(|(cfg, _, _)| cfg) != wanted {
+                if let Some((_, token, handle)) = running.take() {
+                    token.cancel();
+                    let _ = handle.await;
+                }
+                if metrics.enabled {
+                    let token = CancellationToken::new();
+                    let handle = async_runtime::spawn({
+                        let (state, port, token) = (state.clone(), metrics.port, token.clone());
+                        async move {
+                            if let Err(e) = serve(state, port, token).await {
+                                tracing::error!("metrics endpoint failed: {e:?}");
+                            }
+                        }
+                    });
+                    running = Some((metrics, token, handle));
+                }
+            }
+            if config.changed().await.is_err() {
+                break;
+            }
+        }
+        if let Some((_, token, handle)) = running {
+            token.cancel();
+            let _ = handle.await;
+        }
+    })
+}
@@ -3,9 +3,15 @@
 pub mod dns;
 pub mod endpoint;
 pub mod interface;
+pub mod monitor;
+pub mod ntp;
 pub mod ping;
+pub mod pmtu;
 pub mod probe;
+pub mod quic;
+pub mod run;
 pub mod scan;
+pub mod tls;
 
 use serde::{Deserialize, Serialize};
 
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::model::ping::PingProtocol;
+use crate::stats::RttStats;
+
+/// Health of a monitored target, derived from its thresholds
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitorState {
+    /// No probe has completed yet
+    Unknown,
+    Up,
+    Degraded,
+    Down,
+}
+
+/// Current status of a monitor
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MonitorStatus {
+    pub id: String,
+    pub name: String,
+    /// Target as configured (IP address, hostname or URL)
+    pub target: String,
+    /// Last resolved address of the target
+    pub ip_addr: Option<IpAddr>,
+    pub protocol: PingProtocol,
+    pub state: MonitorState,
+    /// Why the monitor is in its current state
+    pub reason: String,
+    /// Time the current state was entered (unix ms)
+    pub since_ms: u64,
+    /// Time of the last completed probe (unix ms)
+    pub last_checked_ms: Option<u64>,
+    pub last_rtt_ms: Option<f64>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    /// Probes sent since the monitor started
+    pub sent: u64,
+    /// Probes answered since the monitor started
+    pub received: u64,
+    /// Statistics over the most recent `window` probes
+    pub window: RttStats,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MonitorStateChangedPayload {
+    pub previous: MonitorState,
+    pub status: MonitorStatus,
+}
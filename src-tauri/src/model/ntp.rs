@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Leap indicator of an NTP reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NtpLeap {
+    None,
+    /// Last minute of the day has 61 seconds
+    AddSecond,
+    /// Last minute of the day has 59 seconds
+    DeleteSecond,
+    /// Server clock is not synchronized
+    Unsynchronized,
+}
+
+impl std::fmt::Display for NtpLeap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            NtpLeap::None => "none",
+            NtpLeap::AddSecond => "+1s",
+            NtpLeap::DeleteSecond => "-1s",
+            NtpLeap::Unsynchronized => "unsynchronized",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Reply of an NTP server to a client request
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NtpResponse {
+    pub version: u8,
+    pub leap: NtpLeap,
+    /// 1 for a server with a reference clock, 2-15 for hops away from one
+    pub stratum: u8,
+    /// Reference clock code for stratum 1 (e.g. `GPS`), else the address of the upstream server
+    pub reference_id: String,
+    /// Offset of the local clock from the server clock; positive if the local clock is behind
+    pub offset_ms: f64,
+    /// Round trip delay excluding the time the server held the request
+    pub delay_ms: f64,
+    /// Round trip delay and dispersion to the reference clock, as reported by the server
+    pub root_delay_ms: f64,
+    pub root_dispersion_ms: f64,
+    /// Precision of the server clock (log2 seconds)
+    pub precision: i8,
+}
+
+/// Result of querying one NTP server
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NtpQueryResult {
+    /// Server as given (hostname or IP address)
+    pub server: String,
+    /// `None` if the name did not resolve
+    pub ip_addr: Option<IpAddr>,
+    pub port: u16,
+    pub response: Option<NtpResponse>,
+    pub error: Option<String>,
+}
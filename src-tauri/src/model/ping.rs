@@ -1,15 +1,20 @@
-use crate::model::probe::ProbeStatus;
+use crate::model::ntp::NtpResponse;
+use crate::model::probe::{IcmpError, ProbeStatus};
+use crate::model::quic::{QuicOption, QuicSampleDetail};
+use crate::stats::RttStats;
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PingProtocol {
     Icmp,
     Tcp,
     Udp,
     Quic,
     Http,
+    Dns,
+    Ntp,
 }
 
 impl std::fmt::Display for PingProtocol {
@@ -20,6 +25,8 @@ impl std::fmt::Display for PingProtocol {
             PingProtocol::Udp => "UDP",
             PingProtocol::Quic => "QUIC",
             PingProtocol::Http => "HTTP",
+            PingProtocol::Dns => "DNS",
+            PingProtocol::Ntp => "NTP",
         };
         write!(f, "{}", s)
     }
@@ -34,6 +41,8 @@ impl FromStr for PingProtocol {
             "udp" => Ok(Self::Udp),
             "quic" => Ok(Self::Quic),
             "http" => Ok(Self::Http),
+            "dns" => Ok(Self::Dns),
+            "ntp" => Ok(Self::Ntp),
             _ => Err(()),
         }
     }
@@ -50,6 +59,295 @@ pub struct PingSetting {
     pub count: u32,
     pub timeout_ms: u64,
     pub send_rate_ms: u64,
+    /// Egress interface name (default: the interface chosen by the OS)
+    pub interface: Option<String>,
+    /// Source address (default: an address of `interface` or of the default interface)
+    pub src_ip: Option<IpAddr>,
+    /// Request options for HTTP ping
+    pub http: Option<HttpPingOption>,
+    /// Query options for DNS ping
+    pub dns: Option<DnsPingOption>,
+    /// Handshake options for QUIC ping
+    pub quic: Option<QuicOption>,
+    /// Request options for UDP ping
+    pub udp: Option<UdpPingOption>,
+    /// Echo request options for ICMP ping
+    pub icmp: Option<IcmpPingOption>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum HttpMethod {
+    #[default]
+    Get,
+    Head,
+}
+
+impl std::fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Head => "HEAD",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for HttpMethod {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "get" => Ok(Self::Get),
+            "head" => Ok(Self::Head),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Request header sent with HTTP ping
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// Request options and response checks for HTTP ping
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct HttpPingOption {
+    pub method: HttpMethod,
+    /// Extra request headers, sent after the default ones (a same-named header replaces the default)
+    pub headers: Vec<HttpHeader>,
+    /// Status codes that count as success (default: any status)
+    pub expected_status: Vec<u16>,
+    /// Text the response body must contain (ignored for HEAD)
+    pub body_contains: Option<String>,
+}
+
+/// Per-phase timing and response details of one HTTP ping sample.
+/// Phases that did not happen (e.g. DNS for an IP address, TLS for http) are `None`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HttpSampleDetail {
+    /// Name resolution
+    pub dns_ms: Option<f64>,
+    /// TCP connect
+    pub connect_ms: Option<f64>,
+    /// TLS handshake
+    pub tls_ms: Option<f64>,
+    /// Request sent until the response headers arrived
+    pub ttfb_ms: Option<f64>,
+    /// Response headers until the end of the body
+    pub download_ms: Option<f64>,
+    /// Whole request including connection setup
+    pub total_ms: Option<f64>,
+    pub status: Option<u16>,
+    /// e.g. `HTTP/1.1`, `HTTP/2`
+    pub version: Option<String>,
+    pub body_bytes: Option<u64>,
+}
+
+/// Transport of DNS ping queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DnsTransport {
+    #[default]
+    Udp,
+    Tcp,
+    /// DNS over TLS (RFC 7858)
+    Tls,
+    /// DNS over HTTPS (RFC 8484)
+    Https,
+}
+
+impl DnsTransport {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            DnsTransport::Udp | DnsTransport::Tcp => 53,
+            DnsTransport::Tls => 853,
+            DnsTransport::Https => 443,
+        }
+    }
+}
+
+impl std::fmt::Display for DnsTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            DnsTransport::Udp => "UDP",
+            DnsTransport::Tcp => "TCP",
+            DnsTransport::Tls => "DoT",
+            DnsTransport::Https => "DoH",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for DnsTransport {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "udp" => Ok(Self::Udp),
+            "tcp" => Ok(Self::Tcp),
+            "tls" | "dot" => Ok(Self::Tls),
+            "https" | "doh" => Ok(Self::Https),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Query options for DNS ping. The target of the ping is the DNS server.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct DnsPingOption {
+    pub transport: DnsTransport,
+    /// Name to query
+    pub query_name: String,
+    /// Record type to query, e.g. `A`, `AAAA`, `MX`
+    pub record_type: String,
+    /// Set the RD (recursion desired) flag; clear it to probe authoritative servers
+    pub recursion_desired: bool,
+    /// URL path of DoH queries
+    pub doh_path: String,
+}
+
+impl Default for DnsPingOption {
+    fn default() -> Self {
+        Self {
+            transport: DnsTransport::Udp,
+            query_name: "example.com".to_string(),
+            record_type: "A".to_string(),
+            recursion_desired: true,
+            doh_path: "/dns-query".to_string(),
+        }
+    }
+}
+
+/// Response details of one DNS ping sample
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DnsSampleDetail {
+    pub transport: DnsTransport,
+    /// Response code, e.g. `NOERROR`, `NXDOMAIN`, `SERVFAIL`
+    pub rcode: Option<String>,
+    /// Number of records in the answer section
+    pub answer_count: Option<u32>,
+    /// Response had the TC (truncated) flag set
+    pub truncated: bool,
+    /// Connection setup before the query (TCP connect and TLS handshake), `None` for UDP
+    pub connect_ms: Option<f64>,
+}
+
+/// What UDP ping sends and what counts as a reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum UdpPingMode {
+    /// Send to a presumably closed port and count ICMP Port Unreachable as the reply
+    #[default]
+    PortUnreachable,
+    /// DNS query for the root NS records
+    Dns,
+    /// NTP client request (RFC 5905)
+    Ntp,
+    /// SNMPv2c get of sysUpTime.0
+    Snmp,
+    /// RFC 862 echo; the reply must carry the same payload
+    Echo,
+    /// User supplied payload; any datagram from the target port is a reply
+    Hex,
+}
+
+impl UdpPingMode {
+    /// Well-known port of the service, `None` if the port has to be given
+    pub fn default_port(&self) -> Option<u16> {
+        match self {
+            UdpPingMode::PortUnreachable => Some(33435),
+            UdpPingMode::Dns => Some(53),
+            UdpPingMode::Ntp => Some(123),
+            UdpPingMode::Snmp => Some(161),
+            UdpPingMode::Echo => Some(7),
+            UdpPingMode::Hex => None,
+        }
+    }
+}
+
+impl std::fmt::Display for UdpPingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            UdpPingMode::PortUnreachable => "unreach",
+            UdpPingMode::Dns => "dns",
+            UdpPingMode::Ntp => "ntp",
+            UdpPingMode::Snmp => "snmp",
+            UdpPingMode::Echo => "echo",
+            UdpPingMode::Hex => "hex",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for UdpPingMode {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "unreach" | "icmp" => Ok(Self::PortUnreachable),
+            "dns" => Ok(Self::Dns),
+            "ntp" => Ok(Self::Ntp),
+            "snmp" => Ok(Self::Snmp),
+            "echo" => Ok(Self::Echo),
+            "hex" => Ok(Self::Hex),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Request options for UDP ping
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct UdpPingOption {
+    pub mode: UdpPingMode,
+    /// Payload for `Hex` mode as hex digits (whitespace and `:` are ignored)
+    pub payload_hex: Option<String>,
+    /// Community for `Snmp` mode; agents silently drop requests with a wrong community
+    pub snmp_community: String,
+}
+
+impl Default for UdpPingOption {
+    fn default() -> Self {
+        Self {
+            mode: UdpPingMode::PortUnreachable,
+            payload_hex: None,
+            snmp_community: "public".to_string(),
+        }
+    }
+}
+
+/// Default ICMP echo payload size, as used by ping(8)
+pub const DEFAULT_ICMP_PAYLOAD_SIZE: usize = 56;
+
+/// Echo request options for ICMP ping
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct IcmpPingOption {
+    /// Payload bytes after the 8-byte ICMP header
+    pub payload_size: usize,
+    /// Bytes repeated to fill the payload, as hex digits (default: a fixed marker)
+    pub pattern_hex: Option<String>,
+}
+
+impl Default for IcmpPingOption {
+    fn default() -> Self {
+        Self {
+            payload_size: DEFAULT_ICMP_PAYLOAD_SIZE,
+            pattern_hex: None,
+        }
+    }
+}
+
+/// Reply details of one ICMP ping sample
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IcmpSampleDetail {
+    /// Size of the ICMP reply (header and payload)
+    pub bytes: usize,
+    /// TTL (IPv4) or hop limit (IPv6) of the reply, `None` if the OS did not report it
+    pub ttl: Option<u8>,
+    /// Another reply to a probe that was already answered
+    pub duplicate: bool,
+    /// Reply to an earlier probe that arrived after its timeout
+    pub late: bool,
 }
 
 /// Single result of a ping operation
@@ -63,12 +361,24 @@ pub struct PingSample {
     pub hostname: Option<String>,
     /// Port
     pub port: Option<u16>,
-    /// Round Trip Time (milliseconds)
-    pub rtt_ms: Option<u64>,
+    /// Round Trip Time (milliseconds, microsecond precision)
+    pub rtt_ms: Option<f64>,
     /// Status
     pub probe_status: ProbeStatus,
     /// Protocol
     pub protocol: PingProtocol,
+    /// Timing and response details (HTTP ping only)
+    pub http: Option<HttpSampleDetail>,
+    /// Response details (DNS ping only)
+    pub dns: Option<DnsSampleDetail>,
+    /// Handshake details (QUIC ping only)
+    pub quic: Option<QuicSampleDetail>,
+    /// Server reply with the clock offset (NTP ping only)
+    pub ntp: Option<NtpResponse>,
+    /// Reply size and TTL (ICMP ping only)
+    pub icmp: Option<IcmpSampleDetail>,
+    /// ICMP error returned for the probe, with the reporting router or host
+    pub icmp_error: Option<IcmpError>,
 }
 
 /// Statistics of ping results
@@ -89,11 +399,63 @@ pub struct PingStat {
     /// Received packets
     pub received_count: usize,
     /// Minimum RTT in milliseconds
-    pub min: Option<u64>,
+    pub min: Option<f64>,
     /// Average RTT in milliseconds
-    pub avg: Option<u64>,
+    pub avg: Option<f64>,
     /// Maximum RTT in milliseconds
-    pub max: Option<u64>,
+    pub max: Option<f64>,
+    /// Standard deviation of RTT in milliseconds
+    pub stddev: Option<f64>,
+    /// RFC 3550 jitter in milliseconds
+    pub jitter: Option<f64>,
+    /// RTT percentiles in milliseconds
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+    pub p99: Option<f64>,
+    /// Longest run of consecutive lost probes
+    pub max_loss_burst: usize,
+    /// Duplicate replies, not counted as received
+    pub duplicate_count: usize,
+    /// Replies that arrived after their probe timed out, not counted as received
+    pub late_count: usize,
+}
+
+impl PingSample {
+    /// Sample of probe `seq` with no reply yet, an OK status and no protocol details
+    pub fn new(
+        seq: u32,
+        ip_addr: IpAddr,
+        hostname: Option<String>,
+        port: Option<u16>,
+        protocol: PingProtocol,
+    ) -> Self {
+        Self {
+            seq,
+            ip_addr,
+            hostname,
+            port,
+            rtt_ms: None,
+            probe_status: ProbeStatus::new(),
+            protocol,
+            http: None,
+            dns: None,
+            quic: None,
+            ntp: None,
+            icmp: None,
+            icmp_error: None,
+        }
+    }
+
+    /// Whether a reply was received for this probe
+    pub fn is_received(&self) -> bool {
+        self.rtt_ms.is_some() && self.probe_status.is_ok() && !self.is_extra_reply()
+    }
+
+    /// Whether this sample reports a duplicate or late reply to a probe
+    /// that already has its own sample
+    pub fn is_extra_reply(&self) -> bool {
+        self.icmp.as_ref().is_some_and(|d| d.duplicate || d.late)
+    }
 }
 
 impl PingStat {
@@ -104,30 +466,41 @@ impl PingStat {
         protocol: PingProtocol,
         samples: Vec<PingSample>,
     ) -> Self {
-        let transmitted_count = samples.len();
-        let received: Vec<_> = samples.iter().filter(|s| s.probe_status.is_ok()).collect();
-        let received_count = received.len();
-        let rtts: Vec<u64> = received.iter().filter_map(|s| s.rtt_ms).collect();
-
-        let min = rtts.iter().min().copied();
-        let max = rtts.iter().max().copied();
-        let avg = if !rtts.is_empty() {
-            Some(rtts.iter().sum::<u64>() / rtts.len() as u64)
-        } else {
-            None
-        };
-
+        let stats =
+            RttStats::from_series(samples.iter().filter(|s| !s.is_extra_reply()).map(|s| {
+                if s.is_received() {
+                    s.rtt_ms
+                } else {
+                    None
+                }
+            }));
+        let duplicate_count = samples
+            .iter()
+            .filter(|s| s.icmp.as_ref().is_some_and(|d| d.duplicate))
+            .count();
+        let late_count = samples
+            .iter()
+            .filter(|s| s.icmp.as_ref().is_some_and(|d| d.late))
+            .count();
         PingStat {
             ip_addr,
             hostname,
             port,
             protocol,
             samples,
-            transmitted_count,
-            received_count,
-            min,
-            avg,
-            max,
+            transmitted_count: stats.transmitted,
+            received_count: stats.received,
+            min: stats.min,
+            avg: stats.avg,
+            max: stats.max,
+            stddev: stats.stddev,
+            jitter: stats.jitter,
+            p50: stats.p50,
+            p90: stats.p90,
+            p99: stats.p99,
+            max_loss_burst: stats.max_loss_burst,
+            duplicate_count,
+            late_count,
         }
     }
 
@@ -166,3 +539,52 @@ pub struct PingErrorPayload {
     pub run_id: String,
     pub message: String,
 }
+
+/// Settings for an ICMP ping sweep over many targets
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MultiPingSetting {
+    /// IP addresses, hostnames or CIDR networks
+    pub targets: Vec<String>,
+    pub hop_limit: u8,
+    /// Echo requests per target
+    pub count: u32,
+    pub timeout_ms: u64,
+    /// Interval between rounds (one echo request to every target per round)
+    pub send_rate_ms: u64,
+    /// Egress interface name (default: the interface chosen by the OS)
+    pub interface: Option<String>,
+    /// Source address for targets of the same family
+    pub src_ip: Option<IpAddr>,
+}
+
+/// Target of a ping sweep after name resolution and CIDR expansion
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MultiPingTarget {
+    pub ip_addr: IpAddr,
+    pub hostname: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MultiPingStartPayload {
+    pub run_id: String,
+    pub targets: Vec<MultiPingTarget>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MultiPingProgressPayload {
+    pub run_id: String,
+    pub sample: PingSample,
+    /// Probes sent to and replies received from this target so far
+    pub transmitted: u32,
+    pub received: u32,
+    /// Probes finished over all targets
+    pub done: u32,
+    pub total: u32,
+}
+
+/// Result of a ping sweep, with one summary per target in target order
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MultiPingReport {
+    pub run_id: String,
+    pub stats: Vec<PingStat>,
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Settings for path MTU discovery
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PmtuSetting {
+    pub ip_addr: IpAddr,
+    pub hostname: Option<String>,
+    /// Largest packet size to try, IP header included
+    /// (default: MTU of the egress interface, or 1500 if unknown)
+    pub max_mtu: Option<u32>,
+    pub hop_limit: u8,
+    /// Timeout per probe (ms)
+    pub timeout_ms: u64,
+    /// Probes per packet size before the size counts as lost (rounded to 1 if 0)
+    pub tries: u8,
+    /// Egress interface name (default: the interface chosen by the OS)
+    pub interface: Option<String>,
+    /// Source address (default: an address of `interface` or of the default interface)
+    pub src_ip: Option<IpAddr>,
+}
+
+/// Outcome of probing one packet size
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PmtuProbeResult {
+    /// Echo reply received
+    Ok,
+    /// Fragmentation Needed / Packet Too Big received, or the size was refused locally
+    TooBig,
+    /// No reply within the timeout
+    Timeout,
+    Error,
+}
+
+/// Payload of the `pmtu:progress` event, one per packet size tried
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PmtuProbe {
+    pub run_id: String,
+    pub seq: u32,
+    /// Packet size including the IP and ICMP headers
+    pub size: u32,
+    pub result: PmtuProbeResult,
+    pub rtt_ms: Option<f64>,
+    /// Next-hop MTU reported in a Fragmentation Needed / Packet Too Big message
+    pub reported_mtu: Option<u32>,
+    /// Router that sent the Fragmentation Needed / Packet Too Big message
+    pub from: Option<IpAddr>,
+    pub message: Option<String>,
+}
+
+/// Payload of the `pmtu:start` event
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PmtuStartPayload {
+    pub run_id: String,
+    #[serde(flatten)]
+    pub setting: PmtuSetting,
+}
+
+/// Result of path MTU discovery
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PmtuReport {
+    pub run_id: String,
+    pub ip_addr: IpAddr,
+    pub hostname: Option<String>,
+    /// Largest packet size that got an echo reply (a lower bound if cancelled)
+    pub path_mtu: Option<u32>,
+    /// Router that reported the constraining MTU, when one did
+    pub constraining_hop: Option<IpAddr>,
+    /// Egress interface and its configured MTU
+    pub interface: Option<String>,
+    pub interface_mtu: Option<u32>,
+    /// True if the path MTU is smaller than the interface MTU (and `max_mtu`),
+    /// i.e. the constraint is somewhere on the path rather than the local link
+    pub below_interface_mtu: bool,
+    /// Number of packet sizes tried
+    pub probes: u32,
+}
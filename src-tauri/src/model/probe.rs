@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::io;
+use std::net::IpAddr;
 
 /// Status of probe
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -72,3 +74,160 @@ impl ProbeStatus {
         matches!(self.kind, ProbeStatusKind::Timeout)
     }
 }
+
+/// Failure reason carried by an ICMP or ICMPv6 error message
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IcmpErrorKind {
+    NetUnreachable,
+    HostUnreachable,
+    PortUnreachable,
+    ProtocolUnreachable,
+    /// Communication administratively prohibited, usually a firewall reject
+    AdminProhibited,
+    /// TTL (IPv4) or hop limit (IPv6) exceeded in transit
+    TtlExceeded,
+    /// Fragmentation needed (IPv4) or Packet Too Big (IPv6)
+    FragmentationNeeded,
+    /// No route to destination (ICMPv6)
+    NoRoute,
+    /// Any other error; see the type and code of the message
+    Other,
+}
+
+impl IcmpErrorKind {
+    /// Classify an ICMP or ICMPv6 message by type and code, `None` if it is not an error
+    pub fn from_icmp(is_v6: bool, icmp_type: u8, icmp_code: u8) -> Option<Self> {
+        use IcmpErrorKind::*;
+        let kind = if is_v6 {
+            match (icmp_type, icmp_code) {
+                (1, 0) => NoRoute,
+                // Prohibited, source address failed policy, reject route
+                (1, 1) | (1, 5) | (1, 6) => AdminProhibited,
+                (1, 3) => HostUnreachable,
+                (1, 4) => PortUnreachable,
+                (2, _) => FragmentationNeeded,
+                (3, 0) => TtlExceeded,
+                (1, _) | (3, _) | (4, _) => Other,
+                _ => return None,
+            }
+        } else {
+            match (icmp_type, icmp_code) {
+                // Also unknown network and unreachable for TOS
+                (3, 0) | (3, 6) | (3, 11) => NetUnreachable,
+                (3, 1) | (3, 7) | (3, 12) => HostUnreachable,
+                (3, 2) => ProtocolUnreachable,
+                (3, 3) => PortUnreachable,
+                (3, 4) => FragmentationNeeded,
+                (3, 9) | (3, 10) | (3, 13) => AdminProhibited,
+                (11, 0) => TtlExceeded,
+                (3, _) | (11, _) | (12, _) => Other,
+                _ => return None,
+            }
+        };
+        Some(kind)
+    }
+
+    /// Classify the error a UDP socket reports for an ICMP error, `None` for a local error
+    pub fn from_udp_error(e: &io::Error) -> Option<Self> {
+        match e.kind() {
+            // Windows reports Port Unreachable as WSAECONNRESET
+            io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset => {
+                Some(IcmpErrorKind::PortUnreachable)
+            }
+            io::ErrorKind::HostUnreachable => Some(IcmpErrorKind::HostUnreachable),
+            io::ErrorKind::NetworkUnreachable => Some(IcmpErrorKind::NetUnreachable),
+            _ => None,
+        }
+    }
+
+    /// Classify a failed TCP connect. A refused connection is left out,
+    /// since it is usually a TCP reset rather than an ICMP error.
+    pub fn from_tcp_error(e: &io::Error) -> Option<Self> {
+        match e.kind() {
+            io::ErrorKind::HostUnreachable => Some(IcmpErrorKind::HostUnreachable),
+            io::ErrorKind::NetworkUnreachable => Some(IcmpErrorKind::NetUnreachable),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for IcmpErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            IcmpErrorKind::NetUnreachable => "network unreachable",
+            IcmpErrorKind::HostUnreachable => "host unreachable",
+            IcmpErrorKind::PortUnreachable => "port unreachable",
+            IcmpErrorKind::ProtocolUnreachable => "protocol unreachable",
+            IcmpErrorKind::AdminProhibited => "administratively prohibited",
+            IcmpErrorKind::TtlExceeded => "TTL exceeded in transit",
+            IcmpErrorKind::FragmentationNeeded => "fragmentation needed",
+            IcmpErrorKind::NoRoute => "no route to destination",
+            IcmpErrorKind::Other => "error",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// ICMP error returned for a probe
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IcmpError {
+    pub kind: IcmpErrorKind,
+    /// Router or host that sent the error.
+    /// `None` when the OS only reported an error code (e.g. for TCP connects).
+    pub from: Option<IpAddr>,
+    /// Type and code of the message, `None` when only an error code was reported
+    pub icmp_type: Option<u8>,
+    pub icmp_code: Option<u8>,
+    /// Next-hop MTU of `FragmentationNeeded`, if the router reported one
+    pub mtu: Option<u32>,
+}
+
+impl IcmpError {
+    /// Error from an ICMP or ICMPv6 message sent by `from`, `None` if the message is not an error.
+    /// `mtu` is the MTU field of Fragmentation Needed or Packet Too Big (0 if not reported).
+    pub fn from_message(
+        is_v6: bool,
+        icmp_type: u8,
+        icmp_code: u8,
+        from: Option<IpAddr>,
+        mtu: u32,
+    ) -> Option<Self> {
+        let kind = IcmpErrorKind::from_icmp(is_v6, icmp_type, icmp_code)?;
+        Some(IcmpError {
+            kind,
+            from,
+            icmp_type: Some(icmp_type),
+            icmp_code: Some(icmp_code),
+            mtu: (kind == IcmpErrorKind::FragmentationNeeded && mtu > 0).then_some(mtu),
+        })
+    }
+
+    /// Error known only from the error code of a socket
+    pub fn from_kind(kind: IcmpErrorKind) -> Self {
+        IcmpError {
+            kind,
+            from: None,
+            icmp_type: None,
+            icmp_code: None,
+            mtu: None,
+        }
+    }
+}
+
+impl fmt::Display for IcmpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ICMP {}", self.kind)?;
+        if let (IcmpErrorKind::Other, Some(t), Some(c)) =
+            (self.kind, self.icmp_type, self.icmp_code)
+        {
+            write!(f, " (type {} code {})", t, c)?;
+        }
+        if let Some(mtu) = self.mtu {
+            write!(f, " (MTU {})", mtu)?;
+        }
+        if let Some(from) = self.from {
+            write!(f, " from {}", from)?;
+        }
+        Ok(())
+    }
+}
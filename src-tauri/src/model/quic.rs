@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::tls::TlsCertificate;
+
+/// Handshake options for QUIC ping and QUIC port scan
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct QuicOption {
+    /// Complete the handshake even if the certificate does not validate
+    pub skip_verify: bool,
+    /// ALPN protocols offered in the handshake, in order of preference
+    pub alpn: Vec<String>,
+    /// Server name sent in the handshake (default: the hostname of the target)
+    pub server_name: Option<String>,
+    /// Reconnect with the session ticket of each handshake to check whether 0-RTT is accepted
+    pub zero_rtt: bool,
+}
+
+impl Default for QuicOption {
+    fn default() -> Self {
+        Self {
+            skip_verify: true,
+            alpn: vec![
+                "h3".to_string(),
+                "hq-29".to_string(),
+                "hq-interop".to_string(),
+            ],
+            server_name: None,
+            zero_rtt: false,
+        }
+    }
+}
+
+impl QuicOption {
+    pub fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        self.alpn.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+}
+
+/// Handshake details of one QUIC ping sample
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuicSampleDetail {
+    /// Server name sent in the handshake
+    pub server_name: String,
+    /// Negotiated QUIC version, e.g. `v1`
+    pub version: String,
+    /// Negotiated ALPN protocol
+    pub alpn: Option<String>,
+    /// Leaf certificate presented by the server
+    pub certificate: Option<TlsCertificate>,
+    /// True if the chain validates against the native root store for the server name
+    pub trusted: bool,
+    /// Why the chain does not validate
+    pub validation_error: Option<String>,
+    /// Whether the server accepted 0-RTT on a second connection resumed with its session ticket.
+    /// `None` if not tested or the server sent no ticket that allows early data.
+    pub zero_rtt_accepted: Option<bool>,
+}
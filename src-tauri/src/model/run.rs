@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunKind {
+    Ping,
+    MultiPing,
+    Pmtu,
+    PortScan,
+    HostScan,
+    NeighborScan,
+    Traceroute,
+    Mtr,
+}
+
+impl RunKind {
+    /// Whether this kind counts towards the concurrent scan limit
+    pub fn is_scan(&self) -> bool {
+        matches!(
+            self,
+            RunKind::MultiPing | RunKind::PortScan | RunKind::HostScan | RunKind::NeighborScan
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Done,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct RunProgress {
+    pub done: u32,
+    pub total: u32,
+}
+
+/// Snapshot of a probe run tracked by the job manager
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunInfo {
+    pub run_id: String,
+    pub kind: RunKind,
+    /// Target host, address range or interface
+    pub target: String,
+    /// Settings the run was started with
+    pub setting: serde_json::Value,
+    pub started_at: SystemTime,
+    pub finished_at: Option<SystemTime>,
+    pub progress: RunProgress,
+    pub status: RunStatus,
+    /// Error message if the run failed
+    pub error: Option<String>,
+}
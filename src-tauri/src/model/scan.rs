@@ -1,3 +1,5 @@
+use crate::model::probe::IcmpError;
+use crate::model::quic::QuicOption;
 use netdev::MacAddr;
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
@@ -17,7 +19,7 @@ pub enum TargetPortsPreset {
     Custom,
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum PortState {
     Open,
     Closed,
@@ -29,7 +31,7 @@ pub struct PortScanStartPayload {
     pub run_id: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PortScanSample {
     pub ip_addr: IpAddr,
     pub port: u16,
@@ -37,11 +39,13 @@ pub struct PortScanSample {
     pub rtt_ms: Option<u64>,
     pub message: Option<String>,
     pub service_name: Option<String>,
+    /// ICMP error that ended the probe, e.g. administratively prohibited
+    pub icmp_error: Option<IcmpError>,
     pub done: u32,
     pub total: u32,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PortScanReport {
     pub run_id: String,
     pub ip_addr: IpAddr,
@@ -60,6 +64,12 @@ pub struct PortScanSetting {
     pub protocol: PortScanProtocol,
     pub timeout_ms: u64,
     pub ordered: bool,
+    /// Egress interface name (default: the interface chosen by the OS)
+    pub interface: Option<String>,
+    /// Source address (default: an address of `interface` or of the default interface)
+    pub src_ip: Option<IpAddr>,
+    /// Handshake options for QUIC scan
+    pub quic: Option<QuicOption>,
 }
 
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
@@ -77,6 +87,11 @@ pub struct HostScanSetting {
     pub payload: Option<String>,
     pub ordered: bool,
     pub concurrency: Option<usize>,
+    /// Egress interface name (default: the interface chosen by the OS)
+    pub interface: Option<String>,
+    /// Source address for targets of the same family; the other family
+    /// uses an address of `interface` or of the default interface
+    pub src_ip: Option<IpAddr>,
 }
 
 impl HostScanSetting {
@@ -102,6 +117,8 @@ impl HostScanSetting {
             payload: Some("np:neigh".to_string()),
             ordered: true,
             concurrency: Some(100),
+            interface: Some(iface.name.clone()),
+            src_ip: None,
         }
     }
 }
@@ -117,6 +134,8 @@ pub struct HostScanProgress {
     pub state: HostState,
     pub rtt_ms: Option<u64>,
     pub message: Option<String>,
+    /// ICMP error returned instead of an echo reply, with the reporting router
+    pub icmp_error: Option<IcmpError>,
     pub done: u32,
     pub total: u32,
 }
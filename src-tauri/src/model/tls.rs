@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Plaintext protocol upgraded to TLS with STARTTLS before the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StartTls {
+    Smtp,
+    Imap,
+    Pop3,
+}
+
+impl StartTls {
+    /// Port of the plaintext service that offers STARTTLS
+    pub fn default_port(&self) -> u16 {
+        match self {
+            StartTls::Smtp => 25,
+            StartTls::Imap => 143,
+            StartTls::Pop3 => 110,
+        }
+    }
+}
+
+impl std::fmt::Display for StartTls {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            StartTls::Smtp => "SMTP",
+            StartTls::Imap => "IMAP",
+            StartTls::Pop3 => "POP3",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for StartTls {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "smtp" => Ok(Self::Smtp),
+            "imap" => Ok(Self::Imap),
+            "pop3" => Ok(Self::Pop3),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Certificate presented by a TLS server
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TlsCertificate {
+    /// Distinguished name in certificate order, e.g. `C=US, O=Example, CN=example.com`
+    pub subject: String,
+    pub issuer: String,
+    /// Serial number as colon separated hex
+    pub serial: String,
+    /// X.509 version (1-3)
+    pub version: u8,
+    /// Subject alternative names. DNS names and IP addresses as is,
+    /// other kinds prefixed with `email:` or `URI:`
+    pub sans: Vec<String>,
+    /// Validity period (RFC 3339, UTC)
+    pub not_before: String,
+    pub not_after: String,
+    /// Whole days until `not_after` (negative once expired)
+    pub days_remaining: i64,
+    pub expired: bool,
+    pub not_yet_valid: bool,
+    /// Subject and issuer are the same (a root or self-signed certificate)
+    pub self_issued: bool,
+    /// e.g. `RSA`, `ECDSA P-256`, `Ed25519`
+    pub key_type: String,
+    pub key_bits: Option<u32>,
+    pub signature_algorithm: String,
+    /// Fingerprints of the DER encoding as colon separated hex
+    pub sha256: String,
+    pub sha1: String,
+    /// Why the certificate could not be decoded; only the fingerprints are set then
+    pub parse_error: Option<String>,
+}
+
+/// Result of a TLS handshake with a server
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TlsInspectReport {
+    pub host: String,
+    pub ip_addr: IpAddr,
+    pub port: u16,
+    /// Server name sent in the handshake (none for an IP address)
+    pub sni: Option<String>,
+    pub starttls: Option<StartTls>,
+    /// Negotiated protocol version, e.g. `TLSv1.3`
+    pub version: String,
+    /// Negotiated cipher suite, e.g. `TLS13_AES_128_GCM_SHA256`
+    pub cipher_suite: String,
+    /// Negotiated ALPN protocol
+    pub alpn: Option<String>,
+    /// Certificates in the order sent by the server, leaf first
+    pub chain: Vec<TlsCertificate>,
+    /// True if the chain validates against the native root store for the server name
+    pub trusted: bool,
+    /// Why the chain does not validate
+    pub validation_error: Option<String>,
+    pub connect_ms: f64,
+    pub handshake_ms: f64,
+}
@@ -28,6 +28,18 @@ pub async fn lookup_host(host: &str, timeout: Duration) -> Result<Host> {
     }
 }
 
+/// Resolve a target given as an IP address or hostname.
+/// The hostname is returned along with the first resolved address.
+pub async fn resolve_target(target: &str, timeout: Duration) -> Result<(IpAddr, Option<String>)> {
+    if let Ok(ip) = target.parse::<IpAddr>() {
+        return Ok((ip, None));
+    }
+    match lookup_ip(target, timeout).await {
+        Some(ips) if !ips.is_empty() => Ok((ips[0], Some(target.to_string()))),
+        _ => anyhow::bail!("failed to resolve host: {}", target),
+    }
+}
+
 /// Lookup a domain and return its associated IP addresses.
 pub async fn lookup_domain(hostname: &str, timeout: Duration) -> Domain {
     let ips = lookup_ip(hostname, timeout).await.unwrap_or_default();
@@ -1,3 +1,6 @@
+use std::net::IpAddr;
+
+use anyhow::Result;
 use netdev::Interface;
 
 pub fn list_interfaces() -> Vec<Interface> {
@@ -14,3 +17,168 @@ pub fn get_display_name(iface: &Interface) -> String {
     }
     iface.name.clone()
 }
+
+/// Pick the first address of the default interface matching the family of `dst`.
+pub fn get_default_source_ip(dst: IpAddr) -> Result<IpAddr> {
+    let default_interface: Interface = netdev::get_default_interface()
+        .map_err(|e| anyhow::anyhow!("Failed to get default interface: {}", e))?;
+    let src_ip = match dst {
+        IpAddr::V4(_) => {
+            // Pick first IPv4 address of default interface
+            let ipv4 = default_interface
+                .ipv4_addrs()
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No IPv4 address found on default interface"))?;
+            IpAddr::V4(ipv4)
+        }
+        IpAddr::V6(_) => {
+            // Pick first IPv6 address of default interface
+            let ipv6 = default_interface
+                .ipv6_addrs()
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No IPv6 address found on default interface"))?;
+            IpAddr::V6(ipv6)
+        }
+    };
+    Ok(src_ip)
+}
+
+/// Find an interface by name (or by friendly name on Windows).
+pub fn find_interface(name: &str) -> Result<Interface> {
+    netdev::get_interfaces()
+        .into_iter()
+        .find(|iface| iface.name == name || get_display_name(iface) == name)
+        .ok_or_else(|| anyhow::anyhow!("Interface not found: {}", name))
+}
+
+/// Interface that probes to `dst` leave through: `interface` if given,
+/// else the egress interface of the route to `dst`, else the default interface.
+pub fn egress_interface(dst: IpAddr, interface: Option<&str>) -> Result<Interface> {
+    if let Some(name) = interface {
+        return find_interface(name);
+    }
+    if let Ok(crate::net::route::EgressRoute {
+        if_index: Some(index),
+        ..
+    }) = crate::net::route::lookup_egress(dst)
+    {
+        if let Some(iface) = netdev::get_interfaces()
+            .into_iter()
+            .find(|iface| iface.index == index)
+        {
+            return Ok(iface);
+        }
+    }
+    netdev::get_default_interface()
+        .map_err(|e| anyhow::anyhow!("Failed to get default interface: {}", e))
+}
+
+/// First address of `iface` in the same family as `dst`.
+/// IPv6 prefers addresses that are not link-local, which cannot be bound without a scope.
+fn first_addr_like(iface: &Interface, dst: IpAddr) -> Option<IpAddr> {
+    match dst {
+        IpAddr::V4(_) => iface.ipv4_addrs().into_iter().next().map(IpAddr::V4),
+        IpAddr::V6(_) => {
+            let addrs = iface.ipv6_addrs();
+            addrs
+                .iter()
+                .find(|ip| !ip.is_unicast_link_local())
+                .or_else(|| addrs.first())
+                .copied()
+                .map(IpAddr::V6)
+        }
+    }
+}
+
+fn has_addr(iface: &Interface, ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => iface.ipv4_addrs().contains(&v4),
+        IpAddr::V6(v6) => iface.ipv6_addrs().contains(&v6),
+    }
+}
+
+/// Pick the source address for probes to `dst`.
+///
+/// - `src_ip` is used as is after checking that it has the family of `dst` and is
+///   assigned to `interface` (or to any local interface when no interface is given).
+/// - With only `interface`, the route's preferred source is used if the route to `dst`
+///   leaves through that interface, else the first matching address of `interface`.
+/// - Without either, the route's preferred source is used (see
+///   [`crate::net::route::lookup_egress`]), falling back to [`get_default_source_ip`].
+pub fn select_source_ip(
+    dst: IpAddr,
+    interface: Option<&str>,
+    src_ip: Option<IpAddr>,
+) -> Result<IpAddr> {
+    let iface = interface.map(find_interface).transpose()?;
+    match (src_ip, iface) {
+        (Some(src), iface) => {
+            if src.is_ipv4() != dst.is_ipv4() {
+                anyhow::bail!(
+                    "Source address {} does not match the family of {}",
+                    src,
+                    dst
+                );
+            }
+            match iface {
+                Some(iface) if !has_addr(&iface, src) => {
+                    anyhow::bail!("Source address {} is not assigned to {}", src, iface.name)
+                }
+                Some(_) => {}
+                None => {
+                    if !netdev::get_interfaces().iter().any(|i| has_addr(i, src)) {
+                        anyhow::bail!("Source address {} is not assigned to this host", src);
+                    }
+                }
+            }
+            Ok(src)
+        }
+        (None, Some(iface)) => {
+            if let Ok(route) = crate::net::route::lookup_egress(dst) {
+                if route.if_index == Some(iface.index) && has_addr(&iface, route.src_ip) {
+                    return Ok(route.src_ip);
+                }
+            }
+            first_addr_like(&iface, dst).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No {} address found on {}",
+                    if dst.is_ipv4() { "IPv4" } else { "IPv6" },
+                    iface.name
+                )
+            })
+        }
+        (None, None) => match crate::net::route::lookup_egress(dst) {
+            Ok(route) => Ok(route.src_ip),
+            Err(e) => {
+                tracing::debug!("route lookup for {} failed: {}", dst, e);
+                get_default_source_ip(dst)
+            }
+        },
+    }
+}
+
+/// Source addresses `(IPv4, IPv6)` for probes to `targets` of either family.
+///
+/// Each family uses [`select_source_ip`] for its first target; `src_ip` only applies
+/// to its own family. A family without targets, or without a source when none was
+/// requested, is `None`.
+pub fn select_source_pair(
+    interface: Option<&str>,
+    src_ip: Option<IpAddr>,
+    targets: &[IpAddr],
+) -> Result<(Option<IpAddr>, Option<IpAddr>)> {
+    let pick = |v4: bool| -> Result<Option<IpAddr>> {
+        let Some(dst) = targets.iter().find(|ip| ip.is_ipv4() == v4) else {
+            return Ok(None);
+        };
+        let src = src_ip.filter(|ip| ip.is_ipv4() == v4);
+        match select_source_ip(*dst, interface, src) {
+            Ok(ip) => Ok(Some(ip)),
+            Err(_) if interface.is_none() && src.is_none() => Ok(None),
+            Err(e) => Err(e),
+        }
+    };
+    Ok((pick(true)?, pick(false)?))
+}
@@ -4,3 +4,7 @@ pub mod route;
 pub mod sys;
 pub mod dns;
 pub mod neigh;
+pub mod ntp;
+pub mod tls;
+#[cfg(target_os = "linux")]
+mod netlink;
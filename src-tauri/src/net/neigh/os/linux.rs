@@ -1,135 +1,24 @@
 use netdev::MacAddr;
-use netlink_packet_core::{NLM_F_DUMP, NLM_F_REQUEST, NetlinkMessage, NetlinkPayload};
+use netlink_packet_core::{NLM_F_DUMP, NLM_F_REQUEST, NetlinkPayload};
 use netlink_packet_route::{
     neighbour::{NeighbourAddress, NeighbourAttribute, NeighbourMessage},
     RouteNetlinkMessage,
 };
-use netlink_sys::{protocols::NETLINK_ROUTE, Socket, SocketAddr};
 use std::{
     collections::HashMap,
-    io::{self, ErrorKind},
+    io,
     net::{IpAddr, Ipv4Addr},
-    thread,
-    time::{Duration, Instant},
 };
 
-const SEQ_BASE: u32 = 0x6E_70_6C_73; // npls (netpulsar)
-const RECV_BUFSZ: usize = 1 << 20;    // 1MB
-const RECV_TIMEOUT: Duration = Duration::from_secs(2);
-const NLMSG_ALIGNTO: usize = 4;
-const MIN_NLMSG_HEADER_LEN: usize = 16;
-
-#[inline]
-fn nlmsg_align(n: usize) -> usize {
-    (n + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
-}
-
-fn open_route_socket() -> io::Result<Socket> {
-    let mut sock = Socket::new(NETLINK_ROUTE)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("netlink open: {e}")))?;
-    sock.bind_auto()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("bind_auto: {e}")))?;
-    sock.set_non_blocking(true).ok();
-    Ok(sock)
-}
-
-fn send_dump(sock: &mut Socket, msg: RouteNetlinkMessage, seq: u32) -> io::Result<()> {
-    let mut nl = NetlinkMessage::from(msg);
-    nl.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
-    nl.header.sequence_number = seq;
-    nl.header.port_number = 0;
-    nl.finalize();
-
-    let blen = nl.buffer_len();
-    if blen < MIN_NLMSG_HEADER_LEN {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("netlink message too short: buffer_len={}", blen),
-        ));
-    }
-
-    let mut buf = vec![0; blen];
-    nl.serialize(&mut buf);
-
-    let kernel = SocketAddr::new(0, 0);
-    sock.send_to(&buf, &kernel, 0)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("netlink send: {e}")))?;
-    Ok(())
-}
-
-fn recv_multi(
-    sock: &mut Socket,
-    expect_seq: u32,
-) -> io::Result<Vec<NetlinkMessage<RouteNetlinkMessage>>> {
-    let mut out = Vec::new();
-    let mut buf = vec![0u8; RECV_BUFSZ];
-    let kernel = SocketAddr::new(0, 0);
-    let deadline = Instant::now() + RECV_TIMEOUT;
-
-    loop {
-        match sock.recv_from(&mut &mut buf[..], 0) {
-            Ok((size, from)) => {
-                let _ = from == kernel;
-                let mut offset = 0usize;
-
-                while offset < size {
-                    if size - offset < MIN_NLMSG_HEADER_LEN {
-                        break;
-                    }
-                    let bytes = &buf[offset..size];
-
-                    let msg =
-                        NetlinkMessage::<RouteNetlinkMessage>::deserialize(bytes).map_err(|e| {
-                            io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                format!("deserialize: {e:?}"),
-                            )
-                        })?;
-
-                    let consumed = msg.header.length as usize;
-                    if consumed < MIN_NLMSG_HEADER_LEN || offset + consumed > size {
-                        break;
-                    }
-
-                    if msg.header.sequence_number != expect_seq {
-                        offset += nlmsg_align(consumed);
-                        continue;
-                    }
-
-                    match &msg.payload {
-                        NetlinkPayload::Done(_) => return Ok(out),
-                        NetlinkPayload::Error(e) => {
-                            if let Some(code) = e.code {
-                                return Err(io::Error::new(
-                                    io::ErrorKind::Other,
-                                    format!("netlink error: code={}", code),
-                                ));
-                            }
-                        }
-                        NetlinkPayload::Noop | NetlinkPayload::Overrun(_) => { /* skip */ }
-                        _ => out.push(msg),
-                    }
-
-                    offset += nlmsg_align(consumed);
-                }
-            }
-            Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                if Instant::now() >= deadline {
-                    return Ok(out);
-                }
-                thread::sleep(Duration::from_millis(5));
-            }
-            Err(e) => return Err(e),
-        }
-    }
-}
+use crate::net::netlink::{open_route_socket, recv_multi, send_request, SEQ_BASE};
 
 fn dump_neigh() -> io::Result<Vec<NeighbourMessage>> {
     let mut sock = open_route_socket()?;
     let seq = SEQ_BASE ^ 0x04;
-    send_dump(
+    send_request(
         &mut sock,
         RouteNetlinkMessage::GetNeighbour(NeighbourMessage::default()),
+        NLM_F_REQUEST | NLM_F_DUMP,
         seq,
     )?;
     let msgs = recv_multi(&mut sock, seq)?;
@@ -0,0 +1,150 @@
+//! Shared NETLINK_ROUTE socket plumbing for the Linux backends.
+
+use netlink_packet_core::{NetlinkMessage, NetlinkPayload};
+use netlink_packet_route::RouteNetlinkMessage;
+use netlink_sys::{protocols::NETLINK_ROUTE, Socket, SocketAddr};
+use std::{
+    io::{self, ErrorKind},
+    thread,
+    time::{Duration, Instant},
+};
+
+pub(crate) const SEQ_BASE: u32 = 0x6E_70_6C_73; // npls (netpulsar)
+const RECV_BUFSZ: usize = 1 << 20; // 1MB
+const RECV_TIMEOUT: Duration = Duration::from_secs(2);
+const NLMSG_ALIGNTO: usize = 4;
+const MIN_NLMSG_HEADER_LEN: usize = 16;
+
+#[inline]
+fn nlmsg_align(n: usize) -> usize {
+    (n + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+
+pub(crate) fn open_route_socket() -> io::Result<Socket> {
+    let mut sock =
+        Socket::new(NETLINK_ROUTE).map_err(|e| io::Error::other(format!("netlink open: {e}")))?;
+    sock.bind_auto()
+        .map_err(|e| io::Error::other(format!("bind_auto: {e}")))?;
+    sock.set_non_blocking(true).ok();
+    Ok(sock)
+}
+
+/// Send `msg` with the given netlink header `flags` (e.g. `NLM_F_REQUEST | NLM_F_DUMP`).
+pub(crate) fn send_request(
+    sock: &mut Socket,
+    msg: RouteNetlinkMessage,
+    flags: u16,
+    seq: u32,
+) -> io::Result<()> {
+    let mut nl = NetlinkMessage::from(msg);
+    nl.header.flags = flags;
+    nl.header.sequence_number = seq;
+    nl.header.port_number = 0;
+    nl.finalize();
+
+    let blen = nl.buffer_len();
+    if blen < MIN_NLMSG_HEADER_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("netlink message too short: buffer_len={}", blen),
+        ));
+    }
+
+    let mut buf = vec![0; blen];
+    nl.serialize(&mut buf);
+
+    let kernel = SocketAddr::new(0, 0);
+    sock.send_to(&buf, &kernel, 0)
+        .map_err(|e| io::Error::other(format!("netlink send: {e}")))?;
+    Ok(())
+}
+
+/// Collect the messages of a dump until `NLMSG_DONE` (or the receive timeout).
+pub(crate) fn recv_multi(
+    sock: &mut Socket,
+    expect_seq: u32,
+) -> io::Result<Vec<NetlinkMessage<RouteNetlinkMessage>>> {
+    recv_messages(sock, expect_seq, true)
+}
+
+/// Receive the single reply to a non-dump request.
+pub(crate) fn recv_reply(
+    sock: &mut Socket,
+    expect_seq: u32,
+) -> io::Result<NetlinkMessage<RouteNetlinkMessage>> {
+    recv_messages(sock, expect_seq, false)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "no netlink reply"))
+}
+
+fn recv_messages(
+    sock: &mut Socket,
+    expect_seq: u32,
+    dump: bool,
+) -> io::Result<Vec<NetlinkMessage<RouteNetlinkMessage>>> {
+    let mut out = Vec::new();
+    let mut buf = vec![0u8; RECV_BUFSZ];
+    let kernel = SocketAddr::new(0, 0);
+    let deadline = Instant::now() + RECV_TIMEOUT;
+
+    loop {
+        match sock.recv_from(&mut &mut buf[..], 0) {
+            Ok((size, from)) => {
+                let _ = from == kernel;
+                let mut offset = 0usize;
+
+                while offset < size {
+                    if size - offset < MIN_NLMSG_HEADER_LEN {
+                        break;
+                    }
+                    let bytes = &buf[offset..size];
+
+                    let msg =
+                        NetlinkMessage::<RouteNetlinkMessage>::deserialize(bytes).map_err(|e| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("deserialize: {e:?}"),
+                            )
+                        })?;
+
+                    let consumed = msg.header.length as usize;
+                    if consumed < MIN_NLMSG_HEADER_LEN || offset + consumed > size {
+                        break;
+                    }
+
+                    if msg.header.sequence_number != expect_seq {
+                        offset += nlmsg_align(consumed);
+                        continue;
+                    }
+
+                    match &msg.payload {
+                        NetlinkPayload::Done(_) => return Ok(out),
+                        NetlinkPayload::Error(e) => {
+                            if let Some(code) = e.code {
+                                return Err(io::Error::other(format!(
+                                    "netlink error: code={}",
+                                    code
+                                )));
+                            }
+                        }
+                        NetlinkPayload::Noop | NetlinkPayload::Overrun(_) => { /* skip */ }
+                        _ => out.push(msg),
+                    }
+
+                    offset += nlmsg_align(consumed);
+                }
+                if !dump && !out.is_empty() {
+                    return Ok(out);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Ok(out);
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
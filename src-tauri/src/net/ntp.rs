@@ -0,0 +1,312 @@
+use anyhow::{bail, Result};
+use rand::Rng;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::model::ntp::{NtpLeap, NtpQueryResult, NtpResponse};
+use crate::model::probe::IcmpErrorKind;
+use crate::socket::udp::{AsyncUdpSocket, UdpConfig};
+use crate::socket::SocketFamily;
+
+pub const NTP_PORT: u16 = 123;
+pub const NTP_PACKET_SIZE: usize = 48;
+/// Seconds from the NTP epoch (1900) to the Unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+/// Fixed point units (2^-32 s) per millisecond
+const UNITS_PER_MS: f64 = 4_294_967.296;
+
+/// NTPv4 client request (RFC 5905) carrying `transmit` as its transmit timestamp.
+/// The server copies it into the origin timestamp of its reply.
+pub fn client_request(transmit: [u8; 8]) -> [u8; NTP_PACKET_SIZE] {
+    let mut packet = [0u8; NTP_PACKET_SIZE];
+    // LI = 0, VN = 4, Mode = 3 (client)
+    packet[0] = 0x23;
+    packet[40..48].copy_from_slice(&transmit);
+    packet
+}
+
+/// Whether `reply` is a server reply (mode 4) to the request with `transmit`
+pub fn is_reply_to(reply: &[u8], transmit: &[u8; 8]) -> bool {
+    reply.len() >= NTP_PACKET_SIZE && reply[0] & 0x07 == 4 && reply[24..32] == transmit[..]
+}
+
+/// 64-bit NTP timestamp as 2^-32 s units since 1900, placing timestamps with the
+/// high bit clear in era 1 (after 2036-02-07)
+fn timestamp_units(bytes: &[u8]) -> i128 {
+    let ts = u64::from_be_bytes(bytes[..8].try_into().unwrap());
+    if ts >> 63 == 0 {
+        ts as i128 + (1i128 << 64)
+    } else {
+        ts as i128
+    }
+}
+
+fn system_time_units(time: SystemTime) -> i128 {
+    let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = (since_unix.as_secs() + NTP_UNIX_OFFSET) as i128;
+    let frac = ((since_unix.subsec_nanos() as u64) << 32) / 1_000_000_000;
+    (secs << 32) + frac as i128
+}
+
+/// Unsigned 16.16 fixed point seconds as milliseconds
+fn short_format_ms(bytes: &[u8]) -> f64 {
+    u32::from_be_bytes(bytes[..4].try_into().unwrap()) as f64 * 1000.0 / 65536.0
+}
+
+fn reference_id(stratum: u8, id: &[u8]) -> String {
+    if stratum <= 1 {
+        // Reference clock or kiss code, NUL padded ASCII
+        id.iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| b as char)
+            .collect()
+    } else {
+        Ipv4Addr::new(id[0], id[1], id[2], id[3]).to_string()
+    }
+}
+
+/// Parse a server reply. `sent` and `received` are the local clock when the request
+/// was sent and the reply received.
+pub fn parse_response(reply: &[u8], sent: SystemTime, received: SystemTime) -> Result<NtpResponse> {
+    if reply.len() < NTP_PACKET_SIZE {
+        bail!("short NTP reply ({} bytes)", reply.len());
+    }
+    let stratum = reply[1];
+    if stratum == 0 {
+        bail!(
+            "kiss-o'-death from server: {}",
+            reference_id(0, &reply[12..16])
+        );
+    }
+    let leap = match reply[0] >> 6 {
+        0 => NtpLeap::None,
+        1 => NtpLeap::AddSecond,
+        2 => NtpLeap::DeleteSecond,
+        _ => NtpLeap::Unsynchronized,
+    };
+    let t1 = system_time_units(sent);
+    let t2 = timestamp_units(&reply[32..40]);
+    let t3 = timestamp_units(&reply[40..48]);
+    let t4 = system_time_units(received);
+    let offset = ((t2 - t1) + (t3 - t4)) / 2;
+    let delay = (t4 - t1) - (t3 - t2);
+    Ok(NtpResponse {
+        version: (reply[0] >> 3) & 0x07,
+        leap,
+        stratum,
+        reference_id: reference_id(stratum, &reply[12..16]),
+        offset_ms: offset as f64 / UNITS_PER_MS,
+        delay_ms: delay as f64 / UNITS_PER_MS,
+        root_delay_ms: short_format_ms(&reply[4..8]),
+        root_dispersion_ms: short_format_ms(&reply[8..12]),
+        precision: reply[3] as i8,
+    })
+}
+
+/// Send one client request to `server` from a fresh socket built from `config`.
+/// Returns the parsed reply and the round trip time of the exchange.
+/// Waits until a reply arrives; callers bound it with a timeout.
+pub async fn query(server: SocketAddr, config: &UdpConfig) -> Result<(NtpResponse, Duration)> {
+    let socket = AsyncUdpSocket::from_config(config)?;
+    socket.connect(server).await?;
+    let transmit = rand::thread_rng().gen::<[u8; 8]>();
+    let request = client_request(transmit);
+
+    let sent = SystemTime::now();
+    let started = Instant::now();
+    socket
+        .send(&request)
+        .await
+        .map_err(|e| anyhow::anyhow!("send error: {e}"))?;
+    let mut buf = [0u8; 1024];
+    let n = loop {
+        let n = socket
+            .recv(&mut buf)
+            .await
+            .map_err(|e| match IcmpErrorKind::from_udp_error(&e) {
+                Some(kind) => anyhow::anyhow!("ICMP {kind}"),
+                None => anyhow::anyhow!("recv error: {e}"),
+            })?;
+        // Ignore anything but the reply to this request
+        if is_reply_to(&buf[..n], &transmit) {
+            break n;
+        }
+    };
+    let rtt = started.elapsed();
+    // Monotonic time for the receive timestamp, so a clock step during the
+    // exchange does not distort the delay
+    let response = parse_response(&buf[..n], sent, sent + rtt)?;
+    Ok((response, rtt))
+}
+
+/// Socket configuration for querying `server` from the default source address
+pub fn default_config(server: IpAddr) -> UdpConfig {
+    let mut config = UdpConfig::new();
+    config.socket_family = SocketFamily::from_ip(&server);
+    let unspecified = match server {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    config.with_bind_addr(SocketAddr::new(unspecified, 0))
+}
+
+/// Query each of `servers` (hostnames or IP addresses) once, concurrently.
+pub async fn query_servers(
+    servers: &[String],
+    port: Option<u16>,
+    timeout: Duration,
+) -> Vec<NtpQueryResult> {
+    let port = port.unwrap_or(NTP_PORT);
+    let queries = servers.iter().map(|server| async move {
+        let mut result = NtpQueryResult {
+            server: server.clone(),
+            ip_addr: None,
+            port,
+            response: None,
+            error: None,
+        };
+        let ip_addr = match crate::net::dns::resolve_target(server, timeout).await {
+            Ok((ip_addr, _)) => ip_addr,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                return result;
+            }
+        };
+        result.ip_addr = Some(ip_addr);
+        let addr = SocketAddr::new(ip_addr, port);
+        match tokio::time::timeout(timeout, query(addr, &default_config(ip_addr))).await {
+            Ok(Ok((response, _))) => result.response = Some(response),
+            Ok(Err(e)) => result.error = Some(e.to_string()),
+            Err(_) => result.error = Some(format!("timeout (>{}ms)", timeout.as_millis())),
+        }
+        result
+    });
+    futures::future::join_all(queries).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// NTP timestamp of `time`, wrapped into its era
+    fn timestamp(time: SystemTime) -> [u8; 8] {
+        (system_time_units(time) as u64).to_be_bytes()
+    }
+
+    fn unix(secs: u64, millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis)
+    }
+
+    /// Server reply with the given receive and transmit times
+    fn reply(receive: SystemTime, transmit: SystemTime) -> [u8; NTP_PACKET_SIZE] {
+        let mut reply = [0u8; NTP_PACKET_SIZE];
+        // LI = 0, VN = 4, Mode = 4 (server)
+        reply[0] = 0x24;
+        reply[1] = 2;
+        reply[3] = -20i8 as u8;
+        reply[4..8].copy_from_slice(&0x0000_8000u32.to_be_bytes());
+        reply[8..12].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        reply[12..16].copy_from_slice(&[192, 0, 2, 1]);
+        reply[32..40].copy_from_slice(&timestamp(receive));
+        reply[40..48].copy_from_slice(&timestamp(transmit));
+        reply
+    }
+
+    fn assert_ms(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-3, "{value} != {expected}");
+    }
+
+    #[test]
+    fn request_and_reply_matching() {
+        let transmit = [1, 2, 3, 4, 5, 6, 7, 8];
+        let request = client_request(transmit);
+        assert_eq!(request[0], 0x23);
+        assert_eq!(request[40..48], transmit);
+
+        let mut reply = [0u8; NTP_PACKET_SIZE];
+        reply[0] = 0x24;
+        reply[24..32].copy_from_slice(&transmit);
+        assert!(is_reply_to(&reply, &transmit));
+        assert!(!is_reply_to(&reply, &[0; 8]));
+        assert!(!is_reply_to(&reply[..NTP_PACKET_SIZE - 1], &transmit));
+        // Our own request looped back
+        reply[0] = 0x23;
+        assert!(!is_reply_to(&reply, &transmit));
+    }
+
+    #[test]
+    fn timestamp_eras() {
+        assert_eq!(
+            system_time_units(UNIX_EPOCH),
+            (NTP_UNIX_OFFSET as i128) << 32
+        );
+        assert_eq!(system_time_units(unix(0, 500)) & 0xffff_ffff, 1 << 31);
+        // Era 0: the high bit is set until 2036
+        let ts = 0xe000_0000_0000_0000u64;
+        assert_eq!(timestamp_units(&ts.to_be_bytes()), ts as i128);
+        // Era 1 starts at 2036-02-07T06:28:16Z, Unix 2085978496
+        let era1 = unix(2_085_978_496, 0);
+        assert_eq!(timestamp(era1), [0; 8]);
+        assert_eq!(timestamp_units(&[0; 8]), 1 << 64);
+        assert_eq!(timestamp_units(&timestamp(era1)), system_time_units(era1));
+    }
+
+    #[test]
+    fn offset_and_delay() {
+        // Server clock 100 ms ahead, 10 ms each way, 5 ms in the server
+        let sent = unix(1_700_000_000, 0);
+        let packet = reply(unix(1_700_000_000, 110), unix(1_700_000_000, 115));
+        let response = parse_response(&packet, sent, unix(1_700_000_000, 25)).unwrap();
+        assert_ms(response.offset_ms, 100.0);
+        assert_ms(response.delay_ms, 20.0);
+        assert_eq!(response.version, 4);
+        assert_eq!(response.leap, NtpLeap::None);
+        assert_eq!(response.stratum, 2);
+        assert_eq!(response.reference_id, "192.0.2.1");
+        assert_eq!(response.root_delay_ms, 500.0);
+        assert_eq!(response.root_dispersion_ms, 1000.0);
+        assert_eq!(response.precision, -20);
+
+        // Server clock 50 ms behind
+        let packet = reply(unix(1_700_000_000, 0), unix(1_700_000_000, 1));
+        let response = parse_response(&packet, sent, unix(1_700_000_000, 101)).unwrap();
+        assert_ms(response.offset_ms, -50.0);
+        assert_ms(response.delay_ms, 100.0);
+    }
+
+    #[test]
+    fn offset_across_era_rollover() {
+        // Sent just before the rollover, answered with era 1 timestamps
+        let rollover = 2_085_978_496;
+        let sent = unix(rollover - 1, 990);
+        let packet = reply(unix(rollover, 100), unix(rollover, 101));
+        assert_eq!(packet[32..36], [0; 4]);
+        let response = parse_response(&packet, sent, unix(rollover, 10)).unwrap();
+        assert_ms(response.offset_ms, 100.5);
+        assert_ms(response.delay_ms, 19.0);
+    }
+
+    #[test]
+    fn reference_clock_and_leap() {
+        let now = unix(1_700_000_000, 0);
+        let mut packet = reply(now, now);
+        packet[0] = 0xe4;
+        packet[1] = 1;
+        packet[12..16].copy_from_slice(b"GPS\0");
+        let response = parse_response(&packet, now, now).unwrap();
+        assert_eq!(response.leap, NtpLeap::Unsynchronized);
+        assert_eq!(response.reference_id, "GPS");
+    }
+
+    #[test]
+    fn rejected_replies() {
+        let now = unix(1_700_000_000, 0);
+        let mut packet = reply(now, now);
+        let error = parse_response(&packet[..47], now, now).unwrap_err();
+        assert_eq!(error.to_string(), "short NTP reply (47 bytes)");
+        packet[1] = 0;
+        packet[12..16].copy_from_slice(b"RATE");
+        let error = parse_response(&packet, now, now).unwrap_err();
+        assert_eq!(error.to_string(), "kiss-o'-death from server: RATE");
+    }
+}
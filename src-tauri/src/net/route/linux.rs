@@ -0,0 +1,66 @@
+use netlink_packet_core::{NetlinkPayload, NLM_F_REQUEST};
+use netlink_packet_route::{
+    route::{RouteAddress, RouteAttribute, RouteMessage},
+    AddressFamily, RouteNetlinkMessage,
+};
+use std::{io, net::IpAddr};
+
+use super::{connected_udp_source, EgressRoute};
+use crate::net::netlink::{open_route_socket, recv_reply, send_request, SEQ_BASE};
+
+fn route_addr_to_ip(a: &RouteAddress) -> Option<IpAddr> {
+    match a {
+        RouteAddress::Inet(v4) => Some(IpAddr::V4(*v4)),
+        RouteAddress::Inet6(v6) => Some(IpAddr::V6(*v6)),
+        _ => None,
+    }
+}
+
+/// `ip route get <dst>`
+pub fn lookup_egress(dst: IpAddr) -> io::Result<EgressRoute> {
+    let mut msg = RouteMessage::default();
+    match dst {
+        IpAddr::V4(_) => {
+            msg.header.address_family = AddressFamily::Inet;
+            msg.header.destination_prefix_length = 32;
+        }
+        IpAddr::V6(_) => {
+            msg.header.address_family = AddressFamily::Inet6;
+            msg.header.destination_prefix_length = 128;
+        }
+    }
+    msg.attributes
+        .push(RouteAttribute::Destination(RouteAddress::from(dst)));
+
+    let mut sock = open_route_socket()?;
+    let seq = SEQ_BASE ^ 0x08;
+    send_request(
+        &mut sock,
+        RouteNetlinkMessage::GetRoute(msg),
+        NLM_F_REQUEST,
+        seq,
+    )?;
+    let reply = recv_reply(&mut sock, seq)?;
+    let NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(route)) = reply.payload else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected RTM_GETROUTE reply",
+        ));
+    };
+
+    let mut src_ip = None;
+    let mut if_index = None;
+    for attr in &route.attributes {
+        match attr {
+            RouteAttribute::PrefSource(a) => src_ip = route_addr_to_ip(a),
+            RouteAttribute::Oif(index) => if_index = Some(*index),
+            _ => {}
+        }
+    }
+    // The kernel omits the preferred source for some routes (e.g. IPv6 without `src`)
+    let src_ip = match src_ip {
+        Some(ip) => ip,
+        None => connected_udp_source(dst)?,
+    };
+    Ok(EgressRoute { src_ip, if_index })
+}
@@ -1,6 +1,63 @@
 use netroute::RouteEntry;
 use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+#[cfg(target_os = "linux")]
+mod linux;
 
 pub fn list_routes() -> io::Result<Vec<RouteEntry>> {
     netroute::list_routes()
 }
+
+/// Egress interface and preferred source address the OS uses for a destination
+#[derive(Clone, Debug)]
+pub struct EgressRoute {
+    /// Preferred source address
+    pub src_ip: IpAddr,
+    /// Index of the egress interface, if known
+    pub if_index: Option<u32>,
+}
+
+/// Resolve the egress interface and preferred source address for `dst`.
+///
+/// Linux asks the kernel with an RTM_GETROUTE query. Elsewhere, or when that fails,
+/// the source is taken from a UDP socket connected to `dst` (no packet is sent)
+/// and the interface from the local address table.
+pub fn lookup_egress(dst: IpAddr) -> io::Result<EgressRoute> {
+    #[cfg(target_os = "linux")]
+    match linux::lookup_egress(dst) {
+        Ok(route) => return Ok(route),
+        Err(e) => tracing::debug!("RTM_GETROUTE for {dst} failed, using UDP fallback: {e}"),
+    }
+    let src_ip = connected_udp_source(dst)?;
+    Ok(EgressRoute {
+        src_ip,
+        if_index: interface_index_of(src_ip),
+    })
+}
+
+/// Local address chosen by the OS for a UDP socket connected to `dst`.
+pub(crate) fn connected_udp_source(dst: IpAddr) -> io::Result<IpAddr> {
+    let bind_addr = match dst {
+        IpAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        IpAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+    let socket = UdpSocket::bind(bind_addr)?;
+    // Connecting a UDP socket only selects the route; the port is arbitrary.
+    socket.connect(SocketAddr::new(dst, 9))?;
+    let src_ip = socket.local_addr()?.ip();
+    if src_ip.is_unspecified() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            format!("no source address for {dst}"),
+        ));
+    }
+    Ok(src_ip)
+}
+
+fn interface_index_of(ip: IpAddr) -> Option<u32> {
+    netdev::get_interfaces()
+        .into_iter()
+        .find(|iface| iface.ip_addrs().contains(&ip))
+        .map(|iface| iface.index)
+}
@@ -0,0 +1,223 @@
+pub mod x509;
+
+use anyhow::{bail, Context, Result};
+use rustls::pki_types::ServerName;
+use rustls::ProtocolVersion;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::model::tls::{StartTls, TlsInspectReport};
+use crate::socket::tcp::{AsyncTcpSocket, TcpConfig};
+use crate::socket::tls::RecordingVerifier;
+use crate::stats::duration_ms;
+
+const DEFAULT_TLS_PORT: u16 = 443;
+/// Longest line accepted from a server during STARTTLS negotiation
+const MAX_LINE: usize = 4096;
+
+/// Read one CRLF (or LF) terminated line without reading past it,
+/// so nothing of the TLS handshake that follows is consumed.
+async fn read_line(stream: &mut TcpStream) -> Result<String> {
+    let mut line = Vec::new();
+    loop {
+        let b = stream.read_u8().await.context("connection closed")?;
+        if b == b'\n' {
+            break;
+        }
+        if line.len() == MAX_LINE {
+            bail!("line too long");
+        }
+        line.push(b);
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// Read an SMTP reply, returning its code and lines.
+async fn read_smtp_reply(stream: &mut TcpStream) -> Result<(u16, Vec<String>)> {
+    let mut lines = Vec::new();
+    loop {
+        let line = read_line(stream).await?;
+        let last = line.as_bytes().get(3) != Some(&b'-');
+        let code = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+        lines.push(line);
+        if last {
+            let Some(code) = code else {
+                bail!("invalid SMTP reply: {}", lines.join(" "));
+            };
+            return Ok((code, lines));
+        }
+    }
+}
+
+/// Negotiate STARTTLS on a plaintext connection, leaving it ready for the TLS handshake.
+async fn starttls(stream: &mut TcpStream, protocol: StartTls) -> Result<()> {
+    match protocol {
+        StartTls::Smtp => {
+            let (code, lines) = read_smtp_reply(stream).await?;
+            if code != 220 {
+                bail!("SMTP greeting: {}", lines.join(" "));
+            }
+            stream.write_all(b"EHLO netpulsar\r\n").await?;
+            let (code, lines) = read_smtp_reply(stream).await?;
+            if code != 250 {
+                bail!("SMTP EHLO: {}", lines.join(" "));
+            }
+            if !lines.iter().any(|l| {
+                l.get(4..)
+                    .is_some_and(|s| s.eq_ignore_ascii_case("STARTTLS"))
+            }) {
+                bail!("SMTP server does not offer STARTTLS");
+            }
+            stream.write_all(b"STARTTLS\r\n").await?;
+            let (code, lines) = read_smtp_reply(stream).await?;
+            if code != 220 {
+                bail!("SMTP STARTTLS: {}", lines.join(" "));
+            }
+        }
+        StartTls::Imap => {
+            let greeting = read_line(stream).await?;
+            if !greeting.starts_with("* OK") {
+                bail!("IMAP greeting: {}", greeting);
+            }
+            stream.write_all(b"a1 STARTTLS\r\n").await?;
+            loop {
+                let line = read_line(stream).await?;
+                if let Some(status) = line.strip_prefix("a1 ") {
+                    if !status.starts_with("OK") {
+                        bail!("IMAP STARTTLS: {}", status);
+                    }
+                    break;
+                }
+            }
+        }
+        StartTls::Pop3 => {
+            let greeting = read_line(stream).await?;
+            if !greeting.starts_with("+OK") {
+                bail!("POP3 greeting: {}", greeting);
+            }
+            stream.write_all(b"STLS\r\n").await?;
+            let reply = read_line(stream).await?;
+            if !reply.starts_with("+OK") {
+                bail!("POP3 STLS: {}", reply);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn version_str(version: ProtocolVersion) -> String {
+    match version {
+        ProtocolVersion::TLSv1_2 => "TLSv1.2".to_string(),
+        ProtocolVersion::TLSv1_3 => "TLSv1.3".to_string(),
+        v => format!("{:?}", v),
+    }
+}
+
+/// Connect to `host` and complete a TLS handshake, returning the negotiated parameters
+/// and the certificate chain sent by the server.
+///
+/// `port` defaults to 443, or to the plaintext port of the `starttls` protocol.
+/// `sni` defaults to `host` when it is a hostname; no SNI is sent for an IP address.
+/// The handshake succeeds whether or not the chain is trusted; `trusted` and
+/// `validation_error` tell whether it validates against the native root store.
+/// A certificate that cannot be decoded is kept in the chain with its `parse_error`.
+pub async fn inspect(
+    host: &str,
+    port: Option<u16>,
+    sni: Option<&str>,
+    starttls_protocol: Option<StartTls>,
+    timeout: Duration,
+) -> Result<TlsInspectReport> {
+    let (ip_addr, _) = crate::net::dns::resolve_target(host, timeout).await?;
+    let port = port.unwrap_or_else(|| {
+        starttls_protocol
+            .map(|p| p.default_port())
+            .unwrap_or(DEFAULT_TLS_PORT)
+    });
+    let server_name = ServerName::try_from(sni.unwrap_or(host).to_string())
+        .map_err(|_| anyhow::anyhow!("invalid server name: {}", sni.unwrap_or(host)))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = RecordingVerifier::new(provider.clone());
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+    if starttls_protocol.is_none() {
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    }
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+
+    let cfg = if ip_addr.is_ipv4() {
+        TcpConfig::v4_stream()
+    } else {
+        TcpConfig::v6_stream()
+    };
+    let socket = AsyncTcpSocket::from_config(&cfg)?;
+    let started = Instant::now();
+    let mut stream = socket
+        .connect_timeout(SocketAddr::new(ip_addr, port), timeout)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to connect to {}:{}: {}", ip_addr, port, e))?;
+    let connect_ms = duration_ms(started.elapsed());
+
+    if let Some(protocol) = starttls_protocol {
+        tokio::time::timeout(timeout, starttls(&mut stream, protocol))
+            .await
+            .map_err(|_| anyhow::anyhow!("{} STARTTLS timed out", protocol))??;
+    }
+
+    let started = Instant::now();
+    let tls = tokio::time::timeout(timeout, connector.connect(server_name.clone(), stream))
+        .await
+        .map_err(|_| anyhow::anyhow!("TLS handshake timed out"))?
+        .map_err(|e| anyhow::anyhow!("TLS handshake failed: {}", e))?;
+    let handshake_ms = duration_ms(started.elapsed());
+    let conn = tls.get_ref().1;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let chain = conn
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .map(|cert| {
+            x509::parse_certificate(cert, now)
+                .unwrap_or_else(|e| x509::unparsed_certificate(cert, e.to_string()))
+        })
+        .collect();
+    let validation = verifier.result();
+
+    Ok(TlsInspectReport {
+        host: host.to_string(),
+        ip_addr,
+        port,
+        sni: match &server_name {
+            ServerName::DnsName(name) => Some(name.as_ref().to_string()),
+            _ => None,
+        },
+        starttls: starttls_protocol,
+        version: conn.protocol_version().map(version_str).unwrap_or_default(),
+        cipher_suite: conn
+            .negotiated_cipher_suite()
+            .map(|s| format!("{:?}", s.suite()))
+            .unwrap_or_default(),
+        alpn: conn
+            .alpn_protocol()
+            .map(|p| String::from_utf8_lossy(p).into_owned()),
+        chain,
+        trusted: validation.is_ok(),
+        validation_error: validation.err(),
+        connect_ms,
+        handshake_ms,
+    })
+}
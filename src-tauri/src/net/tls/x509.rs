@@ -0,0 +1,461 @@
+//! X.509 fields shown by the TLS inspector, decoded with `x509-parser`.
+//! Only what is needed for display is extracted; signatures are not checked here.
+
+use anyhow::Result;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use x509_parser::asn1_rs::Tag;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+use x509_parser::public_key::RSAPublicKey;
+use x509_parser::time::ASN1Time;
+use x509_parser::x509::{AttributeTypeAndValue, SubjectPublicKeyInfo, X509Name};
+
+use crate::model::tls::TlsCertificate;
+
+const OID_RSA: &str = "1.2.840.113549.1.1.1";
+const OID_RSA_PSS: &str = "1.2.840.113549.1.1.10";
+const OID_EC: &str = "1.2.840.10045.2.1";
+const OID_ED25519: &str = "1.3.101.112";
+const OID_ED448: &str = "1.3.101.113";
+const OID_DSA: &str = "1.2.840.10040.4.1";
+
+/// Short name of a distinguished name attribute
+fn attribute_name(oid: &str) -> &str {
+    match oid {
+        "2.5.4.3" => "CN",
+        "2.5.4.5" => "serialNumber",
+        "2.5.4.6" => "C",
+        "2.5.4.7" => "L",
+        "2.5.4.8" => "ST",
+        "2.5.4.10" => "O",
+        "2.5.4.11" => "OU",
+        "1.2.840.113549.1.9.1" => "emailAddress",
+        "0.9.2342.19200300.100.1.25" => "DC",
+        _ => oid,
+    }
+}
+
+fn signature_algorithm_name(oid: &str) -> &str {
+    match oid {
+        "1.2.840.113549.1.1.4" => "md5WithRSAEncryption",
+        "1.2.840.113549.1.1.5" => "sha1WithRSAEncryption",
+        "1.2.840.113549.1.1.11" => "sha256WithRSAEncryption",
+        "1.2.840.113549.1.1.12" => "sha384WithRSAEncryption",
+        "1.2.840.113549.1.1.13" => "sha512WithRSAEncryption",
+        OID_RSA_PSS => "rsassaPss",
+        "1.2.840.10045.4.1" => "ecdsa-with-SHA1",
+        "1.2.840.10045.4.3.2" => "ecdsa-with-SHA256",
+        "1.2.840.10045.4.3.3" => "ecdsa-with-SHA384",
+        "1.2.840.10045.4.3.4" => "ecdsa-with-SHA512",
+        OID_ED25519 => "Ed25519",
+        OID_ED448 => "Ed448",
+        _ => oid,
+    }
+}
+
+/// Decode a directory string value (UTF8String, PrintableString, IA5String, BMPString, ...).
+fn decode_string(attr: &AttributeTypeAndValue) -> String {
+    let value = attr.attr_value();
+    match value.tag() {
+        Tag::BmpString => {
+            let units: Vec<u16> = value
+                .data
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(value.data).into_owned(),
+    }
+}
+
+/// Format a Name as `C=US, O=Example, CN=example.com`, in certificate order.
+fn format_name(name: &X509Name) -> String {
+    name.iter_attributes()
+        .map(|attr| {
+            let oid = attr.attr_type().to_id_string();
+            format!("{}={}", attribute_name(&oid), decode_string(attr))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// RFC 3339 form of a certificate time, e.g. `2025-01-01T00:00:00Z`
+fn format_time(time: &ASN1Time) -> String {
+    let t = time.to_datetime();
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        t.year(),
+        u8::from(t.month()),
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second()
+    )
+}
+
+/// Bit length of an unsigned big-endian integer
+fn bit_len(int: &[u8]) -> u32 {
+    let int = match int.iter().position(|b| *b != 0) {
+        Some(i) => &int[i..],
+        None => return 0,
+    };
+    (int.len() as u32 - 1) * 8 + (8 - int[0].leading_zeros())
+}
+
+/// Key type and size of a SubjectPublicKeyInfo
+fn key_type(spki: &SubjectPublicKeyInfo) -> (String, Option<u32>) {
+    let alg_oid = spki.algorithm.algorithm.to_id_string();
+    match alg_oid.as_str() {
+        OID_RSA | OID_RSA_PSS => {
+            let name = if alg_oid == OID_RSA { "RSA" } else { "RSA-PSS" };
+            // RSA-PSS keys have the same encoding as rsaEncryption keys
+            let bits = RSAPublicKey::from_der(&spki.subject_public_key.data)
+                .ok()
+                .map(|(_, rsa)| bit_len(rsa.modulus));
+            (name.to_string(), bits)
+        }
+        OID_EC => {
+            let curve = spki
+                .algorithm
+                .parameters
+                .as_ref()
+                .and_then(|p| p.as_oid().ok())
+                .map(|oid| oid.to_id_string())
+                .unwrap_or_default();
+            match curve.as_str() {
+                "1.2.840.10045.3.1.7" => ("ECDSA P-256".to_string(), Some(256)),
+                "1.3.132.0.34" => ("ECDSA P-384".to_string(), Some(384)),
+                "1.3.132.0.35" => ("ECDSA P-521".to_string(), Some(521)),
+                _ => ("ECDSA".to_string(), None),
+            }
+        }
+        OID_ED25519 => ("Ed25519".to_string(), Some(256)),
+        OID_ED448 => ("Ed448".to_string(), Some(456)),
+        OID_DSA => ("DSA".to_string(), None),
+        _ => (alg_oid, None),
+    }
+}
+
+/// Names of the subjectAltName extension. DNS names and IP addresses as is,
+/// e-mail addresses and URIs prefixed, other kinds skipped.
+fn subject_alt_names(cert: &X509Certificate) -> Result<Vec<String>> {
+    let Some(san) = cert.subject_alternative_name()? else {
+        return Ok(Vec::new());
+    };
+    let mut names = Vec::new();
+    for name in &san.value.general_names {
+        match name {
+            GeneralName::RFC822Name(email) => names.push(format!("email:{email}")),
+            GeneralName::DNSName(dns) => names.push(dns.to_string()),
+            GeneralName::URI(uri) => names.push(format!("URI:{uri}")),
+            GeneralName::IPAddress(ip) => {
+                let ip = match ip.len() {
+                    4 => Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(*ip)?))),
+                    16 => Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(*ip)?))),
+                    _ => None,
+                };
+                if let Some(ip) = ip {
+                    names.push(ip.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(names)
+}
+
+/// Colon separated upper case hex, e.g. `AB:CD:EF`
+fn hex_colon(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn sha256_fingerprint(der: &[u8]) -> String {
+    hex_colon(ring::digest::digest(&ring::digest::SHA256, der).as_ref())
+}
+
+fn sha1_fingerprint(der: &[u8]) -> String {
+    hex_colon(ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, der).as_ref())
+}
+
+/// Parse a DER encoded certificate. `now` (Unix seconds) is used for `days_remaining`.
+pub fn parse_certificate(der: &[u8], now: i64) -> Result<TlsCertificate> {
+    let (_, cert) =
+        X509Certificate::from_der(der).map_err(|e| anyhow::anyhow!("invalid certificate: {e}"))?;
+    let serial = match cert.raw_serial() {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        serial => serial,
+    };
+    let validity = cert.validity();
+    let not_before_unix = validity.not_before.timestamp();
+    let not_after_unix = validity.not_after.timestamp();
+    let (key_type, key_bits) = key_type(cert.public_key());
+    let signature_algorithm = cert.signature_algorithm.algorithm.to_id_string();
+    Ok(TlsCertificate {
+        subject: format_name(cert.subject()),
+        issuer: format_name(cert.issuer()),
+        serial: hex_colon(serial),
+        version: u8::try_from(cert.version().0).unwrap_or(u8::MAX - 1) + 1,
+        sans: subject_alt_names(&cert)?,
+        not_before: format_time(&validity.not_before),
+        not_after: format_time(&validity.not_after),
+        days_remaining: (not_after_unix - now).div_euclid(86400),
+        expired: now > not_after_unix,
+        not_yet_valid: now < not_before_unix,
+        self_issued: cert.subject().as_raw() == cert.issuer().as_raw(),
+        key_type,
+        key_bits,
+        signature_algorithm: signature_algorithm_name(&signature_algorithm).to_string(),
+        sha256: sha256_fingerprint(der),
+        sha1: sha1_fingerprint(der),
+        parse_error: None,
+    })
+}
+
+/// Entry for a certificate that `parse_certificate` rejected: only the fingerprints
+/// of the DER encoding and the reason are filled in.
+pub fn unparsed_certificate(der: &[u8], error: String) -> TlsCertificate {
+    TlsCertificate {
+        subject: String::new(),
+        issuer: String::new(),
+        serial: String::new(),
+        version: 0,
+        sans: Vec::new(),
+        not_before: String::new(),
+        not_after: String::new(),
+        days_remaining: 0,
+        expired: false,
+        not_yet_valid: false,
+        self_issued: false,
+        key_type: String::new(),
+        key_bits: None,
+        signature_algorithm: String::new(),
+        sha256: sha256_fingerprint(der),
+        sha1: sha1_fingerprint(der),
+        parse_error: Some(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAG_INTEGER: u8 = 0x02;
+    const TAG_BIT_STRING: u8 = 0x03;
+    const TAG_OCTET_STRING: u8 = 0x04;
+    const TAG_NULL: u8 = 0x05;
+    const TAG_OID: u8 = 0x06;
+    const TAG_UTC_TIME: u8 = 0x17;
+    const TAG_GENERALIZED_TIME: u8 = 0x18;
+    const TAG_SEQUENCE: u8 = 0x30;
+    const TAG_SET: u8 = 0x31;
+
+    const OID_CN: &[u8] = &[0x55, 0x04, 0x03];
+    const OID_O: &[u8] = &[0x55, 0x04, 0x0a];
+    const OID_SHA256_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+
+    /// DER element with a short or long form length
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        match content.len() {
+            len @ 0..=0x7f => out.push(len as u8),
+            len @ 0x80..=0xff => out.extend([0x81, len as u8]),
+            len => out.extend([0x82, (len >> 8) as u8, len as u8]),
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    fn seq(items: &[Vec<u8>]) -> Vec<u8> {
+        tlv(TAG_SEQUENCE, &items.concat())
+    }
+
+    /// Name with one attribute per RDN
+    fn name(attrs: &[(&[u8], u8, &[u8])]) -> Vec<u8> {
+        let rdns: Vec<Vec<u8>> = attrs
+            .iter()
+            .map(|(oid, tag, value)| tlv(TAG_SET, &seq(&[tlv(TAG_OID, oid), tlv(*tag, value)])))
+            .collect();
+        seq(&rdns)
+    }
+
+    fn ec_p256_key() -> Vec<u8> {
+        seq(&[
+            seq(&[
+                tlv(TAG_OID, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01]),
+                tlv(TAG_OID, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07]),
+            ]),
+            tlv(TAG_BIT_STRING, &[0, 4, 1, 2]),
+        ])
+    }
+
+    fn rsa_key(modulus: &[u8]) -> Vec<u8> {
+        let key = seq(&[tlv(TAG_INTEGER, modulus), tlv(TAG_INTEGER, &[1, 0, 1])]);
+        seq(&[
+            seq(&[
+                tlv(
+                    TAG_OID,
+                    &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01],
+                ),
+                tlv(TAG_NULL, &[]),
+            ]),
+            tlv(TAG_BIT_STRING, &[&[0u8][..], &key].concat()),
+        ])
+    }
+
+    /// v3 certificate with the given subject, issuer, validity, key and extensions
+    fn certificate(
+        subject: Vec<u8>,
+        issuer: Vec<u8>,
+        validity: Vec<u8>,
+        spki: Vec<u8>,
+        extensions: Vec<Vec<u8>>,
+    ) -> Vec<u8> {
+        let sig_alg = seq(&[tlv(TAG_OID, OID_SHA256_RSA), tlv(TAG_NULL, &[])]);
+        let mut tbs = vec![
+            tlv(0xa0, &tlv(TAG_INTEGER, &[2])),
+            tlv(TAG_INTEGER, &[0x00, 0x8f, 0x01]),
+            sig_alg.clone(),
+            issuer,
+            validity,
+            subject,
+            spki,
+        ];
+        if !extensions.is_empty() {
+            tbs.push(tlv(0xa3, &seq(&extensions)));
+        }
+        seq(&[seq(&tbs), sig_alg, tlv(TAG_BIT_STRING, &[0, 0])])
+    }
+
+    fn validity(not_before: (u8, &[u8]), not_after: (u8, &[u8])) -> Vec<u8> {
+        seq(&[
+            tlv(not_before.0, not_before.1),
+            tlv(not_after.0, not_after.1),
+        ])
+    }
+
+    #[test]
+    fn self_issued_leaf() {
+        let san = seq(&[
+            tlv(0x82, b"example.com"),
+            tlv(0x87, &[127, 0, 0, 1]),
+            tlv(0x81, b"admin@example.com"),
+        ]);
+        let san_ext = seq(&[
+            tlv(TAG_OID, &[0x55, 0x1d, 0x11]),
+            // critical
+            tlv(0x01, &[0xff]),
+            tlv(TAG_OCTET_STRING, &san),
+        ]);
+        let cn = name(&[(OID_CN, 0x0c, b"example.com")]);
+        let der = certificate(
+            cn.clone(),
+            cn,
+            validity(
+                (TAG_UTC_TIME, b"250101000000Z"),
+                (TAG_GENERALIZED_TIME, b"20350101000000Z"),
+            ),
+            ec_p256_key(),
+            vec![san_ext],
+        );
+
+        let cert = parse_certificate(&der, 1735689600).unwrap();
+        assert_eq!(cert.subject, "CN=example.com");
+        assert_eq!(cert.issuer, "CN=example.com");
+        assert!(cert.self_issued);
+        assert_eq!(cert.version, 3);
+        assert_eq!(cert.serial, "8F:01");
+        assert_eq!(
+            cert.sans,
+            ["example.com", "127.0.0.1", "email:admin@example.com"]
+        );
+        assert_eq!(cert.not_before, "2025-01-01T00:00:00Z");
+        assert_eq!(cert.not_after, "2035-01-01T00:00:00Z");
+        assert_eq!(cert.days_remaining, 3652);
+        assert!(!cert.expired && !cert.not_yet_valid);
+        assert_eq!(cert.key_type, "ECDSA P-256");
+        assert_eq!(cert.key_bits, Some(256));
+        assert_eq!(cert.signature_algorithm, "sha256WithRSAEncryption");
+        assert_eq!(cert.sha256, sha256_fingerprint(&der));
+        assert_eq!(cert.sha256.len(), 32 * 3 - 1);
+        assert_eq!(cert.parse_error, None);
+
+        let cert = parse_certificate(&der, 2051222401).unwrap();
+        assert!(cert.expired);
+        assert_eq!(cert.days_remaining, -1);
+        let cert = parse_certificate(&der, 1735689599).unwrap();
+        assert!(cert.not_yet_valid);
+    }
+
+    #[test]
+    fn names_and_rsa_key() {
+        // BMPString "Ex" and two-digit years from 50 in the 1900s
+        let issuer = name(&[(OID_O, 0x1e, &[0, b'E', 0, b'x']), (OID_CN, 0x13, b"Root")]);
+        let subject = name(&[(OID_CN, 0x0c, b"leaf")]);
+        let mut modulus = vec![0x00, 0x80];
+        modulus.resize(257, 0x01);
+        let der = certificate(
+            subject,
+            issuer,
+            validity(
+                (TAG_UTC_TIME, b"991231235959Z"),
+                (TAG_UTC_TIME, b"490101000000Z"),
+            ),
+            rsa_key(&modulus),
+            Vec::new(),
+        );
+        let cert = parse_certificate(&der, 0).unwrap();
+        assert_eq!(cert.issuer, "O=Ex, CN=Root");
+        assert!(!cert.self_issued);
+        assert!(cert.sans.is_empty());
+        assert_eq!(cert.not_before, "1999-12-31T23:59:59Z");
+        assert_eq!(cert.not_after, "2049-01-01T00:00:00Z");
+        assert!(cert.not_yet_valid);
+        assert_eq!(cert.key_type, "RSA");
+        assert_eq!(cert.key_bits, Some(2048));
+    }
+
+    #[test]
+    fn invalid_certificates() {
+        let cn = name(&[(OID_CN, 0x0c, b"example.com")]);
+        let bad_time = certificate(
+            cn.clone(),
+            cn,
+            validity(
+                (TAG_UTC_TIME, "€000000000Z".as_bytes()),
+                (TAG_UTC_TIME, b"350101000000Z"),
+            ),
+            ec_p256_key(),
+            Vec::new(),
+        );
+        assert!(parse_certificate(&bad_time, 0).is_err());
+        // Truncated
+        assert!(parse_certificate(&bad_time[..bad_time.len() - 1], 0).is_err());
+        assert!(parse_certificate(&[], 0).is_err());
+        assert!(parse_certificate(&[TAG_SEQUENCE, 0x84, 0xff, 0xff, 0xff, 0xff], 0).is_err());
+    }
+
+    #[test]
+    fn bit_lengths() {
+        assert_eq!(bit_len(&[]), 0);
+        assert_eq!(bit_len(&[0, 0]), 0);
+        assert_eq!(bit_len(&[0, 1]), 1);
+        assert_eq!(bit_len(&[0x7f, 0xff]), 15);
+        assert_eq!(bit_len(&[0x00, 0x80, 0x00]), 16);
+    }
+
+    #[test]
+    fn unparsed() {
+        let der = seq(&[tlv(TAG_INTEGER, &[1])]);
+        let error = parse_certificate(&der, 0).unwrap_err();
+        let cert = unparsed_certificate(&der, error.to_string());
+        assert!(cert.parse_error.is_some());
+        assert_eq!(cert.sha256, sha256_fingerprint(&der));
+        assert_eq!(cert.sha1, sha1_fingerprint(&der));
+        assert_eq!(cert.subject, "");
+    }
+}
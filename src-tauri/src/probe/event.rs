@@ -0,0 +1,188 @@
+use serde::Serialize;
+
+use crate::model::monitor::MonitorStateChangedPayload;
+use crate::model::ping::{
+    MultiPingProgressPayload, MultiPingReport, MultiPingStartPayload, PingDonePayload,
+    PingErrorPayload, PingProgressPayload, PingStartPayload,
+};
+use crate::model::pmtu::{PmtuProbe, PmtuReport, PmtuStartPayload};
+use crate::model::scan::{
+    HostScanProgress, HostScanReport, HostScanStartPayload, PortScanReport, PortScanSample,
+    PortScanStartPayload,
+};
+use crate::probe::trace::{
+    MtrReport, MtrSnapshot, MtrStartPayload, TraceDonePayload, TraceErrorPayload, TraceHop,
+    TraceStartPayload,
+};
+
+/// Event emitted by a probe while it runs.
+///
+/// Each variant carries the typed payload of one start/progress/done/cancelled/error event.
+#[derive(Clone, Debug)]
+pub enum ProbeEvent {
+    PingStart(PingStartPayload),
+    PingProgress(PingProgressPayload),
+    PingDone(PingDonePayload),
+    PingCancelled(PingDonePayload),
+    PingError(PingErrorPayload),
+    MultiPingStart(MultiPingStartPayload),
+    MultiPingProgress(MultiPingProgressPayload),
+    MultiPingDone(MultiPingReport),
+    MultiPingCancelled(MultiPingReport),
+    PmtuStart(PmtuStartPayload),
+    PmtuProgress(PmtuProbe),
+    PmtuDone(PmtuReport),
+    PmtuCancelled(PmtuReport),
+    PortScanStart(PortScanStartPayload),
+    PortScanProgress(PortScanSample),
+    PortScanDone(PortScanReport),
+    PortScanCancelled(PortScanReport),
+    HostScanStart(HostScanStartPayload),
+    HostScanProgress(HostScanProgress),
+    HostScanDone(HostScanReport),
+    HostScanCancelled(HostScanReport),
+    /// Carries the run_id of the neighbor scan
+    NeighborScanStart(String),
+    /// Carries the run_id of the neighbor scan
+    NeighborScanDone(String),
+    /// Carries the run_id of the neighbor scan
+    NeighborScanCancelled(String),
+    TraceStart(TraceStartPayload),
+    TraceProgress(TraceHop),
+    TraceDone(TraceDonePayload),
+    TraceCancelled(TraceDonePayload),
+    TraceError(TraceErrorPayload),
+    MtrStart(MtrStartPayload),
+    MtrSnapshot(MtrSnapshot),
+    MtrDone(MtrReport),
+    MtrCancelled(MtrReport),
+    MtrError(TraceErrorPayload),
+    MonitorStateChanged(MonitorStateChangedPayload),
+}
+
+impl ProbeEvent {
+    /// Event name used on the frontend, e.g. `ping:progress`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProbeEvent::PingStart(_) => "ping:start",
+            ProbeEvent::PingProgress(_) => "ping:progress",
+            ProbeEvent::PingDone(_) => "ping:done",
+            ProbeEvent::PingError(_) => "ping:error",
+            ProbeEvent::PingCancelled(_) => "ping:cancelled",
+            ProbeEvent::MultiPingStart(_) => "multiping:start",
+            ProbeEvent::MultiPingProgress(_) => "multiping:progress",
+            ProbeEvent::MultiPingDone(_) => "multiping:done",
+            ProbeEvent::MultiPingCancelled(_) => "multiping:cancelled",
+            ProbeEvent::PmtuStart(_) => "pmtu:start",
+            ProbeEvent::PmtuProgress(_) => "pmtu:progress",
+            ProbeEvent::PmtuDone(_) => "pmtu:done",
+            ProbeEvent::PmtuCancelled(_) => "pmtu:cancelled",
+            ProbeEvent::PortScanStart(_) => "portscan:start",
+            ProbeEvent::PortScanProgress(_) => "portscan:progress",
+            ProbeEvent::PortScanDone(_) => "portscan:done",
+            ProbeEvent::PortScanCancelled(_) => "portscan:cancelled",
+            ProbeEvent::HostScanStart(_) => "hostscan:start",
+            ProbeEvent::HostScanProgress(_) => "hostscan:progress",
+            ProbeEvent::HostScanDone(_) => "hostscan:done",
+            ProbeEvent::HostScanCancelled(_) => "hostscan:cancelled",
+            ProbeEvent::NeighborScanStart(_) => "neighborscan:start",
+            ProbeEvent::NeighborScanDone(_) => "neighborscan:done",
+            ProbeEvent::NeighborScanCancelled(_) => "neighborscan:cancelled",
+            ProbeEvent::TraceStart(_) => "traceroute:start",
+            ProbeEvent::TraceProgress(_) => "traceroute:progress",
+            ProbeEvent::TraceDone(_) => "traceroute:done",
+            ProbeEvent::TraceCancelled(_) => "traceroute:cancelled",
+            ProbeEvent::TraceError(_) => "traceroute:error",
+            ProbeEvent::MtrStart(_) => "mtr:start",
+            ProbeEvent::MtrSnapshot(_) => "mtr:snapshot",
+            ProbeEvent::MtrDone(_) => "mtr:done",
+            ProbeEvent::MtrCancelled(_) => "mtr:cancelled",
+            ProbeEvent::MtrError(_) => "mtr:error",
+            ProbeEvent::MonitorStateChanged(_) => "monitor:state_changed",
+        }
+    }
+}
+
+/// Serializes only the payload, so the event can be forwarded under `name()`.
+impl Serialize for ProbeEvent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ProbeEvent::PingStart(p) => p.serialize(serializer),
+            ProbeEvent::PingProgress(p) => p.serialize(serializer),
+            ProbeEvent::PingDone(p) => p.serialize(serializer),
+            ProbeEvent::PingError(p) => p.serialize(serializer),
+            ProbeEvent::PingCancelled(p) => p.serialize(serializer),
+            ProbeEvent::MultiPingStart(p) => p.serialize(serializer),
+            ProbeEvent::MultiPingProgress(p) => p.serialize(serializer),
+            ProbeEvent::MultiPingDone(p) => p.serialize(serializer),
+            ProbeEvent::MultiPingCancelled(p) => p.serialize(serializer),
+            ProbeEvent::PmtuStart(p) => p.serialize(serializer),
+            ProbeEvent::PmtuProgress(p) => p.serialize(serializer),
+            ProbeEvent::PmtuDone(p) => p.serialize(serializer),
+            ProbeEvent::PmtuCancelled(p) => p.serialize(serializer),
+            ProbeEvent::PortScanStart(p) => p.serialize(serializer),
+            ProbeEvent::PortScanProgress(p) => p.serialize(serializer),
+            ProbeEvent::PortScanDone(p) => p.serialize(serializer),
+            ProbeEvent::PortScanCancelled(p) => p.serialize(serializer),
+            ProbeEvent::HostScanStart(p) => p.serialize(serializer),
+            ProbeEvent::HostScanProgress(p) => p.serialize(serializer),
+            ProbeEvent::HostScanDone(p) => p.serialize(serializer),
+            ProbeEvent::HostScanCancelled(p) => p.serialize(serializer),
+            ProbeEvent::NeighborScanStart(p) => p.serialize(serializer),
+            ProbeEvent::NeighborScanDone(p) => p.serialize(serializer),
+            ProbeEvent::NeighborScanCancelled(p) => p.serialize(serializer),
+            ProbeEvent::TraceStart(p) => p.serialize(serializer),
+            ProbeEvent::TraceProgress(p) => p.serialize(serializer),
+            ProbeEvent::TraceDone(p) => p.serialize(serializer),
+            ProbeEvent::TraceCancelled(p) => p.serialize(serializer),
+            ProbeEvent::TraceError(p) => p.serialize(serializer),
+            ProbeEvent::MtrStart(p) => p.serialize(serializer),
+            ProbeEvent::MtrSnapshot(p) => p.serialize(serializer),
+            ProbeEvent::MtrDone(p) => p.serialize(serializer),
+            ProbeEvent::MtrCancelled(p) => p.serialize(serializer),
+            ProbeEvent::MtrError(p) => p.serialize(serializer),
+            ProbeEvent::MonitorStateChanged(p) => p.serialize(serializer),
+        }
+    }
+}
+
+/// Destination for the events emitted by probes.
+///
+/// The desktop app forwards them to the webview, the CLI prints them,
+/// and tests collect them with `MemoryEventSink`.
+pub trait ProbeEventSink: Send + Sync {
+    fn emit(&self, event: ProbeEvent);
+}
+
+/// Sink that drops every event.
+pub struct NullEventSink;
+
+impl ProbeEventSink for NullEventSink {
+    fn emit(&self, _event: ProbeEvent) {}
+}
+
+/// Sink that keeps every event in memory, for checking the events of a probe in tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryEventSink {
+    events: std::sync::Mutex<Vec<ProbeEvent>>,
+}
+
+#[cfg(test)]
+impl MemoryEventSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the events received so far, leaving the sink empty.
+    pub fn take(&self) -> Vec<ProbeEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+impl ProbeEventSink for MemoryEventSink {
+    fn emit(&self, event: ProbeEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
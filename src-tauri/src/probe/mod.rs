@@ -1,4 +1,6 @@
+pub mod event;
 pub mod packet;
 pub mod ping;
+pub mod pmtu;
 pub mod scan;
 pub mod trace;
@@ -1,12 +1,9 @@
 use bytes::Bytes;
-use nex_packet::icmp::echo_reply::EchoReplyPacket as IcmpEchoReplyPacket;
-use nex_packet::icmpv6::echo_reply::EchoReplyPacket as Icmpv6EchoReplyPacket;
 use nex_packet::{
     builder::{icmp::IcmpPacketBuilder, icmpv6::Icmpv6PacketBuilder},
-    icmp::{self, IcmpPacket, IcmpType},
-    icmpv6::{self, Icmpv6Packet, Icmpv6Type},
-    ipv4::Ipv4Packet,
+    icmp::{self, IcmpType},
     packet::Packet,
+    icmpv6::{self, Icmpv6Type},
 };
 use std::net::IpAddr;
 
@@ -15,67 +12,379 @@ pub fn build_icmp_echo_bytes(src: IpAddr, dst: IpAddr, id: u16, seq: u16, payloa
         (IpAddr::V4(s), IpAddr::V4(d)) => IcmpPacketBuilder::new(s, d)
             .icmp_type(IcmpType::EchoRequest)
             .icmp_code(icmp::echo_request::IcmpCodes::NoCode)
-            .echo_fields(id, seq)
+            // echo_fields prepends id/seq to the payload, so it must come after payload()
             .payload(Bytes::copy_from_slice(payload))
+            .echo_fields(id, seq)
             .build()
             .to_bytes(),
         (IpAddr::V6(s), IpAddr::V6(d)) => Icmpv6PacketBuilder::new(s, d)
             .icmpv6_type(Icmpv6Type::EchoRequest)
             .icmpv6_code(icmpv6::echo_request::Icmpv6Codes::NoCode)
-            .echo_fields(id, seq)
+            // echo_fields prepends id/seq to the payload, so it must come after payload()
             .payload(Bytes::copy_from_slice(payload))
+            .echo_fields(id, seq)
             .build()
             .to_bytes(),
         _ => panic!("Source and destination IP version mismatch"),
     }
 }
 
-/// Extract id/seq of ICMP Echo Reply (IPv4)
-pub fn parse_icmp_echo_v4(buf: &[u8]) -> Option<IcmpEchoReplyPacket> {
-    if let Some(ipv4_packet) = Ipv4Packet::from_buf(&buf) {
-        if ipv4_packet.header.next_level_protocol == nex_packet::ip::IpNextProtocol::Icmp {
-            if let Some(icmp_packet) = IcmpPacket::from_bytes(ipv4_packet.payload()) {
-                match icmp::echo_reply::EchoReplyPacket::try_from(icmp_packet) {
-                    Ok(reply) => {
-                        return Some(reply);
-                    }
-                    Err(_) => {
-                        return None;
-                    }
-                }
+/// ICMP or ICMPv6 Echo Reply
+#[derive(Clone, Copy, Debug)]
+pub struct EchoReply<'a> {
+    pub id: u16,
+    pub seq: u16,
+    /// Data after the identifier and sequence number
+    pub payload: &'a [u8],
+    /// Size of the ICMP message (header and payload)
+    pub size: usize,
+    /// TTL from the IPv4 header, when the socket delivers the header
+    pub ttl: Option<u8>,
+}
+
+/// Parse an ICMP or ICMPv6 Echo Reply; any other message is `None`.
+/// IPv4 replies are accepted with the IP header (RAW sockets) or without it (Linux DGRAM sockets).
+/// The IPv6 header is never included.
+pub fn parse_echo_reply(buf: &[u8], is_v6: bool) -> Option<EchoReply<'_>> {
+    let (icmp, ttl, reply_type) = match buf.first() {
+        _ if is_v6 => (buf, None, 129),
+        Some(b) if b >> 4 == 4 => {
+            let ihl = (b & 0x0f) as usize * 4;
+            if ihl < 20 || buf.len() < ihl || buf[9] != 1 {
+                return None;
             }
+            (&buf[ihl..], Some(buf[8]), 0)
+        }
+        _ => (buf, None, 0),
+    };
+    // type(1) code(1) checksum(2) identifier(2) sequence(2)
+    if icmp.len() < 8 || icmp[0] != reply_type || icmp[1] != 0 {
+        return None;
+    }
+    Some(EchoReply {
+        id: u16::from_be_bytes([icmp[4], icmp[5]]),
+        seq: u16::from_be_bytes([icmp[6], icmp[7]]),
+        payload: &icmp[8..],
+        size: icmp.len(),
+        ttl,
+    })
+}
+
+/// Identifier and sequence number of an ICMP or ICMPv6 Echo Reply.
+pub fn parse_echo_reply_ids(buf: &[u8], is_v6: bool) -> Option<(u16, u16)> {
+    parse_echo_reply(buf, is_v6).map(|r| (r.id, r.seq))
+}
+
+/// ICMP or ICMPv6 error message and the start of the packet it quotes
+#[derive(Clone, Copy, Debug)]
+pub struct IcmpErrorPacket<'a> {
+    pub icmp_type: u8,
+    pub icmp_code: u8,
+    /// Next-hop MTU of Fragmentation Needed / Packet Too Big (0 if not reported)
+    pub mtu: u32,
+    /// Destination of the quoted packet
+    pub dst: IpAddr,
+    /// IP protocol (next header) of the quoted packet
+    pub protocol: u8,
+    /// Transport header of the quoted packet (at least 8 bytes)
+    pub transport: &'a [u8],
+}
+
+impl IcmpErrorPacket<'_> {
+    /// Identifier and sequence number of a quoted ICMP or ICMPv6 echo request
+    pub fn quoted_echo(&self) -> Option<(u16, u16)> {
+        let t = self.transport;
+        match self.protocol {
+            1 if t[0] == 8 => {}
+            58 if t[0] == 128 => {}
+            _ => return None,
+        }
+        Some((
+            u16::from_be_bytes([t[4], t[5]]),
+            u16::from_be_bytes([t[6], t[7]]),
+        ))
+    }
+
+    /// Source and destination ports of a quoted UDP or TCP packet
+    pub fn quoted_ports(&self) -> Option<(u16, u16)> {
+        let t = self.transport;
+        match self.protocol {
+            6 | 17 => Some((
+                u16::from_be_bytes([t[0], t[1]]),
+                u16::from_be_bytes([t[2], t[3]]),
+            )),
+            _ => None,
         }
     }
-    None
 }
 
-/// Extract id/seq of ICMPv6 Echo Reply. (ICMPv6 Header only)
-/// The IPv6 header is automatically cropped off when recvfrom() is used.
-pub fn parse_icmp_echo_v6(buf: &[u8]) -> Option<Icmpv6EchoReplyPacket> {
-    if let Some(icmpv6_packet) = Icmpv6Packet::from_buf(&buf) {
-        match icmpv6_packet.header.icmpv6_type {
-            Icmpv6Type::EchoReply => match Icmpv6EchoReplyPacket::from_buf(&buf) {
-                Some(reply) => {
-                    return Some(reply);
-                }
-                None => {
-                    return Some(Icmpv6EchoReplyPacket {
-                        header: icmpv6_packet.header,
-                        identifier: 0,
-                        sequence_number: 0,
-                        payload: icmpv6_packet.payload,
-                    });
-                }
-            },
-            _ => {
-                return Some(Icmpv6EchoReplyPacket {
-                    header: icmpv6_packet.header,
-                    identifier: 0,
-                    sequence_number: 0,
-                    payload: icmpv6_packet.payload,
-                });
+/// Parse an ICMP Destination Unreachable, Time Exceeded or Parameter Problem message
+/// (ICMPv6: Destination Unreachable, Packet Too Big, Time Exceeded or Parameter Problem).
+/// IPv4 messages are accepted with or without the IP header; the IPv6 header is never included.
+pub fn parse_icmp_error(buf: &[u8], is_v6: bool) -> Option<IcmpErrorPacket<'_>> {
+    let icmp = match buf.first() {
+        _ if is_v6 => buf,
+        Some(b) if b >> 4 == 4 => {
+            let ihl = (b & 0x0f) as usize * 4;
+            if ihl < 20 || buf.len() < ihl || buf[9] != 1 {
+                return None;
             }
+            &buf[ihl..]
+        }
+        _ => buf,
+    };
+    // type(1) code(1) checksum(2) unused / mtu / pointer(4), then the quoted packet
+    if icmp.len() < 8 {
+        return None;
+    }
+    let (icmp_type, icmp_code) = (icmp[0], icmp[1]);
+    let quoted = &icmp[8..];
+    if is_v6 {
+        if !(1..=4).contains(&icmp_type) {
+            return None;
         }
+        // Quoted IPv6 header (40) followed by the transport header (8)
+        if quoted.len() < 48 || quoted[0] >> 4 != 6 {
+            return None;
+        }
+        let mtu = if icmp_type == 2 {
+            u32::from_be_bytes([icmp[4], icmp[5], icmp[6], icmp[7]])
+        } else {
+            0
+        };
+        let dst: [u8; 16] = quoted[24..40].try_into().ok()?;
+        return Some(IcmpErrorPacket {
+            icmp_type,
+            icmp_code,
+            mtu,
+            dst: IpAddr::from(dst),
+            protocol: quoted[6],
+            transport: &quoted[40..],
+        });
+    }
+    if !matches!(icmp_type, 3 | 11 | 12) {
+        return None;
+    }
+    // Quoted IPv4 header followed by the first 8 bytes of the transport header
+    let ihl = (*quoted.first()? & 0x0f) as usize * 4;
+    if ihl < 20 || quoted.len() < ihl + 8 || quoted[0] >> 4 != 4 {
+        return None;
+    }
+    let mtu = if icmp_type == 3 && icmp_code == 4 {
+        u16::from_be_bytes([icmp[6], icmp[7]]) as u32
+    } else {
+        0
+    };
+    let dst: [u8; 4] = quoted[16..20].try_into().ok()?;
+    Some(IcmpErrorPacket {
+        icmp_type,
+        icmp_code,
+        mtu,
+        dst: IpAddr::from(dst),
+        protocol: quoted[9],
+        transport: &quoted[ihl..],
+    })
+}
+
+/// ICMP Fragmentation Needed (IPv4) or ICMPv6 Packet Too Big quoting an echo request
+#[derive(Clone, Copy, Debug)]
+pub struct PacketTooBig {
+    /// Next-hop MTU reported by the router (0 if it did not report one)
+    pub mtu: u32,
+    /// Destination of the quoted echo request
+    pub dst: IpAddr,
+    /// Identifier and sequence number of the quoted echo request
+    pub id: u16,
+    pub seq: u16,
+}
+
+/// Parse an ICMP Fragmentation Needed or ICMPv6 Packet Too Big message
+/// whose quoted packet is an ICMP echo request.
+pub fn parse_packet_too_big(buf: &[u8], is_v6: bool) -> Option<PacketTooBig> {
+    let error = parse_icmp_error(buf, is_v6)?;
+    let too_big = if is_v6 {
+        error.icmp_type == 2
+    } else {
+        error.icmp_type == 3 && error.icmp_code == 4
+    };
+    if !too_big {
+        return None;
+    }
+    let (id, seq) = error.quoted_echo()?;
+    Some(PacketTooBig {
+        mtu: error.mtu,
+        dst: error.dst,
+        id,
+        seq,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const V4_SRC: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const V4_DST: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+    const V6_SRC: IpAddr = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+    const V6_DST: IpAddr = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2));
+
+    /// IPv4 header (20 bytes) carrying `payload` as `protocol`
+    fn ipv4_header(protocol: u8, ttl: u8, src: IpAddr, dst: IpAddr, payload_len: usize) -> Vec<u8> {
+        let (IpAddr::V4(src), IpAddr::V4(dst)) = (src, dst) else {
+            unreachable!()
+        };
+        let mut h = vec![0x45, 0, 0, 0, 0, 0, 0, 0, ttl, protocol, 0, 0];
+        h[2..4].copy_from_slice(&((20 + payload_len) as u16).to_be_bytes());
+        h.extend_from_slice(&src.octets());
+        h.extend_from_slice(&dst.octets());
+        h
+    }
+
+    /// IPv6 header (40 bytes) carrying `payload_len` bytes of `next_header`
+    fn ipv6_header(next_header: u8, src: IpAddr, dst: IpAddr, payload_len: usize) -> Vec<u8> {
+        let (IpAddr::V6(src), IpAddr::V6(dst)) = (src, dst) else {
+            unreachable!()
+        };
+        let mut h = vec![0x60, 0, 0, 0];
+        h.extend_from_slice(&(payload_len as u16).to_be_bytes());
+        h.extend_from_slice(&[next_header, 64]);
+        h.extend_from_slice(&src.octets());
+        h.extend_from_slice(&dst.octets());
+        h
+    }
+
+    /// ICMP error `icmp_type`/`icmp_code` with `rest` (unused / MTU field) quoting `quoted`
+    fn icmp_error(icmp_type: u8, icmp_code: u8, rest: [u8; 4], quoted: &[u8]) -> Vec<u8> {
+        let mut pkt = vec![icmp_type, icmp_code, 0, 0];
+        pkt.extend_from_slice(&rest);
+        pkt.extend_from_slice(quoted);
+        pkt
+    }
+
+    /// Echo request as quoted in an ICMP error: IP header and the first 8 bytes of ICMP
+    fn quoted_echo_request(src: IpAddr, dst: IpAddr, id: u16, seq: u16) -> Vec<u8> {
+        let echo = build_icmp_echo_bytes(src, dst, id, seq, &[0; 32]);
+        let mut quoted = if src.is_ipv6() {
+            ipv6_header(58, src, dst, echo.len())
+        } else {
+            ipv4_header(1, 1, src, dst, echo.len())
+        };
+        quoted.extend_from_slice(&echo[..8]);
+        quoted
+    }
+
+    /// Echo request turned into the matching echo reply
+    fn echo_reply(src: IpAddr, dst: IpAddr, id: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+        let mut pkt = build_icmp_echo_bytes(src, dst, id, seq, payload).to_vec();
+        pkt[0] = if src.is_ipv6() { 129 } else { 0 };
+        pkt
+    }
+
+    #[test]
+    fn echo_request_layout() {
+        let pkt = build_icmp_echo_bytes(V4_SRC, V4_DST, 0x1234, 7, b"np");
+        assert_eq!(
+            &pkt[..],
+            &[8, 0, pkt[2], pkt[3], 0x12, 0x34, 0, 7, b'n', b'p']
+        );
+        let pkt = build_icmp_echo_bytes(V6_SRC, V6_DST, 0x1234, 7, b"np");
+        assert_eq!(
+            &pkt[..],
+            &[128, 0, pkt[2], pkt[3], 0x12, 0x34, 0, 7, b'n', b'p']
+        );
+    }
+
+    #[test]
+    fn echo_reply_without_ip_header() {
+        let pkt = echo_reply(V4_DST, V4_SRC, 0xbeef, 42, b"np:ping");
+        let reply = parse_echo_reply(&pkt, false).unwrap();
+        assert_eq!((reply.id, reply.seq), (0xbeef, 42));
+        assert_eq!(reply.payload, b"np:ping");
+        assert_eq!(reply.size, 15);
+        assert_eq!(reply.ttl, None);
+        assert_eq!(parse_echo_reply_ids(&pkt, false), Some((0xbeef, 42)));
+
+        let pkt = echo_reply(V6_DST, V6_SRC, 1, 2, b"x");
+        assert_eq!(parse_echo_reply_ids(&pkt, true), Some((1, 2)));
+    }
+
+    #[test]
+    fn echo_reply_with_ipv4_header() {
+        let icmp = echo_reply(V4_DST, V4_SRC, 9, 10, b"abc");
+        let mut pkt = ipv4_header(1, 57, V4_DST, V4_SRC, icmp.len());
+        pkt.extend_from_slice(&icmp);
+        let reply = parse_echo_reply(&pkt, false).unwrap();
+        assert_eq!((reply.id, reply.seq), (9, 10));
+        assert_eq!(reply.payload, b"abc");
+        assert_eq!(reply.ttl, Some(57));
+
+        // Not ICMP
+        pkt[9] = 17;
+        assert!(parse_echo_reply(&pkt, false).is_none());
+    }
+
+    #[test]
+    fn other_messages_are_not_echo_replies() {
+        // Echo request
+        let pkt = build_icmp_echo_bytes(V4_SRC, V4_DST, 1, 1, b"");
+        assert!(parse_echo_reply_ids(&pkt, false).is_none());
+        let pkt = build_icmp_echo_bytes(V6_SRC, V6_DST, 1, 1, b"");
+        assert!(parse_echo_reply_ids(&pkt, true).is_none());
+        // Truncated
+        let pkt = echo_reply(V4_DST, V4_SRC, 1, 1, b"");
+        assert!(parse_echo_reply_ids(&pkt[..7], false).is_none());
+        assert!(parse_echo_reply_ids(&[], false).is_none());
+        // IPv4 header with a bad length
+        let mut pkt = ipv4_header(1, 64, V4_DST, V4_SRC, 8);
+        pkt[0] = 0x44;
+        assert!(parse_echo_reply_ids(&pkt, false).is_none());
+    }
+
+    #[test]
+    fn fragmentation_needed() {
+        let quoted = quoted_echo_request(V4_SRC, V4_DST, 0x0102, 5);
+        let icmp = icmp_error(3, 4, [0, 0, 0x05, 0x14], &quoted);
+        let too_big = parse_packet_too_big(&icmp, false).unwrap();
+        assert_eq!(too_big.mtu, 1300);
+        assert_eq!(too_big.dst, V4_DST);
+        assert_eq!((too_big.id, too_big.seq), (0x0102, 5));
+
+        // With the IP header of the error message (RAW sockets)
+        let router = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 254));
+        let mut pkt = ipv4_header(1, 64, router, V4_SRC, icmp.len());
+        pkt.extend_from_slice(&icmp);
+        let too_big = parse_packet_too_big(&pkt, false).unwrap();
+        assert_eq!(too_big.mtu, 1300);
+        assert_eq!(too_big.dst, V4_DST);
+
+        // Router that does not report the MTU
+        let icmp = icmp_error(3, 4, [0; 4], &quoted);
+        assert_eq!(parse_packet_too_big(&icmp, false).unwrap().mtu, 0);
+    }
+
+    #[test]
+    fn packet_too_big_v6() {
+        let quoted = quoted_echo_request(V6_SRC, V6_DST, 7, 8);
+        let icmp = icmp_error(2, 0, 1280u32.to_be_bytes(), &quoted);
+        let too_big = parse_packet_too_big(&icmp, true).unwrap();
+        assert_eq!(too_big.mtu, 1280);
+        assert_eq!(too_big.dst, V6_DST);
+        assert_eq!((too_big.id, too_big.seq), (7, 8));
+    }
+
+    #[test]
+    fn other_errors_are_not_packet_too_big() {
+        let quoted = quoted_echo_request(V4_SRC, V4_DST, 1, 1);
+        // Host unreachable, Time Exceeded
+        assert!(parse_packet_too_big(&icmp_error(3, 1, [0; 4], &quoted), false).is_none());
+        assert!(parse_packet_too_big(&icmp_error(11, 0, [0; 4], &quoted), false).is_none());
+        // Quoted packet cut short
+        let icmp = icmp_error(3, 4, [0, 0, 5, 0x14], &quoted[..24]);
+        assert!(parse_packet_too_big(&icmp, false).is_none());
+
+        let quoted = quoted_echo_request(V6_SRC, V6_DST, 1, 1);
+        assert!(parse_packet_too_big(&icmp_error(3, 0, [0; 4], &quoted), true).is_none());
+        assert!(parse_packet_too_big(&icmp_error(2, 0, [0; 4], &quoted[..40]), true).is_none());
     }
-    None
 }
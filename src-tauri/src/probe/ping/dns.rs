@@ -0,0 +1,410 @@
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::ping::{emit_finished, wait_next};
+use anyhow::Result;
+use bytes::Bytes;
+use hickory_resolver::proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_resolver::proto::rr::{Name, RecordType};
+use http_body_util::{BodyExt, Full};
+use hyper::header::{HeaderValue, ACCEPT, CONTENT_TYPE, HOST};
+use hyper::{Method, Request, StatusCode};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use rand::Rng;
+use rustls::pki_types::ServerName;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::model::ping::{
+    DnsPingOption, DnsSampleDetail, DnsTransport, PingProgressPayload, PingProtocol, PingSample,
+    PingSetting, PingStat,
+};
+use crate::model::probe::ProbeStatus;
+use crate::socket::tcp::{AsyncTcpSocket, TcpConfig};
+use crate::socket::tls::tls_client_config;
+use crate::socket::udp::{AsyncUdpSocket, UdpConfig};
+use crate::socket::SocketFamily;
+use crate::stats::duration_ms;
+
+/// EDNS UDP payload size advertised in queries (DNS flag day 2020)
+const EDNS_PAYLOAD_SIZE: u16 = 1232;
+const MAX_MESSAGE_SIZE: usize = 65535;
+const DNS_MESSAGE_MEDIA_TYPE: &str = "application/dns-message";
+
+/// Name of a response code as printed by dig
+pub fn rcode_name(rcode: ResponseCode) -> String {
+    match u16::from(rcode) {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        6 => "YXDOMAIN".to_string(),
+        7 => "YXRRSET".to_string(),
+        8 => "NXRRSET".to_string(),
+        9 => "NOTAUTH".to_string(),
+        10 => "NOTZONE".to_string(),
+        16 => "BADVERS".to_string(),
+        n => format!("RCODE{}", n),
+    }
+}
+
+/// Response of one query with its timings
+struct DnsOutcome {
+    response: Message,
+    rtt: Duration,
+    connect: Option<Duration>,
+}
+
+trait DnsIo: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> DnsIo for T {}
+
+struct DnsQuerier<'a> {
+    setting: &'a PingSetting,
+    option: DnsPingOption,
+    server: SocketAddr,
+    name: Name,
+    record_type: RecordType,
+    tls: Option<tokio_rustls::TlsConnector>,
+    /// Host for SNI and the DoH authority: the server hostname, else its address
+    host: String,
+}
+
+impl DnsQuerier<'_> {
+    fn build_query(&self) -> Result<Vec<u8>> {
+        let mut msg = Message::new();
+        // DoH uses ID 0 so that responses are cacheable (RFC 8484)
+        let id = match self.option.transport {
+            DnsTransport::Https => 0,
+            _ => rand::thread_rng().gen(),
+        };
+        msg.set_id(id)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(self.option.recursion_desired)
+            .add_query(Query::query(self.name.clone(), self.record_type));
+        let mut edns = Edns::new();
+        edns.set_max_payload(EDNS_PAYLOAD_SIZE);
+        msg.set_edns(edns);
+        Ok(msg.to_vec()?)
+    }
+
+    async fn query(&self) -> Result<DnsOutcome> {
+        let query = self.build_query()?;
+        let id = u16::from_be_bytes([query[0], query[1]]);
+        match self.option.transport {
+            DnsTransport::Udp => self.query_udp(&query, id).await,
+            DnsTransport::Tcp | DnsTransport::Tls => {
+                let started = Instant::now();
+                let mut stream = self.connect().await?;
+                let connect = started.elapsed();
+                let started = Instant::now();
+                let response = exchange_stream(&mut stream, &query).await?;
+                let rtt = started.elapsed();
+                Ok(DnsOutcome {
+                    response: parse_response(&response, id)?,
+                    rtt,
+                    connect: Some(connect),
+                })
+            }
+            DnsTransport::Https => self.query_https(query, id).await,
+        }
+    }
+
+    async fn query_udp(&self, query: &[u8], id: u16) -> Result<DnsOutcome> {
+        let mut cfg = UdpConfig::new();
+        cfg.socket_family = SocketFamily::from_ip(&self.server.ip());
+        let unspecified = match self.server.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        cfg = cfg.with_bind_addr(SocketAddr::new(
+            self.setting.src_ip.unwrap_or(unspecified),
+            0,
+        ));
+        if let Some(iface) = &self.setting.interface {
+            cfg = cfg.with_bind_device(iface);
+        }
+        let socket = AsyncUdpSocket::from_config(&cfg)?;
+        let started = Instant::now();
+        socket
+            .send_to(query, self.server)
+            .await
+            .map_err(|e| anyhow::anyhow!("send error: {e}"))?;
+        let mut buf = vec![0u8; MAX_MESSAGE_SIZE];
+        loop {
+            let (n, from) = socket.recv_from(&mut buf).await?;
+            // Ignore stray datagrams and responses to other queries
+            if from != self.server || n < 2 || u16::from_be_bytes([buf[0], buf[1]]) != id {
+                continue;
+            }
+            let rtt = started.elapsed();
+            return Ok(DnsOutcome {
+                response: parse_response(&buf[..n], id)?,
+                rtt,
+                connect: None,
+            });
+        }
+    }
+
+    async fn query_https(&self, query: Vec<u8>, id: u16) -> Result<DnsOutcome> {
+        let started = Instant::now();
+        let tls = self.connect_tls().await?;
+        let h2 = tls.get_ref().1.alpn_protocol() == Some(b"h2");
+        let io = TokioIo::new(tls);
+        let authority = match self.server.ip() {
+            IpAddr::V6(v6) if self.host == v6.to_string() => format!("[{}]", v6),
+            _ => self.host.clone(),
+        };
+        let authority = match self.server.port() {
+            443 => authority,
+            port => format!("{}:{}", authority, port),
+        };
+        let path = if self.option.doh_path.starts_with('/') {
+            self.option.doh_path.clone()
+        } else {
+            format!("/{}", self.option.doh_path)
+        };
+        let mut builder = Request::builder()
+            .method(Method::POST)
+            .header(CONTENT_TYPE, DNS_MESSAGE_MEDIA_TYPE)
+            .header(ACCEPT, DNS_MESSAGE_MEDIA_TYPE);
+        // HTTP/2 carries the authority in the URI, HTTP/1.1 in the Host header
+        builder = if h2 {
+            builder.uri(format!("https://{}{}", authority, path))
+        } else {
+            builder
+                .uri(path)
+                .header(HOST, HeaderValue::from_str(&authority)?)
+        };
+        let request = builder.body(Full::new(Bytes::from(query)))?;
+
+        let connect;
+        let sent_at;
+        let response = if h2 {
+            let (mut sender, conn) =
+                hyper::client::conn::http2::handshake(TokioExecutor::new(), io).await?;
+            tokio::spawn(conn);
+            connect = started.elapsed();
+            sent_at = Instant::now();
+            sender.send_request(request).await
+        } else {
+            let (mut sender, conn) = hyper::client::conn::http1::handshake(io).await?;
+            tokio::spawn(conn);
+            connect = started.elapsed();
+            sent_at = Instant::now();
+            sender.send_request(request).await
+        };
+        let response = response.map_err(|e| anyhow::anyhow!("http error: {e}"))?;
+        if response.status() != StatusCode::OK {
+            anyhow::bail!("http status {}", response.status());
+        }
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| anyhow::anyhow!("http error: {e}"))?
+            .to_bytes();
+        let rtt = sent_at.elapsed();
+        Ok(DnsOutcome {
+            response: parse_response(&body, id)?,
+            rtt,
+            connect: Some(connect),
+        })
+    }
+
+    async fn connect_tcp(&self) -> Result<TcpStream> {
+        let mut cfg = if self.server.is_ipv4() {
+            TcpConfig::v4_stream()
+        } else {
+            TcpConfig::v6_stream()
+        }
+        .with_nodelay(true);
+        if let Some(src) = self.setting.src_ip {
+            cfg = cfg.with_bind(SocketAddr::new(src, 0));
+        }
+        if let Some(iface) = &self.setting.interface {
+            cfg = cfg.with_bind_device(iface);
+        }
+        let socket = AsyncTcpSocket::from_config(&cfg)?;
+        let timeout = Duration::from_millis(self.setting.timeout_ms);
+        socket
+            .connect_timeout(self.server, timeout)
+            .await
+            .map_err(|e| anyhow::anyhow!("connect error: {e}"))
+    }
+
+    async fn connect_tls(&self) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        let stream = self.connect_tcp().await?;
+        let connector = self
+            .tls
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("TLS is not configured"))?;
+        let server_name = ServerName::try_from(self.host.clone())
+            .map_err(|_| anyhow::anyhow!("invalid TLS server name: {}", self.host))?;
+        connector
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| anyhow::anyhow!("tls error: {e}"))
+    }
+
+    async fn connect(&self) -> Result<Box<dyn DnsIo>> {
+        match self.option.transport {
+            DnsTransport::Tls => Ok(Box::new(self.connect_tls().await?)),
+            _ => Ok(Box::new(self.connect_tcp().await?)),
+        }
+    }
+}
+
+/// Send a query over a stream transport with the two byte length prefix and read the response.
+async fn exchange_stream(stream: &mut Box<dyn DnsIo>, query: &[u8]) -> Result<Vec<u8>> {
+    let mut framed = Vec::with_capacity(query.len() + 2);
+    framed.extend_from_slice(&(query.len() as u16).to_be_bytes());
+    framed.extend_from_slice(query);
+    stream.write_all(&framed).await?;
+    let len = stream.read_u16().await? as usize;
+    let mut response = vec![0u8; len];
+    stream.read_exact(&mut response).await?;
+    Ok(response)
+}
+
+fn parse_response(buf: &[u8], id: u16) -> Result<Message> {
+    let msg = Message::from_vec(buf).map_err(|e| anyhow::anyhow!("invalid response: {e}"))?;
+    if msg.id() != id || msg.message_type() != MessageType::Response {
+        anyhow::bail!("invalid response: ID or QR flag mismatch");
+    }
+    Ok(msg)
+}
+
+/// DNS ping: send a query to the DNS server at `setting.ip_addr` and time the response.
+/// UDP queries use a fresh socket and TCP, DoT and DoH queries a fresh connection per sample;
+/// the RTT covers the query exchange only, connection setup is reported separately.
+/// NOERROR and NXDOMAIN count as answered, any other response code as a failed probe.
+pub async fn dns_ping(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    setting: PingSetting,
+) -> Result<PingStat> {
+    let option = setting.dns.clone().unwrap_or_default();
+    let port = setting.port.unwrap_or(option.transport.default_port());
+    let name = Name::from_str(option.query_name.trim())
+        .map_err(|e| anyhow::anyhow!("invalid query name {:?}: {}", option.query_name, e))?;
+    let record_type = RecordType::from_str(&option.record_type.trim().to_uppercase())
+        .map_err(|_| anyhow::anyhow!("unknown record type: {}", option.record_type))?;
+    let tls = match option.transport {
+        DnsTransport::Tls => Some(tls_client_config(true, &[b"dot"])?),
+        DnsTransport::Https => Some(tls_client_config(true, &[b"h2", b"http/1.1"])?),
+        _ => None,
+    };
+    let host = setting
+        .hostname
+        .clone()
+        .filter(|h| h.parse::<IpAddr>().is_err())
+        .unwrap_or_else(|| setting.ip_addr.to_string());
+    let transport = option.transport;
+    let querier = DnsQuerier {
+        setting: &setting,
+        option,
+        server: SocketAddr::new(setting.ip_addr, port),
+        name,
+        record_type,
+        tls: tls.map(tokio_rustls::TlsConnector::from),
+        host,
+    };
+    let timeout = Duration::from_millis(setting.timeout_ms);
+
+    let mut samples = Vec::with_capacity(setting.count as usize);
+
+    let mut received = 0u32;
+
+    for seq in 1..=setting.count {
+        let mut status = ProbeStatus::new();
+        let mut rtt_ms: Option<f64> = None;
+        let mut detail = DnsSampleDetail {
+            transport,
+            rcode: None,
+            answer_count: None,
+            truncated: false,
+            connect_ms: None,
+        };
+
+        let outcome = tokio::select! {
+            _ = token.cancelled() => break,
+            outcome = tokio::time::timeout(timeout, querier.query()) => outcome,
+        };
+        match outcome {
+            Err(_) => {
+                status = ProbeStatus::with_timeout_message(format!(
+                    "timeout (>{}ms)",
+                    setting.timeout_ms
+                ));
+            }
+            Ok(Err(e)) => {
+                status = ProbeStatus::with_error_message(e.to_string());
+            }
+            Ok(Ok(outcome)) => {
+                let rcode = outcome.response.response_code();
+                rtt_ms = Some(duration_ms(outcome.rtt));
+                detail.rcode = Some(rcode_name(rcode));
+                detail.answer_count = Some(outcome.response.answers().len() as u32);
+                detail.truncated = outcome.response.truncated();
+                detail.connect_ms = outcome.connect.map(duration_ms);
+                if !matches!(rcode, ResponseCode::NoError | ResponseCode::NXDomain) {
+                    status = ProbeStatus::with_error_message(rcode_name(rcode));
+                }
+            }
+        }
+
+        let sample = PingSample {
+            rtt_ms,
+            probe_status: status,
+            dns: Some(detail),
+            ..PingSample::new(
+                seq,
+                setting.ip_addr,
+                setting.hostname.clone(),
+                Some(port),
+                PingProtocol::Dns,
+            )
+        };
+
+        if sample.is_received() {
+            received += 1;
+        }
+
+        let transmitted = seq;
+        let percent = (seq as f32) * 100.0 / (setting.count as f32);
+        // Send progress event
+        sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+            run_id: run_id.to_string(),
+            sample: sample.clone(),
+            transmitted,
+            received,
+            percent,
+        }));
+
+        samples.push(sample);
+
+        if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+            break;
+        }
+    }
+
+    // Summarize samples
+    let stat = PingStat::from_samples(
+        setting.hostname.clone(),
+        setting.ip_addr,
+        Some(port),
+        PingProtocol::Dns,
+        samples,
+    );
+
+    // Send done event
+    emit_finished(sink, token, run_id, &stat);
+
+    Ok(stat)
+}
@@ -1,166 +1,415 @@
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::ping::{emit_finished, wait_next};
 use anyhow::Result;
-use reqwest::Client;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::header::{HeaderName, HeaderValue, ACCEPT, HOST, USER_AGENT};
+use hyper::{Method, Request, Uri, Version};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use rustls::pki_types::ServerName;
+use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::sync::CancellationToken;
+use url::Url;
 
 use crate::model::ping::{
-    PingDonePayload, PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat,
+    HttpMethod, HttpPingOption, HttpSampleDetail, PingProgressPayload, PingProtocol, PingSample,
+    PingSetting, PingStat,
 };
-use crate::model::probe::{ProbeStatus, ProbeStatusKind};
+use crate::model::probe::ProbeStatus;
+use crate::socket::tcp::{AsyncTcpSocket, TcpConfig};
+use crate::socket::tls::tls_client_config;
+use crate::stats::duration_ms;
 
 pub const DEFAULT_USER_AGENT_CHROME: &str =
     "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
 
-fn summarize_rtts(rtts_ms: &[u64]) -> (Option<u64>, Option<u64>, Option<u64>) {
-    if rtts_ms.is_empty() {
-        return (None, None, None);
-    }
-    let mut min = u64::MAX;
-    let mut max = 0u64;
-    let mut sum: u128 = 0;
-    for &v in rtts_ms {
-        if v < min {
-            min = v;
-        }
-        if v > max {
-            max = v;
-        }
-        sum += v as u128;
-    }
-    (
-        Some(min),
-        Some((sum / rtts_ms.len() as u128) as u64),
-        Some(max),
-    )
-}
+/// Most of the body kept for `body_contains`; the rest is only counted
+const MAX_BODY_CHECK: usize = 1024 * 1024;
 
-pub async fn http_ping(app: &AppHandle, run_id: &str, setting: PingSetting) -> Result<PingStat> {
-    // Build HTTP client
-    let per_req_to = Duration::from_millis(setting.timeout_ms);
-    let client = Client::builder()
-        .user_agent(DEFAULT_USER_AGENT_CHROME)
-        .danger_accept_invalid_certs(true)
-        .pool_idle_timeout(Some(Duration::from_secs(5)))
-        .tcp_keepalive(Some(Duration::from_secs(10)))
-        .timeout(per_req_to)
-        .build()?;
-
-    // Build URL
+/// Build the request URL from `setting.hostname` (a URL or host) and `setting.port`.
+/// An explicit port replaces the port of the URL; without a scheme, port 443 selects https.
+pub fn request_url(setting: &PingSetting) -> Result<Url> {
     let host = setting
         .hostname
         .clone()
         .unwrap_or_else(|| setting.ip_addr.to_string());
-    let port = setting.port.unwrap_or_else(|| {
-        if host.starts_with("https://") {
-            443
+    let mut url = if host.starts_with("http://") || host.starts_with("https://") {
+        Url::parse(&host)?
+    } else {
+        let scheme = if setting.port == Some(443) {
+            "https"
         } else {
-            80
+            "http"
+        };
+        let host = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(v6)) => format!("[{v6}]"),
+            _ => host,
+        };
+        Url::parse(&format!("{scheme}://{host}/"))?
+    };
+    if let Some(port) = setting.port {
+        url.set_port(Some(port))
+            .map_err(|_| anyhow::anyhow!("cannot set port on URL: {}", url))?;
+    }
+    Ok(url)
+}
+
+/// Response of one request with its timings
+struct HttpOutcome {
+    detail: HttpSampleDetail,
+    /// Time to first byte measured from the start of the request
+    ttfb_total: Duration,
+    /// Reason the response failed the expectations, if it did
+    mismatch: Option<String>,
+}
+
+/// Error of one request, with the timings of the phases that completed
+struct HttpFailure {
+    detail: HttpSampleDetail,
+    message: String,
+    /// Whether a phase ran out of time, rather than failing
+    timed_out: bool,
+}
+
+trait HttpIo: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> HttpIo for T {}
+
+struct HttpRequester<'a> {
+    setting: &'a PingSetting,
+    option: HttpPingOption,
+    url: Url,
+    port: u16,
+    tls: Option<tokio_rustls::TlsConnector>,
+    timeout: Duration,
+}
+
+impl HttpRequester<'_> {
+    /// One request over a new connection: DNS, connect, TLS, request and full body.
+    async fn run(&self) -> Result<HttpOutcome, HttpFailure> {
+        let mut detail = HttpSampleDetail::default();
+        let started = Instant::now();
+        macro_rules! fail {
+            (timeout, $($arg:tt)*) => {
+                return Err(HttpFailure {
+                    detail,
+                    message: format!($($arg)*),
+                    timed_out: true,
+                })
+            };
+            ($($arg:tt)*) => {
+                return Err(HttpFailure {
+                    detail,
+                    message: format!($($arg)*),
+                    timed_out: false,
+                })
+            };
+        }
+
+        // DNS. The connection still goes to `setting.ip_addr` so all samples hit one address.
+        if let Some(url::Host::Domain(name)) = self.url.host() {
+            let t = Instant::now();
+            let resolved = crate::net::dns::lookup_ip(name, self.timeout).await;
+            detail.dns_ms = Some(duration_ms(t.elapsed()));
+            if resolved.is_none_or(|ips| ips.is_empty()) {
+                fail!("dns error: failed to resolve {name}");
+            }
+        }
+
+        // TCP connect
+        let mut cfg = if self.setting.ip_addr.is_ipv4() {
+            TcpConfig::v4_stream()
+        } else {
+            TcpConfig::v6_stream()
+        }
+        .with_nodelay(true);
+        if let Some(src) = self.setting.src_ip {
+            cfg = cfg.with_bind(SocketAddr::new(src, 0));
+        }
+        if let Some(iface) = &self.setting.interface {
+            cfg = cfg.with_bind_device(iface);
         }
-    });
+        let sock = match AsyncTcpSocket::from_config(&cfg) {
+            Ok(s) => s,
+            Err(e) => fail!("socket error: {e}"),
+        };
+        let t = Instant::now();
+        let target = SocketAddr::new(self.setting.ip_addr, self.port);
+        let stream = match sock.connect_timeout(target, self.timeout).await {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                fail!(timeout, "connect timeout (>{}ms)", self.timeout.as_millis())
+            }
+            Err(e) => fail!("connect error: {e}"),
+        };
+        detail.connect_ms = Some(duration_ms(t.elapsed()));
 
-    let url = if host.starts_with("http://") || host.starts_with("https://") {
-        host
+        // TLS handshake
+        let (io, h2): (Box<dyn HttpIo>, bool) = match &self.tls {
+            Some(connector) => {
+                let server_name = match self.url.host() {
+                    Some(url::Host::Domain(name)) => ServerName::try_from(name.to_string()),
+                    _ => Ok(ServerName::IpAddress(self.setting.ip_addr.into())),
+                };
+                let Ok(server_name) = server_name else {
+                    fail!("invalid TLS server name: {}", self.url);
+                };
+                let t = Instant::now();
+                let tls = match tokio::time::timeout(
+                    self.timeout,
+                    connector.connect(server_name, stream),
+                )
+                .await
+                {
+                    Ok(Ok(tls)) => tls,
+                    Ok(Err(e)) => fail!("tls error: {e}"),
+                    Err(_) => fail!(
+                        timeout,
+                        "tls handshake timeout (>{}ms)",
+                        self.timeout.as_millis()
+                    ),
+                };
+                detail.tls_ms = Some(duration_ms(t.elapsed()));
+                let h2 = tls.get_ref().1.alpn_protocol() == Some(b"h2");
+                (Box::new(tls), h2)
+            }
+            None => (Box::new(stream), false),
+        };
+
+        // Request
+        let request = match self.build_request(h2) {
+            Ok(r) => r,
+            Err(e) => fail!("invalid request: {e}"),
+        };
+        let io = TokioIo::new(io);
+        let sent_at = Instant::now();
+        let response = tokio::time::timeout(self.timeout, async {
+            if h2 {
+                let (mut sender, conn) =
+                    hyper::client::conn::http2::handshake(TokioExecutor::new(), io).await?;
+                tokio::spawn(conn);
+                sender.send_request(request).await
+            } else {
+                let (mut sender, conn) = hyper::client::conn::http1::handshake(io).await?;
+                tokio::spawn(conn);
+                sender.send_request(request).await
+            }
+        })
+        .await;
+        let mut response = match response {
+            Ok(Ok(r)) => r,
+            Ok(Err(e)) => fail!("http error: {e}"),
+            Err(_) => fail!(timeout, "timeout (>{}ms)", self.timeout.as_millis()),
+        };
+        let ttfb_total = started.elapsed();
+        detail.ttfb_ms = Some(duration_ms(sent_at.elapsed()));
+        detail.status = Some(response.status().as_u16());
+        detail.version = Some(version_str(response.version()).to_string());
+
+        // Body
+        let check_body =
+            self.option.method != HttpMethod::Head && self.option.body_contains.is_some();
+        let mut body = Vec::new();
+        let mut body_bytes = 0u64;
+        let t = Instant::now();
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let frame = match tokio::time::timeout_at(deadline, response.body_mut().frame()).await {
+                Ok(Some(Ok(frame))) => frame,
+                Ok(None) => break,
+                Ok(Some(Err(e))) => {
+                    detail.body_bytes = Some(body_bytes);
+                    fail!("body error: {e}");
+                }
+                Err(_) => {
+                    detail.body_bytes = Some(body_bytes);
+                    fail!(timeout, "body timeout (>{}ms)", self.timeout.as_millis());
+                }
+            };
+            if let Some(data) = frame.data_ref() {
+                body_bytes += data.len() as u64;
+                if check_body && body.len() < MAX_BODY_CHECK {
+                    let take = data.len().min(MAX_BODY_CHECK - body.len());
+                    body.extend_from_slice(&data[..take]);
+                }
+            }
+        }
+        detail.download_ms = Some(duration_ms(t.elapsed()));
+        detail.total_ms = Some(duration_ms(started.elapsed()));
+        detail.body_bytes = Some(body_bytes);
+
+        let status = response.status().as_u16();
+        let mut mismatch = None;
+        if !self.option.expected_status.is_empty() && !self.option.expected_status.contains(&status)
+        {
+            mismatch = Some(format!("unexpected status {status}"));
+        } else if let (true, Some(needle)) = (check_body, &self.option.body_contains) {
+            if !String::from_utf8_lossy(&body).contains(needle.as_str()) {
+                mismatch = Some(format!("body does not contain {:?}", needle));
+            }
+        }
+        Ok(HttpOutcome {
+            detail,
+            ttfb_total,
+            mismatch,
+        })
+    }
+
+    fn build_request(&self, h2: bool) -> Result<Request<Empty<Bytes>>> {
+        let method = match self.option.method {
+            HttpMethod::Get => Method::GET,
+            HttpMethod::Head => Method::HEAD,
+        };
+        // HTTP/2 carries the authority in the URI, HTTP/1.1 in the Host header
+        let uri: Uri = if h2 {
+            self.url.as_str().parse()?
+        } else {
+            self.url[url::Position::BeforePath..].parse()?
+        };
+        let mut builder = Request::builder().method(method).uri(uri).version(if h2 {
+            Version::HTTP_2
+        } else {
+            Version::HTTP_11
+        });
+        let headers = builder
+            .headers_mut()
+            .ok_or_else(|| anyhow::anyhow!("invalid request"))?;
+        if !h2 {
+            let authority = &self.url[url::Position::BeforeHost..url::Position::AfterPort];
+            headers.insert(HOST, HeaderValue::from_str(authority)?);
+        }
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_static(DEFAULT_USER_AGENT_CHROME),
+        );
+        headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+        for header in &self.option.headers {
+            headers.insert(
+                HeaderName::from_bytes(header.name.as_bytes())?,
+                HeaderValue::from_str(&header.value)?,
+            );
+        }
+        Ok(builder.body(Empty::new())?)
+    }
+}
+
+fn version_str(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "HTTP/?",
+    }
+}
+
+/// HTTP ping over a new connection per sample, timing DNS, TCP connect, TLS handshake,
+/// time to first byte and download. The RTT of a sample is the time to first byte
+/// from the start of the request. A response that fails `expected_status` or
+/// `body_contains` is reported as an error.
+pub async fn http_ping(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    setting: PingSetting,
+) -> Result<PingStat> {
+    let url = request_url(&setting)?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow::anyhow!("URL has no port: {}", url))?;
+    let tls = if url.scheme() == "https" {
+        let config = tls_client_config(true, &[b"h2", b"http/1.1"])?;
+        Some(tokio_rustls::TlsConnector::from(config))
     } else {
-        let scheme = if port == 443 { "https" } else { "http" };
-        format!("{scheme}://{host}:{port}/")
+        None
+    };
+    let requester = HttpRequester {
+        setting: &setting,
+        option: setting.http.clone().unwrap_or_default(),
+        url,
+        port,
+        tls,
+        timeout: Duration::from_millis(setting.timeout_ms),
     };
 
     let mut samples = Vec::with_capacity(setting.count as usize);
-    let mut rtts_ok: Vec<u64> = Vec::new();
 
     let mut received = 0u32;
 
     for seq in 1..=setting.count {
         let mut status = ProbeStatus::new();
-        let mut rtt_ms: Option<u64> = None;
+        let mut rtt_ms: Option<f64> = None;
 
-        let started = Instant::now();
-        // TTBF(Time to First Byte) as RTT
-        let fut = client.get(&url).send();
-
-        match tokio::time::timeout(per_req_to, fut).await {
-            Err(_) => {
-                status = ProbeStatus::with_timeout_message(format!(
-                    "timeout (>{}ms)",
-                    setting.timeout_ms
-                ));
-            }
-            Ok(Err(e)) => {
-                status = ProbeStatus::with_error_message(format!("http error: {e}"));
-            }
-            Ok(Ok(resp)) => {
-                let rtt = started.elapsed().as_millis() as u64;
-                rtt_ms = Some(rtt);
-                rtts_ok.push(rtt);
-                // Consume response to avoid connection leak
-                drop(resp);
-            }
-        }
+        let detail = tokio::select! {
+            _ = token.cancelled() => break,
+            outcome = requester.run() => match outcome {
+                Ok(outcome) => {
+                    match outcome.mismatch {
+                        Some(reason) => status = ProbeStatus::with_error_message(reason),
+                        // TTBF(Time to First Byte) as RTT
+                        None => rtt_ms = Some(duration_ms(outcome.ttfb_total)),
+                    }
+                    outcome.detail
+                }
+                Err(failure) => {
+                    status = if failure.timed_out {
+                        ProbeStatus::with_timeout_message(failure.message)
+                    } else {
+                        ProbeStatus::with_error_message(failure.message)
+                    };
+                    failure.detail
+                }
+            },
+        };
 
         let sample = PingSample {
-            seq,
-            ip_addr: setting.ip_addr,
-            hostname: setting.hostname.clone(),
-            port: Some(port),
             rtt_ms,
             probe_status: status,
-            protocol: PingProtocol::Http,
+            http: Some(detail),
+            ..PingSample::new(
+                seq,
+                setting.ip_addr,
+                setting.hostname.clone(),
+                Some(port),
+                PingProtocol::Http,
+            )
         };
 
-        if sample.rtt_ms.is_some() && matches!(sample.probe_status.kind, ProbeStatusKind::Done) {
+        if sample.is_received() {
             received += 1;
         }
 
         let transmitted = seq;
         let percent = (seq as f32) * 100.0 / (setting.count as f32);
         // Send progress event
-        let _ = app.emit(
-            "ping:progress",
-            PingProgressPayload {
-                run_id: run_id.to_string(),
-                sample: sample.clone(),
-                transmitted,
-                received,
-                percent,
-            },
-        );
+        sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+            run_id: run_id.to_string(),
+            sample: sample.clone(),
+            transmitted,
+            received,
+            percent,
+        }));
 
         samples.push(sample);
 
-        if seq != setting.count {
-            tokio::time::sleep(Duration::from_millis(setting.send_rate_ms)).await;
+        if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+            break;
         }
     }
 
     // Summarize samples
-    let transmitted = samples.len();
-    let received = samples
-        .iter()
-        .filter(|s| s.rtt_ms.is_some() && s.probe_status.kind == ProbeStatusKind::Done)
-        .count();
-    let (min, avg, max) = summarize_rtts(&rtts_ok);
-
-    let stat = PingStat {
-        ip_addr: setting.ip_addr,
-        hostname: setting.hostname.clone(),
-        port: Some(port),
-        protocol: PingProtocol::Http,
-        samples: samples.clone(),
-        transmitted_count: transmitted,
-        received_count: received,
-        min,
-        avg,
-        max,
-    };
+    let stat = PingStat::from_samples(
+        setting.hostname.clone(),
+        setting.ip_addr,
+        Some(port),
+        PingProtocol::Http,
+        samples,
+    );
 
     // Send done event
-    let _ = app.emit(
-        "ping:done",
-        PingDonePayload {
-            run_id: run_id.to_string(),
-            stat: stat.clone(),
-        },
-    );
+    emit_finished(sink, token, run_id, &stat);
 
     Ok(stat)
 }
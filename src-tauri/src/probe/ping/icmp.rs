@@ -1,49 +1,192 @@
-use anyhow::Result;
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::ping::{emit_finished, wait_next};
+use anyhow::{bail, Result};
+use rand::Rng;
 use std::{
+    collections::HashMap,
+    io,
     net::{IpAddr, SocketAddr},
     sync::Arc,
     time::{Duration, Instant},
 };
-use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     model::{
         ping::{
-            PingDonePayload, PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat,
+            IcmpPingOption, IcmpSampleDetail, PingProgressPayload, PingProtocol, PingSample,
+            PingSetting, PingStat,
         },
-        probe::{ProbeStatus, ProbeStatusKind},
+        probe::{IcmpError, ProbeStatus},
     },
-    probe::packet::{build_icmp_echo_bytes, parse_icmp_echo_v4, parse_icmp_echo_v6},
+    probe::packet::{build_icmp_echo_bytes, parse_echo_reply, parse_icmp_error},
+    probe::ping::udp_payload::parse_hex,
+    probe::scan::icmp::ReceiverGuard,
     socket::icmp::{AsyncIcmpSocket, IcmpConfig, IcmpKind},
+    stats::duration_ms,
 };
 
-fn summarize_rtts(rtts_ms: &[u64]) -> (Option<u64>, Option<u64>, Option<u64>) {
-    if rtts_ms.is_empty() {
-        return (None, None, None);
+/// Largest echo payload that fits in an IPv4 packet (65535 - 20 byte IP header - 8 byte ICMP header)
+const MAX_ICMP_PAYLOAD: usize = 65507;
+/// Largest echo payload that fits in an IPv6 packet without a jumbogram (65535 - 8)
+const MAX_ICMPV6_PAYLOAD: usize = 65527;
+
+/// Default payload pattern
+const PAYLOAD_MARKER: &[u8] = b"np:ping";
+
+/// Echo reply to one of our requests, as seen by the receiver task
+pub(crate) struct Reply {
+    pub seq: u16,
+    pub bytes: usize,
+    pub ttl: Option<u8>,
+    pub received_at: Instant,
+}
+
+/// Message forwarded by the receiver task
+pub(crate) enum Received {
+    Reply(Reply),
+    /// ICMP error quoting the request with this sequence number
+    Error {
+        seq: u16,
+        error: IcmpError,
+        received_at: Instant,
+    },
+}
+
+/// Echo request sent during the run
+struct Sent {
+    seq: u32,
+    sent_at: Instant,
+    answered: bool,
+}
+
+/// Echo request payload: the pattern (or the default marker) repeated to the configured size
+fn build_payload(option: &IcmpPingOption, is_v6: bool) -> Result<Vec<u8>> {
+    let max = if is_v6 {
+        MAX_ICMPV6_PAYLOAD
+    } else {
+        MAX_ICMP_PAYLOAD
+    };
+    if option.payload_size > max {
+        bail!(
+            "payload size {} exceeds the maximum of {} bytes",
+            option.payload_size,
+            max
+        );
     }
-    let mut min = u64::MAX;
-    let mut max = 0u64;
-    let mut sum: u128 = 0;
-    for &v in rtts_ms {
-        if v < min {
-            min = v;
-        }
-        if v > max {
-            max = v;
+    let pattern = match &option.pattern_hex {
+        Some(hex) => parse_hex(hex)?,
+        None => PAYLOAD_MARKER.to_vec(),
+    };
+    Ok(pattern
+        .iter()
+        .cycle()
+        .take(option.payload_size)
+        .copied()
+        .collect())
+}
+
+/// Forward echo replies from `target` carrying our identifier and payload, and ICMP errors
+/// quoting our requests. Messages are timestamped on arrival, so those read while the prober
+/// is busy keep their RTT.
+pub(crate) fn spawn_receiver(
+    socket: Arc<AsyncIcmpSocket>,
+    target: IpAddr,
+    echo_id: u16,
+    payload: Vec<u8>,
+    replies: mpsc::UnboundedSender<io::Result<Received>>,
+) -> ReceiverGuard {
+    // Linux DGRAM sockets rewrite the identifier and only deliver replies to our own requests
+    let check_id =
+        !(cfg!(any(target_os = "linux", target_os = "android")) && socket.socket_type().is_dgram());
+    let is_v6 = socket.socket_family().is_v6();
+    ReceiverGuard(tokio::spawn(async move {
+        let mut buf = vec![0u8; (payload.len() + 128).max(2048)];
+        let mut err_buf = vec![0u8; 2048];
+        loop {
+            let received = tokio::select! {
+                r = socket.recv_from_with_hop_limit(&mut buf) => r.map(|(n, addr, hop_limit)| {
+                    let received_at = Instant::now();
+                    // RAW sockets: errors from routers on the path arrive as ICMP messages
+                    if let Some(error) = parse_icmp_error(&buf[..n], is_v6) {
+                        let (id, seq) = error.quoted_echo()?;
+                        if error.dst != target || (check_id && id != echo_id) {
+                            return None;
+                        }
+                        let error = IcmpError::from_message(
+                            is_v6,
+                            error.icmp_type,
+                            error.icmp_code,
+                            Some(addr.ip()),
+                            error.mtu,
+                        )?;
+                        return Some(Received::Error { seq, error, received_at });
+                    }
+                    if addr.ip() != target {
+                        return None;
+                    }
+                    // Replies to another ping process carry its identifier or payload
+                    let reply = parse_echo_reply(&buf[..n], is_v6)?;
+                    if (check_id && reply.id != echo_id) || reply.payload != payload.as_slice() {
+                        return None;
+                    }
+                    Some(Received::Reply(Reply {
+                        seq: reply.seq,
+                        bytes: reply.size,
+                        ttl: hop_limit.or(reply.ttl),
+                        received_at,
+                    }))
+                }),
+                // Linux DGRAM sockets: errors are queued with the request that caused them
+                r = socket.recv_icmp_error(&mut err_buf) => r.map(|queued| {
+                    let request = &err_buf[..queued.len.min(err_buf.len())];
+                    if queued.dst.map(|dst| dst.ip()) != Some(target) || request.len() < 8 {
+                        return None;
+                    }
+                    let error = IcmpError::from_message(
+                        queued.is_v6,
+                        queued.icmp_type,
+                        queued.icmp_code,
+                        queued.offender,
+                        queued.info,
+                    )?;
+                    Some(Received::Error {
+                        seq: u16::from_be_bytes([request[6], request[7]]),
+                        error,
+                        received_at: Instant::now(),
+                    })
+                }),
+            };
+            match received {
+                Ok(Some(received)) => {
+                    let _ = replies.send(Ok(received));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let _ = replies.send(Err(e));
+                    break;
+                }
+            }
         }
-        sum += v as u128;
-    }
-    let avg = (sum / rtts_ms.len() as u128) as u64;
-    (Some(min), Some(avg), Some(max))
+    }))
 }
 
+/// ICMP ping: send echo requests to `setting.ip_addr` and match the replies on
+/// identifier, sequence number and payload. Duplicate replies, and replies that arrive
+/// after their probe timed out, are reported as extra samples flagged in `icmp`.
+/// An ICMP error quoting a request fails its probe, with the error in `icmp_error`.
 pub async fn icmp_ping(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
     run_id: &str,
+    token: &CancellationToken,
     src_ip: IpAddr,
     setting: PingSetting,
 ) -> Result<PingStat> {
-    let cfg = if setting.ip_addr.is_ipv4() {
+    let option = setting.icmp.clone().unwrap_or_default();
+    let payload = build_payload(&option, setting.ip_addr.is_ipv6())?;
+
+    let mut cfg = if setting.ip_addr.is_ipv4() {
         let mut c = IcmpConfig::new(IcmpKind::V4);
         c = c.with_ttl(setting.hop_limit as u32);
         c
@@ -52,6 +195,13 @@ pub async fn icmp_ping(
         c = c.with_hoplimit(setting.hop_limit as u32);
         c
     };
+    cfg = cfg.with_recv_hop_limit(true).with_recv_icmp_errors(true);
+    if let Some(src) = setting.src_ip {
+        cfg = cfg.with_bind(SocketAddr::new(src, 0));
+    }
+    if let Some(iface) = &setting.interface {
+        cfg = cfg.with_interface(iface);
+    }
 
     let socket = match AsyncIcmpSocket::new(&cfg).await {
         Ok(s) => Arc::new(s),
@@ -62,118 +212,180 @@ pub async fn icmp_ping(
 
     let target = SocketAddr::new(setting.ip_addr, 0);
 
-    let echo_id: u16 = 0x1234;
-    let payload = b"np:ping";
+    let echo_id: u16 = rand::thread_rng().gen();
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
+    let _receiver = spawn_receiver(
+        socket.clone(),
+        setting.ip_addr,
+        echo_id,
+        payload.clone(),
+        reply_tx,
+    );
+    // Probes by wire sequence number; a wrapped number replaces the oldest probe
+    let mut sent: HashMap<u16, Sent> = HashMap::new();
 
     let mut samples = Vec::with_capacity(setting.count as usize);
-    let mut rtts_ok = Vec::<u64>::new();
 
     let mut received = 0u32;
 
+    let progress = |sample: &PingSample, transmitted: u32, received: u32| {
+        let percent = (transmitted as f32) * 100.0 / (setting.count as f32);
+        sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+            run_id: run_id.to_string(),
+            sample: sample.clone(),
+            transmitted,
+            received,
+            percent,
+        }));
+    };
+    // Sample for a reply to one of our probes, flagged as duplicate or late unless it is
+    // the first reply to the `current` probe; `None` for an unknown sequence number
+    let extra_sample = |sent: &mut HashMap<u16, Sent>, reply: &Reply, current: u32| {
+        let probe = sent.get_mut(&reply.seq)?;
+        let detail = IcmpSampleDetail {
+            bytes: reply.bytes,
+            ttl: reply.ttl,
+            duplicate: probe.answered,
+            late: !probe.answered && probe.seq != current,
+        };
+        probe.answered = true;
+        let rtt_ms = duration_ms(reply.received_at.saturating_duration_since(probe.sent_at));
+        Some(PingSample {
+            rtt_ms: Some(rtt_ms),
+            icmp: Some(detail),
+            ..PingSample::new(
+                probe.seq,
+                setting.ip_addr,
+                setting.hostname.clone(),
+                None,
+                PingProtocol::Icmp,
+            )
+        })
+    };
+
     for seq in 1..=setting.count {
-        let pkt = build_icmp_echo_bytes(src_ip, setting.ip_addr, echo_id, seq as u16, payload);
+        let wire_seq = seq as u16;
+        let pkt = build_icmp_echo_bytes(src_ip, setting.ip_addr, echo_id, wire_seq, &payload);
 
         let sent_at = Instant::now();
         let mut status = ProbeStatus::new();
         let mut rtt_ms = None;
+        let mut detail = None;
+        let mut icmp_error = None;
 
         // Send ICMP Echo Request
         if let Err(e) = socket.send_to(&pkt, target).await {
             status = ProbeStatus::with_error_message(format!("send error: {e}"));
         } else {
-            // Wait for response (with timeout)
-            let mut buf = vec![0u8; 2048];
-            let to = Duration::from_millis(setting.timeout_ms);
-            match tokio::time::timeout(to, socket.recv_from(&mut buf)).await {
-                Err(_) => {
-                    status = ProbeStatus::with_timeout_message(format!(
-                        "timeout (>{}ms)",
-                        setting.timeout_ms
-                    ));
-                }
-                Ok(Err(e)) => {
-                    status = ProbeStatus::with_error_message(format!("recv error: {e}"));
-                }
-                Ok(Ok((n, _addr))) => {
-                    let ok = match setting.ip_addr {
-                        IpAddr::V4(_) => parse_icmp_echo_v4(&buf[..n]).is_some(),
-                        IpAddr::V6(_) => parse_icmp_echo_v6(&buf[..n]).is_some(),
-                    };
-                    if ok {
-                        let rtt = sent_at.elapsed().as_millis() as u64;
-                        rtt_ms = Some(rtt);
-                        rtts_ok.push(rtt);
-                    } else {
-                        status = ProbeStatus::with_error_message("unexpected reply".to_string());
+            sent.insert(
+                wire_seq,
+                Sent {
+                    seq,
+                    sent_at,
+                    answered: false,
+                },
+            );
+            // Wait for the reply to this request (with timeout)
+            let deadline =
+                tokio::time::Instant::from_std(sent_at) + Duration::from_millis(setting.timeout_ms);
+            loop {
+                let reply = match tokio::time::timeout_at(deadline, reply_rx.recv()).await {
+                    Err(_) => {
+                        status = ProbeStatus::with_timeout_message(format!(
+                            "timeout (>{}ms)",
+                            setting.timeout_ms
+                        ));
+                        break;
+                    }
+                    Ok(Some(Ok(Received::Reply(reply)))) => reply,
+                    Ok(Some(Ok(Received::Error {
+                        seq: error_seq,
+                        error,
+                        received_at,
+                    }))) => {
+                        // Errors for earlier probes are dropped with them
+                        if error_seq != wire_seq {
+                            continue;
+                        }
+                        rtt_ms = Some(duration_ms(received_at.saturating_duration_since(sent_at)));
+                        status = ProbeStatus::with_error_message(error.to_string());
+                        icmp_error = Some(error);
+                        break;
+                    }
+                    Ok(Some(Err(e))) => {
+                        status = ProbeStatus::with_error_message(format!("recv error: {e}"));
+                        break;
                     }
+                    Ok(None) => {
+                        status = ProbeStatus::with_error_message(
+                            "recv error: receiver stopped".to_string(),
+                        );
+                        break;
+                    }
+                };
+                let Some(sample) = extra_sample(&mut sent, &reply, seq) else {
+                    continue;
+                };
+                if sample.seq == seq && !sample.is_extra_reply() {
+                    rtt_ms = sample.rtt_ms;
+                    detail = sample.icmp;
+                    break;
                 }
+                progress(&sample, seq, received);
+                samples.push(sample);
             }
         }
 
         let sample = PingSample {
-            seq,
-            ip_addr: setting.ip_addr,
-            hostname: setting.hostname.clone(),
-            port: None,
             rtt_ms,
             probe_status: status,
-            protocol: PingProtocol::Icmp,
+            icmp: detail,
+            icmp_error,
+            ..PingSample::new(
+                seq,
+                setting.ip_addr,
+                setting.hostname.clone(),
+                None,
+                PingProtocol::Icmp,
+            )
         };
 
-        if sample.rtt_ms.is_some() && matches!(sample.probe_status.kind, ProbeStatusKind::Done) {
+        if sample.is_received() {
             received += 1;
         }
 
-        let transmitted = seq;
-        let percent = (seq as f32) * 100.0 / (setting.count as f32);
-
-        let _ = app.emit(
-            "ping:progress",
-            PingProgressPayload {
-                run_id: run_id.to_string(),
-                sample: sample.clone(),
-                transmitted,
-                received,
-                percent,
-            },
-        );
+        progress(&sample, seq, received);
 
         samples.push(sample);
 
-        if seq != setting.count {
-            tokio::time::sleep(Duration::from_millis(setting.send_rate_ms)).await;
+        if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+            break;
         }
     }
 
-    // Summarize statistics
-    let transmitted = samples.len();
-    let received = samples
-        .iter()
-        .filter(|s| s.rtt_ms.is_some() && s.probe_status.kind == ProbeStatusKind::Done)
-        .count();
-    let (min, avg, max) = summarize_rtts(&rtts_ok);
-
-    let stat = PingStat {
-        ip_addr: setting.ip_addr,
-        hostname: setting.hostname.clone(),
-        port: None,
-        protocol: PingProtocol::Icmp,
+    // Report duplicate and late replies that arrived after the last probe finished
+    let transmitted = samples.iter().filter(|s| !s.is_extra_reply()).count() as u32;
+    while let Ok(Ok(message)) = reply_rx.try_recv() {
+        let Received::Reply(reply) = message else {
+            continue;
+        };
+        if let Some(sample) = extra_sample(&mut sent, &reply, 0) {
+            progress(&sample, transmitted, received);
+            samples.push(sample);
+        }
+    }
+
+    // Summarize samples
+    let stat = PingStat::from_samples(
+        setting.hostname.clone(),
+        setting.ip_addr,
+        None,
+        PingProtocol::Icmp,
         samples,
-        transmitted_count: transmitted,
-        received_count: received,
-        min,
-        avg,
-        max,
-    };
+    );
 
     // Send done event
-    let _ = app.emit(
-        "ping:done",
-        PingDonePayload {
-            run_id: run_id.to_string(),
-            stat: stat.clone(),
-        },
-    );
+    emit_finished(sink, token, run_id, &stat);
 
     Ok(stat)
 }
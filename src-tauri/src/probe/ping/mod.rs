@@ -1,5 +1,100 @@
+pub mod dns;
 pub mod http;
 pub mod icmp;
+pub mod multi;
+pub mod ntp;
 pub mod quic;
 pub mod tcp;
 pub mod udp;
+pub mod udp_payload;
+
+use anyhow::Result;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::model::ping::{PingDonePayload, PingProtocol, PingSetting, PingStat, UdpPingMode};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+
+/// Run a ping with the probe matching `setting.protocol`.
+pub async fn ping(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    src_ip: IpAddr,
+    setting: PingSetting,
+) -> Result<PingStat> {
+    match setting.protocol {
+        PingProtocol::Icmp => icmp::icmp_ping(sink, run_id, token, src_ip, setting).await,
+        PingProtocol::Tcp => tcp::tcp_ping(sink, run_id, token, src_ip, setting).await,
+        PingProtocol::Udp => match setting.udp.as_ref().map(|u| u.mode).unwrap_or_default() {
+            UdpPingMode::PortUnreachable => {
+                udp::udp_ping_icmp_unreach(sink, run_id, token, src_ip, setting).await
+            }
+            _ => udp::udp_ping_payload(sink, run_id, token, setting).await,
+        },
+        PingProtocol::Quic => quic::quic_ping(sink, run_id, token, src_ip, setting).await,
+        PingProtocol::Http => http::http_ping(sink, run_id, token, setting).await,
+        PingProtocol::Dns => dns::dns_ping(sink, run_id, token, setting).await,
+        PingProtocol::Ntp => ntp::ntp_ping(sink, run_id, token, setting).await,
+    }
+}
+
+/// Resolve a ping target given as an IP address or hostname, or a URL for HTTP.
+/// Returns the address, the hostname (the full URL for HTTP) and the port.
+pub async fn resolve_target(
+    target: &str,
+    protocol: PingProtocol,
+    port: Option<u16>,
+    timeout: Duration,
+) -> Result<(IpAddr, Option<String>, Option<u16>)> {
+    match protocol {
+        PingProtocol::Http => {
+            // Keep the URL as hostname so the probe requests the same scheme and path
+            let url = if target.starts_with("http://") || target.starts_with("https://") {
+                url::Url::parse(target)?
+            } else {
+                url::Url::parse(&format!("http://{}/", target))?
+            };
+            let host = url
+                .host_str()
+                .ok_or_else(|| anyhow::anyhow!("URL has no host: {}", target))?;
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let (ip_addr, _) = crate::net::dns::resolve_target(host, timeout).await?;
+            let port = port.or(url.port_or_known_default());
+            Ok((ip_addr, Some(url.to_string()), port))
+        }
+        _ => {
+            let (ip_addr, hostname) = crate::net::dns::resolve_target(target, timeout).await?;
+            Ok((ip_addr, hostname, port))
+        }
+    }
+}
+
+/// Wait before sending the next probe.
+/// Returns false if the run was cancelled while waiting.
+pub async fn wait_next(token: &CancellationToken, send_rate_ms: u64) -> bool {
+    tokio::select! {
+        _ = token.cancelled() => false,
+        _ = tokio::time::sleep(Duration::from_millis(send_rate_ms)) => true,
+    }
+}
+
+/// Emit `ping:done`, or `ping:cancelled` with the partial result if the run was cancelled.
+pub fn emit_finished(
+    sink: &dyn ProbeEventSink,
+    token: &CancellationToken,
+    run_id: &str,
+    stat: &PingStat,
+) {
+    let payload = PingDonePayload {
+        run_id: run_id.to_string(),
+        stat: stat.clone(),
+    };
+    if token.is_cancelled() {
+        sink.emit(ProbeEvent::PingCancelled(payload));
+    } else {
+        sink.emit(ProbeEvent::PingDone(payload));
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::Result;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::model::ping::{
+    MultiPingProgressPayload, MultiPingReport, MultiPingSetting, MultiPingStartPayload,
+    MultiPingTarget, PingProtocol, PingSample, PingStat,
+};
+use crate::model::probe::ProbeStatus;
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::packet::{build_icmp_echo_bytes, parse_echo_reply_ids};
+use crate::probe::ping::wait_next;
+use crate::probe::scan::icmp::{open_icmp_socket, ReceiverGuard};
+use crate::socket::icmp::{AsyncIcmpSocket, IcmpKind};
+use crate::stats::duration_ms;
+
+/// Maximum number of addresses in one sweep after CIDR expansion
+pub const MAX_MULTI_PING_TARGETS: usize = 4096;
+
+const PAYLOAD: &[u8] = b"np:mping";
+
+/// Echo reply seen by a receiver task: target, sequence number and time of arrival
+type Reply = (IpAddr, u16, Instant);
+
+fn add_target(
+    out: &mut Vec<MultiPingTarget>,
+    seen: &mut HashSet<IpAddr>,
+    ip_addr: IpAddr,
+    hostname: Option<String>,
+) -> Result<()> {
+    if seen.insert(ip_addr) {
+        if out.len() == MAX_MULTI_PING_TARGETS {
+            anyhow::bail!("too many targets (max {})", MAX_MULTI_PING_TARGETS);
+        }
+        out.push(MultiPingTarget { ip_addr, hostname });
+    }
+    Ok(())
+}
+
+/// Resolve hostnames and expand CIDR networks, keeping the first occurrence of each address.
+pub async fn resolve_targets(
+    targets: &[String],
+    timeout: Duration,
+) -> Result<Vec<MultiPingTarget>> {
+    let mut out = Vec::new();
+    let mut seen = HashSet::new();
+    for target in targets.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if let Ok(net) = target.parse::<netdev::ipnet::IpNet>() {
+            for ip in net.hosts() {
+                add_target(&mut out, &mut seen, ip, None)?;
+            }
+        } else {
+            let (ip, hostname) = crate::net::dns::resolve_target(target, timeout).await?;
+            add_target(&mut out, &mut seen, ip, hostname)?;
+        }
+    }
+    Ok(out)
+}
+
+fn spawn_receiver(
+    socket: Arc<AsyncIcmpSocket>,
+    echo_id: u16,
+    replies: mpsc::UnboundedSender<Reply>,
+) -> ReceiverGuard {
+    // Linux DGRAM sockets rewrite the identifier and only deliver replies to our own requests
+    let check_id =
+        !(cfg!(any(target_os = "linux", target_os = "android")) && socket.socket_type().is_dgram());
+    let is_v6 = socket.socket_family().is_v6();
+    ReceiverGuard(tokio::spawn(async move {
+        let mut buf = vec![0u8; 2048];
+        loop {
+            let Ok((n, addr)) = socket.recv_from(&mut buf).await else {
+                // Error on recv, socket might be closed
+                break;
+            };
+            let received_at = Instant::now();
+            let Some((id, seq)) = parse_echo_reply_ids(&buf[..n], is_v6) else {
+                continue;
+            };
+            if check_id && id != echo_id {
+                continue;
+            }
+            let _ = replies.send((addr.ip(), seq, received_at));
+        }
+    }))
+}
+
+/// Ping every target over ICMP in rounds, sharing one socket and receiver task per family.
+///
+/// Each round sends one echo request to every target, then waits up to `timeout_ms`
+/// for the replies. `multiping:progress` is emitted for every probe as it completes.
+pub async fn multi_ping(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    src_ipv4: Option<IpAddr>,
+    src_ipv6: Option<IpAddr>,
+    targets: Vec<MultiPingTarget>,
+    setting: MultiPingSetting,
+) -> Result<MultiPingReport> {
+    let count = setting.count.max(1);
+    let timeout = Duration::from_millis(setting.timeout_ms);
+    let total = (targets.len() as u32).saturating_mul(count);
+
+    // Bind to the source addresses only when a source was requested
+    let pin_source = setting.interface.is_some() || setting.src_ip.is_some();
+    let interface = setting.interface.as_deref();
+    let socket_v4 = if targets.iter().any(|t| t.ip_addr.is_ipv4()) {
+        let bind = src_ipv4.filter(|_| pin_source);
+        Some(open_icmp_socket(IcmpKind::V4, setting.hop_limit, bind, interface, false).await?)
+    } else {
+        None
+    };
+    let socket_v6 = if targets.iter().any(|t| t.ip_addr.is_ipv6()) {
+        let bind = src_ipv6.filter(|_| pin_source);
+        Some(open_icmp_socket(IcmpKind::V6, setting.hop_limit, bind, interface, false).await?)
+    } else {
+        None
+    };
+
+    let echo_id: u16 = rand::thread_rng().gen();
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<Reply>();
+    let rx_v4 = socket_v4
+        .as_ref()
+        .map(|s| spawn_receiver(s.clone(), echo_id, reply_tx.clone()));
+    let rx_v6 = socket_v6
+        .as_ref()
+        .map(|s| spawn_receiver(s.clone(), echo_id, reply_tx.clone()));
+    drop(reply_tx);
+
+    sink.emit(ProbeEvent::MultiPingStart(MultiPingStartPayload {
+        run_id: run_id.to_string(),
+        targets: targets.clone(),
+    }));
+
+    let index: HashMap<IpAddr, usize> = targets
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.ip_addr, i))
+        .collect();
+    let mut samples: Vec<Vec<PingSample>> = vec![Vec::with_capacity(count as usize); targets.len()];
+    let mut received = vec![0u32; targets.len()];
+    let mut done = 0u32;
+    // Probes of the current round waiting for a reply, keyed by target and sequence number
+    let mut pending: HashMap<(IpAddr, u16), Instant> = HashMap::new();
+
+    let mut record = |i: usize, seq: u32, rtt: Option<Duration>, status: ProbeStatus| {
+        let target = &targets[i];
+        let sample = PingSample {
+            rtt_ms: rtt.map(duration_ms),
+            probe_status: status,
+            ..PingSample::new(
+                seq,
+                target.ip_addr,
+                target.hostname.clone(),
+                None,
+                PingProtocol::Icmp,
+            )
+        };
+        if sample.is_received() {
+            received[i] += 1;
+        }
+        done += 1;
+        samples[i].push(sample.clone());
+        sink.emit(ProbeEvent::MultiPingProgress(MultiPingProgressPayload {
+            run_id: run_id.to_string(),
+            sample,
+            transmitted: samples[i].len() as u32,
+            received: received[i],
+            done,
+            total,
+        }));
+    };
+
+    'rounds: for round in 1..=count {
+        if token.is_cancelled() {
+            break;
+        }
+        let round_start = Instant::now();
+        // Sequence numbers wrap after 65535 rounds; earlier probes are resolved by then
+        let seq = round as u16;
+
+        for (i, target) in targets.iter().enumerate() {
+            let (socket, src_ip) = match target.ip_addr {
+                IpAddr::V4(_) => (
+                    socket_v4.as_ref(),
+                    src_ipv4.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                ),
+                IpAddr::V6(_) => (
+                    socket_v6.as_ref(),
+                    src_ipv6.unwrap_or(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+                ),
+            };
+            let Some(socket) = socket else {
+                record(
+                    i,
+                    round,
+                    None,
+                    ProbeStatus::with_error_message("no suitable socket for IP family".into()),
+                );
+                continue;
+            };
+            let pkt = build_icmp_echo_bytes(src_ip, target.ip_addr, echo_id, seq, PAYLOAD);
+            let sent_at = Instant::now();
+            match socket
+                .send_to(&pkt, SocketAddr::new(target.ip_addr, 0))
+                .await
+            {
+                Ok(_) => {
+                    pending.insert((target.ip_addr, seq), sent_at);
+                }
+                Err(e) => {
+                    record(
+                        i,
+                        round,
+                        None,
+                        ProbeStatus::with_error_message(format!("send error: {e}")),
+                    );
+                }
+            }
+        }
+
+        // Collect replies until every probe of this round is answered or timed out.
+        // Replies to earlier rounds are no longer pending and are ignored.
+        let deadline = tokio::time::Instant::now() + timeout;
+        while !pending.is_empty() {
+            let reply = tokio::select! {
+                _ = token.cancelled() => break 'rounds,
+                reply = reply_rx.recv() => reply,
+                // Replies already queued when the deadline fires still count
+                _ = tokio::time::sleep_until(deadline) => match reply_rx.try_recv() {
+                    Ok(reply) => Some(reply),
+                    Err(_) => break,
+                },
+            };
+            let Some((ip, reply_seq, received_at)) = reply else {
+                break;
+            };
+            let Some(sent_at) = pending.remove(&(ip, reply_seq)) else {
+                continue;
+            };
+            if let Some(&i) = index.get(&ip) {
+                let rtt = received_at.saturating_duration_since(sent_at);
+                record(i, round, Some(rtt), ProbeStatus::new());
+            }
+        }
+
+        // Whatever is still pending timed out
+        for ((ip, _), _) in pending.drain() {
+            if let Some(&i) = index.get(&ip) {
+                record(
+                    i,
+                    round,
+                    None,
+                    ProbeStatus::with_timeout_message(format!(
+                        "timeout (>{}ms)",
+                        setting.timeout_ms
+                    )),
+                );
+            }
+        }
+
+        let elapsed = round_start.elapsed().as_millis() as u64;
+        if round != count && !wait_next(token, setting.send_rate_ms.saturating_sub(elapsed)).await {
+            break;
+        }
+    }
+
+    // Terminate receiver tasks before summarizing
+    drop(rx_v4);
+    drop(rx_v6);
+
+    // Summarize samples
+    let stats: Vec<PingStat> = targets
+        .into_iter()
+        .zip(samples)
+        .map(|(t, s)| PingStat::from_samples(t.hostname, t.ip_addr, None, PingProtocol::Icmp, s))
+        .collect();
+    let report = MultiPingReport {
+        run_id: run_id.to_string(),
+        stats,
+    };
+    if token.is_cancelled() {
+        sink.emit(ProbeEvent::MultiPingCancelled(report.clone()));
+    } else {
+        sink.emit(ProbeEvent::MultiPingDone(report.clone()));
+    }
+    Ok(report)
+}
@@ -0,0 +1,121 @@
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::ping::{emit_finished, wait_next};
+use anyhow::Result;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::model::ntp::NtpLeap;
+use crate::model::ping::{PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat};
+use crate::model::probe::ProbeStatus;
+use crate::net::ntp::{self, NTP_PORT};
+use crate::socket::udp::UdpConfig;
+use crate::socket::SocketFamily;
+use crate::stats::duration_ms;
+
+/// NTP ping: send a client request to the NTP server at `setting.ip_addr` and time the reply.
+/// Every sample carries the server reply, so the clock offset can be followed over the run.
+/// A reply from an unsynchronized server keeps its RTT but counts as a failed probe.
+pub async fn ntp_ping(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    setting: PingSetting,
+) -> Result<PingStat> {
+    let port = setting.port.unwrap_or(NTP_PORT);
+    let server = SocketAddr::new(setting.ip_addr, port);
+    let mut cfg = UdpConfig::new();
+    cfg.socket_family = SocketFamily::from_ip(&setting.ip_addr);
+    let unspecified = match setting.ip_addr {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    cfg = cfg.with_bind_addr(SocketAddr::new(setting.src_ip.unwrap_or(unspecified), 0));
+    if let Some(iface) = &setting.interface {
+        cfg = cfg.with_bind_device(iface);
+    }
+    let timeout = Duration::from_millis(setting.timeout_ms);
+
+    let mut samples = Vec::with_capacity(setting.count as usize);
+
+    let mut received = 0u32;
+
+    for seq in 1..=setting.count {
+        let mut status = ProbeStatus::new();
+        let mut rtt_ms: Option<f64> = None;
+        let mut detail = None;
+
+        let outcome = tokio::select! {
+            _ = token.cancelled() => break,
+            outcome = tokio::time::timeout(timeout, ntp::query(server, &cfg)) => outcome,
+        };
+        match outcome {
+            Err(_) => {
+                status = ProbeStatus::with_timeout_message(format!(
+                    "timeout (>{}ms)",
+                    setting.timeout_ms
+                ));
+            }
+            Ok(Err(e)) => {
+                status = ProbeStatus::with_error_message(e.to_string());
+            }
+            Ok(Ok((response, rtt))) => {
+                rtt_ms = Some(duration_ms(rtt));
+                if response.leap == NtpLeap::Unsynchronized {
+                    status = ProbeStatus::with_error_message(
+                        "server clock not synchronized".to_string(),
+                    );
+                }
+                detail = Some(response);
+            }
+        }
+
+        let sample = PingSample {
+            rtt_ms,
+            probe_status: status,
+            ntp: detail,
+            ..PingSample::new(
+                seq,
+                setting.ip_addr,
+                setting.hostname.clone(),
+                Some(port),
+                PingProtocol::Ntp,
+            )
+        };
+
+        if sample.is_received() {
+            received += 1;
+        }
+
+        let transmitted = seq;
+        let percent = (seq as f32) * 100.0 / (setting.count as f32);
+        // Send progress event
+        sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+            run_id: run_id.to_string(),
+            sample: sample.clone(),
+            transmitted,
+            received,
+            percent,
+        }));
+
+        samples.push(sample);
+
+        if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+            break;
+        }
+    }
+
+    // Summarize samples
+    let stat = PingStat::from_samples(
+        setting.hostname.clone(),
+        setting.ip_addr,
+        Some(port),
+        PingProtocol::Ntp,
+        samples,
+    );
+
+    // Send done event
+    emit_finished(sink, token, run_id, &stat);
+
+    Ok(stat)
+}
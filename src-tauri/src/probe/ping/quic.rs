@@ -1,40 +1,32 @@
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::ping::{emit_finished, wait_next};
 use anyhow::Result;
 use std::{
     net::{IpAddr, SocketAddr},
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use tauri::{AppHandle, Emitter};
-
-use crate::model::ping::{
-    PingDonePayload, PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat,
+use tokio_util::sync::CancellationToken;
+
+use crate::model::ping::{PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat};
+use crate::model::probe::ProbeStatus;
+use crate::model::quic::QuicSampleDetail;
+use crate::net::tls::x509;
+use crate::socket::quic::{
+    negotiated_alpn, peer_certificates, version_name, AsyncQuicSocket, QuicConfig, QUIC_VERSION,
 };
-use crate::model::probe::{ProbeStatus, ProbeStatusKind};
-use crate::socket::quic::{AsyncQuicSocket, QuicConfig};
 use crate::socket::SocketFamily;
+use crate::stats::duration_ms;
 
-fn summarize_rtts(rtts_ms: &[u64]) -> (Option<u64>, Option<u64>, Option<u64>) {
-    if rtts_ms.is_empty() {
-        return (None, None, None);
-    }
-    let mut min = u64::MAX;
-    let mut max = 0u64;
-    let mut sum: u128 = 0;
-    for &v in rtts_ms {
-        if v < min {
-            min = v;
-        }
-        if v > max {
-            max = v;
-        }
-        sum += v as u128;
-    }
-    let avg = (sum / rtts_ms.len() as u128) as u64;
-    (Some(min), Some(avg), Some(max))
+/// Time allowed for the session ticket to arrive after the handshake,
+/// before the connection is closed for the 0-RTT check
+fn ticket_wait(conn: &quinn::Connection) -> Duration {
+    (conn.rtt() * 2).clamp(Duration::from_millis(20), Duration::from_millis(500))
 }
 
 pub async fn quic_ping(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
     run_id: &str,
+    token: &CancellationToken,
     _src_ip: IpAddr,
     setting: PingSetting,
 ) -> Result<PingStat> {
@@ -42,22 +34,25 @@ pub async fn quic_ping(
         IpAddr::V4(_) => SocketFamily::IPV4,
         IpAddr::V6(_) => SocketFamily::IPV6,
     };
+    let option = setting.quic.clone().unwrap_or_default();
     let cfg = QuicConfig {
-        skip_verify: true,
-        alpn: vec![b"h3".to_vec(), b"hq-29".to_vec(), b"hq-interop".to_vec()],
+        skip_verify: option.skip_verify,
+        alpn: option.alpn_protocols(),
         family,
+        bind_addr: setting.src_ip.map(|ip| SocketAddr::new(ip, 0)),
+        bind_device: setting.interface.clone(),
     };
 
     let port = setting.port.unwrap_or(443);
     let target = SocketAddr::new(setting.ip_addr, port);
 
-    let server_name = setting
-        .hostname
+    let server_name = option
+        .server_name
         .clone()
+        .or_else(|| setting.hostname.clone())
         .unwrap_or_else(|| "netpulsar.local".to_string());
 
     let mut samples = Vec::with_capacity(setting.count as usize);
-    let mut rtts_ok = Vec::<u64>::new();
 
     let mut received = 0u32;
 
@@ -65,17 +60,48 @@ pub async fn quic_ping(
         let sock = AsyncQuicSocket::from_config(&cfg)?;
         let begin = Instant::now();
         let mut status = ProbeStatus::new();
-        let mut rtt_ms: Option<u64> = None;
+        let mut rtt_ms: Option<f64> = None;
+        let mut detail: Option<QuicSampleDetail> = None;
 
         // Connect with timeout
         let to = Duration::from_millis(setting.timeout_ms);
         match sock.connect_timeout(&target, &server_name, to).await {
             Ok(conn) => {
-                let rtt = begin.elapsed().as_millis() as u64;
-                rtt_ms = Some(rtt);
-                rtts_ok.push(rtt);
+                rtt_ms = Some(duration_ms(begin.elapsed()));
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or_default();
+                let validation = sock.cert_validation();
+                let alpn = negotiated_alpn(&conn);
+                let certificate = peer_certificates(&conn)
+                    .first()
+                    .and_then(|cert| x509::parse_certificate(cert, now).ok());
+                if option.zero_rtt {
+                    tokio::time::sleep(ticket_wait(&conn)).await;
+                }
                 // Close connection
                 conn.close(0u32.into(), b"np");
+
+                let mut zero_rtt_accepted = None;
+                if option.zero_rtt {
+                    if let Ok(Some((conn, accepted))) =
+                        sock.connect_0rtt_timeout(&target, &server_name, to).await
+                    {
+                        zero_rtt_accepted = Some(accepted);
+                        conn.close(0u32.into(), b"np");
+                    }
+                }
+
+                detail = Some(QuicSampleDetail {
+                    server_name: server_name.clone(),
+                    version: version_name(QUIC_VERSION),
+                    alpn,
+                    certificate,
+                    trusted: validation.is_ok(),
+                    validation_error: validation.err(),
+                    zero_rtt_accepted,
+                });
             }
             Err(e) => {
                 let msg = e.to_string();
@@ -92,68 +118,50 @@ pub async fn quic_ping(
         }
 
         let sample = PingSample {
-            seq,
-            ip_addr: setting.ip_addr,
-            hostname: setting.hostname.clone(),
-            port: Some(port),
             rtt_ms,
             probe_status: status,
-            protocol: PingProtocol::Quic,
+            quic: detail,
+            ..PingSample::new(
+                seq,
+                setting.ip_addr,
+                setting.hostname.clone(),
+                Some(port),
+                PingProtocol::Quic,
+            )
         };
 
-        if sample.rtt_ms.is_some() && matches!(sample.probe_status.kind, ProbeStatusKind::Done) {
+        if sample.is_received() {
             received += 1;
         }
 
         let transmitted = seq;
         let percent = (seq as f32) * 100.0 / (setting.count as f32);
-        let _ = app.emit(
-            "ping:progress",
-            PingProgressPayload {
-                run_id: run_id.to_string(),
-                sample: sample.clone(),
-                transmitted,
-                received,
-                percent,
-            },
-        );
+        sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+            run_id: run_id.to_string(),
+            sample: sample.clone(),
+            transmitted,
+            received,
+            percent,
+        }));
 
         samples.push(sample);
 
-        if seq != setting.count {
-            tokio::time::sleep(Duration::from_millis(setting.send_rate_ms)).await;
+        if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+            break;
         }
     }
 
     // Summarize samples
-    let transmitted = samples.len();
-    let received = samples
-        .iter()
-        .filter(|s| s.rtt_ms.is_some() && s.probe_status.kind == ProbeStatusKind::Done)
-        .count();
-    let (min, avg, max) = summarize_rtts(&rtts_ok);
-
-    let stat = PingStat {
-        ip_addr: setting.ip_addr,
-        hostname: setting.hostname.clone(),
-        port: Some(port),
-        protocol: PingProtocol::Quic,
-        samples: samples.clone(),
-        transmitted_count: transmitted,
-        received_count: received,
-        min,
-        avg,
-        max,
-    };
+    let stat = PingStat::from_samples(
+        setting.hostname.clone(),
+        setting.ip_addr,
+        Some(port),
+        PingProtocol::Quic,
+        samples,
+    );
 
     // Send done event
-    let _ = app.emit(
-        "ping:done",
-        PingDonePayload {
-            run_id: run_id.to_string(),
-            stat: stat.clone(),
-        },
-    );
+    emit_finished(sink, token, run_id, &stat);
 
     Ok(stat)
 }
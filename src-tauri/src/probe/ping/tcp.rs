@@ -1,38 +1,20 @@
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::ping::{emit_finished, wait_next};
 use anyhow::Result;
 use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
 use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
 
-use crate::model::ping::{
-    PingDonePayload, PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat,
-};
-use crate::model::probe::{ProbeStatus, ProbeStatusKind};
+use crate::model::ping::{PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat};
+use crate::model::probe::{IcmpError, IcmpErrorKind, ProbeStatus};
 use crate::socket::tcp::{AsyncTcpSocket, TcpConfig, TcpSocketType};
-
-fn summarize_rtts(rtts_ms: &[u64]) -> (Option<u64>, Option<u64>, Option<u64>) {
-    if rtts_ms.is_empty() {
-        return (None, None, None);
-    }
-    let mut min = u64::MAX;
-    let mut max = 0u64;
-    let mut sum: u128 = 0;
-    for &v in rtts_ms {
-        if v < min {
-            min = v;
-        }
-        if v > max {
-            max = v;
-        }
-        sum += v as u128;
-    }
-    let avg = (sum / rtts_ms.len() as u128) as u64;
-    (Some(min), Some(avg), Some(max))
-}
+use crate::stats::duration_ms;
 
 pub async fn tcp_ping(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
     run_id: &str,
+    token: &CancellationToken,
     _src_ip: IpAddr,
     setting: PingSetting,
 ) -> Result<PingStat> {
@@ -40,7 +22,6 @@ pub async fn tcp_ping(
     let target = SocketAddr::new(setting.ip_addr, port);
 
     let mut samples = Vec::with_capacity(setting.count as usize);
-    let mut rtts_ok = Vec::<u64>::new();
 
     let mut received = 0u32;
 
@@ -62,42 +43,44 @@ pub async fn tcp_ping(
         } else {
             None
         };
+        if let Some(src) = setting.src_ip {
+            cfg = cfg.with_bind(SocketAddr::new(src, 0));
+        }
+        if let Some(iface) = &setting.interface {
+            cfg = cfg.with_bind_device(iface);
+        }
 
         let sock = match AsyncTcpSocket::from_config(&cfg) {
             Ok(s) => s,
             Err(e) => {
                 let sample = PingSample {
-                    seq,
-                    ip_addr: setting.ip_addr,
-                    hostname: setting.hostname.clone(),
-                    port: Some(port),
-                    rtt_ms: None,
                     probe_status: ProbeStatus::with_error_message(format!("socket error: {e}")),
-                    protocol: PingProtocol::Tcp,
+                    ..PingSample::new(
+                        seq,
+                        setting.ip_addr,
+                        setting.hostname.clone(),
+                        Some(port),
+                        PingProtocol::Tcp,
+                    )
                 };
 
-                if sample.rtt_ms.is_some()
-                    && matches!(sample.probe_status.kind, ProbeStatusKind::Done)
-                {
+                if sample.is_received() {
                     received += 1;
                 }
 
                 let transmitted = seq;
                 let percent = (seq as f32) * 100.0 / (setting.count as f32);
-                let _ = app.emit(
-                    "ping:progress",
-                    PingProgressPayload {
-                        run_id: run_id.to_string(),
-                        sample: sample.clone(),
-                        transmitted,
-                        received,
-                        percent,
-                    },
-                );
+                sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+                    run_id: run_id.to_string(),
+                    sample: sample.clone(),
+                    transmitted,
+                    received,
+                    percent,
+                }));
                 samples.push(sample);
 
-                if seq != setting.count {
-                    tokio::time::sleep(Duration::from_millis(setting.send_rate_ms)).await;
+                if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+                    break;
                 }
                 continue;
             }
@@ -106,6 +89,7 @@ pub async fn tcp_ping(
         let started = Instant::now();
         let mut status = ProbeStatus::new();
         let mut rtt_ms = None;
+        let mut icmp_error = None;
 
         // Connect with timeout
         match sock
@@ -114,8 +98,7 @@ pub async fn tcp_ping(
         {
             Ok(mut stream) => {
                 // Handshake done. Connected.
-                rtt_ms = Some(started.elapsed().as_millis() as u64);
-                rtts_ok.push(rtt_ms.unwrap());
+                rtt_ms = Some(duration_ms(started.elapsed()));
                 // Close the connection
                 let _ = stream.shutdown().await;
             }
@@ -126,69 +109,151 @@ pub async fn tcp_ping(
                 ));
             }
             Err(e) => {
+                icmp_error = IcmpErrorKind::from_tcp_error(&e).map(IcmpError::from_kind);
                 status = ProbeStatus::with_error_message(format!("connect error: {e}"));
             }
         }
 
         let sample = PingSample {
-            seq,
-            ip_addr: setting.ip_addr,
-            hostname: setting.hostname.clone(),
-            port: Some(port),
             rtt_ms,
             probe_status: status,
-            protocol: PingProtocol::Tcp,
+            icmp_error,
+            ..PingSample::new(
+                seq,
+                setting.ip_addr,
+                setting.hostname.clone(),
+                Some(port),
+                PingProtocol::Tcp,
+            )
         };
-        if sample.rtt_ms.is_some() && matches!(sample.probe_status.kind, ProbeStatusKind::Done) {
+        if sample.is_received() {
             received += 1;
         }
 
         let transmitted = seq;
         let percent = (seq as f32) * 100.0 / (setting.count as f32);
-        let _ = app.emit(
-            "ping:progress",
-            PingProgressPayload {
-                run_id: run_id.to_string(),
-                sample: sample.clone(),
-                transmitted,
-                received,
-                percent,
-            },
-        );
+        sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+            run_id: run_id.to_string(),
+            sample: sample.clone(),
+            transmitted,
+            received,
+            percent,
+        }));
 
         samples.push(sample);
 
-        if seq != setting.count {
-            tokio::time::sleep(Duration::from_millis(setting.send_rate_ms)).await;
+        if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+            break;
         }
     }
 
     // Summarize samples
-    let (min, avg, max) = summarize_rtts(&rtts_ok);
-    let stat = PingStat {
-        ip_addr: setting.ip_addr,
-        hostname: setting.hostname.clone(),
-        port: Some(port),
-        protocol: PingProtocol::Tcp,
-        samples: samples.clone(),
-        transmitted_count: samples.len(),
-        received_count: samples
-            .iter()
-            .filter(|s| s.rtt_ms.is_some() && s.probe_status.kind == ProbeStatusKind::Done)
-            .count(),
-        min,
-        avg,
-        max,
-    };
+    let stat = PingStat::from_samples(
+        setting.hostname.clone(),
+        setting.ip_addr,
+        Some(port),
+        PingProtocol::Tcp,
+        samples,
+    );
 
     // Send done event
-    let _ = app.emit(
-        "ping:done",
-        PingDonePayload {
-            run_id: run_id.to_string(),
-            stat: stat.clone(),
-        },
-    );
+    emit_finished(sink, token, run_id, &stat);
 
     Ok(stat)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::event::MemoryEventSink;
+    use std::net::Ipv4Addr;
+
+    fn setting(port: u16, count: u32) -> PingSetting {
+        PingSetting {
+            ip_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            hostname: None,
+            port: Some(port),
+            hop_limit: 64,
+            protocol: PingProtocol::Tcp,
+            count,
+            timeout_ms: 1000,
+            send_rate_ms: 0,
+            interface: None,
+            src_ip: None,
+            http: None,
+            dns: None,
+            quic: None,
+            udp: None,
+            icmp: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn emits_progress_per_sample_then_done() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = MemoryEventSink::new();
+        let token = CancellationToken::new();
+        let src = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        let stat = tcp_ping(&sink, "run", &token, src, setting(port, 3))
+            .await
+            .unwrap();
+        assert_eq!(stat.transmitted_count, 3);
+        assert_eq!(stat.received_count, 3);
+
+        let events = sink.take();
+        assert_eq!(events.len(), 4);
+        for (i, event) in events[..3].iter().enumerate() {
+            match event {
+                ProbeEvent::PingProgress(p) => {
+                    assert_eq!(p.run_id, "run");
+                    assert_eq!(p.sample.seq, i as u32 + 1);
+                    assert_eq!(p.transmitted, i as u32 + 1);
+                    assert!(p.sample.is_received());
+                }
+                other => panic!("unexpected event {}", other.name()),
+            }
+        }
+        assert!(matches!(&events[3], ProbeEvent::PingDone(p) if p.stat.received_count == 3));
+    }
+
+    #[tokio::test]
+    async fn refused_connection_is_a_failed_sample() {
+        // Bind and drop a listener to get a port nothing listens on
+        let port = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let sink = MemoryEventSink::new();
+        let token = CancellationToken::new();
+        let src = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        let stat = tcp_ping(&sink, "run", &token, src, setting(port, 1))
+            .await
+            .unwrap();
+        assert_eq!(stat.transmitted_count, 1);
+        assert_eq!(stat.received_count, 0);
+        assert!(matches!(sink.take().last(), Some(ProbeEvent::PingDone(_))));
+    }
+
+    #[tokio::test]
+    async fn cancelled_run_emits_cancelled() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = MemoryEventSink::new();
+        let token = CancellationToken::new();
+        token.cancel();
+        let src = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        let mut setting = setting(port, 5);
+        setting.send_rate_ms = 1000;
+        let stat = tcp_ping(&sink, "run", &token, src, setting).await.unwrap();
+        // The first probe is sent, the wait for the next one is cancelled
+        assert_eq!(stat.transmitted_count, 1);
+        assert!(matches!(
+            sink.take().last(),
+            Some(ProbeEvent::PingCancelled(_))
+        ));
+    }
+}
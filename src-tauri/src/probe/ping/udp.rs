@@ -1,5 +1,7 @@
 #![allow(unused)]
 
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::ping::{emit_finished, wait_next};
 use anyhow::Result;
 use bytes::Bytes;
 use nex_packet::icmp::IcmpType;
@@ -9,55 +11,192 @@ use nex_packet::packet::Packet;
 use nex_packet::{icmp::IcmpPacket, ip::IpNextProtocol, ipv4::Ipv4Packet};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use tokio_util::sync::CancellationToken;
 
 use crate::model::ping::{
-    PingDonePayload, PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat,
+    PingProgressPayload, PingProtocol, PingSample, PingSetting, PingStat, UdpPingMode,
 };
-use crate::model::probe::{ProbeStatus, ProbeStatusKind};
+use crate::model::probe::{IcmpError, IcmpErrorKind, ProbeStatus};
+use crate::probe::packet::{parse_icmp_error, IcmpErrorPacket};
+use crate::probe::ping::udp_payload::UdpRequest;
 use crate::socket::icmp::{AsyncIcmpSocket, IcmpConfig, IcmpKind};
 use crate::socket::udp::{AsyncUdpSocket, UdpConfig};
-use crate::socket::SocketFamily;
+use crate::socket::{SocketFamily, SocketIcmpError};
+use crate::stats::duration_ms;
 
 /// Default base target UDP port for traceroute or ping
 const DEFAULT_BASE_TARGET_UDP_PORT: u16 = 33435;
 
-/// Check if the ICMP message is Port Unreachable
-fn is_port_unreach_v4(icmp_bytes: &[u8]) -> bool {
-    if let Some(ip) = Ipv4Packet::from_buf(icmp_bytes) {
-        if ip.header.next_level_protocol == IpNextProtocol::Icmp {
-            if let Some(icmp) = IcmpPacket::from_bytes(ip.payload()) {
-                match icmp.header.icmp_type {
-                    IcmpType::DestinationUnreachable => {
-                        return true;
+/// Error for an ICMP error read from the error queue of a UDP socket
+fn queued_icmp_error(e: &SocketIcmpError) -> Option<IcmpError> {
+    IcmpError::from_message(e.is_v6, e.icmp_type, e.icmp_code, e.offender, e.info)
+}
+
+/// UDP ping with an application request (DNS, NTP, SNMP, echo or a raw payload),
+/// timing the reply of the service. Works unprivileged on every platform.
+///
+/// When the service does not answer, ICMP errors reported on the connected socket
+/// (port, host or network unreachable, and on Linux any ICMP error with the reporting
+/// router) are recorded with their arrival time as an error status; otherwise the probe
+/// times out.
+pub async fn udp_ping_payload(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    setting: PingSetting,
+) -> Result<PingStat> {
+    let option = setting.udp.clone().unwrap_or_default();
+    let dst_ip = setting.ip_addr;
+    let dst_port = setting
+        .port
+        .or(option.mode.default_port())
+        .ok_or_else(|| anyhow::anyhow!("a port is required for {} mode", option.mode))?;
+    let target = SocketAddr::new(dst_ip, dst_port);
+    // Fail early on a bad payload or community
+    UdpRequest::new(&option)?;
+
+    let mut ucfg = UdpConfig::new();
+    ucfg.socket_family = SocketFamily::from_ip(&dst_ip);
+    let unspecified = match dst_ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    if dst_ip.is_ipv4() {
+        ucfg = ucfg.with_ttl(setting.hop_limit as u32);
+    } else {
+        ucfg = ucfg.with_hoplimit(setting.hop_limit as u32);
+    }
+    ucfg = ucfg.with_bind_addr(SocketAddr::new(setting.src_ip.unwrap_or(unspecified), 0));
+    if let Some(iface) = &setting.interface {
+        ucfg = ucfg.with_bind_device(iface);
+    }
+    ucfg = ucfg.with_recv_icmp_errors(true);
+
+    let mut samples = Vec::with_capacity(setting.count as usize);
+    let mut received = 0u32;
+    let to = Duration::from_millis(setting.timeout_ms);
+
+    for seq in 1..=setting.count {
+        let mut status = ProbeStatus::new();
+        let mut rtt_ms = None;
+        let mut icmp_error = None;
+
+        // A fresh socket per probe, so late replies and ICMP errors for
+        // earlier probes are not attributed to this one
+        let setup = async {
+            let request = UdpRequest::new(&option)?;
+            let udp = AsyncUdpSocket::from_config(&ucfg)?;
+            udp.connect(target).await?;
+            anyhow::Ok((request, udp))
+        }
+        .await;
+        match setup {
+            Err(e) => status = ProbeStatus::with_error_message(format!("socket error: {e}")),
+            Ok((request, udp)) => {
+                let sent_at = Instant::now();
+
+                if let Err(e) = udp.send(&request.payload).await {
+                    status = ProbeStatus::with_error_message(format!("send error: {e}"));
+                } else {
+                    let mut buf = vec![0u8; 65535];
+                    let wait_reply = tokio::time::timeout(to, async {
+                        loop {
+                            match udp.recv(&mut buf).await {
+                                Ok(n) if request.matches(&buf[..n]) => break Ok(()),
+                                // Ignore datagrams that do not answer the request
+                                Ok(_) => continue,
+                                Err(e) => break Err(e),
+                            }
+                        }
+                    });
+                    let outcome = tokio::select! {
+                        _ = token.cancelled() => break,
+                        outcome = wait_reply => outcome,
+                    };
+
+                    match outcome {
+                        Ok(Ok(())) => rtt_ms = Some(duration_ms(sent_at.elapsed())),
+                        Ok(Err(e)) => {
+                            // The queued message names the router that sent it
+                            let queued = match udp.take_icmp_error(&mut buf) {
+                                Ok(Some(queued)) => queued_icmp_error(&queued),
+                                _ => None,
+                            };
+                            let error = queued.or_else(|| {
+                                IcmpErrorKind::from_udp_error(&e).map(IcmpError::from_kind)
+                            });
+                            match error {
+                                Some(error) => {
+                                    rtt_ms = Some(duration_ms(sent_at.elapsed()));
+                                    status = ProbeStatus::with_error_message(error.to_string());
+                                    icmp_error = Some(error);
+                                }
+                                None => {
+                                    status =
+                                        ProbeStatus::with_error_message(format!("recv error: {e}"));
+                                }
+                            }
+                        }
+                        Err(_) => {
+                            status = ProbeStatus::with_timeout_message(format!(
+                                "timeout (>{}ms)",
+                                setting.timeout_ms
+                            ));
+                        }
                     }
-                    _ => return false,
                 }
             }
         }
-    }
-    false
-}
 
-/// Check if the ICMPv6 message is Port Unreachable
-/// The IPv6 header is automatically cropped off when recvfrom() is used.
-fn is_port_unreach_v6(icmp_bytes: &[u8]) -> bool {
-    if let Some(icmp6) = Icmpv6Packet::from_buf(icmp_bytes) {
-        match icmp6.header.icmpv6_type {
-            Icmpv6Type::DestinationUnreachable => {
-                return true;
-            }
-            _ => return false,
+        let sample = PingSample {
+            rtt_ms,
+            probe_status: status,
+            icmp_error,
+            ..PingSample::new(
+                seq,
+                dst_ip,
+                setting.hostname.clone(),
+                Some(dst_port),
+                PingProtocol::Udp,
+            )
+        };
+
+        if sample.is_received() {
+            received += 1;
+        }
+
+        let percent = (seq as f32) * 100.0 / (setting.count as f32);
+        sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+            run_id: run_id.to_string(),
+            sample: sample.clone(),
+            transmitted: seq,
+            received,
+            percent,
+        }));
+        samples.push(sample);
+
+        if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+            break;
         }
     }
-    false
+
+    let stat = PingStat::from_samples(
+        setting.hostname.clone(),
+        dst_ip,
+        Some(dst_port),
+        PingProtocol::Udp,
+        samples,
+    );
+    emit_finished(sink, token, run_id, &stat);
+    Ok(stat)
 }
 
 #[cfg(unix)]
 /// UDP Ping using ICMP Port Unreachable messages
 pub async fn udp_ping_icmp_unreach(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
     run_id: &str,
+    token: &CancellationToken,
     _src_ip: IpAddr,
     setting: PingSetting,
 ) -> Result<PingStat> {
@@ -75,6 +214,15 @@ pub async fn udp_ping_icmp_unreach(
         ucfg.hoplimit = Some(setting.hop_limit as u32);
         ucfg.bind_addr = Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0));
     }
+    if let Some(src) = setting.src_ip {
+        ucfg.bind_addr = Some(SocketAddr::new(src, 0));
+    }
+    if let Some(iface) = &setting.interface {
+        ucfg = ucfg.with_bind_device(iface);
+    }
+    // Linux: ICMP errors for the datagrams are also queued on the UDP socket,
+    // which works without privileges
+    ucfg = ucfg.with_recv_icmp_errors(true);
     let udp = AsyncUdpSocket::from_config(&ucfg)?;
 
     let local_addr = udp.local_addr()?;
@@ -86,19 +234,34 @@ pub async fn udp_ping_icmp_unreach(
     } else {
         IcmpKind::V6
     };
-    let icmp = AsyncIcmpSocket::new(&IcmpConfig::new(icmp_kind)).await?;
+    let mut icmp_cfg = IcmpConfig::new(icmp_kind);
+    if let Some(iface) = &setting.interface {
+        icmp_cfg = icmp_cfg.with_interface(iface);
+    }
+    let icmp = AsyncIcmpSocket::new(&icmp_cfg).await?;
+    let is_v6 = dst_ip.is_ipv6();
+    // Whether an ICMP error quotes one of our datagrams
+    let quotes_probe = |error: &IcmpErrorPacket| {
+        error.protocol == 17
+            && error.dst == dst_ip
+            && error.quoted_ports() == Some((local_addr.port(), dst_port))
+    };
 
     let mut samples = Vec::with_capacity(setting.count as usize);
-    let mut rtts = Vec::<u64>::new();
 
     let mut received = 0u32;
 
     for seq in 1..=setting.count {
         let payload = Bytes::from_static(b"np:udp-probe");
-        let sent_at = Instant::now();
 
         let mut status = ProbeStatus::new();
         let mut rtt_ms = None;
+        let mut icmp_error = None;
+
+        // Drop errors for earlier probes that arrived after their timeout
+        let mut qbuf = vec![0u8; 2048];
+        while let Ok(Some(_)) = udp.take_icmp_error(&mut qbuf) {}
+        let sent_at = Instant::now();
 
         // Send UDP packet
         if let Err(e) = udp.send_to(&payload, target).await {
@@ -108,123 +271,116 @@ pub async fn udp_ping_icmp_unreach(
             let to = Duration::from_millis(setting.timeout_ms);
             let mut buf = vec![0u8; 2048];
 
-            let matched = tokio::time::timeout(to, async {
+            let outcome = tokio::time::timeout(to, async {
                 loop {
-                    let (n, _from) = icmp.recv_from(&mut buf).await?;
-                    let ok = match (dst_ip, local_addr.ip()) {
-                        (IpAddr::V4(_d), IpAddr::V4(_s)) => is_port_unreach_v4(&buf[..n]),
-                        (IpAddr::V6(_d), IpAddr::V6(_s)) => is_port_unreach_v6(&buf[..n]),
-                        _ => false,
+                    let error = tokio::select! {
+                        r = icmp.recv_from(&mut buf) => {
+                            let (n, from) = r?;
+                            match parse_icmp_error(&buf[..n], is_v6) {
+                                Some(error) if quotes_probe(&error) => IcmpError::from_message(
+                                    is_v6,
+                                    error.icmp_type,
+                                    error.icmp_code,
+                                    Some(from.ip()),
+                                    error.mtu,
+                                ),
+                                _ => None,
+                            }
+                        }
+                        r = udp.recv_icmp_error(&mut qbuf) => {
+                            let queued = r?;
+                            if queued.dst == Some(target) {
+                                queued_icmp_error(&queued)
+                            } else {
+                                None
+                            }
+                        }
                     };
-                    if ok {
-                        break Ok::<_, std::io::Error>(());
+                    if let Some(error) = error {
+                        break Ok::<_, std::io::Error>(error);
                     }
                 }
             })
-            .await
-            .is_ok();
-
-            if matched {
-                let rtt = sent_at.elapsed().as_millis() as u64;
-                rtt_ms = Some(rtt);
-                rtts.push(rtt);
-            } else {
-                status = ProbeStatus::with_timeout_message(format!(
-                    "timeout (>{}ms)",
-                    setting.timeout_ms
-                ));
+            .await;
+
+            match outcome {
+                Ok(Ok(error)) => {
+                    rtt_ms = Some(duration_ms(sent_at.elapsed()));
+                    // Port Unreachable from the target is the reply; anything else
+                    // (e.g. from a router on the path) means the probe failed
+                    let is_reply = error.kind == IcmpErrorKind::PortUnreachable
+                        && error.from.is_none_or(|from| from == dst_ip);
+                    if !is_reply {
+                        status = ProbeStatus::with_error_message(error.to_string());
+                        icmp_error = Some(error);
+                    }
+                }
+                Ok(Err(e)) => {
+                    status = ProbeStatus::with_error_message(format!("recv error: {e}"));
+                }
+                Err(_) => {
+                    status = ProbeStatus::with_timeout_message(format!(
+                        "timeout (>{}ms)",
+                        setting.timeout_ms
+                    ));
+                }
             }
         }
 
         let sample = PingSample {
-            seq,
-            ip_addr: dst_ip,
-            hostname: setting.hostname.clone(),
-            port: Some(dst_port),
             rtt_ms,
             probe_status: status,
-            protocol: PingProtocol::Udp,
+            icmp_error,
+            ..PingSample::new(
+                seq,
+                dst_ip,
+                setting.hostname.clone(),
+                Some(dst_port),
+                PingProtocol::Udp,
+            )
         };
 
-        if sample.rtt_ms.is_some() && matches!(sample.probe_status.kind, ProbeStatusKind::Done) {
+        if sample.is_received() {
             received += 1;
         }
 
         let transmitted = seq;
         let percent = (seq as f32) * 100.0 / (setting.count as f32);
 
-        let _ = app.emit(
-            "ping:progress",
-            PingProgressPayload {
-                run_id: run_id.to_string(),
-                sample: sample.clone(),
-                transmitted,
-                received,
-                percent,
-            },
-        );
+        sink.emit(ProbeEvent::PingProgress(PingProgressPayload {
+            run_id: run_id.to_string(),
+            sample: sample.clone(),
+            transmitted,
+            received,
+            percent,
+        }));
         samples.push(sample);
 
-        if seq != setting.count {
-            tokio::time::sleep(Duration::from_millis(setting.send_rate_ms)).await;
+        if seq != setting.count && !wait_next(token, setting.send_rate_ms).await {
+            break;
         }
     }
 
     // Summarize samples
-    let (min, avg, max) = if rtts.is_empty() {
-        (None, None, None)
-    } else {
-        let mut min = u64::MAX;
-        let mut max = 0;
-        let mut sum: u128 = 0;
-        for &v in &rtts {
-            if v < min {
-                min = v
-            };
-            if v > max {
-                max = v
-            };
-            sum += v as u128;
-        }
-        (
-            Some(min),
-            Some((sum / (rtts.len() as u128)) as u64),
-            Some(max),
-        )
-    };
-
-    let stat = PingStat {
-        ip_addr: dst_ip,
-        hostname: setting.hostname.clone(),
-        port: Some(dst_port),
-        protocol: PingProtocol::Udp,
-        samples: samples.clone(),
-        transmitted_count: samples.len(),
-        received_count: samples
-            .iter()
-            .filter(|s| s.rtt_ms.is_some() && s.probe_status.kind == ProbeStatusKind::Done)
-            .count(),
-        min,
-        avg,
-        max,
-    };
+    let stat = PingStat::from_samples(
+        setting.hostname.clone(),
+        dst_ip,
+        Some(dst_port),
+        PingProtocol::Udp,
+        samples,
+    );
 
     // Send done event
-    let _ = app.emit(
-        "ping:done",
-        PingDonePayload {
-            run_id: run_id.to_string(),
-            stat: stat.clone(),
-        },
-    );
+    emit_finished(sink, token, run_id, &stat);
 
     Ok(stat)
 }
 
 #[cfg(windows)]
 pub async fn udp_ping_icmp_unreach(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
     run_id: &str,
+    token: &CancellationToken,
     _src_ip: IpAddr,
     setting: PingSetting,
 ) -> Result<PingStat> {
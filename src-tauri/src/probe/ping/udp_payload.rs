@@ -0,0 +1,351 @@
+use anyhow::{bail, Result};
+use hickory_resolver::proto::op::{Message, MessageType, OpCode, Query};
+use hickory_resolver::proto::rr::{Name, RecordType};
+use rand::Rng;
+use x509_parser::asn1_rs::{Any, Class, FromBer, Tag};
+
+use crate::model::ping::{UdpPingMode, UdpPingOption};
+use crate::net::ntp;
+
+/// OID 1.3.6.1.2.1.1.3.0 (sysUpTime.0), BER encoded
+const SYS_UPTIME_OID: [u8; 8] = [0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x03, 0x00];
+/// Longest community that keeps every SNMP length in the short BER form
+const MAX_SNMP_COMMUNITY: usize = 90;
+
+/// Application request sent by UDP ping, and how its reply is recognized
+pub struct UdpRequest {
+    pub payload: Vec<u8>,
+    expect: Expect,
+}
+
+enum Expect {
+    /// DNS response with this ID
+    Dns(u16),
+    /// NTP server reply whose origin timestamp is our transmit timestamp
+    Ntp([u8; 8]),
+    /// SNMP response with this request ID
+    Snmp(i64),
+    /// The payload itself
+    Echo,
+    Any,
+}
+
+impl UdpRequest {
+    /// Build a fresh request for `option.mode`. IDs and timestamps are random per request,
+    /// so replies to earlier requests are not mistaken for this one.
+    pub fn new(option: &UdpPingOption) -> Result<Self> {
+        let mut rng = rand::thread_rng();
+        let request = match option.mode {
+            UdpPingMode::PortUnreachable | UdpPingMode::Echo => {
+                let mut payload = b"np:udp-probe:".to_vec();
+                payload.extend_from_slice(&rng.gen::<[u8; 8]>());
+                Self {
+                    payload,
+                    expect: Expect::Echo,
+                }
+            }
+            UdpPingMode::Dns => {
+                let id = rng.gen();
+                let mut msg = Message::new();
+                msg.set_id(id)
+                    .set_message_type(MessageType::Query)
+                    .set_op_code(OpCode::Query)
+                    .set_recursion_desired(true)
+                    .add_query(Query::query(Name::root(), RecordType::NS));
+                Self {
+                    payload: msg.to_vec()?,
+                    expect: Expect::Dns(id),
+                }
+            }
+            UdpPingMode::Ntp => {
+                let transmit = rng.gen::<[u8; 8]>();
+                Self {
+                    payload: ntp::client_request(transmit).to_vec(),
+                    expect: Expect::Ntp(transmit),
+                }
+            }
+            UdpPingMode::Snmp => {
+                // Keep the ID four bytes long in its minimal encoding
+                let id = rng.gen_range(0x0100_0000..=0x7fff_ffff);
+                Self {
+                    payload: snmp_get_request(&option.snmp_community, id)?,
+                    expect: Expect::Snmp(id as i64),
+                }
+            }
+            UdpPingMode::Hex => {
+                let Some(hex) = &option.payload_hex else {
+                    bail!("hex mode requires a payload");
+                };
+                Self {
+                    payload: parse_hex(hex)?,
+                    expect: Expect::Any,
+                }
+            }
+        };
+        Ok(request)
+    }
+
+    /// Whether `reply` answers this request
+    pub fn matches(&self, reply: &[u8]) -> bool {
+        match &self.expect {
+            Expect::Dns(id) => {
+                // QR bit set: a response
+                reply.len() >= 12
+                    && u16::from_be_bytes([reply[0], reply[1]]) == *id
+                    && reply[2] & 0x80 != 0
+            }
+            Expect::Ntp(transmit) => ntp::is_reply_to(reply, transmit),
+            Expect::Snmp(id) => snmp_response_id(reply) == Some(*id),
+            Expect::Echo => reply == self.payload.as_slice(),
+            Expect::Any => true,
+        }
+    }
+}
+
+/// Parse hex digits, ignoring whitespace and `:` separators.
+pub fn parse_hex(s: &str) -> Result<Vec<u8>> {
+    let digits: String = s
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+    if digits.is_empty() {
+        bail!("empty hex payload");
+    }
+    data_encoding::HEXLOWER_PERMISSIVE
+        .decode(digits.as_bytes())
+        .map_err(|e| anyhow::anyhow!("invalid hex payload: {}", e))
+}
+
+/// SNMPv2c GetRequest for sysUpTime.0
+fn snmp_get_request(community: &str, request_id: u32) -> Result<Vec<u8>> {
+    if community.len() > MAX_SNMP_COMMUNITY {
+        bail!("SNMP community longer than {} bytes", MAX_SNMP_COMMUNITY);
+    }
+    let mut varbind = vec![0x06, SYS_UPTIME_OID.len() as u8];
+    varbind.extend_from_slice(&SYS_UPTIME_OID);
+    varbind.extend_from_slice(&[0x05, 0x00]);
+    let varbind = tlv(0x30, &varbind);
+    let varbinds = tlv(0x30, &varbind);
+
+    let mut pdu = vec![0x02, 0x04];
+    pdu.extend_from_slice(&request_id.to_be_bytes());
+    // error-status, error-index
+    pdu.extend_from_slice(&[0x02, 0x01, 0x00, 0x02, 0x01, 0x00]);
+    pdu.extend_from_slice(&varbinds);
+    let pdu = tlv(0xa0, &pdu);
+
+    // version 1 = SNMPv2c
+    let mut msg = vec![0x02, 0x01, 0x01];
+    msg.extend_from_slice(&tlv(0x04, community.as_bytes()));
+    msg.extend_from_slice(&pdu);
+    Ok(tlv(0x30, &msg))
+}
+
+/// Short form BER TLV
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag, value.len() as u8];
+    out.extend_from_slice(value);
+    out
+}
+
+/// Read one BER object from the front of `buf`, returning it and the rest of the input.
+fn read_ber(buf: &[u8]) -> Option<(Any<'_>, &[u8])> {
+    Any::from_ber(buf).ok().map(|(rest, any)| (any, rest))
+}
+
+/// Request ID of an SNMP Response PDU
+fn snmp_response_id(buf: &[u8]) -> Option<i64> {
+    let (msg, _) = read_ber(buf)?;
+    if msg.tag() != Tag::Sequence {
+        return None;
+    }
+    let (version, rest) = read_ber(msg.data)?;
+    let (community, rest) = read_ber(rest)?;
+    if version.tag() != Tag::Integer || community.tag() != Tag::OctetString {
+        return None;
+    }
+    // Response-PDU is [2] IMPLICIT
+    let (pdu, _) = read_ber(rest)?;
+    if pdu.class() != Class::ContextSpecific || pdu.tag() != Tag(2) {
+        return None;
+    }
+    let (id, _) = read_ber(pdu.data)?;
+    id.as_i64().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(mode: UdpPingMode) -> UdpRequest {
+        UdpRequest::new(&UdpPingOption {
+            mode,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    /// BER TLV, using the long length form from 128 bytes
+    fn ber(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        match content.len() {
+            n if n < 0x80 => out.push(n as u8),
+            n if n <= 0xff => out.extend_from_slice(&[0x81, n as u8]),
+            n => out.extend_from_slice(&[0x82, (n >> 8) as u8, n as u8]),
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    /// SNMPv2c Response carrying `id` as its encoded request ID
+    fn snmp_response(community: &[u8], id: &[u8]) -> Vec<u8> {
+        let mut pdu = ber(0x02, id);
+        pdu.extend_from_slice(&[0x02, 0x01, 0x00, 0x02, 0x01, 0x00]);
+        pdu.extend_from_slice(&ber(0x30, &[]));
+        let mut msg = vec![0x02, 0x01, 0x01];
+        msg.extend_from_slice(&ber(0x04, community));
+        msg.extend_from_slice(&ber(0xa2, &pdu));
+        ber(0x30, &msg)
+    }
+
+    #[test]
+    fn snmp_round_trip() {
+        let req = request(UdpPingMode::Snmp);
+        let Expect::Snmp(id) = req.expect else {
+            panic!("not an SNMP request");
+        };
+        // The GetRequest carries the same ID, but is not a response
+        assert_eq!(snmp_response_id(&req.payload), None);
+        let mut reply = req.payload.clone();
+        let pdu = 2 + 3 + 2 + "public".len();
+        assert_eq!(reply[pdu], 0xa0);
+        reply[pdu] = 0xa2;
+        assert!(req.matches(&reply));
+
+        let other = snmp_response(b"public", &(id as u32 + 1).to_be_bytes());
+        assert!(!req.matches(&other));
+        assert!(!req.matches(&reply[..reply.len() - 1]));
+    }
+
+    #[test]
+    fn snmp_request_encoding() {
+        let req = snmp_get_request("public", 0x0102_0304).unwrap();
+        let mut reply = req.clone();
+        reply[2 + 3 + 2 + 6] = 0xa2;
+        assert_eq!(snmp_response_id(&reply), Some(0x0102_0304));
+        assert_eq!(req[0], 0x30);
+        assert_eq!(req[1] as usize, req.len() - 2);
+        assert!(req.ends_with(&[0x06, 0x08, 0x2b, 6, 1, 2, 1, 1, 3, 0, 0x05, 0x00]));
+
+        let longest = "c".repeat(MAX_SNMP_COMMUNITY);
+        let req = snmp_get_request(&longest, 0x7fff_ffff).unwrap();
+        assert!(req[1] < 0x80);
+        assert!(snmp_get_request(&format!("{}c", longest), 1).is_err());
+    }
+
+    #[test]
+    fn snmp_response_ids() {
+        let cases: [(&[u8], Option<i64>); 6] = [
+            (&[0x00], Some(0)),
+            (&[0x7f], Some(127)),
+            (&[0x00, 0x80], Some(128)),
+            (&[0xff], Some(-1)),
+            (&[0xff, 0xff, 0xff, 0xfe], Some(-2)),
+            (&[0x80, 0x00, 0x00, 0x00], Some(i32::MIN as i64)),
+        ];
+        for (id, expected) in cases {
+            assert_eq!(
+                snmp_response_id(&snmp_response(b"public", id)),
+                expected,
+                "{:02x?}",
+                id
+            );
+        }
+        // Long length forms from an agent with a long community
+        let community = [b'c'; 200];
+        let reply = snmp_response(&community, &[0x12, 0x34]);
+        assert_eq!(reply[1], 0x81);
+        assert_eq!(snmp_response_id(&reply), Some(0x1234));
+        let community = [b'c'; 300];
+        assert_eq!(
+            snmp_response_id(&snmp_response(&community, &[0x56])),
+            Some(0x56)
+        );
+
+        assert_eq!(snmp_response_id(&[]), None);
+        assert_eq!(snmp_response_id(&ber(0x04, b"public")), None);
+        let mut trap = snmp_response(b"public", &[0x01]);
+        trap[2 + 3 + 2 + 6] = 0xa7;
+        assert_eq!(snmp_response_id(&trap), None);
+    }
+
+    #[test]
+    fn dns_round_trip() {
+        let req = request(UdpPingMode::Dns);
+        let query = Message::from_vec(&req.payload).unwrap();
+        assert_eq!(query.message_type(), MessageType::Query);
+        assert_eq!(query.queries().len(), 1);
+        // A resolver reflecting the query back is not an answer
+        assert!(!req.matches(&req.payload));
+
+        let mut response = query.clone();
+        response.set_message_type(MessageType::Response);
+        let reply = response.to_vec().unwrap();
+        assert!(req.matches(&reply));
+        assert!(!req.matches(&reply[..11]));
+
+        response.set_id(query.id().wrapping_add(1));
+        assert!(!req.matches(&response.to_vec().unwrap()));
+    }
+
+    #[test]
+    fn echo_round_trip() {
+        for mode in [UdpPingMode::Echo, UdpPingMode::PortUnreachable] {
+            let req = request(mode);
+            assert!(req.payload.starts_with(b"np:udp-probe:"));
+            assert!(req.matches(&req.payload));
+            let mut other = req.payload.clone();
+            *other.last_mut().unwrap() ^= 0xff;
+            assert!(!req.matches(&other));
+            assert!(!req.matches(&req.payload[..req.payload.len() - 1]));
+        }
+    }
+
+    #[test]
+    fn ntp_round_trip() {
+        let req = request(UdpPingMode::Ntp);
+        // The request is a client packet; a mirror of it is not a server reply
+        assert!(!req.matches(&req.payload));
+
+        let mut reply = [0u8; ntp::NTP_PACKET_SIZE];
+        reply[0] = 0x24;
+        reply[1] = 2;
+        reply[24..32].copy_from_slice(&req.payload[40..48]);
+        assert!(req.matches(&reply));
+        assert!(!req.matches(&reply[..ntp::NTP_PACKET_SIZE - 1]));
+        reply[24] ^= 0xff;
+        assert!(!req.matches(&reply));
+    }
+
+    #[test]
+    fn hex_payload() {
+        let req = UdpRequest::new(&UdpPingOption {
+            mode: UdpPingMode::Hex,
+            payload_hex: Some("de:ad be\nEF".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(req.payload, [0xde, 0xad, 0xbe, 0xef]);
+        assert!(req.matches(b""));
+        assert!(req.matches(b"anything"));
+
+        assert!(UdpRequest::new(&UdpPingOption {
+            mode: UdpPingMode::Hex,
+            ..Default::default()
+        })
+        .is_err());
+        assert!(parse_hex(" : ").is_err());
+        assert!(parse_hex("abc").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::Result;
+use rand::Rng;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::model::pmtu::{PmtuProbe, PmtuProbeResult, PmtuReport, PmtuSetting, PmtuStartPayload};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::packet::{build_icmp_echo_bytes, parse_echo_reply_ids, parse_packet_too_big};
+use crate::socket::icmp::{AsyncIcmpSocket, IcmpConfig, IcmpKind};
+use crate::stats::duration_ms;
+
+/// IPv4 header (20) + ICMP echo header (8)
+const V4_OVERHEAD: u32 = 28;
+/// IPv6 header (40) + ICMPv6 echo header (8)
+const V6_OVERHEAD: u32 = 48;
+/// Smallest MTU every IPv4 link must support (RFC 791)
+const MIN_MTU_V4: u32 = 68;
+/// Smallest MTU every IPv6 link must support (RFC 8200)
+const MIN_MTU_V6: u32 = 1280;
+/// Upper bound when neither the setting nor the interface gives one
+const DEFAULT_MAX_MTU: u32 = 1500;
+const MAX_IP_PACKET: u32 = 65535;
+
+const PAYLOAD_PATTERN: &[u8] = b"np:pmtu";
+
+/// Whether a send failed because the packet exceeds the MTU known to the local stack
+fn is_msg_too_long(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        e.raw_os_error() == Some(libc::EMSGSIZE)
+    }
+    #[cfg(windows)]
+    {
+        // WSAEMSGSIZE
+        e.raw_os_error() == Some(10040)
+    }
+}
+
+struct Prober<'a> {
+    socket: AsyncIcmpSocket,
+    sink: &'a dyn ProbeEventSink,
+    run_id: &'a str,
+    token: &'a CancellationToken,
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    echo_id: u16,
+    check_id: bool,
+    overhead: u32,
+    timeout: Duration,
+    tries: u8,
+    /// Echo sequence number, one per packet sent
+    seq: u16,
+    /// Number of packet sizes tried
+    probes: u32,
+    buf: Vec<u8>,
+}
+
+impl Prober<'_> {
+    /// Probe one packet size, retrying on timeout. Returns `None` if cancelled.
+    async fn probe(&mut self, size: u32) -> Option<PmtuProbe> {
+        self.probes += 1;
+        let payload: Vec<u8> = PAYLOAD_PATTERN
+            .iter()
+            .copied()
+            .cycle()
+            .take((size - self.overhead) as usize)
+            .collect();
+        let mut probe = PmtuProbe {
+            run_id: self.run_id.to_string(),
+            seq: self.probes,
+            size,
+            result: PmtuProbeResult::Timeout,
+            rtt_ms: None,
+            reported_mtu: None,
+            from: None,
+            message: None,
+        };
+        for _ in 0..self.tries {
+            self.seq = self.seq.wrapping_add(1);
+            let pkt =
+                build_icmp_echo_bytes(self.src_ip, self.dst_ip, self.echo_id, self.seq, &payload);
+            let sent_at = Instant::now();
+            if let Err(e) = self
+                .socket
+                .send_to(&pkt, SocketAddr::new(self.dst_ip, 0))
+                .await
+            {
+                if is_msg_too_long(&e) {
+                    probe.result = PmtuProbeResult::TooBig;
+                    probe.message = Some("larger than the MTU known to the local stack".into());
+                } else {
+                    probe.result = PmtuProbeResult::Error;
+                    probe.message = Some(format!("send error: {e}"));
+                }
+                break;
+            }
+            let deadline = tokio::time::Instant::now() + self.timeout;
+            let done = loop {
+                let recv = tokio::select! {
+                    _ = self.token.cancelled() => return None,
+                    _ = tokio::time::sleep_until(deadline) => break false,
+                    recv = self.socket.recv_from(&mut self.buf) => recv,
+                };
+                let Ok((n, from)) = recv else {
+                    continue;
+                };
+                let is_v6 = self.dst_ip.is_ipv6();
+                let buf = &self.buf[..n];
+                if let Some((id, seq)) = parse_echo_reply_ids(buf, is_v6) {
+                    if from.ip() == self.dst_ip
+                        && seq == self.seq
+                        && (!self.check_id || id == self.echo_id)
+                    {
+                        probe.result = PmtuProbeResult::Ok;
+                        probe.rtt_ms = Some(duration_ms(sent_at.elapsed()));
+                        break true;
+                    }
+                } else if let Some(ptb) = parse_packet_too_big(buf, is_v6) {
+                    if ptb.dst == self.dst_ip
+                        && ptb.seq == self.seq
+                        && (!self.check_id || ptb.id == self.echo_id)
+                    {
+                        probe.result = PmtuProbeResult::TooBig;
+                        probe.reported_mtu = (ptb.mtu > 0).then_some(ptb.mtu);
+                        probe.from = Some(from.ip());
+                        break true;
+                    }
+                }
+            };
+            if done {
+                break;
+            }
+        }
+        if probe.result == PmtuProbeResult::Timeout {
+            probe.message = Some(format!("timeout (>{}ms)", self.timeout.as_millis()));
+        }
+        self.sink.emit(ProbeEvent::PmtuProgress(probe.clone()));
+        Some(probe)
+    }
+}
+
+/// Discover the path MTU to `setting.ip_addr` with ICMP echo requests that must not be fragmented.
+///
+/// Packet sizes are binary searched between the minimum MTU of the IP version and
+/// `max_mtu` (the egress interface MTU by default). A size counts as too big when a
+/// router answers with Fragmentation Needed / Packet Too Big, when the local stack
+/// refuses it, or when no reply arrives after `tries` probes; a next-hop MTU reported
+/// by a router is tried next. Linux DGRAM ICMP sockets do not deliver these errors,
+/// so there the search relies on timeouts and the constraining hop stays unknown.
+pub async fn pmtu_discover(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    src_ip: IpAddr,
+    setting: PmtuSetting,
+) -> Result<PmtuReport> {
+    let dst_ip = setting.ip_addr;
+    let (kind, overhead, floor) = match dst_ip {
+        IpAddr::V4(_) => (IcmpKind::V4, V4_OVERHEAD, MIN_MTU_V4),
+        IpAddr::V6(_) => (IcmpKind::V6, V6_OVERHEAD, MIN_MTU_V6),
+    };
+
+    let egress = crate::net::interface::egress_interface(dst_ip, setting.interface.as_deref()).ok();
+    let interface_mtu = egress.as_ref().and_then(|iface| iface.mtu);
+    let ceiling = setting
+        .max_mtu
+        .or(interface_mtu)
+        .unwrap_or(DEFAULT_MAX_MTU)
+        .clamp(floor, MAX_IP_PACKET);
+
+    let mut cfg = IcmpConfig::new(kind).with_dont_fragment(true);
+    cfg = match kind {
+        IcmpKind::V4 => cfg.with_ttl(setting.hop_limit as u32),
+        IcmpKind::V6 => cfg.with_hoplimit(setting.hop_limit as u32),
+    };
+    if let Some(src) = setting.src_ip {
+        cfg = cfg.with_bind(SocketAddr::new(src, 0));
+    }
+    if let Some(iface) = &setting.interface {
+        cfg = cfg.with_interface(iface);
+    }
+    let socket = AsyncIcmpSocket::new(&cfg)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to create ICMP socket: {}", e))?;
+    // Linux DGRAM sockets rewrite the identifier and only deliver replies to our own requests
+    let check_id =
+        !(cfg!(any(target_os = "linux", target_os = "android")) && socket.socket_type().is_dgram());
+
+    sink.emit(ProbeEvent::PmtuStart(PmtuStartPayload {
+        run_id: run_id.to_string(),
+        setting: setting.clone(),
+    }));
+
+    let mut prober = Prober {
+        socket,
+        sink,
+        run_id,
+        token,
+        src_ip,
+        dst_ip,
+        echo_id: rand::thread_rng().gen(),
+        check_id,
+        overhead,
+        timeout: Duration::from_millis(setting.timeout_ms),
+        tries: setting.tries.max(1),
+        seq: 0,
+        probes: 0,
+        buf: vec![0u8; ceiling as usize + 512],
+    };
+
+    // Largest size that got a reply, smallest size that did not,
+    // and routers that reported a next-hop MTU
+    let mut lo: Option<u32> = None;
+    let mut hi = ceiling + 1;
+    let mut reports: Vec<(u32, IpAddr)> = Vec::new();
+    let mut next = Some(ceiling);
+    let mut cancelled = false;
+
+    while let Some(size) = next {
+        let Some(probe) = prober.probe(size).await else {
+            cancelled = true;
+            break;
+        };
+        let mut hint = None;
+        match probe.result {
+            PmtuProbeResult::Ok => lo = Some(size),
+            _ => {
+                hi = size;
+                if let (Some(mtu), Some(from)) = (probe.reported_mtu, probe.from) {
+                    reports.push((mtu, from));
+                    hint = Some(mtu);
+                }
+            }
+        }
+        // A reported next-hop MTU, else the interface MTU, is the most likely answer
+        let hint = hint.or(interface_mtu);
+        next = match lo {
+            // Nothing confirmed yet: fall back to the minimum MTU
+            None if size != floor => Some(hint.filter(|m| *m >= floor && *m < hi).unwrap_or(floor)),
+            None => None,
+            Some(lo) if hi - lo > 1 => {
+                Some(hint.filter(|m| *m > lo && *m < hi).unwrap_or((lo + hi) / 2))
+            }
+            Some(_) => None,
+        };
+    }
+
+    if lo.is_none() && !cancelled {
+        anyhow::bail!(
+            "no echo reply from {} even at the minimum MTU ({} bytes)",
+            dst_ip,
+            floor
+        );
+    }
+
+    let constraining_hop = lo.and_then(|mtu| {
+        reports
+            .iter()
+            .rev()
+            .find(|(reported, _)| *reported == mtu)
+            .or(reports.last())
+            .map(|(_, from)| *from)
+    });
+    let report = PmtuReport {
+        run_id: run_id.to_string(),
+        ip_addr: dst_ip,
+        hostname: setting.hostname.clone(),
+        path_mtu: lo,
+        constraining_hop,
+        interface: egress.map(|iface| iface.name),
+        interface_mtu,
+        below_interface_mtu: matches!((lo, interface_mtu), (Some(p), Some(i)) if p < i.min(ceiling)),
+        probes: prober.probes,
+    };
+    if cancelled {
+        sink.emit(ProbeEvent::PmtuCancelled(report.clone()));
+    } else {
+        sink.emit(ProbeEvent::PmtuDone(report.clone()));
+    }
+    Ok(report)
+}
@@ -6,11 +6,13 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use tokio_util::sync::CancellationToken;
 use tokio::sync::{oneshot, Mutex};
 
+use crate::model::probe::IcmpError;
 use crate::model::scan::{HostScanProgress, HostScanReport, HostScanSetting, HostState};
-use crate::probe::packet::{build_icmp_echo_bytes, parse_icmp_echo_v4, parse_icmp_echo_v6};
+use crate::probe::packet::{build_icmp_echo_bytes, parse_echo_reply, parse_icmp_error};
 use crate::socket::icmp::{AsyncIcmpSocket, IcmpConfig, IcmpKind};
 use crate::socket::SocketFamily;
 
@@ -19,43 +21,147 @@ pub const HOSTS_CONCURRENCY: usize = 256;
 struct Pending {
     #[allow(dead_code)]
     ip: IpAddr,
+    /// Identifier and sequence number of the outstanding echo request
+    id: u16,
+    seq: u16,
     sent_at: Instant,
-    tx: oneshot::Sender<u64>,
+    /// RTT of the reply, or the ICMP error returned instead
+    tx: oneshot::Sender<Result<u64, IcmpError>>,
+}
+
+/// Aborts the receiver task when dropped, so it never outlives the scan
+/// (including when the scan is cancelled or returns early with an error).
+pub(crate) struct ReceiverGuard(pub(crate) tokio::task::JoinHandle<()>);
+
+impl Drop for ReceiverGuard {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Answer the outstanding request to `ip` if it has identifier `id` and sequence number `seq`
+async fn resolve_pending(
+    pending: &Mutex<HashMap<IpAddr, Pending>>,
+    ip: IpAddr,
+    (id, seq): (u16, u16),
+    check_id: bool,
+    outcome: Result<(), IcmpError>,
+) {
+    let mut map = pending.lock().await;
+    let matched = map
+        .get(&ip)
+        .is_some_and(|p| p.seq == seq && (!check_id || p.id == id));
+    if matched {
+        if let Some(p) = map.remove(&ip) {
+            let _ = p
+                .tx
+                .send(outcome.map(|_| p.sent_at.elapsed().as_millis() as u64));
+        }
+    }
 }
 
 fn spawn_receiver(
     socket: Arc<AsyncIcmpSocket>,
     pending: Arc<Mutex<HashMap<IpAddr, Pending>>>,
+    payload: Vec<u8>,
     is_v6: bool,
-) -> tokio::task::JoinHandle<()> {
-    tokio::spawn(async move {
+) -> ReceiverGuard {
+    // Linux DGRAM sockets rewrite the identifier and only deliver replies to our own requests
+    let check_id =
+        !(cfg!(any(target_os = "linux", target_os = "android")) && socket.socket_type().is_dgram());
+    ReceiverGuard(tokio::spawn(async move {
         let mut buf = vec![0u8; 2048];
+        let mut err_buf = vec![0u8; 2048];
         loop {
-            let Ok((n, addr)) = socket.recv_from(&mut buf).await else {
-                // Error on recv, socket might be closed
-                break;
-            };
-            let is_echo_reply = if !is_v6 {
-                // IPv4
-                parse_icmp_echo_v4(&buf[..n]).is_some()
-            } else {
-                // IPv6
-                parse_icmp_echo_v6(&buf[..n]).is_some()
-            };
-
-            if is_echo_reply {
-                let mut map = pending.lock().await;
-                if let Some(p) = map.remove(&addr.ip()) {
-                    let _ = p.tx.send(p.sent_at.elapsed().as_millis() as u64);
+            tokio::select! {
+                r = socket.recv_from(&mut buf) => {
+                    let Ok((n, addr)) = r else {
+                        // Error on recv, socket might be closed
+                        break;
+                    };
+                    // An error from a router on the path, quoting the request
+                    if let Some(error) = parse_icmp_error(&buf[..n], is_v6) {
+                        let Some(ids) = error.quoted_echo() else {
+                            continue;
+                        };
+                        let Some(icmp_error) = IcmpError::from_message(
+                            is_v6,
+                            error.icmp_type,
+                            error.icmp_code,
+                            Some(addr.ip()),
+                            error.mtu,
+                        ) else {
+                            continue;
+                        };
+                        resolve_pending(&pending, error.dst, ids, check_id, Err(icmp_error)).await;
+                        continue;
+                    }
+                    // Only a reply to the outstanding request of that host counts
+                    let Some(reply) = parse_echo_reply(&buf[..n], is_v6) else {
+                        continue;
+                    };
+                    if reply.payload != payload.as_slice() {
+                        continue;
+                    }
+                    resolve_pending(&pending, addr.ip(), (reply.id, reply.seq), check_id, Ok(()))
+                        .await;
+                }
+                // Linux DGRAM sockets: errors are queued with the request that caused them
+                r = socket.recv_icmp_error(&mut err_buf) => {
+                    let Ok(queued) = r else {
+                        break;
+                    };
+                    let request = &err_buf[..queued.len.min(err_buf.len())];
+                    let (Some(dst), true) = (queued.dst, request.len() >= 8) else {
+                        continue;
+                    };
+                    let Some(icmp_error) = IcmpError::from_message(
+                        queued.is_v6,
+                        queued.icmp_type,
+                        queued.icmp_code,
+                        queued.offender,
+                        queued.info,
+                    ) else {
+                        continue;
+                    };
+                    let ids = (
+                        u16::from_be_bytes([request[4], request[5]]),
+                        u16::from_be_bytes([request[6], request[7]]),
+                    );
+                    resolve_pending(&pending, dst.ip(), ids, check_id, Err(icmp_error)).await;
                 }
             }
         }
-    })
+    }))
+}
+
+/// Open an ICMP socket shared by all probes of one family.
+/// With `recv_icmp_errors`, the caller must read queued errors (`recv_icmp_error`).
+pub(crate) async fn open_icmp_socket(
+    kind: IcmpKind,
+    hop_limit: u8,
+    bind: Option<IpAddr>,
+    interface: Option<&str>,
+    recv_icmp_errors: bool,
+) -> Result<Arc<AsyncIcmpSocket>> {
+    let mut cfg = IcmpConfig::new(kind).with_recv_icmp_errors(recv_icmp_errors);
+    cfg = match kind {
+        IcmpKind::V4 => cfg.with_ttl(hop_limit.max(1) as u32),
+        IcmpKind::V6 => cfg.with_hoplimit(hop_limit.max(1) as u32),
+    };
+    if let Some(src) = bind {
+        cfg = cfg.with_bind(SocketAddr::new(src, 0));
+    }
+    if let Some(iface) = interface {
+        cfg = cfg.with_interface(iface);
+    }
+    Ok(Arc::new(AsyncIcmpSocket::new(&cfg).await?))
 }
 
 pub async fn host_scan(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
     run_id: &str,
+    token: &CancellationToken,
     src_ipv4: Option<IpAddr>,
     src_ipv6: Option<IpAddr>,
     mut setting: HostScanSetting,
@@ -72,18 +178,20 @@ pub async fn host_scan(
         setting.targets.shuffle(&mut thread_rng());
     }
 
+    // Bind to the source addresses only when a source was requested
+    let pin_source = setting.interface.is_some() || setting.src_ip.is_some();
+    let interface = setting.interface.as_deref();
+
     let socket_v4 = if setting.targets.iter().any(|ip| ip.is_ipv4()) {
-        let mut cfg = IcmpConfig::new(IcmpKind::V4);
-        cfg = cfg.with_ttl(setting.hop_limit.max(1) as u32);
-        Some(Arc::new(AsyncIcmpSocket::new(&cfg).await?))
+        let bind = src_ipv4.filter(|_| pin_source);
+        Some(open_icmp_socket(IcmpKind::V4, setting.hop_limit, bind, interface, true).await?)
     } else {
         None
     };
 
     let socket_v6 = if setting.targets.iter().any(|ip| ip.is_ipv6()) {
-        let mut cfg = IcmpConfig::new(IcmpKind::V6);
-        cfg = cfg.with_hoplimit(setting.hop_limit.max(1) as u32);
-        Some(Arc::new(AsyncIcmpSocket::new(&cfg).await?))
+        let bind = src_ipv6.filter(|_| pin_source);
+        Some(open_icmp_socket(IcmpKind::V6, setting.hop_limit, bind, interface, true).await?)
     } else {
         None
     };
@@ -93,12 +201,12 @@ pub async fn host_scan(
     let pending_v6: Arc<Mutex<HashMap<IpAddr, Pending>>> = Arc::new(Mutex::new(HashMap::new()));
 
     // Spawn receiver tasks
-    let rx_v4 = socket_v4
-        .as_ref()
-        .map(|s| spawn_receiver(s.clone(), pending_v4.clone(), false));
-    let rx_v6 = socket_v6
-        .as_ref()
-        .map(|s| spawn_receiver(s.clone(), pending_v6.clone(), true));
+    let rx_v4 = socket_v4.as_ref().map(|s| {
+        spawn_receiver(s.clone(), pending_v4.clone(), payload.as_bytes().to_vec(), false)
+    });
+    let rx_v6 = socket_v6.as_ref().map(|s| {
+        spawn_receiver(s.clone(), pending_v6.clone(), payload.as_bytes().to_vec(), true)
+    });
 
     // Clone for tasks
     let socket_v4_for_tasks = socket_v4.clone();
@@ -106,7 +214,6 @@ pub async fn host_scan(
     let pending_v4_for_tasks = pending_v4.clone();
     let pending_v6_for_tasks = pending_v6.clone();
 
-    let app_cl = app.clone();
     let timeout_cl = timeout;
     let payload_cl = payload.clone();
     let count_cl = setting.count.max(1);
@@ -114,7 +221,6 @@ pub async fn host_scan(
 
     let mut stream_send = stream::iter(setting.targets.clone().into_iter())
         .map(move |dst_ip| {
-            let app = app_cl.clone();
             let socket_v4 = socket_v4_for_tasks.clone();
             let socket_v6 = socket_v6_for_tasks.clone();
             let pending_v4 = pending_v4_for_tasks.clone();
@@ -149,21 +255,23 @@ pub async fn host_scan(
                         state: HostState::Unreachable,
                         rtt_ms: None,
                         message: Some("no suitable socket for IP family".into()),
+                        icmp_error: None,
                         done,
                         total,
                     };
-                    let _ = app.emit("hostscan:progress", p.clone());
+                    sink.emit(ProbeEvent::HostScanProgress(p.clone()));
                     return p;
                 };
 
                 let target = SocketAddr::new(dst_ip, 0);
                 let mut best_rtt: Option<u64> = None;
                 let mut last_err: Option<String> = None;
+                let mut icmp_error: Option<IcmpError> = None;
 
                 for seq in 1..=cnt {
                     // Regist pending
                     let id: u16 = rand::thread_rng().gen();
-                    let (tx, rx) = oneshot::channel::<u64>();
+                    let (tx, rx) = oneshot::channel();
 
                     {
                         let mut map = pending_map.lock().await;
@@ -171,6 +279,8 @@ pub async fn host_scan(
                             dst_ip,
                             Pending {
                                 ip: dst_ip,
+                                id,
+                                seq: seq as u16,
                                 sent_at: Instant::now(),
                                 tx,
                             },
@@ -181,8 +291,13 @@ pub async fn host_scan(
                     let pkt =
                         build_icmp_echo_bytes(src_ip, dst_ip, id, seq as u16, payload.as_bytes());
 
-                    // Send ICMP Echo Request
-                    if let Err(e) = sock.send_to(&pkt, target).await {
+                    // Send ICMP Echo Request. A send can fail once with the pending
+                    // error of a queued ICMP error for another probe; retry it then.
+                    let mut sent = sock.send_to(&pkt, target).await;
+                    if sent.is_err() {
+                        sent = sock.send_to(&pkt, target).await;
+                    }
+                    if let Err(e) = sent {
                         let mut map = pending_map.lock().await;
                         map.remove(&dst_ip);
                         last_err = Some(format!("send error: {}", e));
@@ -191,10 +306,14 @@ pub async fn host_scan(
 
                     // Wait for reply or timeout
                     match tokio::time::timeout(timeout, rx).await {
-                        Ok(Ok(rtt)) => {
+                        Ok(Ok(Ok(rtt))) => {
                             best_rtt = Some(best_rtt.map_or(rtt, |b| b.min(rtt)));
                             break;
                         }
+                        Ok(Ok(Err(error))) => {
+                            last_err = Some(error.to_string());
+                            icmp_error = Some(error);
+                        }
                         Ok(Err(_canceled)) => {
                             last_err = Some("wait canceled".into());
                         }
@@ -213,6 +332,7 @@ pub async fn host_scan(
                         state: HostState::Alive,
                         rtt_ms: Some(rtt),
                         message: None,
+                        icmp_error: None,
                         done,
                         total,
                     }
@@ -222,11 +342,12 @@ pub async fn host_scan(
                         state: HostState::Unreachable,
                         rtt_ms: None,
                         message: last_err,
+                        icmp_error,
                         done,
                         total,
                     }
                 };
-                let _ = app.emit("hostscan:progress", p.clone());
+                sink.emit(ProbeEvent::HostScanProgress(p.clone()));
                 p
             }
         })
@@ -236,22 +357,23 @@ pub async fn host_scan(
     let mut alive: Vec<(IpAddr, u64)> = Vec::new();
     let mut unreachable: Vec<IpAddr> = Vec::new();
 
-    while let Some(p) = stream_send.next().await {
+    // Stop collecting as soon as the run is cancelled
+    while let Some(p) = tokio::select! {
+        _ = token.cancelled() => None,
+        next = stream_send.next() => next,
+    } {
         match p.state {
             HostState::Alive => alive.push((p.ip_addr, p.rtt_ms.unwrap_or(0))),
             HostState::Unreachable => unreachable.push(p.ip_addr),
         }
     }
 
-    // Drop sockets to gracefully terminate receiver tasks
+    // Drop pending probes and sockets, then terminate receiver tasks
+    drop(stream_send);
     drop(socket_v4);
     drop(socket_v6);
-    if let Some(h) = rx_v4 {
-        let _ = h.abort();
-    }
-    if let Some(h) = rx_v6 {
-        let _ = h.abort();
-    }
+    drop(rx_v4);
+    drop(rx_v6);
 
     // Report results
     let report = HostScanReport {
@@ -260,6 +382,10 @@ pub async fn host_scan(
         unreachable,
         total,
     };
-    let _ = app.emit("hostscan:done", report.clone());
+    if token.is_cancelled() {
+        sink.emit(ProbeEvent::HostScanCancelled(report.clone()));
+    } else {
+        sink.emit(ProbeEvent::HostScanDone(report.clone()));
+    }
     Ok(report)
 }
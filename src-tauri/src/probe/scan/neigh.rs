@@ -1,35 +1,31 @@
 use std::net::IpAddr;
 
 use anyhow::Result;
-use tauri::{AppHandle, Emitter};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use tokio_util::sync::CancellationToken;
 
 use crate::model::scan::{NeighborHost, NeighborScanReport};
 
-pub async fn neighbor_scan(app: &AppHandle, run_id: &str, iface: netdev::Interface) -> Result<NeighborScanReport> {
-    //let iface = netdev::get_default_interface().map_err(|e| anyhow::anyhow!("Failed to get default interface: {}", e))?;
-    let src_ipv4_opt = iface
-        .ipv4_addrs()
-        .into_iter()
-        .next()
-        .map(std::net::IpAddr::V4);
-    let src_ipv6_opt = iface
-        .ipv6_addrs()
-        .into_iter()
-        .next()
-        .map(std::net::IpAddr::V6);
-
-    let _ = app.emit(
-        "hostscan:start",
-        crate::model::scan::HostScanStartPayload {
-            run_id: run_id.to_string(),
-        },
-    );
+pub async fn neighbor_scan(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    iface: netdev::Interface,
+) -> Result<NeighborScanReport> {
+    sink.emit(ProbeEvent::HostScanStart(crate::model::scan::HostScanStartPayload {
+        run_id: run_id.to_string(),
+    }));
 
     let setting = crate::model::scan::HostScanSetting::neighbor_scan_default(&iface);
+    let (src_ipv4_opt, src_ipv6_opt) = crate::net::interface::select_source_pair(
+        setting.interface.as_deref(),
+        None,
+        &setting.targets,
+    )?;
 
     // Perform host scan
     // hostscan:progress and hostscan:done events will be emitted during the scan
-    let hostscan_result = crate::probe::scan::icmp::host_scan(&app, &run_id, src_ipv4_opt, src_ipv6_opt, setting)
+    let hostscan_result = crate::probe::scan::icmp::host_scan(sink, run_id, token, src_ipv4_opt, src_ipv6_opt, setting)
         .await?;
 
     let neigh_table = crate::net::neigh::get_neighbor_table()?;
@@ -84,10 +80,11 @@ pub async fn neighbor_scan(app: &AppHandle, run_id: &str, iface: netdev::Interfa
 
     let total = hostscan_result.total;
 
-    let _ = app.emit(
-        "neighborscan:done",
-        run_id.to_string(),
-    );
+    if token.is_cancelled() {
+        sink.emit(ProbeEvent::NeighborScanCancelled(run_id.to_string()));
+    } else {
+        sink.emit(ProbeEvent::NeighborScanDone(run_id.to_string()));
+    }
 
     Ok(NeighborScanReport{
         run_id: run_id.to_string(),
@@ -9,14 +9,26 @@ use std::{
     },
     time::{Duration, Instant},
 };
-use tauri::{AppHandle, Emitter};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use tokio_util::sync::CancellationToken;
 
 use crate::model::scan::{PortScanReport, PortScanSample, PortScanSetting, PortState};
 use crate::probe::scan::{expand_ports, PORTS_CONCURRENCY};
 
+/// Whether the handshake was aborted by our own TLS stack, e.g. an untrusted certificate
+fn is_local_tls_error(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<quinn::ConnectionError>() {
+        Some(quinn::ConnectionError::TransportError(te)) => {
+            (0x100..0x200).contains(&u64::from(te.code))
+        }
+        _ => false,
+    }
+}
+
 pub async fn port_scan(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
     run_id: &str,
+    token: &CancellationToken,
     _src_ip: IpAddr,
     setting: PortScanSetting,
 ) -> Result<PortScanReport> {
@@ -25,18 +37,27 @@ pub async fn port_scan(
         ports.shuffle(&mut thread_rng());
     }
 
-    let app = app.clone();
     let ip = setting.ip_addr;
+    let src_ip = setting.src_ip;
     let timeout = Duration::from_millis(setting.timeout_ms);
 
+    let option = setting.quic.clone().unwrap_or_default();
+    let alpn = option.alpn_protocols();
+    let server_name = option
+        .server_name
+        .clone()
+        .or_else(|| setting.hostname.clone())
+        .unwrap_or_else(|| ip.to_string());
+
     let total = ports.len() as u32;
     let done_ctr = Arc::new(AtomicU32::new(0));
 
     let mut tasks = stream::iter(ports.into_iter())
         .map(|port| {
-            let app = app.clone();
             let done_ctr = done_ctr.clone();
-            let hostname_opt = setting.hostname.clone();
+            let interface = setting.interface.clone();
+            let alpn = alpn.clone();
+            let server_name = server_name.as_str();
 
             async move {
                 let family = if ip.is_ipv4() {
@@ -46,19 +67,19 @@ pub async fn port_scan(
                 };
 
                 let quic_cfg = crate::socket::quic::QuicConfig {
-                    skip_verify: true,
-                    alpn: vec![b"h3".to_vec(), b"hq-29".to_vec(), b"hq-interop".to_vec()],
+                    skip_verify: option.skip_verify,
+                    alpn,
                     family,
+                    bind_addr: src_ip.map(|ip| SocketAddr::new(ip, 0)),
+                    bind_device: interface,
                 };
 
                 let (state, rtt_ms, msg) =
                     match crate::socket::quic::AsyncQuicSocket::from_config(&quic_cfg) {
                         Ok(ep) => {
-                            let server_name =
-                                hostname_opt.clone().unwrap_or_else(|| ip.to_string());
                             let start = Instant::now();
                             match ep
-                                .connect_timeout(&SocketAddr::new(ip, port), &server_name, timeout)
+                                .connect_timeout(&SocketAddr::new(ip, port), server_name, timeout)
                                 .await
                             {
                                 Ok(conn) => {
@@ -69,6 +90,12 @@ pub async fn port_scan(
                                         None,
                                     )
                                 }
+                                // The server answered but its certificate was rejected (--verify)
+                                Err(e) if is_local_tls_error(&e) => (
+                                    PortState::Open,
+                                    Some(start.elapsed().as_millis() as u64),
+                                    Some(e.to_string()),
+                                ),
                                 Err(e) => {
                                     let st = if let Some(ioe) = e.downcast_ref::<std::io::Error>() {
                                         if ioe.kind() == std::io::ErrorKind::TimedOut {
@@ -98,10 +125,11 @@ pub async fn port_scan(
                     rtt_ms,
                     message: msg,
                     service_name: None,
+                    icmp_error: None,
                     done,
                     total,
                 };
-                let _ = app.emit("portscan:progress", sample.clone());
+                sink.emit(ProbeEvent::PortScanProgress(sample.clone()));
                 sample
             }
         })
@@ -110,7 +138,11 @@ pub async fn port_scan(
     // Collect only Open samples
     let mut open_samples = Vec::new();
     let udp_service_db = ndb_udp_service::UdpServiceDb::bundled();
-    while let Some(sample) = tasks.next().await {
+    // Stop collecting as soon as the run is cancelled; in-flight probes are dropped with the stream
+    while let Some(sample) = tokio::select! {
+        _ = token.cancelled() => None,
+        next = tasks.next() => next,
+    } {
         if matches!(sample.state, PortState::Open) {
             let mut sample = sample;
             match udp_service_db.get(sample.port) {
@@ -131,6 +163,10 @@ pub async fn port_scan(
         samples: open_samples,
     };
 
-    let _ = app.emit("portscan:done", report.clone());
+    if token.is_cancelled() {
+        sink.emit(ProbeEvent::PortScanCancelled(report.clone()));
+    } else {
+        sink.emit(ProbeEvent::PortScanDone(report.clone()));
+    }
     Ok(report)
 }
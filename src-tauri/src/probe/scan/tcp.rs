@@ -5,14 +5,17 @@ use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use tokio_util::sync::CancellationToken;
 
+use crate::model::probe::{IcmpError, IcmpErrorKind};
 use crate::model::scan::{PortScanReport, PortScanSample, PortScanSetting, PortState};
 use crate::probe::scan::{expand_ports, PORTS_CONCURRENCY};
 
 pub async fn port_scan(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
     run_id: &str,
+    token: &CancellationToken,
     _src_ip: IpAddr,
     setting: PortScanSetting,
 ) -> Result<PortScanReport> {
@@ -21,8 +24,8 @@ pub async fn port_scan(
         ports.shuffle(&mut thread_rng());
     }
 
-    let app = app.clone();
     let ip = setting.ip_addr;
+    let src_ip = setting.src_ip;
     let timeout = Duration::from_millis(setting.timeout_ms);
 
     let total = ports.len() as u32;
@@ -32,14 +35,20 @@ pub async fn port_scan(
     // Collect results as they complete
     let mut tasks = stream::iter(ports.into_iter())
         .map(|port| {
-            let app = app.clone();
             let done_ctr = done_ctr.clone();
+            let interface = setting.interface.clone();
             async move {
-                let cfg = if ip.is_ipv4() {
+                let mut cfg = if ip.is_ipv4() {
                     crate::socket::tcp::TcpConfig::v4_stream()
                 } else {
                     crate::socket::tcp::TcpConfig::v6_stream()
                 };
+                if let Some(src) = src_ip {
+                    cfg = cfg.with_bind(SocketAddr::new(src, 0));
+                }
+                if let Some(iface) = interface {
+                    cfg = cfg.with_bind_device(iface);
+                }
                 let sock_addr = SocketAddr::new(ip, port);
                 let sock = match crate::socket::tcp::AsyncTcpSocket::from_config(&cfg) {
                     Ok(s) => s,
@@ -52,16 +61,18 @@ pub async fn port_scan(
                             rtt_ms: None,
                             message: Some(format!("tcp socket error: {}", e)),
                             service_name: None,
+                            icmp_error: None,
                             done,
                             total,
                         };
-                        let _ = app.emit("portscan:progress", sample.clone());
+                        sink.emit(ProbeEvent::PortScanProgress(sample.clone()));
                         return sample;
                     }
                 };
 
                 let start = Instant::now();
 
+                let mut icmp_error = None;
                 let (state, rtt_ms, msg) = match sock.connect_timeout(sock_addr, timeout).await {
                     Ok(stream) => {
                         drop(stream);
@@ -83,6 +94,7 @@ pub async fn port_scan(
                             }
                             _ => PortState::Closed,
                         };
+                        icmp_error = IcmpErrorKind::from_tcp_error(&e).map(IcmpError::from_kind);
                         (st, None, Some(e.to_string()))
                     }
                 };
@@ -95,11 +107,12 @@ pub async fn port_scan(
                     rtt_ms,
                     message: msg,
                     service_name: None,
+                    icmp_error,
                     done,
                     total,
                 };
 
-                let _ = app.emit("portscan:progress", sample.clone());
+                sink.emit(ProbeEvent::PortScanProgress(sample.clone()));
                 sample
             }
         })
@@ -108,7 +121,11 @@ pub async fn port_scan(
     // Collect only Open samples
     let mut open_samples: Vec<PortScanSample> = Vec::new();
     let tcp_service_db = ndb_tcp_service::TcpServiceDb::bundled();
-    while let Some(sample) = tasks.next().await {
+    // Stop collecting as soon as the run is cancelled; in-flight probes are dropped with the stream
+    while let Some(sample) = tokio::select! {
+        _ = token.cancelled() => None,
+        next = tasks.next() => next,
+    } {
         if matches!(sample.state, PortState::Open) {
             let mut sample = sample;
             match tcp_service_db.get(sample.port) {
@@ -132,6 +149,10 @@ pub async fn port_scan(
         samples: open_samples,
     };
 
-    let _ = app.emit("portscan:done", report.clone());
+    if token.is_cancelled() {
+        sink.emit(ProbeEvent::PortScanCancelled(report.clone()));
+    } else {
+        sink.emit(ProbeEvent::PortScanDone(report.clone()));
+    }
     Ok(report)
 }
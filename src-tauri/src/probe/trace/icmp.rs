@@ -6,7 +6,8 @@ use nex_packet::ipv4::Ipv4Packet;
 use nex_packet::packet::Packet;
 use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use tokio_util::sync::CancellationToken;
 
 use super::{TraceHop, TracerouteSetting};
 use crate::probe::packet::build_icmp_echo_bytes;
@@ -40,10 +41,11 @@ fn is_echo_reply(dst_ip: IpAddr, icmp_bytes: &[u8]) -> bool {
 /// - Send tries_per_hop times for each hop and summarize the best RTT in `TraceHop`
 /// - If an Echo Reply is received from the destination, end with `reached = true`
 pub async fn icmp_traceroute(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
+    token: &CancellationToken,
     src_ip: IpAddr,
     setting: &TracerouteSetting,
-) -> Result<bool> {
+) -> Result<Vec<TraceHop>> {
     let dst_ip = setting.ip_addr;
     let icmp_kind = if dst_ip.is_ipv4() {
         IcmpKind::V4
@@ -56,10 +58,13 @@ pub async fn icmp_traceroute(
     let echo_id: u16 = 0x1234;
     let payload = b"np:trace-icmp";
 
-    // Whether reached the destination at any hop
-    let mut reached = false;
+    // Hops in order, the last one has `reached = true` if the destination replied
+    let mut hops: Vec<TraceHop> = Vec::new();
 
     'ttl_loop: for ttl in 1..=setting.max_hops {
+        if token.is_cancelled() {
+            break;
+        }
         // Create socket for each TTL/HopLimit
         let mut cfg = IcmpConfig::new(icmp_kind);
         if dst_ip.is_ipv4() {
@@ -73,6 +78,9 @@ pub async fn icmp_traceroute(
                 cfg = cfg.with_bind(SocketAddr::new(IpAddr::V6(v6), 0));
             }
         }
+        if let Some(iface) = &setting.interface {
+            cfg = cfg.with_interface(iface);
+        }
 
         let socket = AsyncIcmpSocket::new(&cfg).await?;
         let target = SocketAddr::new(dst_ip, 0);
@@ -125,9 +133,9 @@ pub async fn icmp_traceroute(
                     // Check if the ICMP packet is an Echo Reply from the destination
                     if is_echo_reply(dst_ip, &buf[..n]) {
                         best.reached = true;
-                        reached = true;
                         // Emit this hop as progress and break the ttl_loop
-                        app.emit("traceroute:progress", &best).ok();
+                        sink.emit(ProbeEvent::TraceProgress(best.clone()));
+                        hops.push(best);
                         break 'ttl_loop;
                     }
                 }
@@ -139,8 +147,9 @@ pub async fn icmp_traceroute(
             best.note = Some("timeout".into());
         }
 
-        app.emit("traceroute:progress", &best).ok();
+        sink.emit(ProbeEvent::TraceProgress(best.clone()));
+        hops.push(best);
     }
 
-    Ok(reached)
+    Ok(hops)
 }
@@ -1,9 +1,11 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
-use tauri::{AppHandle, Emitter};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use tokio_util::sync::CancellationToken;
 
 pub mod icmp;
+pub mod mtr;
 pub mod udp;
 
 /// Protocol used for traceroute
@@ -29,10 +31,14 @@ pub struct TracerouteSetting {
     pub timeout_ms: u64,
     /// icmp / udp
     pub protocol: TraceProtocol,
+    /// Egress interface name (default: the interface chosen by the OS)
+    pub interface: Option<String>,
+    /// Source address (default: an address of `interface` or of the default interface)
+    pub src_ip: Option<IpAddr>,
 }
 
 /// Result for one hop
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TraceHop {
     pub hop: u8,
     pub ip_addr: Option<IpAddr>,
@@ -43,6 +49,120 @@ pub struct TraceHop {
     pub note: Option<String>,
 }
 
+/// Payload of the `traceroute:start` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceStartPayload {
+    pub run_id: String,
+    #[serde(flatten)]
+    pub setting: TracerouteSetting,
+}
+
+/// Payload of the `traceroute:done` and `traceroute:cancelled` events
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceDonePayload {
+    pub run_id: String,
+    pub reached: bool,
+    pub hops: u8,
+    pub ip_addr: IpAddr,
+    pub hostname: Option<String>,
+    pub protocol: TraceProtocol,
+}
+
+/// Result of a traceroute run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceReport {
+    pub run_id: String,
+    pub reached: bool,
+    pub ip_addr: IpAddr,
+    pub hostname: Option<String>,
+    pub protocol: TraceProtocol,
+    pub hops: Vec<TraceHop>,
+}
+
+/// Payload of the `traceroute:error` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceErrorPayload {
+    pub message: String,
+}
+
+/// Settings for an MTR run: ICMP probes to every hop, repeated in rounds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MtrSetting {
+    /// Resolved destination IP
+    pub ip_addr: IpAddr,
+    /// Display hostname (optional)
+    pub hostname: Option<String>,
+    /// Maximum hops (30 if 0)
+    pub max_hops: u8,
+    /// Number of rounds; `None` or 0 runs until cancelled
+    pub rounds: Option<u32>,
+    /// Time from the start of one round to the next (ms).
+    /// A round lasts at least `timeout_ms`.
+    pub interval_ms: u64,
+    /// Timeout per probe (ms)
+    pub timeout_ms: u64,
+    /// Egress interface name (default: the interface chosen by the OS)
+    pub interface: Option<String>,
+    /// Source address (default: an address of `interface` or of the default interface)
+    pub src_ip: Option<IpAddr>,
+}
+
+/// Statistics of one hop over the rounds of an MTR run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MtrHop {
+    pub hop: u8,
+    /// Address that answered last
+    pub ip_addr: Option<IpAddr>,
+    /// Every address that answered, in order of first answer (several on load balanced paths)
+    pub ip_addrs: Vec<IpAddr>,
+    pub sent: u32,
+    pub received: u32,
+    /// Loss in percent (0.0 to 100.0)
+    pub loss_pct: f64,
+    /// RTT of the last answer, and the average, best, worst and
+    /// population standard deviation over all answers (ms)
+    pub last_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    pub best_ms: Option<f64>,
+    pub worst_ms: Option<f64>,
+    pub stddev_ms: Option<f64>,
+    /// True if the destination answered at this hop
+    pub reached: bool,
+    /// Last ICMP error other than TTL exceeded, e.g. host unreachable
+    pub note: Option<String>,
+}
+
+/// Payload of the `mtr:start` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MtrStartPayload {
+    pub run_id: String,
+    #[serde(flatten)]
+    pub setting: MtrSetting,
+}
+
+/// Payload of the `mtr:snapshot` event: the whole path table after a round
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MtrSnapshot {
+    pub run_id: String,
+    /// Rounds completed so far
+    pub round: u32,
+    pub reached: bool,
+    pub hops: Vec<MtrHop>,
+}
+
+/// Result of an MTR run, also the payload of the `mtr:done` and `mtr:cancelled` events
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MtrReport {
+    pub run_id: String,
+    pub ip_addr: IpAddr,
+    pub hostname: Option<String>,
+    pub rounds: u32,
+    pub reached: bool,
+    pub hops: Vec<MtrHop>,
+    /// Why the run stopped before its last round, e.g. a receive error
+    pub note: Option<String>,
+}
+
 impl TraceHop {
     #[allow(dead_code)]
     pub fn timeout(hop: u8) -> Self {
@@ -68,31 +188,47 @@ fn sanitize_setting(mut setting: TracerouteSetting) -> TracerouteSetting {
 
 /// Entry point called from Tauri command
 pub async fn traceroute(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
     src_ip: IpAddr,
     setting: TracerouteSetting,
-) -> Result<()> {
+) -> Result<TraceReport> {
     let setting = sanitize_setting(setting);
 
-    app.emit("traceroute:start", &setting).ok();
+    sink.emit(ProbeEvent::TraceStart(TraceStartPayload {
+        run_id: run_id.to_string(),
+        setting: setting.clone(),
+    }));
 
-    let reached = match setting.protocol {
-        TraceProtocol::Icmp => icmp::icmp_traceroute(app, src_ip, &setting).await?,
-        TraceProtocol::Udp => udp::udp_traceroute(app, src_ip, &setting).await?,
+    let hops = match setting.protocol {
+        TraceProtocol::Icmp => icmp::icmp_traceroute(sink, token, src_ip, &setting).await?,
+        TraceProtocol::Udp => udp::udp_traceroute(sink, token, src_ip, &setting).await?,
     };
 
+    let reached = hops.last().is_some_and(|h| h.reached);
+
     // Send done event
-    app.emit(
-        "traceroute:done",
-        &serde_json::json!({
-            "reached": reached,
-            "hops": setting.max_hops,
-            "ip_addr": setting.ip_addr,
-            "hostname": setting.hostname,
-            "protocol": setting.protocol,
-        }),
-    )
-    .ok();
-
-    Ok(())
+    let payload = TraceDonePayload {
+        run_id: run_id.to_string(),
+        reached,
+        hops: setting.max_hops,
+        ip_addr: setting.ip_addr,
+        hostname: setting.hostname.clone(),
+        protocol: setting.protocol,
+    };
+    if token.is_cancelled() {
+        sink.emit(ProbeEvent::TraceCancelled(payload));
+    } else {
+        sink.emit(ProbeEvent::TraceDone(payload));
+    }
+
+    Ok(TraceReport {
+        run_id: run_id.to_string(),
+        reached,
+        ip_addr: setting.ip_addr,
+        hostname: setting.hostname,
+        protocol: setting.protocol,
+        hops,
+    })
 }
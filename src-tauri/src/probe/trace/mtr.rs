@@ -0,0 +1,377 @@
+use anyhow::{bail, Result};
+use rand::Rng;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::{MtrHop, MtrReport, MtrSetting, MtrSnapshot, MtrStartPayload};
+use crate::model::probe::IcmpErrorKind;
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::probe::packet::build_icmp_echo_bytes;
+use crate::probe::ping::icmp::{spawn_receiver, Received};
+use crate::socket::icmp::{AsyncIcmpSocket, IcmpConfig, IcmpKind};
+use crate::stats::{duration_ms, round_us};
+
+/// Echo request payload, checked in echo replies
+const PAYLOAD: &[u8] = b"np:mtr";
+/// Attempts after a failed send (see `send_probe`)
+const SEND_RETRIES: usize = 3;
+
+/// Statistics of a hop, with the running mean and sum of squared deviations
+/// (Welford) behind `avg_ms` and `stddev_ms`
+struct HopState {
+    hop: MtrHop,
+    mean: f64,
+    m2: f64,
+}
+
+impl HopState {
+    fn new(hop: u8) -> Self {
+        Self {
+            hop: MtrHop {
+                hop,
+                ip_addr: None,
+                ip_addrs: Vec::new(),
+                sent: 0,
+                received: 0,
+                loss_pct: 0.0,
+                last_ms: None,
+                avg_ms: None,
+                best_ms: None,
+                worst_ms: None,
+                stddev_ms: None,
+                reached: false,
+                note: None,
+            },
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    /// Record an answer from `from` that took `rtt_ms`
+    fn record(&mut self, from: Option<IpAddr>, rtt_ms: f64) {
+        let hop = &mut self.hop;
+        hop.received += 1;
+        if let Some(ip) = from {
+            hop.ip_addr = Some(ip);
+            if !hop.ip_addrs.contains(&ip) {
+                hop.ip_addrs.push(ip);
+            }
+        }
+        let n = hop.received as f64;
+        let delta = rtt_ms - self.mean;
+        self.mean += delta / n;
+        self.m2 += delta * (rtt_ms - self.mean);
+        hop.last_ms = Some(rtt_ms);
+        hop.avg_ms = Some(round_us(self.mean));
+        hop.best_ms = Some(hop.best_ms.map_or(rtt_ms, |best| best.min(rtt_ms)));
+        hop.worst_ms = Some(hop.worst_ms.map_or(rtt_ms, |worst| worst.max(rtt_ms)));
+        hop.stddev_ms = Some(round_us((self.m2 / n).sqrt()));
+    }
+
+    fn update_loss(&mut self) {
+        let hop = &mut self.hop;
+        if hop.sent > 0 {
+            let lost = hop.sent.saturating_sub(hop.received);
+            hop.loss_pct = round_us(lost as f64 * 100.0 / hop.sent as f64);
+        }
+    }
+}
+
+/// Drop the unanswered probes of an interrupted round; they are not counted as sent.
+fn discard_round(hops: &mut [HopState], pending: &mut HashMap<u16, (usize, Instant)>) {
+    for (index, _) in pending.drain().map(|(_, probe)| probe) {
+        hops[index].hop.sent -= 1;
+    }
+}
+
+/// Send a probe. On Linux DGRAM sockets an ICMP error queued for an earlier probe
+/// also fails the next send until the receiver reads it, so failed sends are retried.
+async fn send_probe(socket: &AsyncIcmpSocket, pkt: &[u8], target: SocketAddr) -> io::Result<()> {
+    let mut result = socket.send_to(pkt, target).await;
+    for _ in 0..SEND_RETRIES {
+        if result.is_ok() {
+            break;
+        }
+        tokio::task::yield_now().await;
+        result = socket.send_to(pkt, target).await;
+    }
+    result.map(|_| ())
+}
+
+/// MTR: send an ICMP echo request to every hop up to the destination, round after round,
+/// and keep loss and RTT statistics per hop. Each round emits a snapshot of the whole path.
+///
+/// - A round sends one probe per hop and waits up to `timeout_ms` for the answers
+///   (Time Exceeded from routers, Echo Reply from the destination)
+/// - The path ends at the first hop where the destination, or an ICMP error other than
+///   TTL exceeded, answers
+/// - Runs `rounds` rounds, or until cancelled. A receive error, or failing to set the
+///   hop limit, also ends the run, with the statistics so far and the error in `note`
+pub async fn mtr(
+    sink: &dyn ProbeEventSink,
+    run_id: &str,
+    token: &CancellationToken,
+    src_ip: IpAddr,
+    mut setting: MtrSetting,
+) -> Result<MtrReport> {
+    if setting.max_hops == 0 {
+        setting.max_hops = 30;
+    }
+    sink.emit(ProbeEvent::MtrStart(MtrStartPayload {
+        run_id: run_id.to_string(),
+        setting: setting.clone(),
+    }));
+
+    let dst_ip = setting.ip_addr;
+    let kind = if dst_ip.is_ipv4() {
+        IcmpKind::V4
+    } else {
+        IcmpKind::V6
+    };
+    let mut cfg = IcmpConfig::new(kind).with_recv_icmp_errors(true);
+    if let Some(src) = setting.src_ip {
+        cfg = cfg.with_bind(SocketAddr::new(src, 0));
+    }
+    if let Some(iface) = &setting.interface {
+        cfg = cfg.with_interface(iface);
+    }
+    let socket = match AsyncIcmpSocket::new(&cfg).await {
+        Ok(s) => Arc::new(s),
+        Err(e) => bail!("failed to create ICMP socket: {}", e),
+    };
+    let target = SocketAddr::new(dst_ip, 0);
+
+    let echo_id: u16 = rand::thread_rng().gen();
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
+    let _receiver = spawn_receiver(socket.clone(), dst_ip, echo_id, PAYLOAD.to_vec(), reply_tx);
+
+    let timeout = Duration::from_millis(setting.timeout_ms);
+    let interval = Duration::from_millis(setting.interval_ms);
+    let rounds = setting.rounds.filter(|&r| r > 0);
+    let mut hops: Vec<HopState> = (1..=setting.max_hops).map(HopState::new).collect();
+    // Probes of the current round by sequence number: index of the hop and send time
+    let mut pending: HashMap<u16, (usize, Instant)> = HashMap::new();
+    let mut seq: u16 = 0;
+    let mut round = 0u32;
+    let mut note = None;
+
+    'rounds: while rounds.is_none_or(|r| round < r) {
+        let round_start = Instant::now();
+        for (index, state) in hops.iter_mut().enumerate() {
+            if let Err(e) = socket.set_hop_limit(index as u32 + 1) {
+                note = Some(format!("failed to set hop limit: {e}"));
+                break;
+            }
+            seq = seq.wrapping_add(1);
+            let pkt = build_icmp_echo_bytes(src_ip, dst_ip, echo_id, seq, PAYLOAD);
+            let sent_at = Instant::now();
+            state.hop.sent += 1;
+            match send_probe(&socket, &pkt, target).await {
+                Ok(()) => {
+                    pending.insert(seq, (index, sent_at));
+                }
+                Err(e) => state.hop.note = Some(format!("send error: {e}")),
+            }
+        }
+        if note.is_some() {
+            discard_round(&mut hops, &mut pending);
+            break 'rounds;
+        }
+
+        // Collect the answers of this round
+        let mut path_len = hops.len();
+        let deadline = tokio::time::Instant::from_std(round_start + timeout);
+        while !pending.is_empty() {
+            let received = tokio::select! {
+                _ = token.cancelled() => None,
+                r = tokio::time::timeout_at(deadline, reply_rx.recv()) => match r {
+                    Err(_) => break,
+                    Ok(Some(Ok(received))) => Some(received),
+                    Ok(Some(Err(e))) => {
+                        note = Some(format!("recv error: {e}"));
+                        None
+                    }
+                    Ok(None) => {
+                        note = Some("recv error: receiver stopped".to_string());
+                        None
+                    }
+                },
+            };
+            let Some(received) = received else {
+                discard_round(&mut hops, &mut pending);
+                break 'rounds;
+            };
+            match received {
+                Received::Reply(reply) => {
+                    let Some((index, sent_at)) = pending.remove(&reply.seq) else {
+                        continue;
+                    };
+                    let rtt_ms = duration_ms(reply.received_at.saturating_duration_since(sent_at));
+                    let state = &mut hops[index];
+                    state.record(Some(dst_ip), rtt_ms);
+                    state.hop.reached = true;
+                    path_len = path_len.min(index + 1);
+                }
+                Received::Error {
+                    seq,
+                    error,
+                    received_at,
+                } => {
+                    let Some((index, sent_at)) = pending.remove(&seq) else {
+                        continue;
+                    };
+                    let rtt_ms = duration_ms(received_at.saturating_duration_since(sent_at));
+                    let state = &mut hops[index];
+                    state.record(error.from, rtt_ms);
+                    if error.kind != IcmpErrorKind::TtlExceeded {
+                        state.hop.note = Some(error.to_string());
+                        path_len = path_len.min(index + 1);
+                    }
+                }
+            }
+        }
+        // Lost probes; their answers are ignored from now on
+        pending.clear();
+        round += 1;
+
+        // Hops past the end of the path are not probed again
+        hops.truncate(path_len);
+        for state in hops.iter_mut() {
+            state.update_loss();
+        }
+        sink.emit(ProbeEvent::MtrSnapshot(MtrSnapshot {
+            run_id: run_id.to_string(),
+            round,
+            reached: hops.last().is_some_and(|h| h.hop.reached),
+            hops: hops.iter().map(|h| h.hop.clone()).collect(),
+        }));
+
+        if rounds.is_some_and(|r| round >= r) {
+            break;
+        }
+        let next_round = tokio::time::Instant::from_std(round_start + interval);
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = tokio::time::sleep_until(next_round) => {}
+        }
+    }
+
+    for state in hops.iter_mut() {
+        state.update_loss();
+    }
+    let report = MtrReport {
+        run_id: run_id.to_string(),
+        ip_addr: dst_ip,
+        hostname: setting.hostname.clone(),
+        rounds: round,
+        reached: hops.last().is_some_and(|h| h.hop.reached),
+        hops: hops.into_iter().map(|h| h.hop).collect(),
+        note,
+    };
+    if token.is_cancelled() {
+        sink.emit(ProbeEvent::MtrCancelled(report.clone()));
+    } else {
+        sink.emit(ProbeEvent::MtrDone(report.clone()));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn ip(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last)))
+    }
+
+    #[test]
+    fn record_statistics() {
+        let mut state = HopState::new(3);
+        state.record(ip(1), 12.5);
+        assert_eq!(state.hop.avg_ms, Some(12.5));
+        assert_eq!(state.hop.stddev_ms, Some(0.0));
+
+        for rtt in [20.0, 30.0, 40.0] {
+            state.record(ip(1), rtt);
+        }
+        let hop = &state.hop;
+        assert_eq!(hop.received, 4);
+        assert_eq!(hop.last_ms, Some(40.0));
+        assert_eq!(hop.best_ms, Some(12.5));
+        assert_eq!(hop.worst_ms, Some(40.0));
+        assert_eq!(hop.avg_ms, Some(25.625));
+        // Population standard deviation of 12.5, 20, 30 and 40
+        assert_eq!(hop.stddev_ms, Some(10.364));
+    }
+
+    #[test]
+    fn welford_matches_two_pass() {
+        let samples: Vec<f64> = (0..200)
+            .map(|i| 1000.0 + ((i * 37) % 23) as f64 * 0.125)
+            .collect();
+        let mut state = HopState::new(1);
+        for &rtt in &samples {
+            state.record(None, rtt);
+        }
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let var = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+        assert_eq!(state.hop.avg_ms, Some(round_us(mean)));
+        assert_eq!(state.hop.stddev_ms, Some(round_us(var.sqrt())));
+    }
+
+    #[test]
+    fn record_addresses() {
+        let mut state = HopState::new(5);
+        state.record(ip(1), 1.0);
+        state.record(ip(2), 1.0);
+        state.record(ip(1), 1.0);
+        state.record(None, 1.0);
+        assert_eq!(state.hop.ip_addr, ip(1));
+        assert_eq!(state.hop.ip_addrs, [ip(1).unwrap(), ip(2).unwrap()]);
+        assert_eq!(state.hop.received, 4);
+    }
+
+    #[test]
+    fn loss() {
+        let cases = [
+            (0, 0, 0.0),
+            (4, 4, 0.0),
+            (4, 0, 100.0),
+            (3, 1, 66.667),
+            (8, 7, 12.5),
+            // A late answer counted after the probe was given up
+            (2, 3, 0.0),
+        ];
+        for (sent, received, loss_pct) in cases {
+            let mut state = HopState::new(1);
+            state.hop.sent = sent;
+            state.hop.received = received;
+            state.update_loss();
+            assert_eq!(
+                state.hop.loss_pct, loss_pct,
+                "{sent} sent, {received} received"
+            );
+        }
+    }
+
+    #[test]
+    fn discard_round_uncounts_pending() {
+        let mut hops: Vec<HopState> = (1..=3).map(HopState::new).collect();
+        for state in hops.iter_mut() {
+            state.hop.sent = 2;
+        }
+        let now = Instant::now();
+        let mut pending = HashMap::from([(7, (0, now)), (9, (2, now))]);
+        discard_round(&mut hops, &mut pending);
+        assert!(pending.is_empty());
+        let sent: Vec<_> = hops.iter().map(|h| h.hop.sent).collect();
+        assert_eq!(sent, [1, 2, 1]);
+    }
+}
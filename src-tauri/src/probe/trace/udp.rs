@@ -11,7 +11,8 @@ use nex_packet::ipv4::Ipv4Packet;
 use nex_packet::packet::Packet;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use tokio_util::sync::CancellationToken;
 
 use super::{TraceHop, TracerouteSetting};
 use crate::socket::icmp::{AsyncIcmpSocket, IcmpConfig, IcmpKind};
@@ -43,10 +44,11 @@ fn is_port_unreach_v6(icmp_bytes: &[u8]) -> bool {
 
 #[cfg(unix)]
 pub async fn udp_traceroute(
-    app: &AppHandle,
+    sink: &dyn ProbeEventSink,
+    token: &CancellationToken,
     _src_ip: IpAddr,
     setting: &TracerouteSetting,
-) -> Result<bool> {
+) -> Result<Vec<TraceHop>> {
     let dst_ip = setting.ip_addr;
     let timeout = Duration::from_millis(setting.timeout_ms);
 
@@ -56,11 +58,18 @@ pub async fn udp_traceroute(
     } else {
         IcmpKind::V6
     };
-    let icmp = AsyncIcmpSocket::new(&IcmpConfig::new(icmp_kind)).await?;
+    let mut icmp_cfg = IcmpConfig::new(icmp_kind);
+    if let Some(iface) = &setting.interface {
+        icmp_cfg = icmp_cfg.with_interface(iface);
+    }
+    let icmp = AsyncIcmpSocket::new(&icmp_cfg).await?;
 
-    let mut reached = false;
+    let mut hops: Vec<TraceHop> = Vec::new();
 
     'ttl_loop: for ttl in 1..=setting.max_hops {
+        if token.is_cancelled() {
+            break;
+        }
         let mut ucfg = UdpConfig::new();
         ucfg.socket_family = SocketFamily::from_ip(&dst_ip);
 
@@ -73,6 +82,12 @@ pub async fn udp_traceroute(
             ucfg.bind_addr =
                 Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0));
         }
+        if let Some(src) = setting.src_ip {
+            ucfg.bind_addr = Some(SocketAddr::new(src, 0));
+        }
+        if let Some(iface) = &setting.interface {
+            ucfg = ucfg.with_bind_device(iface);
+        }
 
         let udp = AsyncUdpSocket::from_config(&ucfg)?;
         let local_addr = udp.local_addr()?;
@@ -131,8 +146,8 @@ pub async fn udp_traceroute(
 
                     if is_dest {
                         best.reached = true;
-                        reached = true;
-                        app.emit("traceroute:progress", &best).ok();
+                        sink.emit(ProbeEvent::TraceProgress(best.clone()));
+                        hops.push(best);
                         break 'ttl_loop;
                     }
                 }
@@ -143,18 +158,20 @@ pub async fn udp_traceroute(
             best.note = Some("timeout".into());
         }
 
-        app.emit("traceroute:progress", &best).ok();
+        sink.emit(ProbeEvent::TraceProgress(best.clone()));
+        hops.push(best);
     }
 
-    Ok(reached)
+    Ok(hops)
 }
 
 #[cfg(windows)]
 pub async fn udp_traceroute(
-    _app: &AppHandle,
+    _sink: &dyn ProbeEventSink,
+    _token: &CancellationToken,
     _src_ip: IpAddr,
     _setting: &TracerouteSetting,
-) -> Result<bool> {
+) -> Result<Vec<TraceHop>> {
     // Currently, windows is not supported for UDP traceroute via ICMP Port Unreachable
     // because it requires enabling promiscuous mode on ICMP socket.
     // and it needs admin privileges.
@@ -1,3 +1,4 @@
+pub mod monitor;
 pub mod task;
 
 use std::{sync::Arc, time::Duration};
@@ -5,20 +6,97 @@ use tauri::async_runtime;
 use tauri::async_runtime::JoinHandle;
 use tauri::AppHandle;
 use tauri::Emitter;
-use tokio::time::interval;
+use tokio::sync::watch;
+use tokio::time::{interval, interval_at, Instant, Interval};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
+    config::{AppConfig, MIN_IFACE_RELOAD_INTERVAL_MS, MIN_REFRESH_INTERVAL_MS},
+    service::monitor::MonitorSet,
     service::task::{reload_interfaces, update_interface_state},
     state::AppState,
 };
 
+/// Tick intervals of the supervisor, derived from `AppConfig`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SupervisorIntervals {
+    stats: Duration,
+    ifaces: Duration,
+}
+
+impl SupervisorIntervals {
+    fn from_config(cfg: &AppConfig) -> Self {
+        Self {
+            stats: Duration::from_millis(cfg.refresh_interval_ms.max(MIN_REFRESH_INTERVAL_MS)),
+            ifaces: Duration::from_millis(
+                cfg.iface_reload_interval_ms
+                    .max(MIN_IFACE_RELOAD_INTERVAL_MS),
+            ),
+        }
+    }
+}
+
+/// Interval whose first tick is one period from now
+fn delayed_interval(period: Duration) -> Interval {
+    interval_at(Instant::now() + period, period)
+}
+
+/// Handle of the running supervisor task
+pub struct SupervisorTask {
+    handle: JoinHandle<()>,
+    shutdown: CancellationToken,
+}
+
+impl std::fmt::Debug for SupervisorTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SupervisorTask")
+            .field("cancelled", &self.shutdown.is_cancelled())
+            .finish()
+    }
+}
+
+impl SupervisorTask {
+    /// Signal the task to stop and wait until it has exited.
+    pub async fn stop(self) {
+        self.shutdown.cancel();
+        if let Err(e) = self.handle.await {
+            tracing::warn!("supervisor task ended abnormally: {e}");
+        }
+    }
+}
+
+/// Stop the running supervisor (if any) and start a new one.
+/// The supervisor follows config updates published on `config`.
+pub async fn restart_supervisor(
+    app: AppHandle,
+    state: Arc<AppState>,
+    config: watch::Receiver<AppConfig>,
+) {
+    let mut task = state.task.lock().await;
+    if let Some(old) = task.take() {
+        old.stop().await;
+    }
+    *task = Some(spawn_supervisor(app, state.clone(), config));
+}
+
 /// Spawn a background task that periodically
-/// - updates interface stats every second
-/// - reloads interface list every 30 seconds
-pub fn spawn_supervisor(app: AppHandle, state: Arc<AppState>) -> JoinHandle<()> {
-    async_runtime::spawn(async move {
-        let mut tick_stats = interval(Duration::from_secs(1));
-        let mut tick_ifaces = interval(Duration::from_secs(30));
+/// - updates interface stats every `refresh_interval_ms`
+/// - reloads interface list every `iface_reload_interval_ms`
+///
+/// and runs the configured `monitors`.
+/// Intervals and monitors are re-read whenever a new config is published on `config`.
+pub fn spawn_supervisor(
+    app: AppHandle,
+    state: Arc<AppState>,
+    mut config: watch::Receiver<AppConfig>,
+) -> SupervisorTask {
+    let shutdown = CancellationToken::new();
+    let token = shutdown.clone();
+    let handle = async_runtime::spawn(async move {
+        let cfg = config.borrow_and_update().clone();
+        let mut intervals = SupervisorIntervals::from_config(&cfg);
+        let mut tick_stats = interval(intervals.stats);
+        let mut tick_ifaces = interval(intervals.ifaces);
 
         if let Err(e) = reload_interfaces(&state).await {
             tracing::warn!("initial reload_interfaces failed: {e}");
@@ -26,8 +104,31 @@ pub fn spawn_supervisor(app: AppHandle, state: Arc<AppState>) -> JoinHandle<()>
             let _ = app.emit("interfaces_updated", ());
         }
 
+        let mut monitors = MonitorSet::default();
+        monitors.apply(&app, &state, &cfg.monitors, &token).await;
+
         loop {
             tokio::select! {
+                _ = token.cancelled() => break,
+                changed = config.changed() => {
+                    if changed.is_err() {
+                        // Config sender dropped: the app is shutting down
+                        break;
+                    }
+                    let cfg = config.borrow_and_update().clone();
+                    monitors.apply(&app, &state, &cfg.monitors, &token).await;
+                    let next = SupervisorIntervals::from_config(&cfg);
+                    if next != intervals {
+                        tracing::info!("supervisor intervals updated: {next:?}");
+                        if next.stats != intervals.stats {
+                            tick_stats = delayed_interval(next.stats);
+                        }
+                        if next.ifaces != intervals.ifaces {
+                            tick_ifaces = delayed_interval(next.ifaces);
+                        }
+                        intervals = next;
+                    }
+                },
                 _ = tick_stats.tick() => {
                     if let Err(e) = update_interface_state(&state).await {
                         tracing::warn!("update_interface_state failed: {e}");
@@ -46,5 +147,7 @@ pub fn spawn_supervisor(app: AppHandle, state: Arc<AppState>) -> JoinHandle<()>
                 }
             }
         }
-    })
+        monitors.stop_all(&state).await;
+    });
+    SupervisorTask { handle, shutdown }
 }
@@ -0,0 +1,439 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tauri::async_runtime::{self, JoinHandle};
+use tauri::AppHandle;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{MonitorConfig, MAX_MONITOR_WINDOW, MIN_MONITOR_INTERVAL_MS},
+    history::unix_millis,
+    model::monitor::{MonitorState, MonitorStateChangedPayload, MonitorStatus},
+    model::ping::PingSetting,
+    probe::event::{NullEventSink, ProbeEvent, ProbeEventSink},
+    probe::ping,
+    state::AppState,
+    stats::RttStats,
+};
+
+const MONITOR_HOP_LIMIT: u8 = 64;
+
+/// Outcomes of the most recent probes of a monitor (RTT, or `None` if lost)
+struct RollingWindow {
+    capacity: usize,
+    rtts: VecDeque<Option<f64>>,
+}
+
+impl RollingWindow {
+    fn new(capacity: u32) -> Self {
+        let capacity = capacity.clamp(1, MAX_MONITOR_WINDOW) as usize;
+        Self {
+            capacity,
+            rtts: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn push(&mut self, rtt_ms: Option<f64>) {
+        if self.rtts.len() == self.capacity {
+            self.rtts.pop_front();
+        }
+        self.rtts.push_back(rtt_ms);
+    }
+
+    fn stats(&self) -> RttStats {
+        RttStats::from_series(self.rtts.iter().copied())
+    }
+}
+
+/// Decide the state of a monitor from its thresholds and current statistics.
+fn evaluate(cfg: &MonitorConfig, status: &MonitorStatus) -> (MonitorState, String) {
+    let t = &cfg.thresholds;
+    if status.consecutive_failures >= t.down_after_failures.max(1) {
+        return (
+            MonitorState::Down,
+            format!("{} consecutive failures", status.consecutive_failures),
+        );
+    }
+    let w = &status.window;
+    if w.loss_pct > t.max_loss_pct {
+        return (
+            MonitorState::Degraded,
+            format!("loss {:.1}% > {:.1}%", w.loss_pct, t.max_loss_pct),
+        );
+    }
+    if let (Some(avg), Some(max)) = (w.avg, t.max_avg_rtt_ms) {
+        if avg > max as f64 {
+            return (
+                MonitorState::Degraded,
+                format!("average RTT {avg}ms > {max}ms"),
+            );
+        }
+    }
+    (MonitorState::Up, "ok".to_string())
+}
+
+/// Send a single probe to the monitor target.
+/// Returns the resolved address (if any) and the RTT or the failure message.
+async fn probe_once(
+    cfg: &MonitorConfig,
+    token: &CancellationToken,
+) -> (Option<IpAddr>, Result<f64, String>) {
+    let timeout = Duration::from_millis(cfg.timeout_ms);
+    let (ip_addr, hostname, port) =
+        match ping::resolve_target(&cfg.target, cfg.protocol, cfg.port, timeout).await {
+            Ok(target) => target,
+            Err(e) => return (None, Err(e.to_string())),
+        };
+    let src_ip = match crate::net::interface::select_source_ip(ip_addr, None, None) {
+        Ok(ip) => ip,
+        Err(e) => return (Some(ip_addr), Err(e.to_string())),
+    };
+    let setting = PingSetting {
+        ip_addr,
+        hostname,
+        port,
+        hop_limit: MONITOR_HOP_LIMIT,
+        protocol: cfg.protocol,
+        count: 1,
+        timeout_ms: cfg.timeout_ms,
+        send_rate_ms: 0,
+        interface: None,
+        src_ip: None,
+        http: None,
+        dns: None,
+        quic: None,
+        udp: None,
+        icmp: None,
+    };
+    let outcome = match ping::ping(&NullEventSink, &cfg.id, token, src_ip, setting).await {
+        Ok(stat) => match stat.samples.first() {
+            Some(s) if s.probe_status.is_ok() => s.rtt_ms.ok_or_else(|| "no RTT".to_string()),
+            Some(s) => Err(s.probe_status.message.clone()),
+            None => Err("no probe sent".to_string()),
+        },
+        Err(e) => Err(e.to_string()),
+    };
+    (Some(ip_addr), outcome)
+}
+
+fn initial_status(cfg: &MonitorConfig) -> MonitorStatus {
+    MonitorStatus {
+        id: cfg.id.clone(),
+        name: cfg.name.clone(),
+        target: cfg.target.clone(),
+        ip_addr: None,
+        protocol: cfg.protocol,
+        state: MonitorState::Unknown,
+        reason: "waiting for first probe".to_string(),
+        since_ms: unix_millis(SystemTime::now()),
+        last_checked_ms: None,
+        last_rtt_ms: None,
+        last_error: None,
+        consecutive_failures: 0,
+        sent: 0,
+        received: 0,
+        window: RttStats::default(),
+    }
+}
+
+/// Probe the target every `interval_ms` until `token` is cancelled,
+/// keeping the status in `AppState` and emitting `monitor:state_changed`.
+async fn run_monitor(
+    app: AppHandle,
+    state: Arc<AppState>,
+    cfg: MonitorConfig,
+    token: CancellationToken,
+) {
+    let mut status = initial_status(&cfg);
+    let mut window = RollingWindow::new(cfg.window);
+    state
+        .monitors
+        .lock()
+        .await
+        .insert(cfg.id.clone(), status.clone());
+
+    let mut tick = interval(Duration::from_millis(
+        cfg.interval_ms.max(MIN_MONITOR_INTERVAL_MS),
+    ));
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = tick.tick() => {}
+        }
+        let (ip_addr, outcome) = probe_once(&cfg, &token).await;
+        if token.is_cancelled() {
+            break;
+        }
+        let now = unix_millis(SystemTime::now());
+        let rtt_ms = outcome.as_ref().ok().copied();
+        window.push(rtt_ms);
+        state.ping_metrics.record(
+            &cfg.name,
+            &cfg.target,
+            rtt_ms.map(|ms| Duration::from_secs_f64(ms / 1000.0)),
+        );
+
+        status.ip_addr = ip_addr.or(status.ip_addr);
+        status.last_checked_ms = Some(now);
+        status.sent += 1;
+        match outcome {
+            Ok(rtt) => {
+                status.received += 1;
+                status.consecutive_failures = 0;
+                status.last_rtt_ms = Some(rtt);
+                status.last_error = None;
+            }
+            Err(e) => {
+                status.consecutive_failures += 1;
+                status.last_rtt_ms = None;
+                status.last_error = Some(e);
+            }
+        }
+        status.window = window.stats();
+        let (next, reason) = evaluate(&cfg, &status);
+        status.reason = reason;
+        let previous = status.state;
+        if next != previous {
+            status.state = next;
+            status.since_ms = now;
+            tracing::info!(
+                "monitor {} ({}): {:?} -> {:?} ({})",
+                cfg.name,
+                cfg.target,
+                previous,
+                next,
+                status.reason
+            );
+            ProbeEventSink::emit(
+                &app,
+                ProbeEvent::MonitorStateChanged(MonitorStateChangedPayload {
+                    previous,
+                    status: status.clone(),
+                }),
+            );
+        }
+        state
+            .monitors
+            .lock()
+            .await
+            .insert(cfg.id.clone(), status.clone());
+    }
+}
+
+struct RunningMonitor {
+    config: MonitorConfig,
+    token: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+impl RunningMonitor {
+    async fn stop(self, state: &AppState) {
+        self.token.cancel();
+        if let Err(e) = self.handle.await {
+            tracing::warn!("monitor {} ended abnormally: {e}", self.config.name);
+        }
+        state.monitors.lock().await.remove(&self.config.id);
+        state
+            .ping_metrics
+            .remove(&self.config.name, &self.config.target);
+    }
+}
+
+/// Monitors started by the supervisor, keyed by id
+#[derive(Default)]
+pub struct MonitorSet {
+    running: HashMap<String, RunningMonitor>,
+}
+
+impl MonitorSet {
+    /// Start, stop and restart monitors so that exactly the enabled entries
+    /// of `configs` are running. Monitors whose settings changed are restarted
+    /// with fresh statistics.
+    pub async fn apply(
+        &mut self,
+        app: &AppHandle,
+        state: &Arc<AppState>,
+        configs: &[MonitorConfig],
+        parent: &CancellationToken,
+    ) {
+        let wanted: HashMap<&str, &MonitorConfig> = configs
+            .iter()
+            .filter(|m| m.enabled)
+            .map(|m| (m.id.as_str(), m))
+            .collect();
+        let stale: Vec<String> = self
+            .running
+            .iter()
+            .filter(|(id, m)| wanted.get(id.as_str()) != Some(&&m.config))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in stale {
+            if let Some(m) = self.running.remove(&id) {
+                m.stop(state).await;
+            }
+        }
+        for (id, cfg) in wanted {
+            if self.running.contains_key(id) {
+                continue;
+            }
+            let token = parent.child_token();
+            let handle = async_runtime::spawn(run_monitor(
+                app.clone(),
+                state.clone(),
+                cfg.clone(),
+                token.clone(),
+            ));
+            self.running.insert(
+                id.to_string(),
+                RunningMonitor {
+                    config: cfg.clone(),
+                    token,
+                    handle,
+                },
+            );
+        }
+    }
+
+    /// Stop every monitor.
+    pub async fn stop_all(&mut self, state: &AppState) {
+        for (_, m) in self.running.drain() {
+            m.stop(state).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MonitorThresholds;
+
+    fn window(series: &[Option<f64>]) -> RttStats {
+        let mut window = RollingWindow::new(series.len() as u32);
+        for &rtt in series {
+            window.push(rtt);
+        }
+        window.stats()
+    }
+
+    /// down_after_failures, max_loss_pct, max_avg_rtt_ms, consecutive failures,
+    /// window and the expected state
+    type Case = (
+        u32,
+        f64,
+        Option<u64>,
+        u32,
+        &'static [Option<f64>],
+        MonitorState,
+    );
+
+    #[test]
+    fn evaluate_thresholds() {
+        const OK: &[Option<f64>] = &[Some(10.0), Some(20.0), Some(30.0), Some(40.0)];
+        // One lost probe in four: 25% loss
+        const LOSSY: &[Option<f64>] = &[Some(10.0), None, Some(20.0), Some(30.0)];
+        // One lost probe in five: exactly 20% loss
+        const AT_LOSS: &[Option<f64>] = &[Some(10.0), None, Some(20.0), Some(30.0), Some(40.0)];
+
+        #[rustfmt::skip]
+        let cases: [Case; 14] = [
+            // down_after_failures boundary
+            (3, 20.0, None, 0, OK, MonitorState::Up),
+            (3, 20.0, None, 2, OK, MonitorState::Up),
+            (3, 20.0, None, 3, OK, MonitorState::Down),
+            (3, 20.0, None, 4, OK, MonitorState::Down),
+            (1, 20.0, None, 1, OK, MonitorState::Down),
+            // 0 behaves as 1
+            (0, 20.0, None, 0, OK, MonitorState::Up),
+            (0, 20.0, None, 1, OK, MonitorState::Down),
+            // Loss threshold is exclusive
+            (3, 20.0, None, 0, AT_LOSS, MonitorState::Up),
+            (3, 20.0, None, 0, LOSSY, MonitorState::Degraded),
+            (3, 100.0, None, 0, &[None, None], MonitorState::Up),
+            // Average RTT threshold (average 25ms) is exclusive
+            (3, 20.0, Some(25), 0, OK, MonitorState::Up),
+            (3, 20.0, Some(24), 0, OK, MonitorState::Degraded),
+            (3, 100.0, Some(1), 0, &[None], MonitorState::Up),
+            // Down wins over degraded
+            (3, 0.0, Some(1), 3, LOSSY, MonitorState::Down),
+        ];
+        for (i, (down_after_failures, max_loss_pct, max_avg_rtt_ms, failures, series, expected)) in
+            cases.into_iter().enumerate()
+        {
+            let cfg = MonitorConfig {
+                thresholds: MonitorThresholds {
+                    max_loss_pct,
+                    max_avg_rtt_ms,
+                    down_after_failures,
+                },
+                ..Default::default()
+            };
+            let mut status = initial_status(&cfg);
+            status.consecutive_failures = failures;
+            status.window = window(series);
+            let (state, reason) = evaluate(&cfg, &status);
+            assert_eq!(state, expected, "case {i}: {reason}");
+        }
+    }
+
+    #[test]
+    fn evaluate_reasons() {
+        let mut cfg = MonitorConfig::default();
+        cfg.thresholds.max_avg_rtt_ms = Some(10);
+        let mut status = initial_status(&cfg);
+        status.window = window(&[Some(12.5)]);
+        assert_eq!(evaluate(&cfg, &status).1, "average RTT 12.5ms > 10ms");
+        status.window = window(&[Some(1.0), None]);
+        assert_eq!(evaluate(&cfg, &status).1, "loss 50.0% > 20.0%");
+        status.consecutive_failures = 5;
+        assert_eq!(evaluate(&cfg, &status).1, "5 consecutive failures");
+        status.consecutive_failures = 0;
+        status.window = window(&[Some(1.0)]);
+        assert_eq!(
+            evaluate(&cfg, &status),
+            (MonitorState::Up, "ok".to_string())
+        );
+    }
+
+    #[test]
+    fn window_capacity() {
+        for (requested, capacity) in [
+            (0, 1),
+            (1, 1),
+            (5, 5),
+            (MAX_MONITOR_WINDOW, MAX_MONITOR_WINDOW as usize),
+            (MAX_MONITOR_WINDOW + 1, MAX_MONITOR_WINDOW as usize),
+            (u32::MAX, MAX_MONITOR_WINDOW as usize),
+        ] {
+            assert_eq!(
+                RollingWindow::new(requested).capacity,
+                capacity,
+                "{requested}"
+            );
+        }
+    }
+
+    #[test]
+    fn window_keeps_the_latest_probes() {
+        let mut window = RollingWindow::new(3);
+        assert_eq!(window.stats().transmitted, 0);
+        for rtt in [None, Some(1.0), Some(2.0), Some(3.0), Some(4.0)] {
+            window.push(rtt);
+        }
+        assert_eq!(window.rtts, [Some(2.0), Some(3.0), Some(4.0)]);
+        let stats = window.stats();
+        assert_eq!(stats.transmitted, 3);
+        assert_eq!(stats.loss_pct, 0.0);
+        assert_eq!(stats.avg, Some(3.0));
+
+        let mut window = RollingWindow::new(0);
+        window.push(Some(1.0));
+        window.push(None);
+        assert_eq!(window.rtts, [None]);
+        assert_eq!(window.stats().loss_pct, 100.0);
+    }
+}
@@ -1,4 +1,4 @@
-use super::SocketFamily;
+use super::{SocketFamily, SocketIcmpError};
 use socket2::{Domain, Protocol, Socket, Type as SockType};
 use std::io;
 use std::net::{SocketAddr, UdpSocket as StdUdpSocket};
@@ -69,6 +69,13 @@ pub struct IcmpConfig {
     pub sock_type_hint: IcmpSocketType,
     /// FreeBSD only: optional FIB (Forwarding Information Base) support.
     pub fib: Option<u32>,
+    /// Set the Don't-Fragment bit (IPv4) / IPV6_DONTFRAG (IPv6), e.g. for path MTU discovery.
+    pub dont_fragment: bool,
+    /// Ask the kernel for the TTL / hop limit of received packets.
+    pub recv_hop_limit: bool,
+    /// Queue ICMP errors for sent packets on Linux DGRAM sockets, which do not
+    /// receive them otherwise. RAW sockets receive the error messages themselves.
+    pub recv_icmp_errors: bool,
 }
 
 impl IcmpConfig {
@@ -87,6 +94,9 @@ impl IcmpConfig {
             interface: None,
             sock_type_hint: IcmpSocketType::Dgram,
             fib: None,
+            dont_fragment: false,
+            recv_hop_limit: false,
+            recv_icmp_errors: false,
         }
     }
 
@@ -137,6 +147,24 @@ impl IcmpConfig {
         self.fib = Some(fib);
         self
     }
+
+    /// Forbid fragmentation of outgoing packets.
+    pub fn with_dont_fragment(mut self, on: bool) -> Self {
+        self.dont_fragment = on;
+        self
+    }
+
+    /// Report the TTL / hop limit of received packets. (see `recv_from_with_hop_limit`)
+    pub fn with_recv_hop_limit(mut self, on: bool) -> Self {
+        self.recv_hop_limit = on;
+        self
+    }
+
+    /// Queue ICMP errors for sent packets. (see `recv_icmp_error`)
+    pub fn with_recv_icmp_errors(mut self, on: bool) -> Self {
+        self.recv_icmp_errors = on;
+        self
+    }
 }
 
 /// Asynchronous ICMP socket built on Tokio.
@@ -183,6 +211,19 @@ impl AsyncIcmpSocket {
         if let Some(timeout) = config.write_timeout {
             socket.set_write_timeout(Some(timeout))?;
         }
+        if config.dont_fragment {
+            super::os::set_dont_fragment(&socket, config.socket_family)?;
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd"
+        ))]
+        if config.recv_hop_limit {
+            super::os::set_recv_hop_limit(&socket, config.socket_family)?;
+        }
         // FreeBSD only: optional FIB support
         #[cfg(target_os = "freebsd")]
         if let Some(fib) = config.fib {
@@ -200,6 +241,10 @@ impl AsyncIcmpSocket {
         }
 
         let sock_type = socket.r#type()?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if config.recv_icmp_errors && sock_type == SockType::DGRAM {
+            super::os::set_recv_err(&socket, config.socket_family)?;
+        }
 
         // Convert socket2::Socket into std::net::UdpSocket
         #[cfg(windows)]
@@ -235,6 +280,77 @@ impl AsyncIcmpSocket {
         self.inner.recv_from(buf).await
     }
 
+    /// Receive a packet asynchronously, along with the TTL (IPv4) or hop limit (IPv6)
+    /// it arrived with. The hop limit is only reported by sockets created with
+    /// `with_recv_hop_limit`.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    ))]
+    pub async fn recv_from_with_hop_limit(
+        &self,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Option<u8>)> {
+        use std::os::fd::AsRawFd;
+        let fd = self.inner.as_raw_fd();
+        self.inner
+            .async_io(tokio::io::Interest::READABLE, || {
+                super::os::recv_from_with_hop_limit(fd, buf)
+            })
+            .await
+    }
+
+    /// Receive a packet asynchronously. The hop limit is not reported on this platform.
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    )))]
+    pub async fn recv_from_with_hop_limit(
+        &self,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Option<u8>)> {
+        let (n, addr) = self.inner.recv_from(buf).await?;
+        Ok((n, addr, None))
+    }
+
+    /// Wait for the next ICMP error queued for a sent packet, copying the packet that
+    /// caused it into `buf`. Only Linux DGRAM sockets created with `with_recv_icmp_errors`
+    /// queue errors; on other sockets this never completes.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub async fn recv_icmp_error(&self, buf: &mut [u8]) -> io::Result<SocketIcmpError> {
+        if self.socket_type.is_raw() {
+            return std::future::pending().await;
+        }
+        use std::os::fd::AsRawFd;
+        let fd = self.inner.as_raw_fd();
+        self.inner
+            .async_io(tokio::io::Interest::ERROR, || {
+                super::os::recv_icmp_error(fd, buf)
+            })
+            .await
+    }
+
+    /// Wait for the next queued ICMP error. Never completes on this platform.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub async fn recv_icmp_error(&self, _buf: &mut [u8]) -> io::Result<SocketIcmpError> {
+        std::future::pending().await
+    }
+
+    /// Change the TTL (IPv4) or hop limit (IPv6) of packets sent from now on.
+    pub fn set_hop_limit(&self, hops: u32) -> io::Result<()> {
+        let socket = socket2::SockRef::from(&self.inner);
+        match self.socket_family {
+            SocketFamily::IPV4 => socket.set_ttl(hops),
+            SocketFamily::IPV6 => socket.set_unicast_hops_v6(hops),
+        }
+    }
+
     /// Retrieve the local address.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.inner.local_addr()
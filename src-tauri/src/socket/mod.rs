@@ -9,6 +9,24 @@ pub mod udp;
 
 use std::net::{IpAddr, SocketAddr};
 
+/// ICMP error for a datagram sent on a socket, read from the socket error queue
+/// (Linux `IP_RECVERR` / `IPV6_RECVERR`).
+#[derive(Debug, Clone, Copy)]
+pub struct SocketIcmpError {
+    /// Whether the error is an ICMPv6 message
+    pub is_v6: bool,
+    pub icmp_type: u8,
+    pub icmp_code: u8,
+    /// Info field of the message, e.g. the next-hop MTU of Fragmentation Needed
+    pub info: u32,
+    /// Router or host that sent the error
+    pub offender: Option<IpAddr>,
+    /// Destination of the datagram that caused the error
+    pub dst: Option<SocketAddr>,
+    /// Bytes of the datagram's payload copied into the receive buffer
+    pub len: usize,
+}
+
 /// Represents the socket address family (IPv4 or IPv6)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SocketFamily {
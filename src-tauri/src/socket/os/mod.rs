@@ -1,2 +1,274 @@
 #[cfg(windows)]
 pub mod windows;
+
+use super::{SocketFamily, SocketIcmpError};
+use socket2::Socket;
+use std::io;
+
+/// Set the Don't-Fragment bit (IPv4) or IPV6_DONTFRAG (IPv6) on outgoing packets.
+///
+/// Linux uses the `*_PMTUDISC_PROBE` mode, which sets DF but ignores the cached
+/// path MTU, so packets larger than the cached value are still sent.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn set_dont_fragment(socket: &Socket, family: SocketFamily) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let fd = socket.as_raw_fd();
+    match family {
+        SocketFamily::IPV4 => setsockopt_int(
+            fd,
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_PROBE,
+        ),
+        SocketFamily::IPV6 => {
+            setsockopt_int(
+                fd,
+                libc::IPPROTO_IPV6,
+                libc::IPV6_MTU_DISCOVER,
+                libc::IPV6_PMTUDISC_PROBE,
+            )?;
+            setsockopt_int(fd, libc::IPPROTO_IPV6, libc::IPV6_DONTFRAG, 1)
+        }
+    }
+}
+
+/// Set the Don't-Fragment bit (IPv4) or IPV6_DONTFRAG (IPv6) on outgoing packets.
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+pub(crate) fn set_dont_fragment(socket: &Socket, family: SocketFamily) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let fd = socket.as_raw_fd();
+    match family {
+        SocketFamily::IPV4 => setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_DONTFRAG, 1),
+        SocketFamily::IPV6 => setsockopt_int(fd, libc::IPPROTO_IPV6, libc::IPV6_DONTFRAG, 1),
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    windows
+)))]
+pub(crate) fn set_dont_fragment(_socket: &Socket, _family: SocketFamily) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Don't-Fragment is not supported on this platform",
+    ))
+}
+
+/// Set the Don't-Fragment bit (IPv4) or IPV6_DONTFRAG (IPv6) on outgoing packets.
+#[cfg(windows)]
+pub(crate) fn set_dont_fragment(socket: &Socket, family: SocketFamily) -> io::Result<()> {
+    use std::os::windows::io::AsRawSocket;
+    use windows_sys::Win32::Networking::WinSock as sock;
+    let raw = socket.as_raw_socket() as sock::SOCKET;
+    let (level, optname) = match family {
+        SocketFamily::IPV4 => (sock::IPPROTO_IP, sock::IP_DONTFRAGMENT),
+        SocketFamily::IPV6 => (sock::IPPROTO_IPV6, sock::IPV6_DONTFRAG),
+    };
+    unsafe { windows::setsockopt(raw, level as i32, optname as i32, 1u32) }
+}
+
+/// Report the TTL (IPv4) or hop limit (IPv6) of received packets as control messages,
+/// read by [`recv_from_with_hop_limit`].
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+))]
+pub(crate) fn set_recv_hop_limit(socket: &Socket, family: SocketFamily) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let fd = socket.as_raw_fd();
+    match family {
+        SocketFamily::IPV4 => setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_RECVTTL, 1),
+        SocketFamily::IPV6 => setsockopt_int(fd, libc::IPPROTO_IPV6, libc::IPV6_RECVHOPLIMIT, 1),
+    }
+}
+
+/// Receive a datagram along with its TTL or hop limit, if the kernel attached one.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+))]
+pub(crate) fn recv_from_with_hop_limit(
+    fd: std::os::fd::RawFd,
+    buf: &mut [u8],
+) -> io::Result<(usize, std::net::SocketAddr, Option<u8>)> {
+    // u64 elements keep the buffer aligned for cmsghdr
+    let mut control = [0u64; 16];
+    let ((n, hop_limit), addr) = unsafe {
+        socket2::SockAddr::try_init(|storage, len| {
+            let mut iov = libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            };
+            let mut msg: libc::msghdr = std::mem::zeroed();
+            msg.msg_name = storage.cast();
+            msg.msg_namelen = *len;
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr().cast();
+            msg.msg_controllen = std::mem::size_of_val(&control) as _;
+            let n = libc::recvmsg(fd, &mut msg, 0);
+            if n < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            *len = msg.msg_namelen;
+
+            let mut hop_limit = None;
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                let hdr = &*cmsg;
+                // Linux reports IP_TTL as an int, the BSDs IP_RECVTTL as a single byte
+                let is_hop_limit = (hdr.cmsg_level == libc::IPPROTO_IP
+                    && (hdr.cmsg_type == libc::IP_TTL || hdr.cmsg_type == libc::IP_RECVTTL))
+                    || (hdr.cmsg_level == libc::IPPROTO_IPV6
+                        && hdr.cmsg_type == libc::IPV6_HOPLIMIT);
+                if is_hop_limit {
+                    let data = libc::CMSG_DATA(cmsg);
+                    let data_len = hdr.cmsg_len as usize - (data as usize - cmsg as usize);
+                    hop_limit = Some(if data_len >= std::mem::size_of::<libc::c_int>() {
+                        std::ptr::read_unaligned(data as *const libc::c_int) as u8
+                    } else {
+                        *data
+                    });
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+            Ok((n as usize, hop_limit))
+        })?
+    };
+    let addr = addr
+        .as_socket()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an IP source address"))?;
+    Ok((n, addr, hop_limit))
+}
+
+/// Queue ICMP errors for sent datagrams on the socket error queue, read by
+/// [`recv_icmp_error`]. Unconnected sockets learn of ICMP errors only this way.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn set_recv_err(socket: &Socket, family: SocketFamily) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let fd = socket.as_raw_fd();
+    match family {
+        SocketFamily::IPV4 => setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_RECVERR, 1),
+        SocketFamily::IPV6 => setsockopt_int(fd, libc::IPPROTO_IPV6, libc::IPV6_RECVERR, 1),
+    }
+}
+
+/// Read the next ICMP error from the socket error queue, copying the payload of the
+/// datagram that caused it into `buf`. Fails with `WouldBlock` when the queue is empty;
+/// errors of local origin are skipped.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn recv_icmp_error(
+    fd: std::os::fd::RawFd,
+    buf: &mut [u8],
+) -> io::Result<SocketIcmpError> {
+    loop {
+        // u64 elements keep the buffer aligned for cmsghdr
+        let mut control = [0u64; 32];
+        let ((n, ee, offender), dst) = unsafe {
+            socket2::SockAddr::try_init(|storage, len| {
+                let mut iov = libc::iovec {
+                    iov_base: buf.as_mut_ptr().cast(),
+                    iov_len: buf.len(),
+                };
+                let mut msg: libc::msghdr = std::mem::zeroed();
+                msg.msg_name = storage.cast();
+                msg.msg_namelen = *len;
+                msg.msg_iov = &mut iov;
+                msg.msg_iovlen = 1;
+                msg.msg_control = control.as_mut_ptr().cast();
+                msg.msg_controllen = std::mem::size_of_val(&control) as _;
+                let n = libc::recvmsg(fd, &mut msg, libc::MSG_ERRQUEUE);
+                if n < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                *len = msg.msg_namelen;
+
+                let mut found = None;
+                let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+                while !cmsg.is_null() {
+                    let hdr = &*cmsg;
+                    if (hdr.cmsg_level == libc::IPPROTO_IP && hdr.cmsg_type == libc::IP_RECVERR)
+                        || (hdr.cmsg_level == libc::IPPROTO_IPV6
+                            && hdr.cmsg_type == libc::IPV6_RECVERR)
+                    {
+                        let ee_ptr = libc::CMSG_DATA(cmsg) as *const libc::sock_extended_err;
+                        let ee = std::ptr::read_unaligned(ee_ptr);
+                        let offender = sockaddr_ip(libc::SO_EE_OFFENDER(ee_ptr));
+                        found = Some((ee, offender));
+                    }
+                    cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+                }
+                let (ee, offender) = found.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "error queue entry without details",
+                    )
+                })?;
+                Ok((n as usize, ee, offender))
+            })?
+        };
+        let is_v6 = match ee.ee_origin {
+            libc::SO_EE_ORIGIN_ICMP => false,
+            libc::SO_EE_ORIGIN_ICMP6 => true,
+            _ => continue,
+        };
+        return Ok(SocketIcmpError {
+            is_v6,
+            icmp_type: ee.ee_type,
+            icmp_code: ee.ee_code,
+            info: ee.ee_info,
+            offender,
+            dst: dst.as_socket(),
+            len: n,
+        });
+    }
+}
+
+/// IP address of a `sockaddr_in` or `sockaddr_in6`, `None` for other families
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn sockaddr_ip(addr: *const libc::sockaddr) -> Option<std::net::IpAddr> {
+    match std::ptr::read_unaligned(addr).sa_family as libc::c_int {
+        libc::AF_INET => {
+            let sin = std::ptr::read_unaligned(addr as *const libc::sockaddr_in);
+            Some(std::net::Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)).into())
+        }
+        libc::AF_INET6 => {
+            let sin6 = std::ptr::read_unaligned(addr as *const libc::sockaddr_in6);
+            Some(std::net::Ipv6Addr::from(sin6.sin6_addr.s6_addr).into())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn setsockopt_int(
+    fd: std::os::fd::RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
@@ -1,26 +1,67 @@
-use super::tls::SkipServerVerification;
+use super::tls::{native_root_store, RecordingVerifier};
 use anyhow::Result;
 use quinn::{ClientConfig, Endpoint as QuinnEndpoint};
-use rustls::{ClientConfig as RustlsClientConfig, RootCertStore};
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use rustls::client::danger::ServerCertVerifier;
+use rustls::pki_types::CertificateDer;
+use rustls::ClientConfig as RustlsClientConfig;
+use socket2::{Protocol, Socket, Type as SockType};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
-/// Create a QUIC client configuration with optional certificate verification skipping and ALPN protocols.
-pub fn quic_client_config(skip_verify: bool, alpn: &Vec<Vec<u8>>) -> Result<ClientConfig> {
-    let mut roots = RootCertStore::empty();
-    for cert in rustls_native_certs::load_native_certs()? {
-        let _ = roots.add(cert);
+/// QUIC version offered by the client (RFC 9000). A server that does not support it
+/// answers with version negotiation and the connection fails, so an established
+/// connection always runs this version.
+pub const QUIC_VERSION: u32 = 0x0000_0001;
+
+/// Human readable name of a QUIC version number
+pub fn version_name(version: u32) -> String {
+    match version {
+        0x0000_0001 => "v1".to_string(),
+        0x6b33_43cf => "v2".to_string(),
+        v if v & 0xffff_ff00 == 0xff00_0000 => format!("draft-{}", v & 0xff),
+        v => format!("0x{:08x}", v),
     }
+}
+
+/// Create a QUIC client configuration with the given ALPN protocols.
+/// The certificate is checked by `verifier`, or against the native root store if `None`.
+pub fn quic_client_config(
+    verifier: Option<Arc<dyn ServerCertVerifier>>,
+    alpn: &[Vec<u8>],
+) -> Result<ClientConfig> {
+    let roots = native_root_store()?;
     let mut tls = RustlsClientConfig::builder()
         .with_root_certificates(roots)
         .with_no_client_auth();
-    if skip_verify {
-        tls.dangerous()
-            .set_certificate_verifier(SkipServerVerification::new());
+    if let Some(verifier) = verifier {
+        tls.dangerous().set_certificate_verifier(verifier);
     }
     tls.enable_early_data = true;
-    tls.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+    tls.alpn_protocols = alpn.to_vec();
     let client_conf = quinn::crypto::rustls::QuicClientConfig::try_from(tls)?;
-    Ok(ClientConfig::new(Arc::new(client_conf)))
+    let mut config = ClientConfig::new(Arc::new(client_conf));
+    config.version(QUIC_VERSION);
+    Ok(config)
+}
+
+/// ALPN protocol negotiated on an established connection
+pub fn negotiated_alpn(conn: &quinn::Connection) -> Option<String> {
+    conn.handshake_data()?
+        .downcast::<quinn::crypto::rustls::HandshakeData>()
+        .ok()?
+        .protocol
+        .map(|p| String::from_utf8_lossy(&p).into_owned())
+}
+
+/// Certificate chain presented by the server, leaf first
+pub fn peer_certificates(conn: &quinn::Connection) -> Vec<CertificateDer<'static>> {
+    conn.peer_identity()
+        .and_then(|id| id.downcast::<Vec<CertificateDer<'static>>>().ok())
+        .map(|chain| *chain)
+        .unwrap_or_default()
 }
 
 /// Configuration options for a QUIC socket.
@@ -29,29 +70,73 @@ pub struct QuicConfig {
     pub skip_verify: bool,
     pub alpn: Vec<Vec<u8>>,
     pub family: super::SocketFamily,
+    /// Local address to bind (default: unspecified address of `family`, ephemeral port).
+    pub bind_addr: Option<SocketAddr>,
+    /// Bind to a specific interface (Linux only).
+    pub bind_device: Option<String>,
 }
 
 /// Asynchronous QUIC socket built on quinn and tokio.
 #[derive(Debug)]
 pub struct AsyncQuicSocket {
     inner: QuinnEndpoint,
+    /// Set when `skip_verify` is enabled, to still tell whether the certificate is trusted
+    verifier: Option<Arc<RecordingVerifier>>,
 }
 
 impl AsyncQuicSocket {
     /// Create an asynchronous QUIC socket from the given configuration.
     pub fn from_config(config: &QuicConfig) -> Result<Self> {
-        let client_cfg = quic_client_config(config.skip_verify, &config.alpn)?;
-        let mut endpoint = QuinnEndpoint::client(
-            (if config.family.is_v6() {
-                "[::]:0"
+        let verifier = config
+            .skip_verify
+            .then(|| RecordingVerifier::new(Arc::new(rustls::crypto::ring::default_provider())));
+        let client_cfg = quic_client_config(
+            verifier.clone().map(|v| v as Arc<dyn ServerCertVerifier>),
+            &config.alpn,
+        )?;
+        let bind_addr = config.bind_addr.unwrap_or_else(|| {
+            if config.family.is_v6() {
+                SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
             } else {
-                "0.0.0.0:0"
-            })
-            .parse()
-            .unwrap(),
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+            }
+        });
+        let socket = Socket::new(
+            config.family.to_domain(),
+            SockType::DGRAM,
+            Some(Protocol::UDP),
+        )?;
+
+        // Linux: optional interface name
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "fuchsia"))]
+        if let Some(iface) = &config.bind_device {
+            socket.bind_device(Some(iface.as_bytes()))?;
+        }
+
+        socket.bind(&bind_addr.into())?;
+        let runtime =
+            quinn::default_runtime().ok_or_else(|| anyhow::anyhow!("no async runtime found"))?;
+        let mut endpoint = QuinnEndpoint::new(
+            quinn::EndpointConfig::default(),
+            None,
+            socket.into(),
+            runtime,
         )?;
         endpoint.set_default_client_config(client_cfg);
-        Ok(Self { inner: endpoint })
+        Ok(Self {
+            inner: endpoint,
+            verifier,
+        })
+    }
+
+    /// Whether the certificate of the last established connection validates against
+    /// the native root store. Always `Ok` without `skip_verify`, as an untrusted
+    /// certificate fails the handshake instead.
+    pub fn cert_validation(&self) -> Result<(), String> {
+        match &self.verifier {
+            Some(verifier) => verifier.result(),
+            None => Ok(()),
+        }
     }
 
     /// Connect to the specified remote address using QUIC.
@@ -74,4 +159,21 @@ impl AsyncQuicSocket {
         let conn = tokio::time::timeout(timeout, conn_fut).await??;
         Ok(conn)
     }
+
+    /// Connect again with 0-RTT, using the session ticket of an earlier connection
+    /// to the same server from this socket. Returns the connection and whether the
+    /// server accepted the early data, or `None` if no ticket allows early data.
+    pub async fn connect_0rtt_timeout(
+        &self,
+        remote_addr: &SocketAddr,
+        server_name: &str,
+        timeout: Duration,
+    ) -> Result<Option<(quinn::Connection, bool)>> {
+        let connecting = self.inner.connect(*remote_addr, server_name)?;
+        let Ok((conn, accepted)) = connecting.into_0rtt() else {
+            return Ok(None);
+        };
+        let accepted = tokio::time::timeout(timeout, accepted).await?;
+        Ok(Some((conn, accepted)))
+    }
 }
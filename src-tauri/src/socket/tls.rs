@@ -1,7 +1,10 @@
 use anyhow::Result;
-use rustls::client::danger::ServerCertVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
 use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
-use std::sync::Arc;
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use std::sync::{Arc, Mutex};
 
 /// Dummy certificate verifier that treats any certificate as valid.
 /// NOTE, such verification is vulnerable to MITM attacks, but convenient for testing.
@@ -58,3 +61,116 @@ impl ServerCertVerifier for SkipServerVerification {
         self.0.signature_verification_algorithms.supported_schemes()
     }
 }
+
+/// Certificate verifier that accepts any chain so that untrusted servers can be inspected too,
+/// recording whether the chain validates against the native root store.
+#[derive(Debug)]
+pub struct RecordingVerifier {
+    provider: Arc<CryptoProvider>,
+    /// `None` if no native roots could be loaded
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    result: Mutex<Option<Result<(), String>>>,
+}
+
+impl RecordingVerifier {
+    pub fn new(provider: Arc<CryptoProvider>) -> Arc<Self> {
+        let webpki = native_root_store().ok().and_then(|roots| {
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .ok()
+        });
+        Arc::new(Self {
+            provider,
+            webpki,
+            result: Mutex::new(None),
+        })
+    }
+
+    /// Outcome of validating the chain, or an error if no certificate was verified yet
+    pub fn result(&self) -> Result<(), String> {
+        self.result
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| Err("certificate was not verified".to_string()))
+    }
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = match &self.webpki {
+            Some(webpki) => webpki
+                .verify_server_cert(end_entity, intermediates, server_name, ocsp, now)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            None => Err("no native root certificates available".to_string()),
+        };
+        *self.result.lock().unwrap() = Some(result);
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Load the root certificates of the OS trust store, skipping any that fail to parse.
+pub fn native_root_store() -> Result<rustls::RootCertStore> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs()? {
+        let _ = roots.add(cert);
+    }
+    Ok(roots)
+}
+
+/// Create a rustls client configuration over the native root store,
+/// optionally skipping certificate verification, with the given ALPN protocols.
+pub fn tls_client_config(skip_verify: bool, alpn: &[&[u8]]) -> Result<Arc<rustls::ClientConfig>> {
+    let roots = native_root_store()?;
+    let mut tls = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    if skip_verify {
+        tls.dangerous()
+            .set_certificate_verifier(SkipServerVerification::new());
+    }
+    tls.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+    Ok(Arc::new(tls))
+}
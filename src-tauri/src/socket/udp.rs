@@ -1,8 +1,9 @@
-use super::SocketFamily;
+use super::{SocketFamily, SocketIcmpError};
 use socket2::{Domain, Protocol, Socket, Type as SockType};
 use std::io;
 use std::net::{SocketAddr, UdpSocket as StdUdpSocket};
 use std::time::Duration;
+use tokio::io::Interest;
 use tokio::net::UdpSocket;
 
 /// UDP socket type, either DGRAM or RAW.
@@ -55,6 +56,8 @@ pub struct UdpConfig {
     pub write_timeout: Option<Duration>,
     /// Bind to a specific interface (Linux only).
     pub bind_device: Option<String>,
+    /// Queue ICMP errors for sent datagrams, read with `recv_icmp_error` (Linux only).
+    pub recv_icmp_errors: bool,
 }
 
 impl Default for UdpConfig {
@@ -70,6 +73,7 @@ impl Default for UdpConfig {
             read_timeout: None,
             write_timeout: None,
             bind_device: None,
+            recv_icmp_errors: false,
         }
     }
 }
@@ -127,6 +131,12 @@ impl UdpConfig {
         self.bind_device = Some(iface.into());
         self
     }
+
+    /// Queue ICMP errors for sent datagrams (Linux only).
+    pub fn with_recv_icmp_errors(mut self, on: bool) -> Self {
+        self.recv_icmp_errors = on;
+        self
+    }
 }
 
 /// Asynchronous UDP socket built on top of Tokio.
@@ -171,6 +181,10 @@ impl AsyncUdpSocket {
         if let Some(iface) = &config.bind_device {
             socket.bind_device(Some(iface.as_bytes()))?;
         }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if config.recv_icmp_errors {
+            super::os::set_recv_err(&socket, config.socket_family)?;
+        }
 
         // bind to the specified address if provided
         if let Some(addr) = config.bind_addr {
@@ -244,6 +258,67 @@ impl AsyncUdpSocket {
         self.inner.recv_from(buf).await
     }
 
+    /// Connect to `target`, so that only its datagrams are received and
+    /// ICMP errors for sent datagrams are reported by `send`/`recv`.
+    pub async fn connect(&self, target: SocketAddr) -> io::Result<()> {
+        self.inner.connect(target).await
+    }
+
+    /// Send data to the connected address.
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf).await
+    }
+
+    /// Receive data from the connected address, or the error of an ICMP message
+    /// for a sent datagram (e.g. `ConnectionRefused` for Port Unreachable).
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        // A pending socket error only raises error readiness on Linux, not readable
+        self.inner
+            .async_io(Interest::READABLE | Interest::ERROR, || {
+                if let Some(e) = self.inner.take_error()? {
+                    return Err(e);
+                }
+                self.inner.try_recv(buf)
+            })
+            .await
+    }
+
+    /// Take the next queued ICMP error without waiting, copying the payload of the
+    /// datagram that caused it into `buf`. Only sockets created with
+    /// `with_recv_icmp_errors` queue errors.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn take_icmp_error(&self, buf: &mut [u8]) -> io::Result<Option<SocketIcmpError>> {
+        use std::os::fd::AsRawFd;
+        match super::os::recv_icmp_error(self.inner.as_raw_fd(), buf) {
+            Ok(error) => Ok(Some(error)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// ICMP errors are not queued on this platform.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn take_icmp_error(&self, _buf: &mut [u8]) -> io::Result<Option<SocketIcmpError>> {
+        Ok(None)
+    }
+
+    /// Wait for the next queued ICMP error. (see `take_icmp_error`)
+    /// Never completes on platforms without an error queue.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub async fn recv_icmp_error(&self, buf: &mut [u8]) -> io::Result<SocketIcmpError> {
+        use std::os::fd::AsRawFd;
+        let fd = self.inner.as_raw_fd();
+        self.inner
+            .async_io(Interest::ERROR, || super::os::recv_icmp_error(fd, buf))
+            .await
+    }
+
+    /// Wait for the next queued ICMP error. Never completes on this platform.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub async fn recv_icmp_error(&self, _buf: &mut [u8]) -> io::Result<SocketIcmpError> {
+        std::future::pending().await
+    }
+
     /// Retrieve the local socket address.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.inner.local_addr()
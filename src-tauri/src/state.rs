@@ -1,11 +1,19 @@
+use anyhow::{bail, Result};
 use netdev::Interface;
 use std::{
     collections::HashMap,
     sync::Arc,
     time::{Instant, SystemTime},
 };
-use tauri::async_runtime::JoinHandle;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::history::HistoryStore;
+use crate::metrics::PingMetrics;
+use crate::model::monitor::MonitorStatus;
+use crate::model::run::{RunInfo, RunKind, RunProgress, RunStatus};
+use crate::probe::event::{ProbeEvent, ProbeEventSink};
+use crate::service::SupervisorTask;
 
 #[derive(Debug, Clone)]
 pub struct IfStats {
@@ -29,8 +37,16 @@ pub struct AppState {
     pub stats: Mutex<HashMap<u32, IfStats>>,
     /// Last refresh time
     pub last_refresh: Mutex<SystemTime>,
-    /// Update task handle
-    pub task: Mutex<Option<JoinHandle<()>>>,
+    /// Supervisor task handle
+    pub task: Mutex<Option<SupervisorTask>>,
+    /// Probe runs (job manager)
+    pub runs: RunRegistry,
+    /// Persistent history of completed runs
+    pub history: Arc<HistoryStore>,
+    /// Current status of each running monitor, keyed by monitor id
+    pub monitors: Mutex<HashMap<String, MonitorStatus>>,
+    /// Results of recurring ping monitors, exported on `/metrics`
+    pub ping_metrics: PingMetrics,
 }
 
 impl Default for AppState {
@@ -40,7 +56,189 @@ impl Default for AppState {
             stats: Mutex::new(HashMap::new()),
             last_refresh: Mutex::new(SystemTime::now()),
             task: Mutex::new(None),
+            runs: RunRegistry::default(),
+            history: Arc::new(HistoryStore::new(HistoryStore::default_dir())),
+            monitors: Mutex::new(HashMap::new()),
+            ping_metrics: PingMetrics::default(),
+        }
+    }
+}
+
+/// Maximum number of finished runs kept for inspection
+const MAX_FINISHED_RUNS: usize = 32;
+
+#[derive(Debug)]
+struct RunEntry {
+    info: RunInfo,
+    token: CancellationToken,
+}
+
+/// Job manager for probe runs, keyed by run_id.
+/// Tracks running probes with their cancellation handles and keeps
+/// the most recent finished runs for inspection.
+#[derive(Debug, Default)]
+pub struct RunRegistry {
+    runs: std::sync::Mutex<HashMap<String, RunEntry>>,
+}
+
+impl RunRegistry {
+    /// Register a new run and return its cancellation token.
+    /// Fails if `kind` is a scan and `max_scans` scans are already running (0 = no limit).
+    pub fn register(
+        &self,
+        run_id: &str,
+        kind: RunKind,
+        target: String,
+        setting: serde_json::Value,
+        total: u32,
+        max_scans: u32,
+    ) -> Result<CancellationToken> {
+        let mut runs = self.runs.lock().unwrap();
+        if kind.is_scan() && max_scans > 0 {
+            let running = runs
+                .values()
+                .filter(|e| e.info.kind.is_scan() && e.info.status == RunStatus::Running)
+                .count();
+            if running >= max_scans as usize {
+                bail!("too many concurrent scans (limit: {max_scans})");
+            }
+        }
+        let token = CancellationToken::new();
+        let info = RunInfo {
+            run_id: run_id.to_string(),
+            kind,
+            target,
+            setting,
+            started_at: SystemTime::now(),
+            finished_at: None,
+            progress: RunProgress { done: 0, total },
+            status: RunStatus::Running,
+            error: None,
+        };
+        runs.insert(
+            run_id.to_string(),
+            RunEntry {
+                info,
+                token: token.clone(),
+            },
+        );
+        Ok(token)
+    }
+
+    /// Record the outcome of a run and drop the oldest finished runs.
+    /// Returns the final snapshot of the run.
+    pub fn finish<T>(&self, run_id: &str, result: &Result<T>) -> Option<RunInfo> {
+        let mut runs = self.runs.lock().unwrap();
+        let info = runs.get_mut(run_id).map(|entry| {
+            entry.info.finished_at = Some(SystemTime::now());
+            entry.info.status = match result {
+                Err(e) => {
+                    entry.info.error = Some(e.to_string());
+                    RunStatus::Failed
+                }
+                Ok(_) if entry.token.is_cancelled() => RunStatus::Cancelled,
+                Ok(_) => RunStatus::Done,
+            };
+            entry.info.clone()
+        });
+        let mut finished: Vec<(SystemTime, String)> = runs
+            .values()
+            .filter_map(|e| e.info.finished_at.map(|t| (t, e.info.run_id.clone())))
+            .collect();
+        if finished.len() > MAX_FINISHED_RUNS {
+            finished.sort();
+            for (_, id) in &finished[..finished.len() - MAX_FINISHED_RUNS] {
+                runs.remove(id);
+            }
+        }
+        info
+    }
+
+    /// Cancel a running probe. Returns false if no such run is running.
+    pub fn cancel(&self, run_id: &str) -> bool {
+        match self.runs.lock().unwrap().get(run_id) {
+            Some(entry) if entry.info.status == RunStatus::Running => {
+                entry.token.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Snapshot of all tracked runs, oldest first.
+    pub fn list(&self) -> Vec<RunInfo> {
+        let mut list: Vec<RunInfo> = self
+            .runs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|e| e.info.clone())
+            .collect();
+        list.sort_by_key(|r| r.started_at);
+        list
+    }
+
+    /// Snapshot of a single run.
+    pub fn get(&self, run_id: &str) -> Option<RunInfo> {
+        self.runs
+            .lock()
+            .unwrap()
+            .get(run_id)
+            .map(|e| e.info.clone())
+    }
+
+    fn update_progress(&self, run_id: &str, done: u32, total: Option<u32>) {
+        if let Some(entry) = self.runs.lock().unwrap().get_mut(run_id) {
+            entry.info.progress.done = done;
+            if let Some(total) = total {
+                entry.info.progress.total = total;
+            }
+        }
+    }
+
+    /// Wrap `inner` so that progress events of `run_id` update the registry.
+    pub fn sink<'a>(&'a self, run_id: &'a str, inner: &'a dyn ProbeEventSink) -> RunSink<'a> {
+        RunSink {
+            runs: self,
+            run_id,
+            inner,
+        }
+    }
+}
+
+/// Event sink that records run progress before forwarding events.
+pub struct RunSink<'a> {
+    runs: &'a RunRegistry,
+    run_id: &'a str,
+    inner: &'a dyn ProbeEventSink,
+}
+
+impl ProbeEventSink for RunSink<'_> {
+    fn emit(&self, event: ProbeEvent) {
+        match &event {
+            ProbeEvent::PingProgress(p) => {
+                self.runs.update_progress(self.run_id, p.transmitted, None)
+            }
+            ProbeEvent::MultiPingProgress(p) => {
+                self.runs
+                    .update_progress(self.run_id, p.done, Some(p.total))
+            }
+            ProbeEvent::PmtuProgress(p) => self.runs.update_progress(self.run_id, p.seq, None),
+            ProbeEvent::PortScanProgress(s) => {
+                self.runs
+                    .update_progress(self.run_id, s.done, Some(s.total))
+            }
+            ProbeEvent::HostScanProgress(p) => {
+                self.runs
+                    .update_progress(self.run_id, p.done, Some(p.total))
+            }
+            ProbeEvent::TraceProgress(hop) => {
+                self.runs.update_progress(self.run_id, hop.hop as u32, None)
+            }
+            ProbeEvent::MtrSnapshot(s) => self.runs.update_progress(self.run_id, s.round, None),
+            _ => {}
         }
+        self.inner.emit(event);
     }
 }
 
@@ -0,0 +1,162 @@
+//! RTT statistics shared by every ping protocol and the monitors.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// RTT of a probe in milliseconds, with microsecond precision.
+pub fn duration_ms(d: Duration) -> f64 {
+    d.as_micros() as f64 / 1000.0
+}
+
+/// Round a millisecond value to microsecond precision.
+pub fn round_us(ms: f64) -> f64 {
+    (ms * 1000.0).round() / 1000.0
+}
+
+/// Nearest-rank percentile of an ascending, non-empty slice.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Summary of a series of probes. All RTT values are in milliseconds.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RttStats {
+    pub transmitted: usize,
+    pub received: usize,
+    /// Loss in percent (0.0 to 100.0)
+    pub loss_pct: f64,
+    pub min: Option<f64>,
+    pub avg: Option<f64>,
+    pub max: Option<f64>,
+    /// Population standard deviation
+    pub stddev: Option<f64>,
+    /// Interarrival jitter estimate of RFC 3550 (6.4.1) over consecutive replies
+    pub jitter: Option<f64>,
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+    pub p99: Option<f64>,
+    /// Longest run of consecutive lost probes
+    pub max_loss_burst: usize,
+}
+
+impl RttStats {
+    /// Compute the statistics of probes in send order.
+    /// Each item is the RTT of a probe, or `None` if it was lost.
+    pub fn from_series<I>(series: I) -> RttStats
+    where
+        I: IntoIterator<Item = Option<f64>>,
+    {
+        let mut stats = RttStats::default();
+        let mut rtts = Vec::new();
+        let mut burst = 0;
+        let mut jitter: Option<f64> = None;
+        for rtt in series {
+            stats.transmitted += 1;
+            match rtt {
+                Some(rtt) => {
+                    burst = 0;
+                    if let Some(prev) = rtts.last() {
+                        let d: f64 = rtt - prev;
+                        let j = jitter.unwrap_or(0.0);
+                        jitter = Some(j + (d.abs() - j) / 16.0);
+                    }
+                    rtts.push(rtt);
+                }
+                None => {
+                    burst += 1;
+                    stats.max_loss_burst = stats.max_loss_burst.max(burst);
+                }
+            }
+        }
+        stats.received = rtts.len();
+        if stats.transmitted > 0 {
+            stats.loss_pct = round_us(
+                (stats.transmitted - stats.received) as f64 * 100.0 / stats.transmitted as f64,
+            );
+        }
+        if rtts.is_empty() {
+            return stats;
+        }
+
+        let n = rtts.len() as f64;
+        let mean = rtts.iter().sum::<f64>() / n;
+        let variance = rtts.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        stats.avg = Some(round_us(mean));
+        stats.stddev = Some(round_us(variance.sqrt()));
+        stats.jitter = jitter.map(round_us);
+
+        rtts.sort_by(f64::total_cmp);
+        stats.min = rtts.first().copied();
+        stats.max = rtts.last().copied();
+        stats.p50 = Some(percentile(&rtts, 50.0));
+        stats.p90 = Some(percentile(&rtts, 90.0));
+        stats.p99 = Some(percentile(&rtts, 99.0));
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentile() {
+        // Reference values of the nearest-rank method
+        let sorted = [15.0, 20.0, 35.0, 40.0, 50.0];
+        assert_eq!(percentile(&sorted, 5.0), 15.0);
+        assert_eq!(percentile(&sorted, 30.0), 20.0);
+        assert_eq!(percentile(&sorted, 40.0), 20.0);
+        assert_eq!(percentile(&sorted, 50.0), 35.0);
+        assert_eq!(percentile(&sorted, 100.0), 50.0);
+        assert_eq!(percentile(&sorted, 0.0), 15.0);
+        assert_eq!(percentile(&[7.0], 99.0), 7.0);
+    }
+
+    #[test]
+    fn rfc3550_jitter() {
+        // J += (|D| - J) / 16 per pair of consecutive replies
+        let stats = RttStats::from_series([Some(10.0), Some(20.0)]);
+        assert_eq!(stats.jitter, Some(0.625));
+        let stats = RttStats::from_series([Some(10.0), Some(20.0), Some(10.0)]);
+        // 0.625 + (10 - 0.625) / 16 = 1.2109375
+        assert_eq!(stats.jitter, Some(1.211));
+        // Lost probes do not break the pairs of replies
+        let stats = RttStats::from_series([Some(10.0), None, Some(20.0)]);
+        assert_eq!(stats.jitter, Some(0.625));
+        assert_eq!(RttStats::from_series([Some(10.0)]).jitter, None);
+    }
+
+    #[test]
+    fn summary_with_loss() {
+        let stats = RttStats::from_series([Some(1.0), None, None, Some(3.0), None]);
+        assert_eq!(stats.transmitted, 5);
+        assert_eq!(stats.received, 2);
+        assert_eq!(stats.loss_pct, 60.0);
+        assert_eq!(stats.max_loss_burst, 2);
+        assert_eq!(stats.min, Some(1.0));
+        assert_eq!(stats.avg, Some(2.0));
+        assert_eq!(stats.max, Some(3.0));
+        assert_eq!(stats.stddev, Some(1.0));
+        assert_eq!(stats.p50, Some(1.0));
+        assert_eq!(stats.p99, Some(3.0));
+    }
+
+    #[test]
+    fn empty_and_all_lost() {
+        assert_eq!(RttStats::from_series([]), RttStats::default());
+        let stats = RttStats::from_series([None, None, None]);
+        assert_eq!(stats.loss_pct, 100.0);
+        assert_eq!(stats.max_loss_burst, 3);
+        assert_eq!(stats.avg, None);
+        assert_eq!(stats.p50, None);
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(duration_ms(Duration::from_micros(1_234)), 1.234);
+        assert_eq!(duration_ms(Duration::from_nanos(999)), 0.0);
+        assert_eq!(round_us(1.23456), 1.235);
+        assert_eq!(round_us(100.0 / 3.0), 33.333);
+    }
+}
@@ -1,12 +1,63 @@
+import type { PingProtocol } from "./probe";
+
 export interface LoggingConfig {
   level: "DEBUG" | "INFO" | "WARN" | "ERROR";
   file_path?: string | null;
 }
 
+export interface ApiConfig {
+  enabled: boolean;
+  port: number;
+  token: string;
+}
+
+export interface MetricsConfig {
+  enabled: boolean;
+  port: number;
+}
+
+export interface MonitorThresholds {
+  max_loss_pct: number;
+  max_avg_rtt_ms?: number | null;
+  down_after_failures: number;
+}
+
+export interface MonitorConfig {
+  id: string;
+  name: string;
+  enabled: boolean;
+  target: string;
+  protocol: PingProtocol;
+  port?: number | null;
+  interval_ms: number;
+  timeout_ms: number;
+  window: number;
+  thresholds: MonitorThresholds;
+}
+
 export interface AppConfig {
+  /** Schema version of the config file */
+  version: number;
   startup: boolean;
   refresh_interval_ms: number;
+  iface_reload_interval_ms?: number;
   theme: "system" | "light" | "dark";
   data_unit: "bits" | "bytes";
   logging: LoggingConfig;
+  max_concurrent_scans?: number;
+  api?: ApiConfig;
+  metrics?: MetricsConfig;
+  monitors?: MonitorConfig[];
 }
+
+export interface ConfigFieldError {
+  /** Dotted path of the field, e.g. `logging.file_path` */
+  field: string;
+  message: string;
+}
+
+/** Error returned from `save_config` */
+export type ConfigError =
+  | { kind: "invalid"; errors: ConfigFieldError[] }
+  | { kind: "io"; message: string }
+  | { kind: "unsupported_version"; version: number };
@@ -0,0 +1,21 @@
+export type NtpLeap = "None" | "AddSecond" | "DeleteSecond" | "Unsynchronized";
+
+export type NtpResponse = {
+  version: number;
+  leap: NtpLeap;
+  stratum: number;
+  reference_id: string;
+  offset_ms: number;
+  delay_ms: number;
+  root_delay_ms: number;
+  root_dispersion_ms: number;
+  precision: number;
+};
+
+export type NtpQueryResult = {
+  server: string;
+  ip_addr?: string | null;
+  port: number;
+  response?: NtpResponse | null;
+  error?: string | null;
+};
@@ -1,11 +1,33 @@
+import type { NtpResponse } from "./ntp";
+import type { TlsCertificate } from "./tls";
+
 export type ProbeStatusKind = "Done" | "Error" | "Timeout";
-export type PingProtocol = "Icmp" | "Tcp" | "Udp" | "Quic" | "Http";
+export type PingProtocol = "Icmp" | "Tcp" | "Udp" | "Quic" | "Http" | "Dns" | "Ntp";
 
 export interface ProbeStatus {
   kind: ProbeStatusKind;
   message: string;
 }
 
+export type IcmpErrorKind =
+  | "NetUnreachable"
+  | "HostUnreachable"
+  | "PortUnreachable"
+  | "ProtocolUnreachable"
+  | "AdminProhibited"
+  | "TtlExceeded"
+  | "FragmentationNeeded"
+  | "NoRoute"
+  | "Other";
+
+export interface IcmpError {
+  kind: IcmpErrorKind;
+  from?: string | null;
+  icmp_type?: number | null;
+  icmp_code?: number | null;
+  mtu?: number | null;
+}
+
 export interface PingSample {
   seq: number;
   ip_addr: string;         
@@ -14,6 +36,12 @@ export interface PingSample {
   rtt_ms?: number | null;
   probe_status: ProbeStatus;
   protocol: PingProtocol;
+  http?: HttpSampleDetail | null;
+  dns?: DnsSampleDetail | null;
+  quic?: QuicSampleDetail | null;
+  ntp?: NtpResponse | null;
+  icmp?: IcmpSampleDetail | null;
+  icmp_error?: IcmpError | null;
 }
 
 export interface PingStat {
@@ -27,6 +55,14 @@ export interface PingStat {
   min?: number | null;
   avg?: number | null;
   max?: number | null;
+  stddev?: number | null;
+  jitter?: number | null;
+  p50?: number | null;
+  p90?: number | null;
+  p99?: number | null;
+  max_loss_burst?: number;
+  duplicate_count?: number;
+  late_count?: number;
 }
 
 export interface PingSetting {
@@ -38,6 +74,164 @@ export interface PingSetting {
   count: number;
   timeout_ms: number;
   send_rate_ms: number;
+  interface?: string | null;
+  src_ip?: string | null;
+  http?: HttpPingOption | null;
+  dns?: DnsPingOption | null;
+  quic?: QuicOption | null;
+  udp?: UdpPingOption | null;
+  icmp?: IcmpPingOption | null;
+}
+
+export type HttpMethod = "Get" | "Head";
+
+export interface HttpHeader {
+  name: string;
+  value: string;
+}
+
+export interface HttpPingOption {
+  method?: HttpMethod;
+  headers?: HttpHeader[];
+  expected_status?: number[];
+  body_contains?: string | null;
+}
+
+export interface HttpSampleDetail {
+  dns_ms?: number | null;
+  connect_ms?: number | null;
+  tls_ms?: number | null;
+  ttfb_ms?: number | null;
+  download_ms?: number | null;
+  total_ms?: number | null;
+  status?: number | null;
+  version?: string | null;
+  body_bytes?: number | null;
+}
+
+export type DnsTransport = "Udp" | "Tcp" | "Tls" | "Https";
+
+export interface DnsPingOption {
+  transport?: DnsTransport;
+  query_name?: string;
+  record_type?: string;
+  recursion_desired?: boolean;
+  doh_path?: string;
+}
+
+export interface DnsSampleDetail {
+  transport: DnsTransport;
+  rcode?: string | null;
+  answer_count?: number | null;
+  truncated: boolean;
+  connect_ms?: number | null;
+}
+
+export type UdpPingMode = "PortUnreachable" | "Dns" | "Ntp" | "Snmp" | "Echo" | "Hex";
+
+export interface UdpPingOption {
+  mode?: UdpPingMode;
+  payload_hex?: string | null;
+  snmp_community?: string;
+}
+
+export interface IcmpPingOption {
+  payload_size?: number;
+  pattern_hex?: string | null;
+}
+
+export interface IcmpSampleDetail {
+  bytes: number;
+  ttl?: number | null;
+  duplicate: boolean;
+  late: boolean;
+}
+
+export interface QuicOption {
+  skip_verify?: boolean;
+  alpn?: string[];
+  server_name?: string | null;
+  zero_rtt?: boolean;
+}
+
+export interface QuicSampleDetail {
+  server_name: string;
+  version: string;
+  alpn?: string | null;
+  certificate?: TlsCertificate | null;
+  trusted: boolean;
+  validation_error?: string | null;
+  zero_rtt_accepted?: boolean | null;
+}
+
+export interface MultiPingSetting {
+  targets: string[];
+  hop_limit: number;
+  count: number;
+  timeout_ms: number;
+  send_rate_ms: number;
+  interface?: string | null;
+  src_ip?: string | null;
+}
+
+export interface MultiPingTarget {
+  ip_addr: string;
+  hostname?: string | null;
+}
+
+export interface MultiPingStartPayload {
+  run_id: string;
+  targets: MultiPingTarget[];
+}
+
+export interface MultiPingProgressPayload {
+  run_id: string;
+  sample: PingSample;
+  transmitted: number;
+  received: number;
+  done: number;
+  total: number;
+}
+
+export interface MultiPingReport {
+  run_id: string;
+  stats: PingStat[];
+}
+
+export interface PmtuSetting {
+  ip_addr: string;
+  hostname?: string | null;
+  max_mtu?: number | null;
+  hop_limit: number;
+  timeout_ms: number;
+  tries: number;
+  interface?: string | null;
+  src_ip?: string | null;
+}
+
+export type PmtuProbeResult = "Ok" | "TooBig" | "Timeout" | "Error";
+
+export interface PmtuProbe {
+  run_id: string;
+  seq: number;
+  size: number;
+  result: PmtuProbeResult;
+  rtt_ms?: number | null;
+  reported_mtu?: number | null;
+  from?: string | null;
+  message?: string | null;
+}
+
+export interface PmtuReport {
+  run_id: string;
+  ip_addr: string;
+  hostname?: string | null;
+  path_mtu?: number | null;
+  constraining_hop?: string | null;
+  interface?: string | null;
+  interface_mtu?: number | null;
+  below_interface_mtu: boolean;
+  probes: number;
 }
 
 export type PortScanProtocol = "Tcp" | "Quic";
@@ -52,6 +246,7 @@ export interface PortScanSample {
   rtt_ms?: number | null;
   message?: string | null;
   service_name?: string | null;
+  icmp_error?: IcmpError | null;
   done?: number;
   total?: number;
 }
@@ -72,6 +267,9 @@ export interface PortScanSetting {
   protocol: PortScanProtocol;
   timeout_ms: number;
   ordered: boolean;
+  interface?: string | null;
+  src_ip?: string | null;
+  quic?: QuicOption | null;
 }
 
 export type HostState = "Alive" | "Unreachable";
@@ -81,6 +279,7 @@ export interface HostScanProgress {
   state: HostState;
   rtt_ms?: number | null;
   message?: string | null;
+  icmp_error?: IcmpError | null;
   done: number;
   total: number;
 }
@@ -100,6 +299,8 @@ export interface HostScanSetting {
   payload?: string | null;
   ordered: boolean;
   concurrency?: number | null;
+  interface?: string | null;
+  src_ip?: string | null;
 }
 
 export type NeighborHost = {
@@ -125,6 +326,8 @@ export interface TraceSetting {
   tries_per_hop: number;
   timeout_ms: number;
   protocol: TraceProtocol;
+  interface?: string | null;
+  src_ip?: string | null;
 }
 
 export interface TraceHop {
@@ -142,3 +345,92 @@ export interface TraceDonePayload {
   hostname?: string | null;
   protocol: TraceProtocol;
 }
+
+export interface MtrSetting {
+  hostname: string | null;
+  ip_addr: string;
+  max_hops: number;
+  /** Rounds to run; null or 0 runs until cancelled */
+  rounds?: number | null;
+  interval_ms: number;
+  timeout_ms: number;
+  interface?: string | null;
+  src_ip?: string | null;
+}
+
+export interface MtrHop {
+  hop: number;
+  ip_addr?: string | null;
+  ip_addrs: string[];
+  sent: number;
+  received: number;
+  loss_pct: number;
+  last_ms?: number | null;
+  avg_ms?: number | null;
+  best_ms?: number | null;
+  worst_ms?: number | null;
+  stddev_ms?: number | null;
+  reached: boolean;
+  note?: string | null;
+}
+
+export interface MtrStartPayload extends MtrSetting {
+  run_id: string;
+}
+
+export interface MtrSnapshot {
+  run_id: string;
+  round: number;
+  reached: boolean;
+  hops: MtrHop[];
+}
+
+export interface MtrReport {
+  run_id: string;
+  ip_addr: string;
+  hostname?: string | null;
+  rounds: number;
+  reached: boolean;
+  hops: MtrHop[];
+  note?: string | null;
+}
+
+export type MonitorState = "unknown" | "up" | "degraded" | "down";
+
+export interface RttStats {
+  transmitted: number;
+  received: number;
+  loss_pct: number;
+  min?: number | null;
+  avg?: number | null;
+  max?: number | null;
+  stddev?: number | null;
+  jitter?: number | null;
+  p50?: number | null;
+  p90?: number | null;
+  p99?: number | null;
+  max_loss_burst: number;
+}
+
+export interface MonitorStatus {
+  id: string;
+  name: string;
+  target: string;
+  ip_addr?: string | null;
+  protocol: PingProtocol;
+  state: MonitorState;
+  reason: string;
+  since_ms: number;
+  last_checked_ms?: number | null;
+  last_rtt_ms?: number | null;
+  last_error?: string | null;
+  consecutive_failures: number;
+  sent: number;
+  received: number;
+  window: RttStats;
+}
+
+export interface MonitorStateChangedPayload {
+  previous: MonitorState;
+  status: MonitorStatus;
+}
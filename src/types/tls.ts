@@ -0,0 +1,37 @@
+export type StartTls = "Smtp" | "Imap" | "Pop3";
+
+export type TlsCertificate = {
+  subject: string;
+  issuer: string;
+  serial: string;
+  version: number;
+  sans: string[];
+  not_before: string;
+  not_after: string;
+  days_remaining: number;
+  expired: boolean;
+  not_yet_valid: boolean;
+  self_issued: boolean;
+  key_type: string;
+  key_bits?: number | null;
+  signature_algorithm: string;
+  sha256: string;
+  sha1: string;
+  parse_error?: string | null;
+};
+
+export type TlsInspectReport = {
+  host: string;
+  ip_addr: string;
+  port: number;
+  sni?: string | null;
+  starttls?: StartTls | null;
+  version: string;
+  cipher_suite: string;
+  alpn?: string | null;
+  chain: TlsCertificate[];
+  trusted: boolean;
+  validation_error?: string | null;
+  connect_ms: number;
+  handshake_ms: number;
+};